@@ -1,3 +1,6 @@
+mod mem;
+mod results;
+
 use std::cmp::Ordering;
 use std::fs::{self, File};
 use std::path::{Path, PathBuf};
@@ -9,6 +12,9 @@ use c_vec::CVec;
 use structopt::StructOpt;
 use walkdir::{DirEntry, WalkDir};
 
+use crate::mem::MemStats;
+use crate::results::Record;
+
 fn black_box<T>(dummy: T) -> T {
     unsafe {
         let ret = core::ptr::read_volatile(&dummy);
@@ -28,15 +34,15 @@ fn mean(v: &[f64]) -> f64 {
     v.iter().sum::<f64>() / v.len() as f64
 }
 
-fn find_pngs(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
-    let is_png_file = |path: &PathBuf| {
-        path.is_file()
-            && path.extension().unwrap_or_default().to_string_lossy().to_ascii_lowercase() == "png"
+fn find_images(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let is_image_file = |path: &PathBuf| {
+        let ext = path.extension().unwrap_or_default().to_string_lossy().to_ascii_lowercase();
+        path.is_file() && (ext == "png" || ext == "qoi")
     };
 
     let mut out = vec![];
     for path in paths {
-        if is_png_file(path) {
+        if is_image_file(path) {
             out.push(path.clone());
         } else if path.is_dir() {
             out.extend(
@@ -45,7 +51,7 @@ fn find_pngs(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
                     .into_iter()
                     .filter_map(Result::ok)
                     .map(DirEntry::into_path)
-                    .filter(is_png_file),
+                    .filter(is_image_file),
             )
         } else {
             bail!("path doesn't exist: {}", path.to_string_lossy());
@@ -55,6 +61,10 @@ fn find_pngs(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
     Ok(out)
 }
 
+fn is_qoi_file(path: &Path) -> bool {
+    path.extension().unwrap_or_default().to_string_lossy().to_ascii_lowercase() == "qoi"
+}
+
 fn grayscale_to_rgb(buf: &[u8]) -> Vec<u8> {
     let mut out = Vec::with_capacity(buf.len() * 3);
     for &px in buf {
@@ -112,6 +122,19 @@ impl Image {
         Ok(Self { width: info.width, height: info.height, channels, data })
     }
 
+    /// Reads a QOI file, returning both the decoded image and the encoded bytes.
+    fn read_qoi(filename: &Path) -> Result<(Self, Vec<u8>)> {
+        let encoded = fs::read(filename)?;
+        let (header, data) = qoi::decode_to_vec(&encoded)?;
+        let img = Self {
+            width: header.width,
+            height: header.height,
+            channels: header.channels.as_u8(),
+            data,
+        };
+        Ok((img, encoded))
+    }
+
     pub const fn n_pixels(&self) -> usize {
         (self.width as usize) * (self.height as usize)
     }
@@ -125,6 +148,10 @@ trait Codec {
     type Output: AsRef<[u8]>;
 
     fn name() -> &'static str;
+    /// Whether the codec allocates via the Rust global allocator (so heap usage is tracked).
+    fn tracks_heap() -> bool {
+        true
+    }
     fn encode(img: &Image) -> Result<Self::Output>;
     fn decode(data: &[u8], img: &Image) -> Result<Self::Output>;
 }
@@ -156,6 +183,10 @@ impl Codec for CodecQoiC {
         "qoi.h"
     }
 
+    fn tracks_heap() -> bool {
+        false
+    }
+
     fn encode(img: &Image) -> Result<CVec<u8>> {
         libqoi::qoi_encode(&img.data, img.width, img.height, img.channels)
     }
@@ -165,19 +196,68 @@ impl Codec for CodecQoiC {
     }
 }
 
+/// PNG via the `png` crate (default or fast compression), as a general-purpose baseline.
+#[cfg(feature = "png-codec")]
+struct CodecPng<const FAST: bool>;
+
+#[cfg(feature = "png-codec")]
+impl<const FAST: bool> Codec for CodecPng<FAST> {
+    type Output = Vec<u8>;
+
+    fn name() -> &'static str {
+        if FAST {
+            "png-fast"
+        } else {
+            "png"
+        }
+    }
+
+    fn encode(img: &Image) -> Result<Vec<u8>> {
+        let mut out = vec![];
+        let mut encoder = png::Encoder::new(&mut out, img.width, img.height);
+        encoder.set_color(if img.channels == 4 {
+            png::ColorType::Rgba
+        } else {
+            png::ColorType::Rgb
+        });
+        encoder.set_depth(png::BitDepth::Eight);
+        if FAST {
+            encoder.set_compression(png::Compression::Fast);
+        }
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&img.data)?;
+        writer.finish()?;
+        Ok(out)
+    }
+
+    fn decode(data: &[u8], _img: &Image) -> Result<Vec<u8>> {
+        let mut reader = png::Decoder::new(data).read_info()?;
+        let mut out = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut out)?;
+        out.truncate(info.buffer_size());
+        Ok(out)
+    }
+}
+
 #[derive(Clone)]
 struct BenchResult {
     pub codec: String,
     pub decode_sec: Vec<f64>,
     pub encode_sec: Vec<f64>,
+    pub decode_mem: MemStats,
+    /// Missing if encoding wasn't benchmarked (decode-only mode).
+    pub encode_mem: Option<MemStats>,
 }
 
 impl BenchResult {
-    pub fn new(codec: impl AsRef<str>, mut decode_sec: Vec<f64>, mut encode_sec: Vec<f64>) -> Self {
+    pub fn new(
+        codec: impl AsRef<str>, mut decode_sec: Vec<f64>, mut encode_sec: Vec<f64>,
+        decode_mem: MemStats, encode_mem: Option<MemStats>,
+    ) -> Self {
         decode_sec.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
         encode_sec.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
         let codec = codec.as_ref().into();
-        Self { codec, decode_sec, encode_sec }
+        Self { codec, decode_sec, encode_sec, decode_mem, encode_mem }
     }
 
     pub fn average_decode_sec(&self, use_median: bool) -> f64 {
@@ -188,8 +268,11 @@ impl BenchResult {
         }
     }
 
+    /// Returns NaN if encoding wasn't benchmarked (decode-only mode).
     pub fn average_encode_sec(&self, use_median: bool) -> f64 {
-        if use_median {
+        if self.encode_sec.is_empty() {
+            f64::NAN
+        } else if use_median {
             self.encode_sec[self.encode_sec.len() / 2]
         } else {
             mean(&self.encode_sec)
@@ -199,14 +282,26 @@ impl BenchResult {
 
 #[derive(Clone)]
 struct ImageBench {
+    name: String,
+    width: u32,
+    height: u32,
+    channels: u8,
     results: Vec<BenchResult>,
     n_pixels: usize,
     n_bytes: usize,
 }
 
 impl ImageBench {
-    pub fn new(img: &Image) -> Self {
-        Self { results: vec![], n_pixels: img.n_pixels(), n_bytes: img.n_bytes() }
+    pub fn new(name: impl AsRef<str>, img: &Image) -> Self {
+        Self {
+            name: name.as_ref().into(),
+            width: img.width,
+            height: img.height,
+            channels: img.channels,
+            results: vec![],
+            n_pixels: img.n_pixels(),
+            n_bytes: img.n_bytes(),
+        }
     }
 
     pub fn run<C: Codec>(&mut self, img: &Image, sec_allowed: f64) -> Result<()> {
@@ -235,10 +330,67 @@ impl ImageBench {
         }
         let decode_sec = decode_tm.iter().map(Duration::as_secs_f64).collect();
 
-        self.results.push(BenchResult::new(C::name(), decode_sec, encode_sec));
+        // memory usage is measured separately, on a single call each
+        let encode_mem = mem::measure(C::tracks_heap(), || C::encode(img)).1;
+        let decode_mem = mem::measure(C::tracks_heap(), || C::decode(encoded.as_ref(), img)).1;
+
+        let result =
+            BenchResult::new(C::name(), decode_sec, encode_sec, decode_mem, Some(encode_mem));
+        self.results.push(result);
+        Ok(())
+    }
+
+    pub fn run_decode_only<C: Codec>(
+        &mut self, encoded: &[u8], img: &Image, sec_allowed: f64,
+    ) -> Result<()> {
+        let (decoded, t_decode) = timeit(|| C::decode(encoded, img));
+        let decoded = decoded?;
+        let roundtrip = decoded.as_ref() == img.data.as_slice();
+        ensure!(roundtrip, "{}: decoded data doesn't match", C::name());
+
+        let n_decode = (sec_allowed / t_decode.as_secs_f64()).max(2.).ceil() as usize;
+        let mut decode_tm = Vec::with_capacity(n_decode);
+        for _ in 0..n_decode {
+            decode_tm.push(timeit(|| C::decode(encoded, img)).1);
+        }
+        let decode_sec = decode_tm.iter().map(Duration::as_secs_f64).collect();
+
+        let decode_mem = mem::measure(C::tracks_heap(), || C::decode(encoded, img)).1;
+
+        self.results.push(BenchResult::new(C::name(), decode_sec, vec![], decode_mem, None));
         Ok(())
     }
 
+    pub fn records(&self, use_median: bool) -> Vec<Record> {
+        let mpixels = self.n_pixels as f64 / 1e6;
+        self.results
+            .iter()
+            .map(|r| {
+                let decode_sec = r.average_decode_sec(use_median);
+                let encode_sec = r.average_encode_sec(use_median);
+                let encode_mem = r.encode_mem.unwrap_or_default();
+                let kb = |bytes: Option<usize>| bytes.map(|b| b as f64 / 1024.);
+                Record {
+                    file: self.name.clone(),
+                    codec: r.codec.clone(),
+                    width: self.width,
+                    height: self.height,
+                    channels: self.channels,
+                    decode_ms: decode_sec * 1e3,
+                    encode_ms: Some(encode_sec * 1e3).filter(|t| !t.is_nan()),
+                    decode_mpps: mpixels / decode_sec,
+                    encode_mpps: Some(mpixels / encode_sec).filter(|t| !t.is_nan()),
+                    decode_allocs: r.decode_mem.allocs,
+                    encode_allocs: encode_mem.allocs,
+                    decode_heap_kb: kb(r.decode_mem.peak_heap),
+                    encode_heap_kb: kb(encode_mem.peak_heap),
+                    decode_rss_kb: kb(r.decode_mem.peak_rss),
+                    encode_rss_kb: kb(encode_mem.peak_rss),
+                }
+            })
+            .collect()
+    }
+
     pub fn report(&self, use_median: bool) {
         let (w_name, w_col) = (9, 13);
         print!("{:<w$}", "", w = w_name);
@@ -266,6 +418,35 @@ impl ImageBench {
             print!("{:>w$.1}", encode_mbps, w = w_col);
             println!();
         }
+        self.report_memory();
+    }
+
+    fn report_memory(&self) {
+        let (w_name, w_col) = (9, 14);
+        print!("{:<w$}", "", w = w_name);
+        print!("{:>w$}", "decode:allocs", w = w_col);
+        print!("{:>w$}", "encode:allocs", w = w_col);
+        print!("{:>w$}", "decode:heapKB", w = w_col);
+        print!("{:>w$}", "encode:heapKB", w = w_col);
+        print!("{:>w$}", "decode:rssMB", w = w_col);
+        print!("{:>w$}", "encode:rssMB", w = w_col);
+        println!();
+        // missing values: heap isn't tracked for C codecs, RSS is Linux-only
+        let fmt = |v: Option<f64>, precision| match v {
+            Some(v) => format!("{:>w$.p$}", v, w = w_col, p = precision),
+            None => format!("{:>w$}", "-", w = w_col),
+        };
+        for r in &self.results {
+            let (dec, enc) = (r.decode_mem, r.encode_mem.unwrap_or_default());
+            let count = |v: Option<usize>| fmt(v.map(|v| v as f64), 0);
+            let kb = |v: Option<usize>| fmt(v.map(|v| v as f64 / 1024.), 1);
+            let mb = |v: Option<usize>| fmt(v.map(|v| v as f64 / 1024. / 1024.), 1);
+            print!("{:<w$}", r.codec, w = w_name);
+            print!("{}{}", count(dec.allocs), count(enc.allocs));
+            print!("{}{}", kb(dec.peak_heap), kb(enc.peak_heap));
+            print!("{}{}", mb(dec.peak_rss), mb(enc.peak_rss));
+            println!();
+        }
     }
 }
 
@@ -283,21 +464,33 @@ impl BenchTotals {
         self.results.push(b.clone())
     }
 
+    pub fn records(&self, use_median: bool) -> Vec<Record> {
+        self.results.iter().flat_map(|r| r.records(use_median)).collect()
+    }
+
     pub fn report(&self, use_median: bool, fancy: bool) {
         if self.results.is_empty() {
             return;
         }
-        let codec_names: Vec<_> = self.results[0].results.iter().map(|r| r.codec.clone()).collect();
+        // only codecs that have been run on all images are comparable (e.g., extra codecs
+        // are skipped for QOI inputs)
+        let codec_names: Vec<_> = self.results[0]
+            .results
+            .iter()
+            .map(|r| r.codec.clone())
+            .filter(|name| self.results.iter().all(|b| b.results.iter().any(|r| &r.codec == name)))
+            .collect();
         let n_codecs = codec_names.len();
         let (mut total_decode_sec, mut total_encode_sec, mut n_pixels_total, mut n_bytes_total) =
             (vec![0.; n_codecs], vec![0.; n_codecs], 0, 0);
         for r in &self.results {
             n_pixels_total += r.n_pixels;
             n_bytes_total += r.n_bytes;
-            for i in 0..n_codecs {
+            for (i, name) in codec_names.iter().enumerate() {
+                let result = r.results.iter().find(|r| &r.codec == name).unwrap();
                 // sum of medians is not the median of sums, but w/e, good enough here
-                total_decode_sec[i] += r.results[i].average_decode_sec(use_median);
-                total_encode_sec[i] += r.results[i].average_encode_sec(use_median);
+                total_decode_sec[i] += result.average_decode_sec(use_median);
+                total_encode_sec[i] += result.average_encode_sec(use_median);
             }
         }
         let mpixels = n_pixels_total as f64 / 1e6;
@@ -372,17 +565,48 @@ fn bench_png(filename: &Path, seconds: f64, use_median: bool) -> Result<ImageBen
         "{} ({}x{}:{}, {} KB png, {:.2} MB raw, {:.2} MP)",
         f, img.width, img.height, img.channels, size_png_kb, size_mb_raw, mpixels
     );
-    let mut bench = ImageBench::new(&img);
+    let mut bench = ImageBench::new(&f, &img);
     bench.run::<CodecQoiC>(&img, seconds)?;
     bench.run::<CodecQoiRust>(&img, seconds)?;
+    // extra codecs to compare against, only for PNG inputs since they can't decode QOI
+    #[cfg(feature = "png-codec")]
+    {
+        bench.run::<CodecPng<false>>(&img, seconds)?;
+        bench.run::<CodecPng<true>>(&img, seconds)?;
+    }
+    bench.report(use_median);
+    Ok(bench)
+}
+
+fn bench_qoi(filename: &Path, seconds: f64, use_median: bool) -> Result<ImageBench> {
+    let f = filename.to_string_lossy();
+    let (img, encoded) =
+        Image::read_qoi(filename).context(format!("error reading QOI file: {}", f))?;
+    let size_qoi_kb = encoded.len() / 1024;
+    let size_mb_raw = img.n_bytes() as f64 / 1024. / 1024.;
+    let mpixels = img.n_pixels() as f64 / 1e6;
+    println!(
+        "{} ({}x{}:{}, {} KB qoi, {:.2} MB raw, {:.2} MP, decode only)",
+        f, img.width, img.height, img.channels, size_qoi_kb, size_mb_raw, mpixels
+    );
+    let mut bench = ImageBench::new(&f, &img);
+    bench.run_decode_only::<CodecQoiC>(&encoded, &img, seconds)?;
+    bench.run_decode_only::<CodecQoiRust>(&encoded, &img, seconds)?;
     bench.report(use_median);
     Ok(bench)
 }
 
-fn bench_suite(files: &[PathBuf], seconds: f64, use_median: bool, fancy: bool) -> Result<()> {
+fn bench_suite(
+    files: &[PathBuf], seconds: f64, use_median: bool, fancy: bool,
+) -> Result<BenchTotals> {
     let mut totals = BenchTotals::new();
     for file in files {
-        match bench_png(file, seconds, use_median) {
+        let res = if is_qoi_file(file) {
+            bench_qoi(file, seconds, use_median)
+        } else {
+            bench_png(file, seconds, use_median)
+        };
+        match res {
             Ok(res) => totals.update(&res),
             Err(err) => eprintln!("{:?}", err),
         }
@@ -390,12 +614,12 @@ fn bench_suite(files: &[PathBuf], seconds: f64, use_median: bool, fancy: bool) -
     if totals.results.len() > 1 {
         totals.report(use_median, fancy);
     }
-    Ok(())
+    Ok(totals)
 }
 
 #[derive(Debug, StructOpt)]
 struct Args {
-    /// Files or directories containing png images.
+    /// Files or directories containing png images (or qoi images, decode-only).
     #[structopt(parse(from_os_str))]
     paths: Vec<PathBuf>,
     /// Number of seconds allocated for each image/codec.
@@ -405,15 +629,37 @@ struct Args {
     #[structopt(short, long)]
     average: bool,
     /// Simple totals, no fancy tables.
-    #[structopt(short, long)]
+    #[structopt(long)]
     simple: bool,
+    /// Write per-image results to a JSON file.
+    #[structopt(long, parse(from_os_str))]
+    json: Option<PathBuf>,
+    /// Write per-image results to a CSV file.
+    #[structopt(long, parse(from_os_str))]
+    csv: Option<PathBuf>,
+    /// Compare two result files (JSON or CSV) instead of running benchmarks.
+    #[structopt(long, parse(from_os_str), number_of_values = 2, value_names = &["OLD", "NEW"])]
+    compare: Vec<PathBuf>,
+    /// Slowdown (in percent) to be flagged as a regression when comparing.
+    #[structopt(short, long, default_value = "5")]
+    threshold: f64,
 }
 
 fn main() -> Result<()> {
     let args = <Args as StructOpt>::from_args();
+    if let [old, new] = args.compare.as_slice() {
+        return results::compare(old, new, args.threshold);
+    }
     ensure!(!args.paths.is_empty(), "no input paths given");
-    let files = find_pngs(&args.paths)?;
-    ensure!(!files.is_empty(), "no PNG files found in given paths");
-    bench_suite(&files, args.seconds, !args.average, !args.simple)?;
+    let files = find_images(&args.paths)?;
+    ensure!(!files.is_empty(), "no PNG or QOI files found in given paths");
+    let use_median = !args.average;
+    let totals = bench_suite(&files, args.seconds, use_median, !args.simple)?;
+    if let Some(path) = &args.json {
+        results::write_json(path, &totals.records(use_median))?;
+    }
+    if let Some(path) = &args.csv {
+        results::write_csv(path, &totals.records(use_median))?;
+    }
     Ok(())
 }
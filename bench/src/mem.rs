@@ -0,0 +1,97 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+/// System allocator wrapper keeping track of the number of allocations and of the
+/// current and peak heap usage; only sees allocations made on the Rust side.
+struct CountingAlloc;
+
+static N_ALLOCS: AtomicUsize = AtomicUsize::new(0);
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn on_alloc(size: usize) {
+    N_ALLOCS.fetch_add(1, Relaxed);
+    let current = CURRENT.fetch_add(size, Relaxed) + size;
+    PEAK.fetch_max(current, Relaxed);
+}
+
+fn on_dealloc(size: usize) {
+    CURRENT.fetch_sub(size, Relaxed);
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            on_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        on_dealloc(layout.size());
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            on_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            on_dealloc(layout.size());
+            on_alloc(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// Resets the peak resident set size of the process to its current value (Linux only).
+fn reset_peak_rss() -> bool {
+    fs::write("/proc/self/clear_refs", "5").is_ok()
+}
+
+/// Returns the peak resident set size of the process in bytes (Linux only).
+fn peak_rss() -> Option<usize> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kb = line.trim_start_matches("VmHWM:").trim().trim_end_matches("kB").trim();
+    kb.parse::<usize>().ok().map(|kb| kb * 1024)
+}
+
+/// Memory usage of a single call, see [`measure`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemStats {
+    /// Number of heap allocations, including reallocations.
+    pub allocs: Option<usize>,
+    /// Peak heap usage in bytes above the level at the start of the call.
+    pub peak_heap: Option<usize>,
+    /// Peak resident set size of the whole process in bytes.
+    pub peak_rss: Option<usize>,
+}
+
+/// Runs the function once and records its memory usage. Heap statistics are only
+/// available if `track_heap` is set (that is, if the function allocates via the Rust
+/// global allocator), peak RSS is only available on Linux.
+pub fn measure<T>(track_heap: bool, func: impl FnOnce() -> T) -> (T, MemStats) {
+    let has_rss = reset_peak_rss();
+    let heap_start = CURRENT.load(Relaxed);
+    PEAK.store(heap_start, Relaxed);
+    let n_allocs_start = N_ALLOCS.load(Relaxed);
+    let out = func();
+    let stats = MemStats {
+        allocs: Some(N_ALLOCS.load(Relaxed) - n_allocs_start).filter(|_| track_heap),
+        peak_heap: Some(PEAK.load(Relaxed) - heap_start).filter(|_| track_heap),
+        peak_rss: peak_rss().filter(|_| has_rss),
+    };
+    (out, stats)
+}
@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// A single benchmark measurement: one codec on one image.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub file: String,
+    pub codec: String,
+    pub width: u32,
+    pub height: u32,
+    pub channels: u8,
+    pub decode_ms: f64,
+    /// Missing if encoding wasn't benchmarked (decode-only mode).
+    pub encode_ms: Option<f64>,
+    pub decode_mpps: f64,
+    pub encode_mpps: Option<f64>,
+    /// Memory usage of a single call; heap stats are missing for non-Rust codecs, RSS
+    /// is missing on platforms other than Linux (and in results from older versions).
+    #[serde(default)]
+    pub decode_allocs: Option<usize>,
+    #[serde(default)]
+    pub encode_allocs: Option<usize>,
+    #[serde(default)]
+    pub decode_heap_kb: Option<f64>,
+    #[serde(default)]
+    pub encode_heap_kb: Option<f64>,
+    #[serde(default)]
+    pub decode_rss_kb: Option<f64>,
+    #[serde(default)]
+    pub encode_rss_kb: Option<f64>,
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().unwrap_or_default().to_string_lossy().to_ascii_lowercase() == "json"
+}
+
+pub fn write_json(path: &Path, records: &[Record]) -> Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, records)
+        .with_context(|| format!("error writing JSON file: {}", path.to_string_lossy()))
+}
+
+pub fn write_csv(path: &Path, records: &[Record]) -> Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    for record in records {
+        writer.serialize(record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn read_records(path: &Path) -> Result<Vec<Record>> {
+    let f = path.to_string_lossy();
+    if is_json(path) {
+        let reader = BufReader::new(File::open(path)?);
+        serde_json::from_reader(reader).with_context(|| format!("error reading JSON file: {}", f))
+    } else {
+        let mut reader = csv::Reader::from_path(path)?;
+        let records: Result<Vec<Record>, _> = reader.deserialize().collect();
+        records.with_context(|| format!("error reading CSV file: {}", f))
+    }
+}
+
+fn pct_change(old: f64, new: f64) -> f64 {
+    (new / old - 1.) * 100.
+}
+
+/// Compares two sets of results and reports any timings that got slower by more
+/// than `threshold` percent; returns an error if any regressions were found.
+pub fn compare(old_path: &Path, new_path: &Path, threshold: f64) -> Result<()> {
+    let key = |r: &Record| (r.codec.clone(), r.file.clone());
+    let old: BTreeMap<_, _> = read_records(old_path)?.into_iter().map(|r| (key(&r), r)).collect();
+    let new: BTreeMap<_, _> = read_records(new_path)?.into_iter().map(|r| (key(&r), r)).collect();
+
+    let (w_name, w_col) = (9, 13);
+    let mut totals: BTreeMap<&str, [f64; 4]> = BTreeMap::new();
+    let mut n_regressions = 0;
+    for ((codec, file), r_new) in &new {
+        let r_old = match old.get(&(codec.clone(), file.clone())) {
+            Some(r) => r,
+            None => continue,
+        };
+        let encode_ms = r_old.encode_ms.zip(r_new.encode_ms);
+        let t = totals.entry(codec.as_str()).or_default();
+        t[0] += r_old.decode_ms;
+        t[1] += r_new.decode_ms;
+        if let Some((t_old, t_new)) = encode_ms {
+            t[2] += t_old;
+            t[3] += t_new;
+        }
+        let timings = [
+            Some(("decode", (r_old.decode_ms, r_new.decode_ms))),
+            encode_ms.map(|t| ("encode", t)),
+        ];
+        for &(op, (t_old, t_new)) in timings.iter().flatten() {
+            let change = pct_change(t_old, t_new);
+            if change > threshold {
+                n_regressions += 1;
+                println!(
+                    "REGRESSION {:<w$} {} {}: {:.3} ms -> {:.3} ms ({:+.1}%)",
+                    codec,
+                    op,
+                    file,
+                    t_old,
+                    t_new,
+                    change,
+                    w = w_name
+                );
+            }
+        }
+    }
+    if totals.is_empty() {
+        bail!("no common (codec, file) entries found in the result files");
+    }
+
+    println!("---");
+    print!("{:<w$}", "", w = w_name);
+    print!("{:>w$}", "decode:old", w = w_col);
+    print!("{:>w$}", "decode:new", w = w_col);
+    print!("{:>w$}", "decode:%", w = w_col);
+    print!("{:>w$}", "encode:old", w = w_col);
+    print!("{:>w$}", "encode:new", w = w_col);
+    print!("{:>w$}", "encode:%", w = w_col);
+    println!();
+    for (codec, t) in &totals {
+        print!("{:<w$}", codec, w = w_name);
+        print!("{:>w$.2}", t[0], w = w_col);
+        print!("{:>w$.2}", t[1], w = w_col);
+        print!("{:>+w$.1}", pct_change(t[0], t[1]), w = w_col);
+        print!("{:>w$.2}", t[2], w = w_col);
+        print!("{:>w$.2}", t[3], w = w_col);
+        print!("{:>+w$.1}", pct_change(t[2], t[3]), w = w_col);
+        println!();
+    }
+
+    if n_regressions != 0 {
+        bail!("{} regression(s) found beyond the {}% threshold", n_regressions, threshold);
+    }
+    Ok(())
+}
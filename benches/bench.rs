@@ -0,0 +1,117 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::{rngs::StdRng, SeedableRng};
+
+use qoi::{
+    testutil, ChannelMap, ChannelSource, Channels, Decoder, Encoder, EncoderBuilder, SourceChannels,
+};
+
+const WIDTH: u32 = 512;
+const HEIGHT: u32 = 512;
+
+type Generator = fn(usize) -> Vec<u8>;
+
+fn channels(channels: usize) -> Channels {
+    Channels::try_from(channels as u8).unwrap()
+}
+
+fn gen_gradient(n: usize) -> Vec<u8> {
+    testutil::gradient(WIDTH, HEIGHT, channels(n))
+}
+
+fn gen_noise(n: usize) -> Vec<u8> {
+    testutil::noise(&mut StdRng::seed_from_u64(0), WIDTH, HEIGHT, channels(n))
+}
+
+fn gen_screenshot(n: usize) -> Vec<u8> {
+    testutil::screenshot(&mut StdRng::seed_from_u64(1), WIDTH, HEIGHT, channels(n))
+}
+
+fn gen_runs(n: usize) -> Vec<u8> {
+    testutil::runs(&mut StdRng::seed_from_u64(2), WIDTH, HEIGHT, channels(n))
+}
+
+fn bench_codec(c: &mut Criterion) {
+    let generators: [(&str, Generator); 4] = [
+        ("gradient", gen_gradient),
+        ("noise", gen_noise),
+        ("screenshot", gen_screenshot),
+        ("runs", gen_runs),
+    ];
+    for channels in [3, 4] {
+        let mut group = c.benchmark_group(format!("rgb{}", if channels == 4 { "a" } else { "" }));
+        group.throughput(Throughput::Elements(u64::from(WIDTH * HEIGHT)));
+        for (name, gen) in generators {
+            let pixels = gen(channels);
+            let encoder = Encoder::new(&pixels, WIDTH, HEIGHT).unwrap();
+            let encoded = encoder.encode_to_vec().unwrap();
+
+            group.bench_function(BenchmarkId::new("encode_to_buf", name), |b| {
+                let mut buf = vec![0; encoder.required_buf_len()];
+                b.iter(|| encoder.encode_to_buf(&mut buf).unwrap());
+            });
+            group.bench_function(BenchmarkId::new("encode_to_stream", name), |b| {
+                let mut buf = Vec::with_capacity(encoder.required_buf_len());
+                b.iter(|| {
+                    buf.clear();
+                    encoder.encode_to_stream(&mut buf).unwrap()
+                });
+            });
+            group.bench_function(BenchmarkId::new("decode_to_buf", name), |b| {
+                let mut buf = vec![0; pixels.len()];
+                b.iter(|| Decoder::new(&encoded).unwrap().decode_to_buf(&mut buf).unwrap());
+            });
+            group.bench_function(BenchmarkId::new("decode_from_stream", name), |b| {
+                let mut buf = vec![0; pixels.len()];
+                b.iter(|| {
+                    Decoder::from_stream(encoded.as_slice())
+                        .unwrap()
+                        .decode_to_buf(&mut buf)
+                        .unwrap()
+                });
+            });
+        }
+        group.finish();
+    }
+}
+
+fn bench_source_channels(c: &mut Criterion) {
+    let custom = ChannelMap::new(
+        6,
+        [ChannelSource::Byte(4), ChannelSource::Byte(2), ChannelSource::Byte(0)],
+        Some(ChannelSource::Const(0xff)),
+    )
+    .unwrap();
+    let sources = [
+        ("rgb", SourceChannels::Rgb),
+        ("bgr", SourceChannels::Bgr),
+        ("rgba", SourceChannels::Rgba),
+        ("bgra", SourceChannels::Bgra),
+        ("argb", SourceChannels::Argb),
+        ("abgr", SourceChannels::Abgr),
+        ("rgbx", SourceChannels::Rgbx),
+        ("xrgb", SourceChannels::Xrgb),
+        ("bgrx", SourceChannels::Bgrx),
+        ("xbgr", SourceChannels::Xbgr),
+        ("custom", SourceChannels::Custom(custom)),
+    ];
+    let rgba = gen_screenshot(4);
+    let mut group = c.benchmark_group("source_channels");
+    group.throughput(Throughput::Elements(u64::from(WIDTH * HEIGHT)));
+    for (name, source) in sources {
+        let bpp = source.channel_map().bytes_per_pixel();
+        let pixels: Vec<u8> =
+            rgba.chunks_exact(4).flat_map(|p| p.iter().cycle().take(bpp)).copied().collect();
+        let encoder = EncoderBuilder::new(&pixels, WIDTH, HEIGHT)
+            .with_source_channels(source)
+            .build()
+            .unwrap();
+        group.bench_function(BenchmarkId::new("encode_to_buf", name), |b| {
+            let mut buf = vec![0; encoder.required_buf_len()];
+            b.iter(|| encoder.encode_to_buf(&mut buf).unwrap());
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_codec, bench_source_channels);
+criterion_main!(benches);
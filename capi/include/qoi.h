@@ -0,0 +1,58 @@
+/*
+ * C API of the `qoi-capi` library, compatible with the reference qoi.h
+ * (https://github.com/phoboslab/qoi): declarations are the same, so C/C++ code
+ * written against qoi.h can be linked against this library instead of defining
+ * QOI_IMPLEMENTATION. Buffers returned by qoi_encode, qoi_decode and qoi_read
+ * are allocated via malloc() and must be released via free().
+ */
+
+#ifndef QOI_H
+#define QOI_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define QOI_SRGB   0
+#define QOI_LINEAR 1
+
+typedef struct {
+	unsigned int width;
+	unsigned int height;
+	unsigned char channels;
+	unsigned char colorspace;
+} qoi_desc;
+
+/* Encode raw RGB or RGBA pixels into a QOI image and write it to a file.
+   Returns the number of bytes written, or 0 on failure. */
+int qoi_write(const char *filename, const void *data, const qoi_desc *desc);
+
+/* Read and decode a QOI image from a file. If channels is 0, the number of
+   channels from the file header is used, otherwise it must be 3 or 4.
+   Returns the decoded pixels and fills desc, or returns NULL on failure. */
+void *qoi_read(const char *filename, qoi_desc *desc, int channels);
+
+/* Encode raw RGB or RGBA pixels into a QOI image in memory.
+   Returns the encoded image and sets out_len, or returns NULL on failure. */
+void *qoi_encode(const void *data, const qoi_desc *desc, int *out_len);
+
+/* Decode a QOI image from memory, see qoi_read() for channels.
+   Returns the decoded pixels and fills desc, or returns NULL on failure. */
+void *qoi_decode(const void *data, int size, qoi_desc *desc, int channels);
+
+/* Extensions (not part of the reference qoi.h). */
+
+/* Version of the Rust `qoi` crate backing this library, e.g. "0.4.1". */
+const char *qoi_rs_version(void);
+
+/* 1 if the given `qoi` crate feature (e.g. "reference") is enabled, 0 otherwise. */
+int qoi_rs_has_feature(const char *name);
+
+/* 1 if the given non-standard format extension (e.g. "gray-alpha") is supported. */
+int qoi_rs_has_extension(const char *name);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* QOI_H */
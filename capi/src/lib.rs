@@ -0,0 +1,241 @@
+//! C API compatible with the reference [`qoi.h`](https://github.com/phoboslab/qoi), backed
+//! by the `qoi` crate.
+//!
+//! Exports `qoi_encode`, `qoi_decode`, `qoi_write` and `qoi_read` with the same signatures
+//! and conventions as `qoi.h` (buffers are allocated via `malloc` and must be released via
+//! `free`, failures are reported as `NULL` or `0`), so that C/C++ projects can link against
+//! this library instead of compiling the reference implementation. See `include/qoi.h`.
+//!
+//! Unlike the reference decoder, which decodes truncated or corrupt input as far as it goes,
+//! this one rejects such input; the output for valid images is the same.
+//!
+//! In addition, `qoi_rs_version`, `qoi_rs_has_feature` and `qoi_rs_has_extension` allow
+//! querying the capabilities of the build at runtime (see [`qoi::Feature`]).
+
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::path::PathBuf;
+use std::ptr;
+use std::slice;
+use std::sync::OnceLock;
+
+use libc::{c_char, c_int, c_uchar, c_uint, c_void};
+
+use qoi::consts::{QOI_HEADER_SIZE, QOI_PADDING_SIZE};
+use qoi::{Channels, ColorSpace, Decoder, EncoderBuilder, Feature, Header};
+
+/// Image description, same layout as `qoi_desc` in `qoi.h`.
+#[derive(Debug, Copy, Clone, Default)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub struct qoi_desc {
+    pub width: c_uint,
+    pub height: c_uint,
+    pub channels: c_uchar,
+    pub colorspace: c_uchar,
+}
+
+impl qoi_desc {
+    fn header(&self) -> Option<Header> {
+        let channels = Channels::try_from(self.channels).ok()?;
+        let colorspace = ColorSpace::try_from(self.colorspace).ok()?;
+        Header::try_new(self.width, self.height, channels, colorspace).ok()
+    }
+}
+
+impl From<Header> for qoi_desc {
+    fn from(header: Header) -> Self {
+        Self {
+            width: header.width,
+            height: header.height,
+            channels: header.channels.as_u8(),
+            colorspace: header.colorspace.into(),
+        }
+    }
+}
+
+/// Copies the data into a new `malloc`-allocated buffer that the caller must `free`.
+unsafe fn malloc_copy(data: &[u8]) -> *mut c_void {
+    let ptr = libc::malloc(data.len().max(1));
+    if !ptr.is_null() {
+        ptr::copy_nonoverlapping(data.as_ptr(), ptr.cast::<u8>(), data.len());
+    }
+    ptr
+}
+
+unsafe fn encode(data: *const c_void, desc: *const qoi_desc) -> Option<Vec<u8>> {
+    if data.is_null() || desc.is_null() {
+        return None;
+    }
+    let header = (*desc).header()?;
+    let len = header.n_bytes();
+    let data = slice::from_raw_parts(data.cast::<u8>(), len);
+    let encoder = EncoderBuilder::new(data, header.width, header.height)
+        .with_source_channels(header.channels.into())
+        .with_colorspace(header.colorspace)
+        .build()
+        .ok()?;
+    encoder.encode_to_vec().ok()
+}
+
+unsafe fn decode(data: &[u8], desc: *mut qoi_desc, channels: c_int) -> Option<Vec<u8>> {
+    if desc.is_null() || data.len() < QOI_HEADER_SIZE + QOI_PADDING_SIZE {
+        return None;
+    }
+    let mut decoder = Decoder::new(data).ok()?;
+    *desc = (*decoder.header()).into();
+    if channels != 0 {
+        decoder = decoder.with_channels(u8::try_from(channels).ok()?).ok()?;
+    }
+    decoder.decode_to_vec().ok()
+}
+
+unsafe fn path(filename: *const c_char) -> Option<PathBuf> {
+    if filename.is_null() {
+        return None;
+    }
+    let filename = CStr::from_ptr(filename);
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Some(std::ffi::OsStr::from_bytes(filename.to_bytes()).into())
+    }
+    #[cfg(not(unix))]
+    {
+        filename.to_str().ok().map(Into::into)
+    }
+}
+
+/// Encodes raw RGB or RGBA pixels into a QOI image in memory.
+///
+/// Returns a pointer to the encoded image (to be released via `free`) and stores its size
+/// in `out_len`, or returns `NULL` on failure (invalid arguments or description).
+///
+/// # Safety
+///
+/// `data` must point to `width * height * channels` readable bytes as described by `desc`;
+/// `desc` and `out_len` must be valid pointers (or `NULL`).
+#[no_mangle]
+pub unsafe extern "C" fn qoi_encode(
+    data: *const c_void, desc: *const qoi_desc, out_len: *mut c_int,
+) -> *mut c_void {
+    if out_len.is_null() {
+        return ptr::null_mut();
+    }
+    match encode(data, desc) {
+        Some(encoded) => match c_int::try_from(encoded.len()) {
+            Ok(len) => {
+                let ptr = malloc_copy(&encoded);
+                if !ptr.is_null() {
+                    *out_len = len;
+                }
+                ptr
+            }
+            Err(_) => ptr::null_mut(),
+        },
+        None => ptr::null_mut(),
+    }
+}
+
+/// Decodes a QOI image from memory into raw pixels.
+///
+/// Fills `desc` with the image description and returns a pointer to the decoded pixels
+/// (to be released via `free`), or returns `NULL` on failure. If `channels` is 0, the
+/// number of channels in the image is used, otherwise it must be 3 or 4.
+///
+/// # Safety
+///
+/// `data` must point to `size` readable bytes; `desc` must be a valid pointer (or `NULL`).
+#[no_mangle]
+pub unsafe extern "C" fn qoi_decode(
+    data: *const c_void, size: c_int, desc: *mut qoi_desc, channels: c_int,
+) -> *mut c_void {
+    let size = match usize::try_from(size) {
+        Ok(size) if !data.is_null() => size,
+        _ => return ptr::null_mut(),
+    };
+    let data = slice::from_raw_parts(data.cast::<u8>(), size);
+    decode(data, desc, channels).map_or(ptr::null_mut(), |decoded| malloc_copy(&decoded))
+}
+
+/// Encodes raw RGB or RGBA pixels into a QOI image and writes it to a file.
+///
+/// Returns the number of bytes written, or 0 on failure.
+///
+/// # Safety
+///
+/// `filename` must be a valid NUL-terminated string (or `NULL`); see [`qoi_encode`] for
+/// `data` and `desc`.
+#[no_mangle]
+pub unsafe extern "C" fn qoi_write(
+    filename: *const c_char, data: *const c_void, desc: *const qoi_desc,
+) -> c_int {
+    let (path, encoded) = match (path(filename), encode(data, desc)) {
+        (Some(path), Some(encoded)) => (path, encoded),
+        _ => return 0,
+    };
+    match c_int::try_from(encoded.len()) {
+        Ok(len) if fs::write(path, encoded).is_ok() => len,
+        _ => 0,
+    }
+}
+
+/// Reads a QOI image from a file and decodes it into raw pixels.
+///
+/// Fills `desc` with the image description and returns a pointer to the decoded pixels
+/// (to be released via `free`), or returns `NULL` on failure; see [`qoi_decode`] for
+/// `channels`.
+///
+/// # Safety
+///
+/// `filename` must be a valid NUL-terminated string (or `NULL`); `desc` must be a valid
+/// pointer (or `NULL`).
+#[no_mangle]
+pub unsafe extern "C" fn qoi_read(
+    filename: *const c_char, desc: *mut qoi_desc, channels: c_int,
+) -> *mut c_void {
+    match path(filename).and_then(|path| fs::read(path).ok()) {
+        Some(data) => {
+            decode(&data, desc, channels).map_or(ptr::null_mut(), |decoded| malloc_copy(&decoded))
+        }
+        None => ptr::null_mut(),
+    }
+}
+
+/// Returns the version of the `qoi` crate backing this library as a static NUL-terminated
+/// string, e.g. `"0.4.1"`.
+#[no_mangle]
+pub extern "C" fn qoi_rs_version() -> *const c_char {
+    static VERSION: OnceLock<CString> = OnceLock::new();
+    VERSION.get_or_init(|| CString::new(qoi::crate_version()).unwrap_or_default()).as_ptr()
+}
+
+/// Returns 1 if the `qoi` crate feature with the given name (e.g. `"reference"`) is
+/// compiled into this library, or 0 if it's disabled or unknown.
+///
+/// # Safety
+///
+/// `name` must be a valid NUL-terminated string (or `NULL`).
+#[no_mangle]
+pub unsafe extern "C" fn qoi_rs_has_feature(name: *const c_char) -> c_int {
+    if name.is_null() {
+        return 0;
+    }
+    let name = CStr::from_ptr(name).to_str().ok();
+    c_int::from(name.and_then(Feature::from_name).is_some_and(Feature::is_enabled))
+}
+
+/// Returns 1 if the non-standard format extension with the given name (e.g. `"gray-alpha"`)
+/// is supported by this library, or 0 otherwise.
+///
+/// # Safety
+///
+/// `name` must be a valid NUL-terminated string (or `NULL`).
+#[no_mangle]
+pub unsafe extern "C" fn qoi_rs_has_extension(name: *const c_char) -> c_int {
+    if name.is_null() {
+        return 0;
+    }
+    let name = CStr::from_ptr(name).to_bytes();
+    c_int::from(qoi::supported_extensions().iter().any(|ext| ext.as_bytes() == name))
+}
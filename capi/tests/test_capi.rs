@@ -0,0 +1,115 @@
+use std::ffi::{CStr, CString};
+use std::path::Path;
+use std::ptr;
+use std::slice;
+
+use libc::{c_int, c_void};
+
+use qoi_rs::{
+    qoi_decode, qoi_desc, qoi_encode, qoi_read, qoi_rs_has_extension, qoi_rs_has_feature,
+    qoi_rs_version, qoi_write,
+};
+
+/// Takes ownership of a `malloc`-allocated buffer returned by the C API.
+unsafe fn take(ptr: *mut c_void, len: usize) -> Vec<u8> {
+    assert!(!ptr.is_null());
+    let vec = slice::from_raw_parts(ptr.cast::<u8>(), len).to_vec();
+    libc::free(ptr);
+    vec
+}
+
+#[test]
+fn test_encode_decode() {
+    let encoded = std::fs::read("../assets/dice.qoi").unwrap();
+    let (header, rgba) = qoi::decode_to_vec(&encoded).unwrap();
+    let rgb: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+
+    unsafe {
+        for (channels, pixels) in [(0, &rgba), (3, &rgb), (4, &rgba)] {
+            let mut desc = qoi_desc::default();
+            let size = encoded.len() as c_int;
+            let ptr = qoi_decode(encoded.as_ptr().cast(), size, &mut desc, channels);
+            assert_eq!((desc.width, desc.height, desc.channels), (800, 600, 4));
+            assert_eq!(desc.colorspace, u8::from(header.colorspace));
+            assert_eq!(&take(ptr, pixels.len()), pixels);
+        }
+
+        let desc = qoi_desc { width: 800, height: 600, channels: 3, colorspace: 1 };
+        let mut out_len = 0;
+        let ptr = qoi_encode(rgb.as_ptr().cast(), &desc, &mut out_len);
+        let encoded = take(ptr, out_len as usize);
+        let (header, decoded) = qoi::decode_to_vec(&encoded).unwrap();
+        assert_eq!((header.width, header.height, header.channels.as_u8()), (800, 600, 3));
+        assert_eq!(u8::from(header.colorspace), 1);
+        assert_eq!(decoded, rgb);
+    }
+}
+
+#[test]
+fn test_invalid_arguments() {
+    let encoded = std::fs::read("../assets/dice.qoi").unwrap();
+    let pixels = [0_u8; 16];
+    let (data, size) = (encoded.as_ptr().cast(), encoded.len() as c_int);
+    let mut desc = qoi_desc::default();
+    let mut out_len = 0;
+
+    unsafe {
+        assert!(qoi_decode(ptr::null(), size, &mut desc, 0).is_null());
+        assert!(qoi_decode(data, size, ptr::null_mut(), 0).is_null());
+        assert!(qoi_decode(data, 21, &mut desc, 0).is_null());
+        assert!(qoi_decode(data, -1, &mut desc, 0).is_null());
+        assert!(qoi_decode(data, size, &mut desc, 2).is_null());
+        // truncated input is rejected rather than decoded partially
+        assert!(qoi_decode(data, size - 100, &mut desc, 0).is_null());
+
+        for (width, height, channels, colorspace) in [(0, 1, 3, 0), (2, 2, 5, 0), (2, 2, 4, 2)] {
+            let desc = qoi_desc { width, height, channels, colorspace };
+            assert!(qoi_encode(pixels.as_ptr().cast(), &desc, &mut out_len).is_null());
+        }
+        let desc = qoi_desc { width: 2, height: 2, channels: 4, colorspace: 0 };
+        assert!(qoi_encode(ptr::null(), &desc, &mut out_len).is_null());
+        assert!(qoi_encode(pixels.as_ptr().cast(), ptr::null(), &mut out_len).is_null());
+        assert!(qoi_encode(pixels.as_ptr().cast(), &desc, ptr::null_mut()).is_null());
+        assert_eq!(out_len, 0);
+    }
+}
+
+#[test]
+fn test_read_write() {
+    let pixels: Vec<u8> = (0..64 * 48 * 4).map(|i| (i % 251) as u8).collect();
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("qoi-capi-test.qoi");
+    let filename = CString::new(path.to_str().unwrap()).unwrap();
+
+    unsafe {
+        let desc = qoi_desc { width: 64, height: 48, channels: 4, colorspace: 0 };
+        let size = qoi_write(filename.as_ptr(), pixels.as_ptr().cast(), &desc);
+        assert_eq!(size as usize, std::fs::metadata(&path).unwrap().len() as usize);
+        assert_eq!(std::fs::read(&path).unwrap(), qoi::encode_to_vec(&pixels, 64, 48).unwrap());
+
+        let mut desc = qoi_desc::default();
+        let ptr = qoi_read(filename.as_ptr(), &mut desc, 0);
+        assert_eq!((desc.width, desc.height, desc.channels), (64, 48, 4));
+        assert_eq!(take(ptr, pixels.len()), pixels);
+
+        let missing = CString::new(path.with_extension("missing").to_str().unwrap()).unwrap();
+        assert!(qoi_read(missing.as_ptr(), &mut desc, 0).is_null());
+        assert!(qoi_read(ptr::null(), &mut desc, 0).is_null());
+        assert_eq!(qoi_write(ptr::null(), pixels.as_ptr().cast(), &desc), 0);
+    }
+}
+
+#[test]
+fn test_capabilities() {
+    let version = unsafe { CStr::from_ptr(qoi_rs_version()) };
+    assert_eq!(version.to_str().unwrap(), qoi::crate_version());
+    let has_feature =
+        |name: &str| unsafe { qoi_rs_has_feature(CString::new(name).unwrap().as_ptr()) != 0 };
+    assert!(has_feature("std"));
+    assert_eq!(has_feature("reference"), qoi::Feature::Reference.is_enabled());
+    assert!(!has_feature("animation"));
+    unsafe {
+        assert_eq!(qoi_rs_has_feature(ptr::null()), 0);
+        let name = CString::new("gray-alpha").unwrap();
+        assert_eq!(qoi_rs_has_extension(name.as_ptr()) != 0, qoi::Feature::GrayAlpha.is_enabled());
+    }
+}
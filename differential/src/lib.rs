@@ -0,0 +1,72 @@
+//! Differential tests checking that images encoded by this crate can be decoded by other
+//! Rust QOI implementations and vice versa, pixel for pixel.
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use rapid_qoi::{Colors, Qoi};
+
+    /// Raw test images: `(width, height, channels, pixels)`.
+    fn corpus() -> Vec<(u32, u32, u8, Vec<u8>)> {
+        let mut images = vec![];
+        for entry in fs::read_dir("../assets").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().map_or(false, |ext| ext == "qoi") {
+                let (header, pixels) = qoi::decode_to_vec(fs::read(&path).unwrap()).unwrap();
+                images.push((header.width, header.height, header.channels.as_u8(), pixels));
+            }
+        }
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..200 {
+            let (w, h, channels) =
+                (rng.gen_range(1..64), rng.gen_range(1..64), rng.gen_range(3..=4));
+            // a small palette with occasional noise exercises all of the op types
+            let palette: Vec<[u8; 4]> = (0..rng.gen_range(1..16)).map(|_| rng.gen()).collect();
+            let mut pixels: Vec<u8> = vec![];
+            for _ in 0..w * h {
+                let px = if rng.gen_bool(0.8) {
+                    palette[rng.gen_range(0..palette.len())]
+                } else {
+                    let prev = pixels.len().checked_sub(channels as usize).map_or(0, |i| pixels[i]);
+                    [prev.wrapping_add(rng.gen_range(0..3)), rng.gen(), rng.gen(), rng.gen()]
+                };
+                pixels.extend_from_slice(&px[..channels as usize]);
+            }
+            images.push((w, h, channels, pixels));
+        }
+        images
+    }
+
+    fn rapid_colors(channels: u8) -> Colors {
+        if channels == 4 {
+            Colors::SrgbLinA
+        } else {
+            Colors::Srgb
+        }
+    }
+
+    #[test]
+    fn test_decode_with_rapid_qoi() {
+        for (w, h, channels, pixels) in corpus() {
+            let encoded = qoi::encode_to_vec(&pixels, w, h).unwrap();
+            let (header, decoded) = Qoi::decode_alloc(&encoded).unwrap();
+            assert_eq!((header.width, header.height), (w, h));
+            assert_eq!(header.colors.channels(), channels as usize);
+            assert_eq!(decoded, pixels);
+        }
+    }
+
+    #[test]
+    fn test_decode_rapid_qoi_output() {
+        for (w, h, channels, pixels) in corpus() {
+            let qoi = Qoi { width: w, height: h, colors: rapid_colors(channels) };
+            let encoded = qoi.encode_alloc(&pixels).unwrap();
+            let (header, decoded) = qoi::decode_to_vec(&encoded).unwrap();
+            assert_eq!((header.width, header.height), (w, h));
+            assert_eq!(header.channels.as_u8(), channels);
+            assert_eq!(decoded, pixels);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use qoi::anim::{AnimationDecoder, AnimationHeader};
+use qoi::{Channels, Decoder};
+
+fuzz_target!(|input: (u8, u8, Option<bool>, &[u8])| {
+    let (w, h, to_4, data) = input;
+    let header = AnimationHeader::try_new(1 + u32::from(w % 16), 1 + u32::from(h % 16)).unwrap();
+    let mut container = header.encode().to_vec();
+    container.extend_from_slice(data);
+    let channels = to_4.map(|to_4| if to_4 { Channels::Rgba } else { Channels::Rgb });
+
+    let mut decoder = AnimationDecoder::new(&container).unwrap();
+    if let Some(channels) = channels {
+        decoder = decoder.with_channels(channels).unwrap();
+    }
+    // every frame must match its encoded image decoded on its own
+    let mut frames = AnimationDecoder::new(&container).unwrap();
+    let mut failed = false;
+    for frame in decoder.by_ref() {
+        let encoded = frames.next_encoded_frame();
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(_) => {
+                failed = true;
+                break;
+            }
+        };
+        let (duration_ms, encoded) = encoded.unwrap().unwrap();
+        assert_eq!(frame.duration_ms, duration_ms);
+        let mut expected = Decoder::new(encoded).unwrap();
+        if let Some(channels) = channels {
+            expected = expected.with_channels(channels).unwrap();
+        }
+        assert_eq!(frame.header.channels, expected.channels());
+        assert_eq!((frame.header.width, frame.header.height), (header.width, header.height));
+        assert_eq!(frame.pixels, expected.decode_to_vec().unwrap());
+    }
+    // the iteration ends after the end marker or after the first error
+    assert!(decoder.is_finished());
+    assert!(decoder.next().is_none());
+
+    if !failed {
+        // skipping over the frames finds all of them
+        let mut decoder = AnimationDecoder::new(&container).unwrap();
+        assert_eq!(decoder.frame_count().unwrap(), frames.frames_read());
+        assert_eq!(decoder.frames_read(), 0);
+    }
+});
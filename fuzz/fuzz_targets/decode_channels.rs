@@ -0,0 +1,85 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use qoi::ops::{Op, Ops};
+use qoi::{Channels, Decoder, Pixel};
+
+/// Straightforward model decoder built on top of `Ops`, always producing RGBA pixels;
+/// RGBA ops are only accepted if the header says the image has alpha.
+fn decode_model(data: &[u8]) -> Option<Vec<[u8; 4]>> {
+    let ops = Ops::new(data).ok()?;
+    let (n_pixels, has_alpha) = (ops.header().n_pixels(), ops.header().channels.is_rgba());
+    let mut index = [[0_u8; 4]; 64];
+    let mut px = [0, 0, 0, 0xff];
+    let mut out = Vec::with_capacity(n_pixels);
+    for op in ops {
+        let mut n = 1;
+        match *op.as_ref().ok()? {
+            Op::Index(i) => px = index[i as usize],
+            Op::Diff { dr, dg, db } => {
+                px[0] = px[0].wrapping_add(dr as u8);
+                px[1] = px[1].wrapping_add(dg as u8);
+                px[2] = px[2].wrapping_add(db as u8);
+            }
+            Op::Luma { dg, dr_dg, db_dg } => {
+                px[0] = px[0].wrapping_add(dg.wrapping_add(dr_dg) as u8);
+                px[1] = px[1].wrapping_add(dg as u8);
+                px[2] = px[2].wrapping_add(dg.wrapping_add(db_dg) as u8);
+            }
+            Op::Rgb { r, g, b } => px = [r, g, b, px[3]],
+            Op::Rgba { r, g, b, a } if has_alpha => px = [r, g, b, a],
+            Op::Rgba { .. } => return None,
+            Op::Run(run) => n = run as usize,
+        }
+        // like the decoder, only store new colors: index and run ops can't produce any,
+        // except for an index op pointing at an initial run of the default pixel
+        if !matches!(op, Ok(Op::Index(_) | Op::Run(_))) {
+            index[Pixel::from_array(px).hash_index() as usize] = px;
+        }
+        out.extend((0..n).map(|_| px));
+    }
+    out.truncate(n_pixels);
+    Some(out)
+}
+
+fuzz_target!(|input: (u16, u16, bool, &[u8])| {
+    let (w, h, is_4, data) = input;
+    let (w, h) = (1 + w % 260, 1 + h % 260);
+    let channels = if is_4 { 4 } else { 3 };
+
+    let mut vec = vec![
+        b'q',
+        b'o',
+        b'i',
+        b'f',
+        0,
+        0,
+        (w >> 8) as u8,
+        (w & 0xff) as u8,
+        0,
+        0,
+        (h >> 8) as u8,
+        (h & 0xff) as u8,
+        channels,
+        0,
+    ];
+    vec.extend(&*data);
+    vec.extend(&[0, 0, 0, 0, 0, 0, 0, 1]);
+
+    let expected = decode_model(&vec);
+    for out_channels in [Channels::Rgb, Channels::Rgba] {
+        let decoded =
+            Decoder::new(&vec).unwrap().with_channels(out_channels).unwrap().decode_to_vec();
+        let n = out_channels.as_u8() as usize;
+        // alpha is dropped when decoding as RGB, and kept as is otherwise
+        let expected =
+            expected.as_ref().map(|v| v.iter().flat_map(|px| px[..n].to_vec()).collect());
+        assert_eq!(
+            decoded.ok(),
+            expected,
+            "{:?} -> {:?}",
+            Channels::try_from(channels),
+            out_channels
+        );
+    }
+});
@@ -0,0 +1,69 @@
+#![no_main]
+use std::io::{self, Read};
+
+use libfuzzer_sys::fuzz_target;
+
+use qoi::{Channels, Decoder};
+
+/// Reader that returns short reads of the given sizes in a loop, with zero sizes
+/// standing for interrupted reads (each followed by a single-byte read).
+struct ChoppyReader<'a> {
+    data: &'a [u8],
+    sizes: &'a [u8],
+    i: usize,
+    interrupted: bool,
+}
+
+impl Read for ChoppyReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = if self.sizes.is_empty() { usize::MAX } else { self.sizes[self.i] as usize };
+        self.i = (self.i + 1) % self.sizes.len().max(1);
+        if size == 0 && !std::mem::replace(&mut self.interrupted, true) {
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+        self.interrupted = false;
+        let n = size.max(1).min(buf.len()).min(self.data.len());
+        buf[..n].copy_from_slice(&self.data[..n]);
+        self.data = &self.data[n..];
+        Ok(n)
+    }
+}
+
+fuzz_target!(|input: (u16, u16, bool, bool, &[u8], &[u8])| {
+    let (w, h, is_4, to_4, sizes, data) = input;
+    let (w, h) = (1 + w % 260, 1 + h % 260);
+    let channels = if is_4 { 4 } else { 3 };
+
+    let mut vec = vec![
+        b'q',
+        b'o',
+        b'i',
+        b'f',
+        0,
+        0,
+        (w >> 8) as u8,
+        (w & 0xff) as u8,
+        0,
+        0,
+        (h >> 8) as u8,
+        (h & 0xff) as u8,
+        channels,
+        0,
+    ];
+    vec.extend(&*data);
+    vec.extend(&[0, 0, 0, 0, 0, 0, 0, 1]);
+
+    let out_channels = if to_4 { Channels::Rgba } else { Channels::Rgb };
+    let decode_slice = || Decoder::new(&vec)?.with_channels(out_channels)?.decode_to_vec();
+    let decode_stream = || {
+        let reader = ChoppyReader { data: &vec, sizes, i: 0, interrupted: false };
+        Decoder::from_stream(reader)?.with_channels(out_channels)?.decode_to_vec()
+    };
+    match (decode_slice(), decode_stream()) {
+        (Ok(expected), Ok(out)) => assert_eq!(out, expected),
+        (Err(_), Err(_)) => {}
+        (expected, out) => {
+            panic!("slice and stream decoding diverged: {:?} vs {:?}", expected.err(), out.err())
+        }
+    }
+});
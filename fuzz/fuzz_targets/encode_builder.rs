@@ -0,0 +1,87 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use qoi::{
+    decode_to_vec, ChannelMap, ChannelSource, Decoder, EncoderBuilder, HeaderOptions,
+    SourceChannels,
+};
+
+fn source_channels(variant: u8, custom: [u8; 5]) -> Option<SourceChannels> {
+    use SourceChannels::*;
+    let channel = |v: u8| {
+        if v & 0x80 == 0 {
+            ChannelSource::Byte(v & 0x07)
+        } else {
+            ChannelSource::Const(v)
+        }
+    };
+    Some(match variant % 11 {
+        0 => Rgb,
+        1 => Bgr,
+        2 => Rgba,
+        3 => Argb,
+        4 => Rgbx,
+        5 => Xrgb,
+        6 => Bgra,
+        7 => Abgr,
+        8 => Bgrx,
+        9 => Xbgr,
+        _ => {
+            let rgb = [channel(custom[1]), channel(custom[2]), channel(custom[3])];
+            let alpha = Some(channel(custom[4])).filter(|_| custom[0] & 0x80 != 0);
+            Custom(ChannelMap::new(1 + custom[0] % 8, rgb, alpha).ok()?)
+        }
+    })
+}
+
+fuzz_target!(|input: (u8, u8, Option<u16>, u8, [u8; 5], bool, bool, &[u8])| {
+    let (w, h, stride, variant, custom, allow_oversized, allow_empty, data) = input;
+    let (w, h) = (w % 32, h % 32);
+    let source = match source_channels(variant, custom) {
+        Some(source) => source,
+        None => return,
+    };
+    let map = source.channel_map();
+    let (bpp, n_channels) = (map.bytes_per_pixel(), map.channels().as_u8() as usize);
+    let row_len = w as usize * bpp;
+    let stride_or_default = stride.map_or(row_len, usize::from);
+    let required = if h == 0 { 0 } else { stride_or_default * (h as usize - 1) + row_len };
+
+    let mut builder = EncoderBuilder::new(data, w.into(), h.into())
+        .with_source_channels(source)
+        .with_allow_oversized(allow_oversized)
+        .with_allow_empty(allow_empty);
+    if let Some(stride) = stride {
+        builder = builder.with_stride(stride.into());
+    }
+    let encoder = builder.build();
+
+    let is_valid = (w != 0 && h != 0 || allow_empty)
+        && stride_or_default >= row_len
+        && (data.len() == required || data.len() > required && allow_oversized);
+    assert_eq!(encoder.is_ok(), is_valid, "{:?}", encoder.err());
+    let encoded = match encoder {
+        Ok(encoder) => encoder.encode_to_vec().unwrap(),
+        Err(_) => return,
+    };
+
+    let mut expected = Vec::with_capacity(w as usize * h as usize * n_channels);
+    for y in 0..h as usize {
+        for x in 0..w as usize {
+            let px = &data[y * stride_or_default + x * bpp..][..bpp];
+            for &channel in &map.sources()[..n_channels] {
+                expected.push(match channel {
+                    ChannelSource::Byte(offset) => px[offset as usize],
+                    ChannelSource::Const(value) => value,
+                });
+            }
+        }
+    }
+    let decoded = if w == 0 || h == 0 {
+        let options = HeaderOptions::new().with_allow_empty(true);
+        Decoder::new_with_options(&encoded, options).unwrap().decode_to_vec().unwrap()
+    } else {
+        decode_to_vec(&encoded).unwrap().1
+    };
+    assert_eq!(decoded, expected);
+});
@@ -0,0 +1,45 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use qoi::{encode_to_vec, EncoderBuilder, SourceChannels};
+
+fuzz_target!(|input: (u8, u8, &[u8])| {
+    use SourceChannels::*;
+
+    let (w_frac, junk, data) = input;
+    let n_pixels = data.len() / 4;
+    if n_pixels == 0 {
+        return;
+    }
+    let w = ((n_pixels * (1 + w_frac as usize)) / 256).max(1);
+    let h = n_pixels / w;
+    let rgba = &data[..w * h * 4];
+    let rgb: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+    let expected_rgba = encode_to_vec(rgba, w as u32, h as u32).unwrap();
+    let expected_rgb = encode_to_vec(&rgb, w as u32, h as u32).unwrap();
+
+    for source in [Rgb, Bgr, Rgba, Argb, Rgbx, Xrgb, Bgra, Abgr, Bgrx, Xbgr] {
+        // the variant name spells out the source layout, e.g. "bgrx"
+        let layout = format!("{:?}", source).to_ascii_lowercase();
+        let data: Vec<u8> = rgba
+            .chunks_exact(4)
+            .flat_map(|p| {
+                layout.bytes().map(move |c| match c {
+                    b'r' => p[0],
+                    b'g' => p[1],
+                    b'b' => p[2],
+                    b'a' => p[3],
+                    _ => junk,
+                })
+            })
+            .collect();
+        let encoded = EncoderBuilder::new(&data, w as u32, h as u32)
+            .with_source_channels(source)
+            .build()
+            .unwrap()
+            .encode_to_vec()
+            .unwrap();
+        let expected = if layout.contains('a') { &expected_rgba } else { &expected_rgb };
+        assert_eq!(&encoded, expected, "{:?}", source);
+    }
+});
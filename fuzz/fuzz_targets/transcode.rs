@@ -0,0 +1,58 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use qoi::{decode_to_vec, transcode_stream, Channels, Decoder, Result, TranscodeOptions};
+
+fuzz_target!(|input: (u16, u16, bool, Option<bool>, u8, &[u8])| {
+    let (w, h, is_4, to_4, strip_rows, data) = input;
+    let (w, h) = (1 + w % 260, 1 + h % 260);
+    let channels = if is_4 { 4 } else { 3 };
+
+    let mut vec = vec![
+        b'q',
+        b'o',
+        b'i',
+        b'f',
+        0,
+        0,
+        (w >> 8) as u8,
+        (w & 0xff) as u8,
+        0,
+        0,
+        (h >> 8) as u8,
+        (h & 0xff) as u8,
+        channels,
+        0,
+    ];
+    vec.extend(&*data);
+    vec.extend(&[0, 0, 0, 0, 0, 0, 0, 1]);
+
+    let out_channels = to_4.map(|to_4| if to_4 { Channels::Rgba } else { Channels::Rgb });
+    let mut options = TranscodeOptions::new().with_strip_rows(strip_rows as usize);
+    if let Some(out_channels) = out_channels {
+        options = options.with_channels(out_channels);
+    }
+
+    // the transcoded image must decode to the same pixels as the source image
+    let source = || -> Result<Vec<u8>> {
+        let mut decoder = Decoder::new(&vec)?;
+        if let Some(out_channels) = out_channels {
+            decoder = decoder.with_channels(out_channels)?;
+        }
+        decoder.decode_to_vec()
+    };
+    let mut out = vec![];
+    match (source(), transcode_stream(vec.as_slice(), &mut out, options)) {
+        (Ok(pixels), Ok(n_written)) => {
+            assert_eq!(n_written, out.len());
+            let (header, decoded) = decode_to_vec(&out).unwrap();
+            assert_eq!((header.width, header.height), (u32::from(w), u32::from(h)));
+            assert_eq!(header.channels.as_u8(), out_channels.map_or(channels, Channels::as_u8));
+            assert_eq!(decoded, pixels);
+        }
+        (Err(_), Err(_)) => {}
+        (source, transcoded) => {
+            panic!("transcoding diverged: {:?} vs {:?}", source.err(), transcoded.err())
+        }
+    }
+});
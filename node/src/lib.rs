@@ -0,0 +1,106 @@
+//! Node.js bindings (N-API) exposing QOI encoding and decoding over `Buffer`s.
+//!
+//! All functions are synchronous and run on the calling thread; errors are thrown as
+//! JavaScript `Error`s with the message of the underlying [`qoi::Error`].
+
+use napi::bindgen_prelude::Buffer;
+use napi::{Error, Result};
+use napi_derive::napi;
+
+use qoi::{ColorSpace, Decoder, Encoder, Header};
+
+/// Image header: dimensions, number of channels (3 or 4) and color space (0 = sRGB,
+/// 1 = linear).
+#[napi(object)]
+pub struct ImageHeader {
+    pub width: u32,
+    pub height: u32,
+    pub channels: u32,
+    pub colorspace: u32,
+}
+
+/// Decoded image: header along with the raw pixel data.
+#[napi(object)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub channels: u32,
+    pub colorspace: u32,
+    pub data: Buffer,
+}
+
+fn to_js_error(err: qoi::Error) -> Error {
+    Error::from_reason(err.to_string())
+}
+
+fn to_u8(value: u32, name: &str) -> Result<u8> {
+    u8::try_from(value).map_err(|_| Error::from_reason(format!("invalid {name}: {value}")))
+}
+
+impl From<&Header> for ImageHeader {
+    fn from(header: &Header) -> Self {
+        Self {
+            width: header.width,
+            height: header.height,
+            channels: header.channels.as_u8().into(),
+            colorspace: header.colorspace.as_u8().into(),
+        }
+    }
+}
+
+/// Decodes the header of a QOI image without decoding the pixels.
+#[napi]
+pub fn decode_header(data: Buffer) -> Result<ImageHeader> {
+    qoi::decode_header(&*data).map(|header| (&header).into()).map_err(to_js_error)
+}
+
+/// Decodes a QOI image, optionally converting it to the given number of channels
+/// (same as in the header by default).
+#[napi]
+pub fn decode(data: Buffer, channels: Option<u32>) -> Result<Image> {
+    let mut decoder = Decoder::new(&*data).map_err(to_js_error)?;
+    if let Some(channels) = channels {
+        decoder = decoder.with_channels(to_u8(channels, "channels")?).map_err(to_js_error)?;
+    }
+    let pixels = decoder.decode_to_vec().map_err(to_js_error)?;
+    let header = ImageHeader::from(decoder.header());
+    Ok(Image {
+        width: header.width,
+        height: header.height,
+        channels: decoder.channels().as_u8().into(),
+        colorspace: header.colorspace,
+        data: pixels.into(),
+    })
+}
+
+/// Encodes raw RGB or RGBA pixels (the number of channels is inferred from the length
+/// of the data) into a QOI image, optionally tagged with the given color space.
+#[napi]
+pub fn encode(data: Buffer, width: u32, height: u32, colorspace: Option<u32>) -> Result<Buffer> {
+    let mut encoder = Encoder::new(&*data, width, height).map_err(to_js_error)?;
+    if let Some(colorspace) = colorspace {
+        let colorspace =
+            ColorSpace::try_from(to_u8(colorspace, "colorspace")?).map_err(to_js_error)?;
+        encoder = encoder.with_colorspace(colorspace);
+    }
+    encoder.encode_to_vec().map(Buffer::from).map_err(to_js_error)
+}
+
+/// Returns the version of the underlying qoi crate, e.g. `"0.4.1"`.
+#[napi]
+pub fn crate_version() -> String {
+    qoi::crate_version().to_owned()
+}
+
+/// Returns the names of non-standard format extensions supported by this build.
+#[napi]
+pub fn supported_extensions() -> Vec<String> {
+    qoi::supported_extensions().iter().map(|&name| name.to_owned()).collect()
+}
+
+/// Returns the names of optional crate features compiled into this build.
+#[napi]
+pub fn enabled_features() -> Vec<String> {
+    let features = qoi::Feature::ALL.iter().filter(|feature| feature.is_enabled());
+    features.map(|feature| feature.name().to_owned()).collect()
+}
@@ -0,0 +1,453 @@
+//! Animation container: a sequence of QOI frames with per-frame durations.
+//!
+//! This is **not** part of the QOI specification, just a simple container for e.g. lossless
+//! screen recordings. It consists of a 16-byte header, followed by the frames, followed by
+//! an 8-byte end marker (all integers are big-endian):
+//!
+//! | Field        | Size | Meaning                                                   |
+//! |--------------|------|-----------------------------------------------------------|
+//! | `magic`      | 4    | `qoia` ([`QOI_ANIM_MAGIC`])                               |
+//! | `width`      | 4    | frame width in pixels                                     |
+//! | `height`     | 4    | frame height in pixels                                    |
+//! | `loop_count` | 4    | number of times to play the animation, 0 means forever    |
+//! | `duration`   | 4    | frame duration in milliseconds (repeated for each frame)  |
+//! | `size`       | 4    | size of the encoded frame in bytes (ditto)                |
+//! | `image`      | size | complete QOI image of `width` by `height` pixels (ditto)  |
+//! | end marker   | 8    | zero `duration` and `size`                                |
+//!
+//! Every frame is a standalone QOI image (RGB or RGBA, frames may differ in that), so it
+//! can be decoded with [`Decoder`] as is. The `duration` and `size` fields act as a small
+//! index: frames can be skipped without decoding them, see [`AnimationDecoder::seek_frame`].
+//! The container can be written to a non-seekable writer, and no bytes are ever read past
+//! its end marker, so it can be embedded into other streams.
+
+use core::fmt::{self, Debug};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+use crate::decode::Decoder;
+use crate::encode::Encoder;
+use crate::error::{Error, Result};
+use crate::header::Header;
+use crate::types::{Channels, ColorSpace};
+use crate::utils::unlikely;
+
+/// Magic bytes of the animation container.
+pub const QOI_ANIM_MAGIC: u32 = u32::from_be_bytes(*b"qoia");
+/// Size of the animation container header in bytes.
+pub const QOI_ANIM_HEADER_SIZE: usize = 16;
+/// Size of the `duration` and `size` fields preceding each frame (and of the end marker).
+pub const QOI_ANIM_FRAME_HEADER_SIZE: usize = 8;
+
+/// Header of an animation: frame dimensions and loop count.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AnimationHeader {
+    /// Frame width in pixels
+    pub width: u32,
+    /// Frame height in pixels
+    pub height: u32,
+    /// Number of times to play the animation, 0 means forever
+    pub loop_count: u32,
+}
+
+impl AnimationHeader {
+    /// Creates a new header of an endlessly looping animation and validates frame
+    /// dimensions (same limits as for [`Header`]).
+    #[inline]
+    pub fn try_new(width: u32, height: u32) -> Result<Self> {
+        Header::try_new(width, height, Channels::Rgba, ColorSpace::Srgb)?;
+        Ok(Self { width, height, loop_count: 0 })
+    }
+
+    /// Creates a new header with modified loop count (0 means forever).
+    #[inline]
+    pub const fn with_loop_count(mut self, loop_count: u32) -> Self {
+        self.loop_count = loop_count;
+        self
+    }
+
+    /// Serializes the header into a 16-byte array, exactly as stored in a container.
+    #[inline]
+    pub fn encode(&self) -> [u8; QOI_ANIM_HEADER_SIZE] {
+        let mut out = [0; QOI_ANIM_HEADER_SIZE];
+        let fields = [QOI_ANIM_MAGIC, self.width, self.height, self.loop_count];
+        out.chunks_exact_mut(4)
+            .zip(fields)
+            .for_each(|(out, v)| out.copy_from_slice(&v.to_be_bytes()));
+        out
+    }
+
+    /// Deserializes the header from a byte array and validates it.
+    ///
+    /// Only the first 16 bytes are read, anything past that is ignored.
+    #[inline]
+    pub fn decode(data: impl AsRef<[u8]>) -> Result<Self> {
+        let data = data.as_ref();
+        if unlikely(data.len() < QOI_ANIM_HEADER_SIZE) {
+            return Err(Error::UnexpectedBufferEnd);
+        }
+        let word = |i: usize| u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        let magic = word(0);
+        if unlikely(magic != QOI_ANIM_MAGIC) {
+            return Err(Error::InvalidMagic { magic });
+        }
+        Ok(Self::try_new(word(4), word(8))?.with_loop_count(word(12)))
+    }
+
+    /// Returns the number of pixels in each frame.
+    #[inline]
+    pub const fn n_pixels(&self) -> usize {
+        (self.width as usize).saturating_mul(self.height as usize)
+    }
+}
+
+/// Checks that a frame has the dimensions of the animation.
+#[inline]
+const fn check_dimensions(header: &AnimationHeader, frame: &Header) -> Result<()> {
+    if unlikely(frame.width != header.width || frame.height != header.height) {
+        return Err(Error::InvalidImageDimensions { width: frame.width, height: frame.height });
+    }
+    Ok(())
+}
+
+/// Encoder that writes a sequence of frames into an animation container.
+///
+/// The header is written to the writer upon construction; each frame is encoded and written
+/// out as soon as it's pushed, and the end marker is written by [`AnimationEncoder::finish`]
+/// (without it, the container is considered truncated).
+///
+/// ### Example
+/// ```rust
+/// let header = AnimationHeader::try_new(width, height)?.with_loop_count(1);
+/// let mut encoder = AnimationEncoder::new(file, header)?;
+/// while let Some(pixels) = capture_next_frame() {
+///     encoder.push_frame(&pixels, 40)?;
+/// }
+/// let file = encoder.finish()?;
+/// ```
+pub struct AnimationEncoder<W> {
+    writer: W,
+    header: AnimationHeader,
+    buf: Vec<u8>,
+    n_frames: usize,
+    n_written: usize,
+}
+
+impl<W> Debug for AnimationEncoder<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AnimationEncoder")
+            .field("header", &self.header)
+            .field("frames_written", &self.n_frames)
+            .field("bytes_written", &self.n_written)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W> AnimationEncoder<W> {
+    /// Returns the header of the animation being encoded.
+    #[inline]
+    pub const fn header(&self) -> &AnimationHeader {
+        &self.header
+    }
+
+    /// Returns the number of frames written so far.
+    #[inline]
+    pub const fn frames_written(&self) -> usize {
+        self.n_frames
+    }
+
+    /// Returns the total number of bytes written so far, including the header.
+    #[inline]
+    pub const fn bytes_written(&self) -> usize {
+        self.n_written
+    }
+
+    /// Returns an immutable reference to the underlying writer.
+    #[inline]
+    pub const fn writer(&self) -> &W {
+        &self.writer
+    }
+}
+
+impl<W: Write> AnimationEncoder<W> {
+    /// Creates a new encoder for an animation with the given header and writes the header.
+    pub fn new(mut writer: W, header: AnimationHeader) -> Result<Self> {
+        let AnimationHeader { width, height, loop_count } = header;
+        let header = AnimationHeader::try_new(width, height)?.with_loop_count(loop_count);
+        writer.write_all(&header.encode())?;
+        Ok(Self { writer, header, buf: Vec::new(), n_frames: 0, n_written: QOI_ANIM_HEADER_SIZE })
+    }
+
+    /// Encodes a frame of RGB or RGBA pixels and writes it out, returning the number of bytes
+    /// written in this call.
+    ///
+    /// The number of channels is inferred from the data length, see [`Encoder::new`].
+    #[inline]
+    pub fn push_frame(&mut self, pixels: impl AsRef<[u8]>, duration_ms: u32) -> Result<usize> {
+        let encoder = Encoder::new(pixels.as_ref(), self.header.width, self.header.height)?;
+        self.push_encoder(&encoder, duration_ms)
+    }
+
+    /// Same as [`AnimationEncoder::push_frame`], but encodes the frame with the given encoder,
+    /// e.g. one created via [`EncoderBuilder`](crate::EncoderBuilder) for other pixel layouts.
+    ///
+    /// The frame must have the dimensions of the animation, otherwise
+    /// [`Error::InvalidImageDimensions`] is returned and nothing is written.
+    pub fn push_encoder(&mut self, encoder: &Encoder, duration_ms: u32) -> Result<usize> {
+        check_dimensions(&self.header, encoder.header())?;
+        let mut buf = core::mem::take(&mut self.buf);
+        buf.resize(encoder.required_buf_len() + QOI_ANIM_FRAME_HEADER_SIZE, 0);
+        let size = encoder.encode_to_buf(&mut buf[QOI_ANIM_FRAME_HEADER_SIZE..])?;
+        // frames are at most 5 bytes per pixel, so this always fits with the 400Mp limit
+        let size_field = u32::try_from(size).unwrap_or(u32::MAX);
+        buf[..4].copy_from_slice(&duration_ms.to_be_bytes());
+        buf[4..8].copy_from_slice(&size_field.to_be_bytes());
+        let n_bytes = QOI_ANIM_FRAME_HEADER_SIZE + size;
+        self.writer.write_all(&buf[..n_bytes])?;
+        self.buf = buf;
+        self.n_frames += 1;
+        self.n_written += n_bytes;
+        Ok(n_bytes)
+    }
+
+    /// Writes the end marker, flushes the writer and returns it back.
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.write_all(&[0; QOI_ANIM_FRAME_HEADER_SIZE])?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// A decoded animation frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    /// Header of the frame image
+    pub header: Header,
+    /// Frame duration in milliseconds
+    pub duration_ms: u32,
+    /// Decoded pixels, with as many channels as `header.channels`
+    pub pixels: Vec<u8>,
+}
+
+/// Decoder that reads frames from an animation container one by one.
+///
+/// Frames are decoded into RGB or RGBA depending on their header unless the number of
+/// channels is set via [`AnimationDecoder::with_channels`]. The decoder is also an
+/// iterator over the frames; once it yields an error, the iteration ends.
+///
+/// ### Example
+/// ```rust
+/// let mut decoder = AnimationDecoder::from_stream(file)?.with_channels(Channels::Rgba)?;
+/// for frame in decoder {
+///     let frame = frame?;
+///     show_frame(&frame.pixels, frame.duration_ms);
+/// }
+/// ```
+pub struct AnimationDecoder<R> {
+    reader: R,
+    header: AnimationHeader,
+    channels: Option<Channels>,
+    buf: Vec<u8>,
+    n_frames: usize,
+    pos: u64,
+    finished: bool,
+}
+
+impl<R> Debug for AnimationDecoder<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AnimationDecoder")
+            .field("header", &self.header)
+            .field("channels", &self.channels)
+            .field("frames_read", &self.n_frames)
+            .field("finished", &self.finished)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> AnimationDecoder<Cursor<&'a [u8]>> {
+    /// Creates a new decoder from a slice of bytes.
+    ///
+    /// The header will be decoded immediately upon construction.
+    #[inline]
+    pub fn new(data: &'a (impl AsRef<[u8]> + ?Sized)) -> Result<Self> {
+        Self::from_stream(Cursor::new(data.as_ref()))
+    }
+}
+
+impl<R> AnimationDecoder<R> {
+    /// Returns a new decoder that decodes all frames into the given number of channels.
+    ///
+    /// Accepts either [`Channels`] or a raw `u8` channel count, see [`Decoder::with_channels`].
+    #[inline]
+    pub fn with_channels<C>(mut self, channels: C) -> Result<Self>
+    where
+        C: TryInto<Channels>,
+        Error: From<C::Error>,
+    {
+        self.channels = Some(channels.try_into()?);
+        Ok(self)
+    }
+
+    /// Returns the header of the animation.
+    #[inline]
+    pub const fn header(&self) -> &AnimationHeader {
+        &self.header
+    }
+
+    /// Returns the number of frames read (decoded or skipped) so far, i.e. the index of the
+    /// next frame.
+    #[inline]
+    pub const fn frames_read(&self) -> usize {
+        self.n_frames
+    }
+
+    /// Returns true once the end marker has been read, or once iterating over the frames
+    /// has failed.
+    #[inline]
+    pub const fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Returns an immutable reference to the underlying reader.
+    #[inline]
+    pub const fn reader(&self) -> &R {
+        &self.reader
+    }
+
+    /// Consumes the decoder and returns the underlying reader back.
+    #[inline]
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> AnimationDecoder<R> {
+    /// Creates a new decoder from a generic reader that implements [`Read`](std::io::Read).
+    ///
+    /// The header will be decoded immediately upon construction.
+    pub fn from_stream(mut reader: R) -> Result<Self> {
+        let mut data = [0; QOI_ANIM_HEADER_SIZE];
+        reader.read_exact(&mut data)?;
+        let header = AnimationHeader::decode(data)?;
+        Ok(Self {
+            reader,
+            header,
+            channels: None,
+            buf: Vec::new(),
+            n_frames: 0,
+            pos: QOI_ANIM_HEADER_SIZE as u64,
+            finished: false,
+        })
+    }
+
+    /// Reads the `duration` and `size` fields of the next frame, or returns `None` at the
+    /// end marker.
+    fn read_frame_header(&mut self) -> Result<Option<(u32, u32)>> {
+        if self.finished {
+            return Ok(None);
+        }
+        let mut data = [0; QOI_ANIM_FRAME_HEADER_SIZE];
+        self.reader.read_exact(&mut data)?;
+        self.pos += QOI_ANIM_FRAME_HEADER_SIZE as u64;
+        let duration = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        let size = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+        self.finished = size == 0;
+        Ok(if self.finished { None } else { Some((duration, size)) })
+    }
+
+    /// Reads the next encoded frame and returns its duration in milliseconds along with the
+    /// encoded QOI image, or returns `None` once all of the frames have been read.
+    ///
+    /// The frame isn't decoded, so e.g. [`Decoder`] can be used with custom options instead.
+    pub fn next_encoded_frame(&mut self) -> Result<Option<(u32, &[u8])>> {
+        let (duration, size) = match self.read_frame_header()? {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+        // the size may be corrupt, so the buffer is only grown as the bytes arrive
+        self.buf.clear();
+        let n_read = self.reader.by_ref().take(size.into()).read_to_end(&mut self.buf)?;
+        if unlikely(n_read != size as usize) {
+            return Err(Error::UnexpectedStreamEnd);
+        }
+        self.pos += u64::from(size);
+        self.n_frames += 1;
+        Ok(Some((duration, &self.buf)))
+    }
+
+    /// Reads and decodes the next frame, or returns `None` once all of the frames have
+    /// been read.
+    ///
+    /// A frame with dimensions other than those of the animation is rejected with
+    /// [`Error::InvalidImageDimensions`].
+    pub fn next_frame(&mut self) -> Result<Option<Frame>> {
+        let (header, channels) = (self.header, self.channels);
+        let (duration_ms, data) = match self.next_encoded_frame()? {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+        let mut decoder = Decoder::new(data)?;
+        check_dimensions(&header, decoder.header())?;
+        if let Some(channels) = channels {
+            decoder = decoder.with_channels(channels)?;
+        }
+        let header = decoder.header().with_channels(decoder.channels());
+        let pixels = decoder.decode_to_vec()?;
+        Ok(Some(Frame { header, duration_ms, pixels }))
+    }
+}
+
+impl<R: Read + Seek> AnimationDecoder<R> {
+    /// Positions the decoder at the frame with the given index, so that it's the one
+    /// returned next, and returns true if it exists (otherwise, the decoder is positioned
+    /// at the end).
+    ///
+    /// Frames are skipped by seeking over them without decoding; seeking backwards restarts
+    /// from the first frame.
+    #[allow(clippy::cast_possible_wrap)]
+    pub fn seek_frame(&mut self, index: usize) -> Result<bool> {
+        if index < self.n_frames {
+            let start = QOI_ANIM_HEADER_SIZE as u64;
+            let offset = i64::try_from(self.pos - start).unwrap_or(i64::MAX);
+            self.reader.seek(SeekFrom::Current(-offset))?;
+            (self.pos, self.n_frames, self.finished) = (start, 0, false);
+        }
+        while self.n_frames < index {
+            let size = match self.read_frame_header()? {
+                Some((_, size)) => size,
+                None => return Ok(false),
+            };
+            self.reader.seek(SeekFrom::Current(size.into()))?;
+            self.pos += u64::from(size);
+            self.n_frames += 1;
+        }
+        if self.read_frame_header()?.is_some() {
+            // the frame is read from its start later on
+            self.reader.seek(SeekFrom::Current(-(QOI_ANIM_FRAME_HEADER_SIZE as i64)))?;
+            self.pos -= QOI_ANIM_FRAME_HEADER_SIZE as u64;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Returns the total number of frames, keeping the decoder position unchanged.
+    pub fn frame_count(&mut self) -> Result<usize> {
+        let index = self.n_frames;
+        while self.seek_frame(self.n_frames + 1)? {}
+        let count = self.n_frames;
+        self.seek_frame(index)?;
+        Ok(count)
+    }
+}
+
+impl<R: Read> Iterator for AnimationDecoder<R> {
+    type Item = Result<Frame>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let frame = self.next_frame();
+        // the stream position is unknown after an error, so iteration stops there
+        self.finished |= frame.is_err();
+        frame.transpose()
+    }
+}
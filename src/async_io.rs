@@ -0,0 +1,471 @@
+//! The async layer: decoding from async readers and encoding to async writers.
+//!
+//! The codec itself is synchronous; here, encoded bytes are moved between the async
+//! reader/writer and an in-memory buffer, and the synchronous codec is run over that buffer,
+//! so that no blocking I/O calls are ever made on the executor. Only the runtime-specific
+//! plumbing (polling the reader/writer) depends on the async I/O traits in use, so the same
+//! implementation serves both tokio (`tokio` feature) and the runtime-agnostic `futures-io`
+//! traits (`futures-io` feature).
+
+// async runtimes require a newer Rust version than the crate itself anyway
+#![allow(clippy::incompatible_msrv)]
+
+use core::fmt::{self, Debug};
+use core::future::poll_fn;
+use core::task::{Context, Poll};
+use std::io::{self, Read};
+use std::vec::Vec;
+
+use crate::consts::{QOI_HEADER_SIZE, QOI_PADDING_SIZE};
+use crate::decode::Decoder;
+use crate::error::{Error, Result};
+use crate::header::{Header, HeaderOptions};
+use crate::types::Channels;
+use crate::utils::unlikely;
+
+use crate::encode::{Encoder, OwnedEncoder};
+use core::pin::Pin;
+
+/// Polls the async reader, returning the number of bytes read into the buffer.
+type PollRead<R> = fn(&mut R, &mut Context<'_>, &mut [u8]) -> Poll<io::Result<usize>>;
+
+/// Polls the async writer, returning the number of bytes written from the buffer.
+type PollWrite<W> = fn(&mut W, &mut Context<'_>, &[u8]) -> Poll<io::Result<usize>>;
+
+/// Polls the async writer to flush it.
+type PollFlush<W> = fn(&mut W, &mut Context<'_>) -> Poll<io::Result<()>>;
+
+/// The maximum number of bytes read from the async reader at once.
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The minimum number of bytes encoded at once before writing them to the async writer.
+const WRITE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The maximum size of a single op in bytes (`QOI_OP_RGBA`).
+const MAX_OP_SIZE: usize = 5;
+
+/// The maximum number of pixels produced by a single op (`QOI_OP_RUN`).
+const MAX_OP_PIXELS: usize = 62;
+
+/// Encoded bytes that have been read from the async reader but not decoded yet.
+///
+/// Reading past the end of the buffer is reported as an unexpected end of stream, which
+/// tells the decoder that more bytes have to be read first; since a failed read may have
+/// consumed some of the bytes already, the position is rewound explicitly in that case.
+#[derive(Debug, Default)]
+pub struct Pending {
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl Pending {
+    fn available(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+}
+
+impl Read for Pending {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let n = out.len().min(self.available());
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Reads up to `n_bytes` more bytes from the async reader into the pending buffer.
+async fn read_more<R>(
+    pending: &mut Pending, n_bytes: usize, reader: &mut R, poll_read: PollRead<R>,
+) -> Result<()> {
+    // only bytes that haven't been decoded yet have to be kept
+    pending.buf.drain(..pending.pos);
+    pending.pos = 0;
+    let len = pending.buf.len();
+    pending.buf.resize(len + n_bytes, 0);
+    let result = loop {
+        match poll_fn(|cx| poll_read(reader, cx, &mut pending.buf[len..])).await {
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            result => break result,
+        }
+    };
+    pending.buf.truncate(len + *result.as_ref().unwrap_or(&0));
+    match result {
+        Ok(0) => Err(Error::UnexpectedStreamEnd),
+        Ok(_) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Decoder that reads the encoded image from an async reader.
+///
+/// Created via [`Decoder::from_async_stream`] (tokio) or [`Decoder::from_futures_stream`]
+/// (`futures-io`). The encoded bytes are read in chunks and
+/// decoded as they arrive, so the image can be decoded e.g. straight from a socket without
+/// blocking the executor; decoding methods only differ from those of [`Decoder`] in that
+/// they are `async`. No bytes are ever read past the end marker, so once the image has
+/// been fully decoded, the reader is positioned right after it (e.g. at the start of the
+/// next message), see [`AsyncDecoder::into_inner`].
+pub struct AsyncDecoder<R> {
+    decoder: Decoder<Pending>,
+    reader: R,
+    poll_read: PollRead<R>,
+}
+
+impl<R> Debug for AsyncDecoder<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AsyncDecoder")
+            .field("decoder", &self.decoder)
+            .field("buffered", &self.decoder.reader.available())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<R> AsyncDecoder<R> {
+    /// Returns a new decoder with modified number of channels, see [`Decoder::with_channels`].
+    #[inline]
+    pub fn with_channels<C>(mut self, channels: C) -> Result<Self>
+    where
+        C: TryInto<Channels>,
+        Error: From<C::Error>,
+    {
+        self.decoder = self.decoder.with_channels(channels)?;
+        Ok(self)
+    }
+
+    /// Returns a new decoder with strict decoding enabled or disabled, see
+    /// [`Decoder::with_strict`].
+    #[inline]
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.decoder = self.decoder.with_strict(strict);
+        self
+    }
+
+    /// Returns a new decoder with the alpha value used when decoding RGB images into RGBA,
+    /// see [`Decoder::with_default_alpha`].
+    #[inline]
+    pub fn with_default_alpha(mut self, alpha: u8) -> Self {
+        self.decoder = self.decoder.with_default_alpha(alpha);
+        self
+    }
+
+    /// Returns a new decoder with alpha premultiplication enabled or disabled, see
+    /// [`Decoder::with_premultiplied_alpha`].
+    #[inline]
+    pub fn with_premultiplied_alpha(mut self, premultiply: bool) -> Self {
+        self.decoder = self.decoder.with_premultiplied_alpha(premultiply);
+        self
+    }
+
+    /// Returns the number of channels in the decoded image.
+    #[inline]
+    pub const fn channels(&self) -> Channels {
+        self.decoder.channels()
+    }
+
+    /// Returns the decoded image header.
+    #[inline]
+    pub const fn header(&self) -> &Header {
+        self.decoder.header()
+    }
+
+    /// Returns the raw color space byte stored in the image header, see
+    /// [`Decoder::colorspace_raw`].
+    #[inline]
+    pub const fn colorspace_raw(&self) -> u8 {
+        self.decoder.colorspace_raw()
+    }
+
+    /// Returns the number of pixels that haven't been decoded yet.
+    #[inline]
+    pub const fn pixels_left(&self) -> usize {
+        self.decoder.pixels_left()
+    }
+
+    /// The number of bytes the rest of the decoded image will take.
+    #[inline]
+    pub const fn required_buf_len(&self) -> usize {
+        self.decoder.required_buf_len()
+    }
+
+    /// Returns the number of encoded bytes consumed so far, header included.
+    #[inline]
+    pub const fn bytes_consumed(&self) -> usize {
+        self.decoder.bytes_consumed()
+    }
+
+    /// Returns an immutable reference to the underlying reader.
+    #[inline]
+    pub const fn reader(&self) -> &R {
+        &self.reader
+    }
+
+    /// Consumes the decoder and returns the underlying reader back.
+    ///
+    /// Once the image has been fully decoded, the reader is positioned right after its
+    /// end marker; otherwise, some of the encoded bytes may have already been read from it.
+    #[inline]
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    async fn new_impl(reader: R, options: HeaderOptions, poll_read: PollRead<R>) -> Result<Self> {
+        let mut reader = reader;
+        let mut pending = Pending::default();
+        // the header is read exactly, without peeking into the image body
+        while pending.available() < QOI_HEADER_SIZE {
+            let n_bytes = QOI_HEADER_SIZE - pending.available();
+            read_more(&mut pending, n_bytes, &mut reader, poll_read).await?;
+        }
+        let decoder = Decoder::from_stream_with_options(pending, options)?;
+        Ok(Self { decoder, reader, poll_read })
+    }
+
+    async fn decode_pixels_impl(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let channels = self.channels().as_u8() as usize;
+        if unlikely(buf.len() % channels != 0) {
+            let Header { width, height, .. } = *self.header();
+            return Err(Error::InvalidImageLength { size: buf.len(), width, height });
+        }
+        let (decoder, reader, poll_read) = (&mut self.decoder, &mut self.reader, self.poll_read);
+        let n_pixels = (buf.len() / channels).min(decoder.pixels_left());
+        let mut n_done = 0;
+        while n_done < n_pixels {
+            // pixels that are guaranteed to be fully buffered (along with the end marker)
+            let available = decoder.reader.available();
+            let n_sure =
+                (n_pixels - n_done).min(available.saturating_sub(QOI_PADDING_SIZE) / MAX_OP_SIZE);
+            if n_sure != 0 {
+                let out = &mut buf[n_done * channels..(n_done + n_sure) * channels];
+                n_done += decoder.decode_pixels(out)?;
+                continue;
+            }
+            // otherwise, try to decode as much as possible and read more bytes if needed
+            let (state, pos) = (decoder.checkpoint(), decoder.reader.pos);
+            match decoder.decode_pixels(&mut buf[n_done * channels..n_pixels * channels]) {
+                Ok(n) => n_done += n,
+                Err(err) if err.is_truncated() => {
+                    decoder.state = state;
+                    decoder.reader.pos = pos;
+                    // the rest of the image takes at least this many bytes, so reading up to
+                    // that many more never consumes anything past its end marker
+                    let ops_left = decoder.pixels_left().saturating_sub(state.pending_run());
+                    let min_len = (ops_left + MAX_OP_PIXELS - 1) / MAX_OP_PIXELS + QOI_PADDING_SIZE;
+                    let n_bytes = min_len.saturating_sub(available).clamp(1, READ_CHUNK_SIZE);
+                    let header = *decoder.header();
+                    read_more(&mut decoder.reader, n_bytes, reader, poll_read)
+                        .await
+                        .map_err(|err| err.with_header(header))?;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(n_pixels)
+    }
+
+    /// Decodes the next pixels of the image into the buffer and returns the number of pixels
+    /// decoded, see [`Decoder::decode_pixels`].
+    pub async fn decode_pixels(&mut self, mut buf: impl AsMut<[u8]>) -> Result<usize> {
+        self.decode_pixels_impl(buf.as_mut()).await
+    }
+
+    /// Decodes the rest of the image to a pre-allocated buffer and returns the number of bytes
+    /// written, see [`Decoder::decode_to_buf`].
+    pub async fn decode_to_buf(&mut self, mut buf: impl AsMut<[u8]>) -> Result<usize> {
+        let buf = buf.as_mut();
+        let size = self.required_buf_len();
+        if unlikely(buf.len() < size) {
+            return Err(Error::OutputBufferTooSmall { size: buf.len(), required: size });
+        }
+        self.decode_pixels_impl(&mut buf[..size]).await?;
+        Ok(size)
+    }
+
+    /// Decodes the rest of the image into a newly allocated vector of bytes.
+    pub async fn decode_to_vec(&mut self) -> Result<Vec<u8>> {
+        let mut out = vec![0; self.required_buf_len()];
+        self.decode_pixels_impl(&mut out).await?;
+        Ok(out)
+    }
+}
+
+/// Writes the encoded image to an async writer in chunks.
+async fn encode_impl<W>(
+    encoder: &Encoder<'_>, writer: &mut W, poll_write: PollWrite<W>, poll_flush: PollFlush<W>,
+) -> Result<usize> {
+    let mut resumable = encoder.resumable();
+    let mut chunk = Vec::with_capacity(WRITE_CHUNK_SIZE + 1024);
+    let mut n_written = 0;
+    while !resumable.is_finished() {
+        chunk.clear();
+        resumable.encode_step(&mut chunk, WRITE_CHUNK_SIZE)?;
+        let mut data = &chunk[..];
+        while !data.is_empty() {
+            match poll_fn(|cx| poll_write(writer, cx, data)).await {
+                Ok(0) => {
+                    let source = io::ErrorKind::WriteZero.into();
+                    return Err(Error::write_failed(n_written, source));
+                }
+                Ok(n) => {
+                    n_written += n;
+                    data = &data[n..];
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(source) => return Err(Error::write_failed(n_written, source)),
+            }
+        }
+    }
+    if encoder.flush {
+        poll_fn(|cx| poll_flush(writer, cx))
+            .await
+            .map_err(|source| Error::write_failed(n_written, source))?;
+    }
+    Ok(n_written)
+}
+
+#[cfg(feature = "tokio")]
+mod with_tokio {
+    use super::{
+        encode_impl, AsyncDecoder, Context, Decoder, Encoder, HeaderOptions, OwnedEncoder, Pin,
+        Poll, Result,
+    };
+    use std::io;
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+    fn poll_read<R: AsyncRead + Unpin>(
+        reader: &mut R, cx: &mut Context<'_>, buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut buf = ReadBuf::new(buf);
+        Pin::new(reader).poll_read(cx, &mut buf).map_ok(|()| buf.filled().len())
+    }
+
+    fn poll_write<W: AsyncWrite + Unpin>(
+        writer: &mut W, cx: &mut Context<'_>, buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(writer).poll_write(cx, buf)
+    }
+
+    fn poll_flush<W: AsyncWrite + Unpin>(
+        writer: &mut W, cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(writer).poll_flush(cx)
+    }
+
+    impl<R: AsyncRead + Unpin> Decoder<R> {
+        /// Creates a new decoder from a tokio async reader, see [`AsyncDecoder`].
+        ///
+        /// The header will be decoded immediately upon construction.
+        #[inline]
+        pub async fn from_async_stream(reader: R) -> Result<AsyncDecoder<R>> {
+            Self::from_async_stream_with_options(reader, HeaderOptions::new()).await
+        }
+
+        /// Same as [`Decoder::from_async_stream`], but with custom header validation options.
+        #[inline]
+        pub async fn from_async_stream_with_options(
+            reader: R, options: HeaderOptions,
+        ) -> Result<AsyncDecoder<R>> {
+            AsyncDecoder::new_impl(reader, options, poll_read).await
+        }
+    }
+
+    impl Encoder<'_> {
+        /// Encodes the image to a tokio async writer and returns the number of bytes written.
+        ///
+        /// The image is encoded in chunks of about 64 KiB in between writes, so the executor
+        /// is never blocked for long and only one chunk of encoded data is buffered at a time.
+        /// Errors are reported the same way as by [`Encoder::encode_to_stream`].
+        #[inline]
+        pub async fn encode_to_async_stream<W: AsyncWrite + Unpin>(
+            &self, writer: &mut W,
+        ) -> Result<usize> {
+            encode_impl(self, writer, poll_write, poll_flush).await
+        }
+    }
+
+    impl<D: AsRef<[u8]>> OwnedEncoder<D> {
+        /// Encodes the image to a tokio async writer, see [`Encoder::encode_to_async_stream`].
+        #[inline]
+        #[allow(clippy::future_not_send)] // `Send` as long as `D` and `W` are
+        pub async fn encode_to_async_stream<W: AsyncWrite + Unpin>(
+            &self, writer: &mut W,
+        ) -> Result<usize> {
+            encode_impl(&self.encoder(), writer, poll_write, poll_flush).await
+        }
+    }
+}
+
+#[cfg(feature = "futures-io")]
+mod with_futures_io {
+    use super::{
+        encode_impl, AsyncDecoder, Context, Decoder, Encoder, HeaderOptions, OwnedEncoder, Pin,
+        Poll, Result,
+    };
+    use futures_io::{AsyncRead, AsyncWrite};
+    use std::io;
+
+    fn poll_read<R: AsyncRead + Unpin>(
+        reader: &mut R, cx: &mut Context<'_>, buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(reader).poll_read(cx, buf)
+    }
+
+    fn poll_write<W: AsyncWrite + Unpin>(
+        writer: &mut W, cx: &mut Context<'_>, buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(writer).poll_write(cx, buf)
+    }
+
+    fn poll_flush<W: AsyncWrite + Unpin>(
+        writer: &mut W, cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(writer).poll_flush(cx)
+    }
+
+    impl<R: AsyncRead + Unpin> Decoder<R> {
+        /// Creates a new decoder from a `futures-io` async reader, see [`AsyncDecoder`].
+        ///
+        /// This works with any runtime whose I/O types implement
+        /// [`futures_io::AsyncRead`] (e.g. async-std or smol); for tokio, see
+        /// [`Decoder::from_async_stream`]. The header will be decoded immediately upon
+        /// construction.
+        #[inline]
+        pub async fn from_futures_stream(reader: R) -> Result<AsyncDecoder<R>> {
+            Self::from_futures_stream_with_options(reader, HeaderOptions::new()).await
+        }
+
+        /// Same as [`Decoder::from_futures_stream`], but with custom header validation options.
+        #[inline]
+        pub async fn from_futures_stream_with_options(
+            reader: R, options: HeaderOptions,
+        ) -> Result<AsyncDecoder<R>> {
+            AsyncDecoder::new_impl(reader, options, poll_read).await
+        }
+    }
+
+    impl Encoder<'_> {
+        /// Encodes the image to a `futures-io` async writer and returns the number of bytes
+        /// written.
+        ///
+        /// Same as [`Encoder::encode_to_async_stream`] for tokio, but for any writer
+        /// implementing [`futures_io::AsyncWrite`].
+        #[inline]
+        pub async fn encode_to_futures_stream<W: AsyncWrite + Unpin>(
+            &self, writer: &mut W,
+        ) -> Result<usize> {
+            encode_impl(self, writer, poll_write, poll_flush).await
+        }
+    }
+
+    impl<D: AsRef<[u8]>> OwnedEncoder<D> {
+        /// Encodes the image to a `futures-io` async writer, see
+        /// [`Encoder::encode_to_futures_stream`].
+        #[inline]
+        #[allow(clippy::future_not_send)] // `Send` as long as `D` and `W` are
+        pub async fn encode_to_futures_stream<W: AsyncWrite + Unpin>(
+            &self, writer: &mut W,
+        ) -> Result<usize> {
+            encode_impl(&self.encoder(), writer, poll_write, poll_flush).await
+        }
+    }
+}
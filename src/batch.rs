@@ -0,0 +1,601 @@
+//! Batch conversion of directory trees between QOI and other image formats.
+//!
+//! [`convert_tree`] walks a source directory and mirrors it into a target directory,
+//! converting every PNG, PPM, TGA or BMP image into QOI (or every QOI image into one of
+//! those, depending on [`BatchOptions::target`]); files in other formats are left alone.
+//! Images are converted in parallel (see [`ParallelOptions`]), while progress is reported
+//! on the calling thread.
+//!
+//! Errors from the PNG decoder/encoder and from walking the directory tree are reported as
+//! [`Error::IoError`].
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::error::{Error, Result};
+use crate::header::Header;
+use crate::parallel::ParallelOptions;
+use crate::source::{ChannelMap, ChannelSource, SourceChannels};
+use crate::types::{Channels, ColorSpace};
+
+/// Image file formats supported by batch conversion.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ImageFormat {
+    /// QOI image (`.qoi`)
+    Qoi,
+    /// PNG image (`.png`), 8-bit only
+    Png,
+    /// Binary PPM/PGM image (`.ppm` or `.pgm`), 8-bit only, without alpha
+    Ppm,
+    /// Uncompressed true-color or grayscale TGA image (`.tga`), 8/24/32-bit
+    Tga,
+    /// Uncompressed BMP image (`.bmp`), 24/32-bit
+    Bmp,
+}
+
+impl ImageFormat {
+    /// Detects the image format from the file extension (case-insensitive).
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "qoi" => Some(Self::Qoi),
+            "png" => Some(Self::Png),
+            "ppm" | "pgm" => Some(Self::Ppm),
+            "tga" => Some(Self::Tga),
+            "bmp" => Some(Self::Bmp),
+            _ => None,
+        }
+    }
+
+    /// Detects the image format from the first bytes of the image data.
+    ///
+    /// TGA images don't start with a magic number and are never detected.
+    pub fn from_magic(data: impl AsRef<[u8]>) -> Option<Self> {
+        match data.as_ref() {
+            [b'q', b'o', b'i', b'f', ..] => Some(Self::Qoi),
+            [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', ..] => Some(Self::Png),
+            [b'P', b'5' | b'6', ..] => Some(Self::Ppm),
+            [b'B', b'M', ..] => Some(Self::Bmp),
+            _ => None,
+        }
+    }
+
+    /// Returns the file extension used for converted images of this format.
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Qoi => "qoi",
+            Self::Png => "png",
+            Self::Ppm => "ppm",
+            Self::Tga => "tga",
+            Self::Bmp => "bmp",
+        }
+    }
+}
+
+fn invalid_data(msg: impl Into<String>) -> Error {
+    Error::IoError(io::Error::new(io::ErrorKind::InvalidData, msg.into()))
+}
+
+fn decode_png(data: &[u8]) -> Result<(Header, Vec<u8>)> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(io::Error::from)?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(io::Error::from)?;
+    buf.truncate(info.buffer_size());
+    if info.bit_depth != png::BitDepth::Eight {
+        return Err(invalid_data(format!("unsupported PNG bit depth: {:?}", info.bit_depth)));
+    }
+    let (channels, data) = match info.color_type {
+        png::ColorType::Grayscale => (Channels::Rgb, buf.iter().flat_map(|&v| [v, v, v]).collect()),
+        png::ColorType::GrayscaleAlpha => (
+            Channels::Rgba,
+            buf.chunks_exact(2).flat_map(|px| [px[0], px[0], px[0], px[1]]).collect(),
+        ),
+        png::ColorType::Rgb => (Channels::Rgb, buf),
+        png::ColorType::Rgba => (Channels::Rgba, buf),
+        color_type @ png::ColorType::Indexed => {
+            return Err(invalid_data(format!("unsupported PNG color type: {color_type:?}")));
+        }
+    };
+    Ok((Header::try_new(info.width, info.height, channels, ColorSpace::Srgb)?, data))
+}
+
+fn write_png(writer: impl Write, header: &Header, data: &[u8]) -> Result<()> {
+    let mut encoder = png::Encoder::new(writer, header.width, header.height);
+    encoder.set_color(match header.channels {
+        Channels::Rgb => png::ColorType::Rgb,
+        Channels::Rgba => png::ColorType::Rgba,
+    });
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::from)?;
+    writer.write_image_data(data).map_err(io::Error::from)?;
+    writer.finish().map_err(io::Error::from)?;
+    Ok(())
+}
+
+/// Parses a binary PPM (`P6`) or PGM (`P5`) image with a maximum value of 255.
+fn decode_ppm(data: &[u8]) -> Result<(Header, Vec<u8>)> {
+    let mut pos = 2;
+    let mut field = || {
+        loop {
+            match data.get(pos) {
+                Some(b'#') => {
+                    while data.get(pos).map_or(false, |&c| c != b'\n') {
+                        pos += 1;
+                    }
+                }
+                Some(c) if c.is_ascii_whitespace() => pos += 1,
+                _ => break,
+            }
+        }
+        let start = pos;
+        while data.get(pos).map_or(false, u8::is_ascii_digit) {
+            pos += 1;
+        }
+        core::str::from_utf8(&data[start..pos]).ok().and_then(|s| s.parse::<u32>().ok())
+    };
+    let gray = match data.get(..2) {
+        Some(b"P6") => false,
+        Some(b"P5") => true,
+        _ => return Err(invalid_data("invalid PPM magic (expected P5 or P6)")),
+    };
+    let (width, height, maxval) = (field(), field(), field());
+    let (width, height) = match (width, height, maxval) {
+        (Some(width), Some(height), Some(255)) => (width, height),
+        _ => return Err(invalid_data("invalid PPM header (only 8-bit images are supported)")),
+    };
+    let header = Header::try_new(width, height, Channels::Rgb, ColorSpace::Srgb)?;
+    let n_bytes = header.n_pixels() * if gray { 1 } else { 3 };
+    let pixels = data
+        .get(pos + 1..)
+        .and_then(|pixels| pixels.get(..n_bytes))
+        .ok_or(Error::UnexpectedBufferEnd)?;
+    let pixels =
+        if gray { pixels.iter().flat_map(|&v| [v, v, v]).collect() } else { pixels.to_vec() };
+    Ok((header, pixels))
+}
+
+fn write_ppm(mut writer: impl Write, header: &Header, data: &[u8]) -> Result<()> {
+    if header.channels.is_rgba() {
+        return Err(invalid_data("PPM images can't store alpha, convert to RGB first"));
+    }
+    write!(writer, "P6\n{} {}\n255\n", header.width, header.height)?;
+    writer.write_all(data)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn read_le<const N: usize>(data: &[u8], pos: usize) -> Result<[u8; N]> {
+    let bytes = data.get(pos..).and_then(|b| b.get(..N)).ok_or(Error::UnexpectedBufferEnd)?;
+    let mut out = [0; N];
+    out.copy_from_slice(bytes);
+    Ok(out)
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Result<u16> {
+    read_le(data, pos).map(u16::from_le_bytes)
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Result<u32> {
+    read_le(data, pos).map(u32::from_le_bytes)
+}
+
+/// Unpacks rows of pixels laid out according to the channel map into RGB(A), where
+/// consecutive rows are `row_stride` bytes apart and may be stored bottom-up.
+fn unpack_rows(
+    data: &[u8], header: &Header, map: ChannelMap, row_stride: usize, bottom_up: bool,
+) -> Result<Vec<u8>> {
+    let (width, height) = (header.width as usize, header.height as usize);
+    let row_len = width * map.bytes_per_pixel();
+    if data.len() < row_stride * (height - 1) + row_len {
+        return Err(Error::UnexpectedBufferEnd);
+    }
+    let sources = &map.sources()[..header.channels.as_u8() as usize];
+    let mut out = Vec::with_capacity(header.n_bytes());
+    for y in 0..height {
+        let y = if bottom_up { height - 1 - y } else { y };
+        for px in data[y * row_stride..][..row_len].chunks_exact(map.bytes_per_pixel()) {
+            out.extend(sources.iter().map(|&source| match source {
+                ChannelSource::Byte(offset) => px[offset as usize],
+                ChannelSource::Const(value) => value,
+            }));
+        }
+    }
+    Ok(out)
+}
+
+/// Writes the image rows as BGR(A), bottom-up if requested, padding each row to `row_stride`.
+fn write_bgr_rows(
+    mut writer: impl Write, header: &Header, data: &[u8], row_stride: usize, bottom_up: bool,
+) -> Result<()> {
+    let n = header.channels.as_u8() as usize;
+    let (row_len, mut row) = (header.width as usize * n, vec![0; row_stride]);
+    for y in 0..header.height as usize {
+        let y = if bottom_up { header.height as usize - 1 - y } else { y };
+        for (src, dst) in
+            data[y * row_len..][..row_len].chunks_exact(n).zip(row.chunks_exact_mut(n))
+        {
+            dst[..3].copy_from_slice(&[src[2], src[1], src[0]]);
+            dst[3..].copy_from_slice(&src[3..]);
+        }
+        writer.write_all(&row)?;
+    }
+    Ok(())
+}
+
+/// Parses an uncompressed true-color (type 2) or grayscale (type 3) TGA image.
+fn decode_tga(data: &[u8]) -> Result<(Header, Vec<u8>)> {
+    let [id_len, color_map_type, image_type] = read_le(data, 0)?;
+    let (width, height) = (read_u16(data, 12)?, read_u16(data, 14)?);
+    let [bits_per_pixel, descriptor] = read_le(data, 16)?;
+    let alpha_bits = descriptor & 0x0f;
+    let map = match (color_map_type, image_type, bits_per_pixel) {
+        (0, 2, 24) => SourceChannels::Bgr.channel_map(),
+        (0, 2, 32) if alpha_bits == 0 => SourceChannels::Bgrx.channel_map(),
+        (0, 2, 32) => SourceChannels::Bgra.channel_map(),
+        (0, 3, 8) => {
+            use ChannelSource::Byte;
+            ChannelMap::new(1, [Byte(0), Byte(0), Byte(0)], None)?
+        }
+        _ => {
+            let msg = format!(
+                "unsupported TGA image (type {image_type}, {bits_per_pixel} bits per pixel), \
+                 only uncompressed true-color and grayscale images are supported"
+            );
+            return Err(invalid_data(msg));
+        }
+    };
+    if descriptor & 0x10 != 0 {
+        return Err(invalid_data("unsupported TGA image (right-to-left pixel order)"));
+    }
+    let header = Header::try_new(width.into(), height.into(), map.channels(), ColorSpace::Srgb)?;
+    let pixels = data.get(18 + id_len as usize..).ok_or(Error::UnexpectedBufferEnd)?;
+    let row_stride = header.width as usize * map.bytes_per_pixel();
+    let bottom_up = descriptor & 0x20 == 0;
+    Ok((header, unpack_rows(pixels, &header, map, row_stride, bottom_up)?))
+}
+
+fn write_tga(mut writer: impl Write, header: &Header, data: &[u8]) -> Result<()> {
+    let (width, height) = match (u16::try_from(header.width), u16::try_from(header.height)) {
+        (Ok(width), Ok(height)) => (width.to_le_bytes(), height.to_le_bytes()),
+        _ => return Err(invalid_data("TGA images can't be larger than 65535x65535")),
+    };
+    // top-down rows, 8 alpha bits per pixel if there's alpha
+    let (bits_per_pixel, descriptor) =
+        if header.channels.is_rgba() { (32, 0x28) } else { (24, 0x20) };
+    writer.write_all(&[0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0])?;
+    writer.write_all(&[width[0], width[1], height[0], height[1], bits_per_pixel, descriptor])?;
+    let row_stride = header.width as usize * header.channels.as_u8() as usize;
+    write_bgr_rows(&mut writer, header, data, row_stride, false)
+}
+
+/// Returns the byte offset of an 8-bit channel within a BMP pixel given its bit mask.
+fn bmp_channel_offset(mask: u32, bytes_per_pixel: u8) -> Result<u8> {
+    (0..bytes_per_pixel)
+        .find(|&i| mask == 0xff << (8 * i))
+        .ok_or_else(|| invalid_data(format!("unsupported BMP channel mask: {mask:#010x}")))
+}
+
+/// Parses an uncompressed 24-bit or 32-bit BMP image (including bit-field channel masks).
+fn decode_bmp(data: &[u8]) -> Result<(Header, Vec<u8>)> {
+    if data.get(..2) != Some(b"BM") {
+        return Err(invalid_data("invalid BMP magic (expected BM)"));
+    }
+    let (pixels_offset, info_size) = (read_u32(data, 10)?, read_u32(data, 14)?);
+    if info_size < 40 {
+        return Err(invalid_data(format!("unsupported BMP header size: {info_size}")));
+    }
+    let width = i32::from_le_bytes(read_le(data, 18)?);
+    let height = i32::from_le_bytes(read_le(data, 22)?);
+    let (bits_per_pixel, compression) = (read_u16(data, 28)?, read_u32(data, 30)?);
+    let bytes_per_pixel: u8 = match bits_per_pixel {
+        24 => 3,
+        32 => 4,
+        _ => {
+            let msg = format!("unsupported BMP bit depth: {bits_per_pixel} (expected 24 or 32)");
+            return Err(invalid_data(msg));
+        }
+    };
+    let map = match (bytes_per_pixel, compression) {
+        (3, 0) => SourceChannels::Bgr.channel_map(),
+        (4, 0) => SourceChannels::Bgrx.channel_map(),
+        // BI_BITFIELDS and BI_ALPHABITFIELDS, the masks follow the 40-byte info header
+        (4, 3 | 6) => {
+            let channel = |pos| {
+                read_u32(data, pos)
+                    .and_then(|mask| bmp_channel_offset(mask, 4))
+                    .map(ChannelSource::Byte)
+            };
+            let rgb = [channel(54)?, channel(58)?, channel(62)?];
+            let has_alpha_mask = compression == 6 || info_size >= 56;
+            let alpha = match read_u32(data, 66) {
+                Ok(mask) if has_alpha_mask && mask != 0 => Some(channel(66)?),
+                _ => None,
+            };
+            ChannelMap::new(4, rgb, alpha)?
+        }
+        _ => return Err(invalid_data(format!("unsupported BMP compression: {compression}"))),
+    };
+    let (width, bottom_up) = (width.unsigned_abs(), height > 0);
+    let header = Header::try_new(width, height.unsigned_abs(), map.channels(), ColorSpace::Srgb)?;
+    let pixels = data.get(pixels_offset as usize..).ok_or(Error::UnexpectedBufferEnd)?;
+    let row_stride = (header.width as usize * bytes_per_pixel as usize + 3) & !3;
+    Ok((header, unpack_rows(pixels, &header, map, row_stride, bottom_up)?))
+}
+
+/// Writes RGB images as 24-bit BMP and RGBA images as 32-bit BMP with a V4 header
+/// carrying the alpha mask; rows are stored bottom-up, as most readers expect.
+fn write_bmp(mut writer: impl Write, header: &Header, data: &[u8]) -> Result<()> {
+    let rgba = header.channels.is_rgba();
+    let bytes_per_pixel = header.channels.as_u8() as usize;
+    let row_stride = (header.width as usize * bytes_per_pixel + 3) & !3;
+    let info_size: u32 = if rgba { 108 } else { 40 };
+    let pixels_offset = 14 + info_size;
+    let image_size = u32::try_from(row_stride * header.height as usize)
+        .ok()
+        .filter(|&size| size <= u32::MAX - pixels_offset)
+        .ok_or_else(|| invalid_data("image is too large to be stored as BMP"))?;
+    let (width, height) = match (i32::try_from(header.width), i32::try_from(header.height)) {
+        (Ok(width), Ok(height)) => (width, height),
+        _ => return Err(invalid_data("image is too large to be stored as BMP")),
+    };
+
+    let mut head = Vec::with_capacity(pixels_offset as usize);
+    head.extend(b"BM");
+    head.extend((pixels_offset + image_size).to_le_bytes());
+    head.extend([0; 4]);
+    head.extend(pixels_offset.to_le_bytes());
+    head.extend(info_size.to_le_bytes());
+    head.extend(width.to_le_bytes());
+    head.extend(height.to_le_bytes());
+    head.extend(1_u16.to_le_bytes());
+    head.extend(if rgba { 32_u16 } else { 24 }.to_le_bytes());
+    head.extend(if rgba { 3_u32 } else { 0 }.to_le_bytes());
+    head.extend(image_size.to_le_bytes());
+    head.extend([0; 16]); // resolution and palette
+    if rgba {
+        for mask in [0x00ff_0000_u32, 0x0000_ff00, 0x0000_00ff, 0xff00_0000] {
+            head.extend(mask.to_le_bytes());
+        }
+        head.extend(b"BGRs"); // LCS_sRGB
+        head.extend([0; 48]); // endpoints and gamma, unused for sRGB
+    }
+    writer.write_all(&head)?;
+    write_bgr_rows(&mut writer, header, data, row_stride, true)
+}
+
+/// Decodes an in-memory image of the given format, e.g. one read from stdin (the format
+/// can be detected via [`ImageFormat::from_magic`]).
+///
+/// Grayscale images are expanded to RGB(A), the color space is assumed to be sRGB unless
+/// it's a QOI image.
+pub fn decode_image(data: impl AsRef<[u8]>, format: ImageFormat) -> Result<(Header, Vec<u8>)> {
+    let data = data.as_ref();
+    match format {
+        ImageFormat::Qoi => crate::decode_to_vec(data),
+        ImageFormat::Png => decode_png(data),
+        ImageFormat::Ppm => decode_ppm(data),
+        ImageFormat::Tga => decode_tga(data),
+        ImageFormat::Bmp => decode_bmp(data),
+    }
+}
+
+/// Encodes an image in the given format into a writer, e.g. stdout.
+///
+/// The color space is only stored in QOI images; PPM images can't store alpha, and TGA images
+/// can't be larger than 65535x65535. The writer is flushed at the end.
+pub fn encode_image(
+    mut writer: impl Write, format: ImageFormat, header: &Header, data: &[u8],
+) -> Result<()> {
+    match format {
+        ImageFormat::Qoi => {
+            let encoder = crate::Encoder::new(data, header.width, header.height)?;
+            let _ = encoder.with_colorspace(header.colorspace).encode_to_stream(&mut writer)?;
+        }
+        ImageFormat::Png => write_png(&mut writer, header, data)?,
+        ImageFormat::Ppm => write_ppm(&mut writer, header, data)?,
+        ImageFormat::Tga => write_tga(&mut writer, header, data)?,
+        ImageFormat::Bmp => write_bmp(&mut writer, header, data)?,
+    }
+    Ok(writer.flush()?)
+}
+
+/// Reads an image of any supported format, detected from the file extension.
+///
+/// See [`decode_image`] for details.
+pub fn read_image(path: impl AsRef<Path>) -> Result<(Header, Vec<u8>)> {
+    let path = path.as_ref();
+    match ImageFormat::from_path(path) {
+        Some(format) => decode_image(fs::read(path)?, format),
+        None => Err(invalid_data(format!("unsupported image format: {}", path.display()))),
+    }
+}
+
+/// Writes an image in the format detected from the file extension.
+///
+/// See [`encode_image`] for details.
+pub fn write_image(path: impl AsRef<Path>, header: &Header, data: &[u8]) -> Result<()> {
+    let path = path.as_ref();
+    match ImageFormat::from_path(path) {
+        Some(format) => encode_image(BufWriter::new(File::create(path)?), format, header, data),
+        None => Err(invalid_data(format!("unsupported image format: {}", path.display()))),
+    }
+}
+
+/// Options for [`convert_tree`].
+#[derive(Clone, Debug)]
+pub struct BatchOptions {
+    target: ImageFormat,
+    parallel: ParallelOptions,
+    overwrite: bool,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self::new(ImageFormat::Qoi)
+    }
+}
+
+impl BatchOptions {
+    /// Creates the default options for converting into the given format.
+    ///
+    /// If the target is QOI, all PNG, PPM, TGA and BMP images are converted; otherwise, all
+    /// QOI images are converted into the target format.
+    pub const fn new(target: ImageFormat) -> Self {
+        Self { target, parallel: ParallelOptions::new(), overwrite: false }
+    }
+
+    /// Parallelism options (default: one image per thread, using available parallelism).
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn with_parallel(mut self, parallel: ParallelOptions) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Overwrite existing files in the target tree (default: false, such files are skipped).
+    pub const fn with_overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Returns the target image format.
+    pub const fn target(&self) -> ImageFormat {
+        self.target
+    }
+
+    /// Returns the parallelism options.
+    pub const fn parallel(&self) -> &ParallelOptions {
+        &self.parallel
+    }
+
+    /// Returns true if existing files in the target tree are overwritten.
+    pub const fn overwrite(&self) -> bool {
+        self.overwrite
+    }
+
+    const fn is_source(&self, format: ImageFormat) -> bool {
+        match self.target {
+            ImageFormat::Qoi => !matches!(format, ImageFormat::Qoi),
+            _ => matches!(format, ImageFormat::Qoi),
+        }
+    }
+}
+
+/// Progress of [`convert_tree`], reported after each converted (or failed) image.
+#[derive(Debug)]
+pub struct Progress<'a> {
+    /// Source image path
+    pub source: &'a Path,
+    /// Target image path
+    pub target: &'a Path,
+    /// Result of the conversion
+    pub result: &'a Result<()>,
+    /// Number of images processed so far, including this one
+    pub done: usize,
+    /// Total number of images to process
+    pub total: usize,
+}
+
+/// Summary of a [`convert_tree`] run.
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    /// Number of images converted successfully
+    pub n_converted: usize,
+    /// Number of images skipped because the target file already exists
+    pub n_skipped: usize,
+    /// Source paths of images that failed to convert (or directory entries that failed to
+    /// be read), along with the errors
+    pub errors: Vec<(PathBuf, Error)>,
+}
+
+struct Job {
+    source: PathBuf,
+    target: PathBuf,
+}
+
+fn convert_file(job: &Job) -> Result<()> {
+    if let Some(parent) = job.target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let (header, data) = read_image(&job.source)?;
+    write_image(&job.target, &header, &data)
+}
+
+/// Same as [`convert_tree_with_progress`], without progress reporting.
+pub fn convert_tree(
+    src: impl AsRef<Path>, dst: impl AsRef<Path>, options: &BatchOptions,
+) -> Result<BatchReport> {
+    convert_tree_with_progress(src, dst, options, |_| {})
+}
+
+/// Converts all images in the `src` directory tree, mirroring the tree into `dst`.
+///
+/// Converted images keep their relative paths, with the extension replaced by that of the
+/// target format. Failures to convert individual images don't stop the conversion and are
+/// collected in the report instead; an error is only returned if `src` is not a directory.
+/// If several images map to the same target path (e.g. `a.png` and `a.ppm`), only the
+/// first one in file name order is converted and the rest are reported as errors.
+/// The progress callback is called on the calling thread after each image.
+pub fn convert_tree_with_progress(
+    src: impl AsRef<Path>, dst: impl AsRef<Path>, options: &BatchOptions,
+    mut progress: impl FnMut(&Progress),
+) -> Result<BatchReport> {
+    let (src, dst) = (src.as_ref(), dst.as_ref());
+    if !fs::metadata(src)?.is_dir() {
+        let msg = format!("not a directory: {}", src.display());
+        return Err(Error::IoError(io::Error::new(io::ErrorKind::InvalidInput, msg)));
+    }
+
+    let mut report = BatchReport::default();
+    let (mut jobs, mut claimed) = (vec![], HashMap::<PathBuf, PathBuf>::new());
+    for entry in WalkDir::new(src).sort_by_file_name() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                let path = err.path().unwrap_or(src).to_path_buf();
+                report.errors.push((path, io::Error::from(err).into()));
+                continue;
+            }
+        };
+        let source = entry.path();
+        match ImageFormat::from_path(source) {
+            Some(format) if entry.file_type().is_file() && options.is_source(format) => {}
+            _ => continue,
+        }
+        let relative = source.strip_prefix(src).unwrap_or(source);
+        let target = dst.join(relative).with_extension(options.target.extension());
+        if !options.overwrite && target.exists() {
+            report.n_skipped += 1;
+        } else if let Some(other) = claimed.get(&target) {
+            // e.g. `a.png` and `a.ppm` both map to `a.qoi`; the first one wins
+            let (target, other) = (target.display(), other.display());
+            let msg = format!("{target} is already converted from {other}");
+            let err = io::Error::new(io::ErrorKind::AlreadyExists, msg);
+            report.errors.push((source.to_path_buf(), err.into()));
+        } else {
+            let _ = claimed.insert(target.clone(), source.to_path_buf());
+            jobs.push(Job { source: source.to_path_buf(), target });
+        }
+    }
+
+    let (total, mut done) = (jobs.len(), 0);
+    let convert = |job: Job| {
+        let result = convert_file(&job);
+        (job, result)
+    };
+    options.parallel.map_unordered(jobs, convert, |(job, result)| {
+        done += 1;
+        let (source, target) = (&job.source, &job.target);
+        progress(&Progress { source, target, result: &result, done, total });
+        match result {
+            Ok(()) => report.n_converted += 1,
+            Err(err) => report.errors.push((job.source, err)),
+        }
+    });
+    Ok(report)
+}
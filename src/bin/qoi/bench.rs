@@ -0,0 +1,180 @@
+use std::cmp::Ordering;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use anyhow::{ensure, Result};
+use structopt::StructOpt;
+
+use crate::image::{find_files, is_png_file, is_qoi_file, Image};
+
+/// Benchmark encoding and decoding speed on a set of images (QOI images are decode-only).
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    /// Files or directories containing PNG or QOI images.
+    #[structopt(parse(from_os_str), required = true)]
+    paths: Vec<PathBuf>,
+    /// Number of seconds allocated for each image.
+    #[structopt(short, long, default_value = "1")]
+    seconds: f64,
+    /// Use average (mean) instead of the median.
+    #[structopt(short, long)]
+    average: bool,
+    /// Write per-image results to a CSV file (compatible with `qoi-bench --compare`).
+    #[structopt(long, parse(from_os_str))]
+    csv: Option<PathBuf>,
+}
+
+/// Runs the function repeatedly for roughly the given number of seconds (at least
+/// twice), returns the last output and the median or the mean of the timings in seconds.
+fn timeit<T>(
+    seconds: f64, use_median: bool, mut func: impl FnMut() -> Result<T>,
+) -> Result<(T, f64)> {
+    let (mut timings, mut out) = (vec![], None);
+    let t_start = Instant::now();
+    while timings.len() < 2 || t_start.elapsed().as_secs_f64() < seconds {
+        let t0 = Instant::now();
+        out = Some(func()?);
+        timings.push(t0.elapsed().as_secs_f64());
+    }
+    timings.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    let sec = if use_median {
+        timings[timings.len() / 2]
+    } else {
+        timings.iter().sum::<f64>() / timings.len() as f64
+    };
+    Ok((out.unwrap(), sec))
+}
+
+struct BenchResult {
+    file: String,
+    img: Image,
+    decode_sec: f64,
+    encode_sec: Option<f64>,
+}
+
+impl BenchResult {
+    fn mpixels(&self) -> f64 {
+        self.img.header.n_pixels() as f64 / 1e6
+    }
+
+    fn mbytes(&self) -> f64 {
+        self.img.header.n_bytes() as f64 / 1024. / 1024.
+    }
+}
+
+fn bench_file(path: &Path, seconds: f64, use_median: bool) -> Result<BenchResult> {
+    let file = path.to_string_lossy().into_owned();
+    let img = Image::read(path)?;
+    let (encoded, encode_sec) = if is_qoi_file(path) {
+        (fs::read(path)?, None)
+    } else {
+        let (encoded, encode_sec) = timeit(seconds / 2., use_median, || img.encode_qoi())?;
+        (encoded, Some(encode_sec))
+    };
+    let ((_, decoded), decode_sec) =
+        timeit(seconds / 2., use_median, || Ok(qoi::decode_to_vec(&encoded)?))?;
+    ensure!(decoded == img.data, "decoded data doesn't roundtrip: {}", file);
+    Ok(BenchResult { file, img, decode_sec, encode_sec })
+}
+
+/// Prints decode and encode timings given as `(megapixels, megabytes, seconds)`.
+fn print_row(name: &str, decode: (f64, f64, f64), encode: Option<(f64, f64, f64)>) {
+    let w_col = 13;
+    let (encode_mpixels, encode_mbytes, encode_sec) = encode.unwrap_or((0., 0., f64::NAN));
+    let (decode_mpixels, decode_mbytes, decode_sec) = decode;
+    print!("{:<w$}", name, w = 9);
+    print!("{:>w$.2}", decode_sec * 1e3, w = w_col);
+    print!("{:>w$.2}", encode_sec * 1e3, w = w_col);
+    print!("{:>w$.1}", decode_mpixels / decode_sec, w = w_col);
+    print!("{:>w$.1}", encode_mpixels / encode_sec, w = w_col);
+    print!("{:>w$.1}", decode_mbytes / decode_sec, w = w_col);
+    print!("{:>w$.1}", encode_mbytes / encode_sec, w = w_col);
+    println!();
+}
+
+fn print_header() {
+    print!("{:<w$}", "", w = 9);
+    for col in
+        ["decode:ms", "encode:ms", "decode:Mp/s", "encode:Mp/s", "decode:MB/s", "encode:MB/s"]
+    {
+        print!("{:>w$}", col, w = 13);
+    }
+    println!();
+}
+
+fn write_csv(path: &Path, results: &[BenchResult]) -> Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    writeln!(w, "file,codec,width,height,channels,decode_ms,encode_ms,decode_mpps,encode_mpps")?;
+    for r in results {
+        let h = &r.img.header;
+        let fmt_opt = |v: Option<f64>| v.map(|v| v.to_string()).unwrap_or_default();
+        writeln!(
+            w,
+            "{},qoi-rust,{},{},{},{},{},{},{}",
+            r.file,
+            h.width,
+            h.height,
+            h.channels.as_u8(),
+            r.decode_sec * 1e3,
+            fmt_opt(r.encode_sec.map(|t| t * 1e3)),
+            r.mpixels() / r.decode_sec,
+            fmt_opt(r.encode_sec.map(|t| r.mpixels() / t)),
+        )?;
+    }
+    Ok(w.flush()?)
+}
+
+pub fn run(args: &Args) -> Result<()> {
+    let files = find_files(&args.paths, |path| is_png_file(path) || is_qoi_file(path))?;
+    ensure!(!files.is_empty(), "no PNG or QOI files found in given paths");
+    let use_median = !args.average;
+    let mut results = vec![];
+    for file in &files {
+        match bench_file(file, args.seconds, use_median) {
+            Ok(r) => {
+                let h = &r.img.header;
+                println!(
+                    "{} ({}x{}:{}, {:.2} MB raw, {:.2} MP{})",
+                    r.file,
+                    h.width,
+                    h.height,
+                    h.channels.as_u8(),
+                    r.mbytes(),
+                    r.mpixels(),
+                    if r.encode_sec.is_none() { ", decode only" } else { "" }
+                );
+                print_header();
+                let timing = |sec| (r.mpixels(), r.mbytes(), sec);
+                print_row("qoi-rust", timing(r.decode_sec), r.encode_sec.map(timing));
+                results.push(r);
+            }
+            Err(err) => eprintln!("{:?}", err),
+        }
+    }
+    if results.len() > 1 {
+        // encoding totals only include the images that have actually been encoded
+        let total = |encode: bool| {
+            let rs: Vec<_> = results.iter().filter(|r| !encode || r.encode_sec.is_some()).collect();
+            let sec = |r: &BenchResult| if encode { r.encode_sec.unwrap() } else { r.decode_sec };
+            let mpixels = rs.iter().map(|r| r.mpixels()).sum();
+            let mbytes = rs.iter().map(|r| r.mbytes()).sum();
+            Some((mpixels, mbytes, rs.iter().map(|r| sec(r)).sum())).filter(|_| !rs.is_empty())
+        };
+        let (mpixels, mbytes, _) = total(false).unwrap();
+        println!("---");
+        println!(
+            "Overall results: ({} images, {:.2} MB raw, {:.2} MP):",
+            results.len(),
+            mbytes,
+            mpixels
+        );
+        print_header();
+        print_row("qoi-rust", total(false).unwrap(), total(true));
+    }
+    if let Some(path) = &args.csv {
+        write_csv(path, &results)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use qoi::batch::ImageFormat;
+use qoi::{Channels, ColorSpace};
+use structopt::StructOpt;
+
+use crate::image::{is_stdio, Image};
+
+fn parse_channels(s: &str) -> Result<Channels> {
+    match s {
+        "3" | "rgb" => Ok(Channels::Rgb),
+        "4" | "rgba" => Ok(Channels::Rgba),
+        _ => bail!("invalid channels (expected 3, 4, rgb or rgba): {}", s),
+    }
+}
+
+fn parse_format(s: &str) -> Result<ImageFormat> {
+    match s {
+        "qoi" => Ok(ImageFormat::Qoi),
+        "png" => Ok(ImageFormat::Png),
+        _ => bail!("invalid format (expected qoi or png): {}", s),
+    }
+}
+
+fn parse_colorspace(s: &str) -> Result<ColorSpace> {
+    match s {
+        "srgb" => Ok(ColorSpace::Srgb),
+        "linear" => Ok(ColorSpace::Linear),
+        _ => bail!("invalid colorspace (expected srgb or linear): {}", s),
+    }
+}
+
+/// Convert images between PNG and QOI formats (based on file extensions).
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    /// Input image (.png or .qoi), or - to read from stdin.
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+    /// Output image (.png or .qoi), or - to write to stdout.
+    #[structopt(parse(from_os_str))]
+    output: PathBuf,
+    /// Output format when writing to stdout: qoi or png (default: qoi).
+    #[structopt(short, long, parse(try_from_str = parse_format))]
+    format: Option<ImageFormat>,
+    /// Output channels: 3/rgb or 4/rgba (default: same as the input).
+    #[structopt(short, long, parse(try_from_str = parse_channels))]
+    channels: Option<Channels>,
+    /// Output colorspace: srgb or linear (default: same as the input).
+    #[structopt(long, parse(try_from_str = parse_colorspace))]
+    colorspace: Option<ColorSpace>,
+    /// Treat input and output as directories: convert all new or changed PNG images in the
+    /// input tree into QOI images in the output tree, then keep watching for changes.
+    #[structopt(short, long)]
+    watch: bool,
+}
+
+impl Args {
+    fn apply(&self, mut img: Image) -> Image {
+        if let Some(channels) = self.channels {
+            img = img.to_channels(channels);
+        }
+        if let Some(colorspace) = self.colorspace {
+            img.header.colorspace = colorspace;
+        }
+        img
+    }
+}
+
+pub fn run(args: &Args) -> Result<()> {
+    if args.watch {
+        if args.format.is_some() || is_stdio(&args.input) || is_stdio(&args.output) {
+            bail!("--watch can't be used with stdin/stdout");
+        }
+        return watch(args);
+    }
+    let img = args.apply(Image::read(&args.input)?);
+    if is_stdio(&args.output) {
+        img.write_stdout(args.format.unwrap_or(ImageFormat::Qoi))
+    } else {
+        if args.format.is_some() {
+            bail!("--format is only used when writing to stdout");
+        }
+        img.write(&args.output)
+    }
+}
+
+#[cfg(feature = "watch")]
+fn watch(args: &Args) -> Result<()> {
+    crate::watch::run(&args.input, &args.output, &|img| args.apply(img))
+}
+
+#[cfg(not(feature = "watch"))]
+fn watch(_: &Args) -> Result<()> {
+    bail!("--watch requires the qoi tool to be built with the `watch` feature")
+}
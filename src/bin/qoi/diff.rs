@@ -0,0 +1,90 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Result};
+use qoi::ops::Ops;
+use qoi::Channels;
+use structopt::StructOpt;
+
+use crate::image::{is_qoi_file, Image};
+
+/// Compare the pixels (and the ops, for QOI images) of two images; exits with status 1
+/// if the pixels differ.
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    /// First image (.png or .qoi).
+    #[structopt(parse(from_os_str))]
+    a: PathBuf,
+    /// Second image (.png or .qoi).
+    #[structopt(parse(from_os_str))]
+    b: PathBuf,
+}
+
+/// Finds and reports the first op that differs between two encoded images, returning
+/// its index and byte offset, or `None` if all of the ops match.
+fn first_op_diff(a: &Path, b: &Path) -> Result<Option<(usize, usize)>> {
+    let (data_a, data_b) = (fs::read(a)?, fs::read(b)?);
+    let (ops_a, ops_b) = (Ops::new(&data_a)?, Ops::new(&data_b)?);
+    let mut offset = qoi::consts::QOI_HEADER_SIZE;
+    for (i, (op_a, op_b)) in ops_a.zip(ops_b).enumerate() {
+        let (op_a, op_b) = (op_a?, op_b?);
+        if op_a != op_b {
+            println!("first differing op: #{} at byte {}: {:?} vs {:?}", i, offset, op_a, op_b);
+            return Ok(Some((i, offset)));
+        }
+        offset += op_a.n_bytes();
+    }
+    Ok(None)
+}
+
+pub fn run(args: &Args) -> Result<()> {
+    let (a, b) = (Image::read(&args.a)?, Image::read(&args.b)?);
+    let (ha, hb) = (a.header, b.header);
+    ensure!(
+        (ha.width, ha.height) == (hb.width, hb.height),
+        "image dimensions differ: {}x{} vs {}x{}",
+        ha.width,
+        ha.height,
+        hb.width,
+        hb.height
+    );
+    if ha.channels != hb.channels {
+        println!("channels differ: {} vs {} (comparing as RGBA)", ha.channels, hb.channels);
+    }
+    if ha.colorspace != hb.colorspace {
+        println!("colorspace differs: {} vs {}", ha.colorspace, hb.colorspace);
+    }
+    let (a, b) = if ha.channels == hb.channels {
+        (a, b)
+    } else {
+        (a.to_channels(Channels::Rgba), b.to_channels(Channels::Rgba))
+    };
+
+    let n = a.header.channels.as_u8() as usize;
+    let width = a.header.width as usize;
+    let metrics = qoi::metrics::compare(&a.data, &b.data, a.header.width, a.header.height)?;
+    let first_diff = a
+        .data
+        .chunks_exact(n)
+        .zip(b.data.chunks_exact(n))
+        .enumerate()
+        .find(|(_, (pa, pb))| pa != pb);
+
+    if let Some((i, (pa, pb))) = first_diff {
+        println!("pixels differ: {} of {}", metrics.n_diff(), metrics.n_pixels());
+        println!("first differing pixel: ({}, {}): {:?} vs {:?}", i % width, i / width, pa, pb);
+        let (names, max_error) = (["R", "G", "B", "A"], metrics.max_channel_error());
+        let errors: Vec<_> = (0..n).map(|c| format!("{}={}", names[c], max_error[c])).collect();
+        println!("max channel error: {}", errors.join(" "));
+        println!("mse: {:.4}, psnr: {:.2} dB", metrics.mse(), metrics.psnr());
+    } else {
+        println!("pixels match");
+    }
+    if is_qoi_file(&args.a) && is_qoi_file(&args.b) && first_op_diff(&args.a, &args.b)?.is_none() {
+        println!("ops match");
+    }
+    if first_diff.is_some() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use qoi::batch::ImageFormat;
+use qoi::{Channels, Header};
+use walkdir::{DirEntry, WalkDir};
+
+/// Raw image pixels along with the header describing them.
+#[derive(Clone)]
+pub struct Image {
+    pub header: Header,
+    pub data: Vec<u8>,
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().unwrap_or_default().to_string_lossy().to_ascii_lowercase()
+}
+
+pub fn is_qoi_file(path: &Path) -> bool {
+    extension(path) == "qoi"
+}
+
+pub fn is_png_file(path: &Path) -> bool {
+    extension(path) == "png"
+}
+
+/// Collects matching files from the given paths, recursing into directories; the result is sorted.
+pub fn find_files(paths: &[PathBuf], is_match: fn(&Path) -> bool) -> Result<Vec<PathBuf>> {
+    let is_match_file = |path: &PathBuf| path.is_file() && is_match(path);
+    let mut out = vec![];
+    for path in paths {
+        if is_match_file(path) {
+            out.push(path.clone());
+        } else if path.is_dir() {
+            out.extend(
+                WalkDir::new(path)
+                    .follow_links(true)
+                    .into_iter()
+                    .filter_map(Result::ok)
+                    .map(DirEntry::into_path)
+                    .filter(is_match_file),
+            );
+        } else {
+            bail!("path doesn't exist: {}", path.to_string_lossy());
+        }
+    }
+    out.sort_unstable();
+    Ok(out)
+}
+
+/// Returns true if the path is `-`, standing for stdin or stdout.
+pub fn is_stdio(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// Reads the whole file, or the whole stdin if the path is `-`.
+pub fn read_bytes(path: &Path) -> Result<Vec<u8>> {
+    if is_stdio(path) {
+        let mut data = vec![];
+        io::stdin().lock().read_to_end(&mut data).context("error reading stdin")?;
+        Ok(data)
+    } else {
+        Ok(fs::read(path)?)
+    }
+}
+
+impl Image {
+    /// Reads a PNG or a QOI image, depending on the file extension; if the path is `-`,
+    /// the image is read from stdin and its format is detected from the contents.
+    pub fn read(path: &Path) -> Result<Self> {
+        let f = path.to_string_lossy();
+        if is_stdio(path) {
+            Self::read_stdin()
+        } else if is_qoi_file(path) {
+            Self::read_qoi(path).with_context(|| format!("error reading QOI file: {}", f))
+        } else if is_png_file(path) {
+            Self::read_png(path).with_context(|| format!("error reading PNG file: {}", f))
+        } else {
+            bail!("unsupported image format (expected .png or .qoi): {}", f)
+        }
+    }
+
+    pub fn read_stdin() -> Result<Self> {
+        let data = read_bytes(Path::new("-"))?;
+        let format = match ImageFormat::from_magic(&data) {
+            Some(format @ (ImageFormat::Qoi | ImageFormat::Png)) => format,
+            _ => bail!("unsupported image format on stdin (expected PNG or QOI)"),
+        };
+        let (header, data) = qoi::batch::decode_image(&data, format)
+            .with_context(|| format!("error reading {:?} image from stdin", format))?;
+        Ok(Self { header, data })
+    }
+
+    pub fn read_qoi(path: &Path) -> Result<Self> {
+        let (header, data) = qoi::decode_to_vec(fs::read(path)?)?;
+        Ok(Self { header, data })
+    }
+
+    pub fn read_png(path: &Path) -> Result<Self> {
+        let (header, data) = qoi::batch::read_image(path)?;
+        Ok(Self { header, data })
+    }
+
+    /// Writes a PNG or a QOI image, depending on the file extension.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let f = path.to_string_lossy();
+        if is_qoi_file(path) {
+            self.write_qoi(path).with_context(|| format!("error writing QOI file: {}", f))
+        } else if is_png_file(path) {
+            self.write_png(path).with_context(|| format!("error writing PNG file: {}", f))
+        } else {
+            bail!("unsupported image format (expected .png or .qoi): {}", f)
+        }
+    }
+
+    /// Writes a PNG or a QOI image to stdout.
+    pub fn write_stdout(&self, format: ImageFormat) -> Result<()> {
+        qoi::batch::encode_image(io::stdout().lock(), format, &self.header, &self.data)
+            .with_context(|| format!("error writing {:?} image to stdout", format))
+    }
+
+    pub fn encode_qoi(&self) -> Result<Vec<u8>> {
+        let Header { width, height, colorspace, .. } = self.header;
+        Ok(qoi::Encoder::new(&self.data, width, height)?
+            .with_colorspace(colorspace)
+            .encode_to_vec()?)
+    }
+
+    pub fn write_qoi(&self, path: &Path) -> Result<()> {
+        Ok(fs::write(path, self.encode_qoi()?)?)
+    }
+
+    pub fn write_png(&self, path: &Path) -> Result<()> {
+        Ok(qoi::batch::write_image(path, &self.header, &self.data)?)
+    }
+
+    /// Converts the image to the given number of channels, dropping or adding opaque alpha.
+    pub fn to_channels(&self, channels: Channels) -> Self {
+        let data = match (self.header.channels, channels) {
+            (Channels::Rgb, Channels::Rgba) => {
+                self.data.chunks_exact(3).flat_map(|px| [px[0], px[1], px[2], 0xff]).collect()
+            }
+            (Channels::Rgba, Channels::Rgb) => {
+                self.data.chunks_exact(4).flat_map(|px| [px[0], px[1], px[2]]).collect()
+            }
+            _ => self.data.clone(),
+        };
+        Self { header: Header { channels, ..self.header }, data }
+    }
+}
@@ -0,0 +1,127 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use qoi::ops::{Op, Ops};
+use structopt::StructOpt;
+
+use crate::image::read_bytes;
+
+/// Print the header, compression ratio and op histogram of QOI images.
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    /// QOI images to inspect (- to read one from stdin).
+    #[structopt(parse(from_os_str), required = true)]
+    paths: Vec<PathBuf>,
+}
+
+pub const OP_NAMES: [&str; 6] = ["INDEX", "DIFF", "LUMA", "RGB", "RGBA", "RUN"];
+
+/// Maximum number of decoding anomalies listed per image.
+const MAX_DIAGNOSTICS: usize = 10;
+
+/// Number of ops, encoded bytes and decoded pixels per op type.
+#[derive(Default)]
+pub struct OpStats {
+    pub ops: [usize; 6],
+    pub bytes: [usize; 6],
+    pub pixels: [usize; 6],
+}
+
+impl OpStats {
+    pub fn update(&mut self, op: &Op) {
+        let i = match op {
+            Op::Index(_) => 0,
+            Op::Diff { .. } => 1,
+            Op::Luma { .. } => 2,
+            Op::Rgb { .. } => 3,
+            Op::Rgba { .. } => 4,
+            Op::Run(_) => 5,
+        };
+        self.ops[i] += 1;
+        self.bytes[i] += op.n_bytes();
+        self.pixels[i] += op.n_pixels();
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        for i in 0..OP_NAMES.len() {
+            self.ops[i] += other.ops[i];
+            self.bytes[i] += other.bytes[i];
+            self.pixels[i] += other.pixels[i];
+        }
+    }
+
+    /// Prints the op histogram table, with pixel percentages relative to `n_pixels`.
+    pub fn print_table(&self, n_pixels: usize) {
+        let (n_ops, n_bytes) = (self.ops.iter().sum(), self.bytes.iter().sum());
+        let (w_name, w_col) = (9, 12);
+        print!("  {:<w$}", "op", w = w_name);
+        for col in ["count", "count:%", "bytes", "bytes:%", "pixels", "pixels:%"] {
+            print!("{:>w$}", col, w = w_col);
+        }
+        println!();
+        for (i, name) in OP_NAMES.iter().enumerate() {
+            print!("  {:<w$}", name, w = w_name);
+            print!("{:>w$}", self.ops[i], w = w_col);
+            print!("{:>w$.2}", pct(self.ops[i], n_ops), w = w_col);
+            print!("{:>w$}", self.bytes[i], w = w_col);
+            print!("{:>w$.2}", pct(self.bytes[i], n_bytes), w = w_col);
+            print!("{:>w$}", self.pixels[i], w = w_col);
+            print!("{:>w$.2}", pct(self.pixels[i], n_pixels), w = w_col);
+            println!();
+        }
+    }
+}
+
+pub fn pct(value: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.
+    } else {
+        value as f64 / total as f64 * 100.
+    }
+}
+
+fn inspect(path: &Path) -> Result<()> {
+    let data = read_bytes(path)?;
+    let mut ops = Ops::new(&data)?;
+    let header = *ops.header();
+    let mut stats = OpStats::default();
+    for op in &mut ops {
+        stats.update(&op?);
+    }
+    let n_trailing = ops.data().len();
+    let n_encoded = data.len() - n_trailing;
+
+    println!("{}:", path.to_string_lossy());
+    println!("  dimensions:  {}x{} ({} pixels)", header.width, header.height, header.n_pixels());
+    println!("  channels:    {}", header.channels);
+    println!("  colorspace:  {}", header.colorspace);
+    println!("  raw size:    {} ({} bytes)", header.n_bytes_human(), header.n_bytes());
+    println!("  encoded:     {} bytes", n_encoded);
+    if n_trailing != 0 {
+        println!("  trailing:    {} bytes", n_trailing);
+    }
+    println!("  ratio:       {:.2}%", pct(n_encoded, header.n_bytes()));
+    println!("  bits/pixel:  {:.3}", n_encoded as f64 * 8. / header.n_pixels().max(1) as f64);
+
+    stats.print_table(header.n_pixels());
+
+    let mut diags = vec![];
+    qoi::Decoder::new(&data)?.decode_to_vec_with_diagnostics(|d| diags.push(d))?;
+    if !diags.is_empty() {
+        println!("  anomalies:   {}", diags.len());
+        for diag in diags.iter().take(MAX_DIAGNOSTICS) {
+            println!("    {}", diag);
+        }
+        if diags.len() > MAX_DIAGNOSTICS {
+            println!("    ...");
+        }
+    }
+    Ok(())
+}
+
+pub fn run(args: &Args) -> Result<()> {
+    for path in &args.paths {
+        inspect(path).with_context(|| format!("error inspecting: {}", path.to_string_lossy()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,41 @@
+//! Command-line tool for working with QOI images.
+//!
+//! Built only when the `cli` feature is enabled: `cargo install qoi --features cli`.
+
+mod bench;
+mod convert;
+mod diff;
+mod image;
+mod inspect;
+mod repack;
+mod stats;
+mod view;
+#[cfg(feature = "watch")]
+mod watch;
+
+use anyhow::Result;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "qoi", about = "Command-line tool for QOI (Quite Okay Image) format.")]
+enum Command {
+    Bench(bench::Args),
+    Convert(convert::Args),
+    Diff(diff::Args),
+    Inspect(inspect::Args),
+    Repack(repack::Args),
+    Stats(stats::Args),
+    View(view::Args),
+}
+
+fn main() -> Result<()> {
+    match Command::from_args() {
+        Command::Bench(args) => bench::run(&args),
+        Command::Convert(args) => convert::run(&args),
+        Command::Diff(args) => diff::run(&args),
+        Command::Inspect(args) => inspect::run(&args),
+        Command::Repack(args) => repack::run(&args),
+        Command::Stats(args) => stats::run(&args),
+        Command::View(args) => view::run(&args),
+    }
+}
@@ -0,0 +1,69 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{ensure, Context, Result};
+use qoi::Channels;
+use structopt::StructOpt;
+
+use crate::image::{is_qoi_file, Image};
+
+/// Re-encode QOI images produced by other (possibly suboptimal) encoders, reporting the size delta.
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    /// QOI image to repack.
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+    /// Output image (default: overwrite the input).
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+    /// Drop the alpha channel if all pixels are fully opaque.
+    #[structopt(short, long)]
+    reduce_channels: bool,
+    /// Use the re-encoded image even if it's not smaller than the input (otherwise the input
+    /// is kept as is, or copied to the output path).
+    #[structopt(short, long)]
+    force: bool,
+    /// Only report the size delta, don't write anything.
+    #[structopt(short = "n", long)]
+    dry_run: bool,
+}
+
+fn is_opaque(img: &Image) -> bool {
+    img.header.channels == Channels::Rgba && img.data.chunks_exact(4).all(|px| px[3] == 0xff)
+}
+
+pub fn run(args: &Args) -> Result<()> {
+    ensure!(is_qoi_file(&args.input), "expected a .qoi file: {}", args.input.display());
+    let output = args.output.as_ref().unwrap_or(&args.input);
+    ensure!(is_qoi_file(output), "expected a .qoi file: {}", output.display());
+
+    let data = fs::read(&args.input)?;
+    let (header, pixels) = qoi::decode_to_vec(&data)
+        .with_context(|| format!("error reading QOI file: {}", args.input.display()))?;
+    let mut img = Image { header, data: pixels };
+    if args.reduce_channels && is_opaque(&img) {
+        img = img.to_channels(Channels::Rgb);
+    }
+    let encoded = img.encode_qoi()?;
+
+    let (old, new) = (data.len(), encoded.len());
+    let delta = new as f64 / old as f64 * 100. - 100.;
+    print!("{}: {} -> {} bytes ({:+.2}%)", args.input.display(), old, new, delta);
+    if img.header.channels != header.channels {
+        print!(", {} -> {} channels", header.channels.as_u8(), img.header.channels.as_u8());
+    }
+    if args.dry_run {
+        println!();
+    } else if new < old || args.force {
+        fs::write(output, encoded)
+            .with_context(|| format!("error writing QOI file: {}", output.display()))?;
+        println!(", written to {}", output.display());
+    } else {
+        if output != &args.input {
+            fs::write(output, &data)
+                .with_context(|| format!("error writing QOI file: {}", output.display()))?;
+        }
+        println!(", kept as is");
+    }
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Context, Result};
+use qoi::ops::Ops;
+use qoi::{Channels, Decoder, Header, HumanBytes};
+use structopt::StructOpt;
+
+use crate::image::{find_files, is_qoi_file};
+use crate::inspect::{pct, OpStats, OP_NAMES};
+
+/// Aggregate compression ratios, op distributions and channel usage over a corpus of QOI images.
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    /// Files or directories containing QOI images.
+    #[structopt(parse(from_os_str), required = true)]
+    paths: Vec<PathBuf>,
+    /// Write per-image statistics to a CSV file.
+    #[structopt(long, parse(from_os_str))]
+    csv: Option<PathBuf>,
+}
+
+struct FileStats {
+    file: String,
+    header: Header,
+    n_encoded: usize,
+    /// Channels actually used by the pixels (RGB if an RGBA image is fully opaque).
+    used_channels: Channels,
+    ops: OpStats,
+}
+
+impl FileStats {
+    fn ratio(&self) -> f64 {
+        pct(self.n_encoded, self.header.n_bytes())
+    }
+
+    fn bits_per_pixel(&self) -> f64 {
+        self.n_encoded as f64 * 8. / self.header.n_pixels().max(1) as f64
+    }
+}
+
+fn analyze(path: &Path) -> Result<FileStats> {
+    let data = fs::read(path)?;
+    let mut ops = Ops::new(&data)?;
+    let header = *ops.header();
+    let mut stats = OpStats::default();
+    for op in &mut ops {
+        stats.update(&op?);
+    }
+    let n_encoded = data.len() - ops.data().len();
+    let used_channels = Decoder::new(&data)?.detect_channels()?;
+    let file = path.to_string_lossy().into_owned();
+    Ok(FileStats { file, header, n_encoded, used_channels, ops: stats })
+}
+
+fn print_summary(results: &[FileStats], n_failed: usize) {
+    let n_pixels: usize = results.iter().map(|r| r.header.n_pixels()).sum();
+    let n_raw: usize = results.iter().map(|r| r.header.n_bytes()).sum();
+    let n_encoded: usize = results.iter().map(|r| r.n_encoded).sum();
+    let mut ratios: Vec<f64> = results.iter().map(FileStats::ratio).collect();
+    ratios.sort_by(f64::total_cmp);
+    let count = |f: &dyn Fn(&FileStats) -> bool| results.iter().filter(|r| f(r)).count();
+
+    println!("images:      {} ({} failed)", results.len(), n_failed);
+    println!("pixels:      {} ({:.1} MP)", n_pixels, n_pixels as f64 / 1e6);
+    println!("raw size:    {} ({} bytes)", HumanBytes(n_raw), n_raw);
+    println!("encoded:     {} ({} bytes)", HumanBytes(n_encoded), n_encoded);
+    println!("ratio:       {:.2}%", pct(n_encoded, n_raw));
+    println!(
+        "per image:   min {:.2}%, median {:.2}%, max {:.2}%",
+        ratios[0],
+        ratios[ratios.len() / 2],
+        ratios[ratios.len() - 1]
+    );
+    println!("bits/pixel:  {:.3}", n_encoded as f64 * 8. / n_pixels.max(1) as f64);
+    println!(
+        "channels:    {} RGB, {} RGBA ({} with opaque alpha only)",
+        count(&|r| r.header.channels.is_rgb()),
+        count(&|r| r.header.channels.is_rgba()),
+        count(&|r| r.header.channels.is_rgba() && r.used_channels.is_rgb()),
+    );
+    println!(
+        "colorspace:  {} sRGB, {} linear",
+        count(&|r| r.header.colorspace.is_srgb()),
+        count(&|r| r.header.colorspace.is_linear()),
+    );
+
+    let mut ops = OpStats::default();
+    results.iter().for_each(|r| ops.merge(&r.ops));
+    ops.print_table(n_pixels);
+}
+
+fn write_csv(path: &Path, results: &[FileStats]) -> Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    write!(w, "file,width,height,channels,used_channels,colorspace,raw_bytes,encoded_bytes")?;
+    write!(w, ",ratio,bits_per_pixel")?;
+    for name in OP_NAMES {
+        write!(w, ",{}", name.to_ascii_lowercase())?;
+    }
+    writeln!(w)?;
+    for r in results {
+        let h = &r.header;
+        write!(
+            w,
+            "{},{},{},{},{},{},{},{},{},{}",
+            r.file,
+            h.width,
+            h.height,
+            h.channels.as_u8(),
+            r.used_channels.as_u8(),
+            h.colorspace.as_u8(),
+            h.n_bytes(),
+            r.n_encoded,
+            r.ratio(),
+            r.bits_per_pixel(),
+        )?;
+        for n_ops in r.ops.ops {
+            write!(w, ",{}", n_ops)?;
+        }
+        writeln!(w)?;
+    }
+    Ok(w.flush()?)
+}
+
+pub fn run(args: &Args) -> Result<()> {
+    let files = find_files(&args.paths, is_qoi_file)?;
+    ensure!(!files.is_empty(), "no QOI files found in given paths");
+    let mut results = vec![];
+    for file in &files {
+        match analyze(file) {
+            Ok(r) => results.push(r),
+            Err(err) => eprintln!("error reading QOI file: {}: {}", file.to_string_lossy(), err),
+        }
+    }
+    ensure!(!results.is_empty(), "none of the {} QOI files could be read", files.len());
+    print_summary(&results, files.len() - results.len());
+    if let Some(path) = &args.csv {
+        write_csv(path, &results)
+            .with_context(|| format!("error writing CSV file: {}", path.to_string_lossy()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,234 @@
+use std::env;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use qoi::Channels;
+use structopt::StructOpt;
+
+use crate::image::Image;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    Auto,
+    Kitty,
+    Sixel,
+    Blocks,
+}
+
+fn parse_protocol(s: &str) -> Result<Protocol> {
+    match s {
+        "auto" => Ok(Protocol::Auto),
+        "kitty" => Ok(Protocol::Kitty),
+        "sixel" => Ok(Protocol::Sixel),
+        "blocks" => Ok(Protocol::Blocks),
+        _ => bail!("invalid protocol (expected auto, kitty, sixel or blocks): {}", s),
+    }
+}
+
+/// Render an image in the terminal (kitty graphics protocol, sixel or half-block cells).
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    /// Image to view (.png or .qoi).
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+    /// Output protocol: auto, kitty, sixel or blocks. Sixel has to be requested explicitly,
+    /// auto picks kitty if the terminal is known to support it and blocks otherwise.
+    #[structopt(short, long, default_value = "auto", parse(try_from_str = parse_protocol))]
+    protocol: Protocol,
+    /// Maximum width in terminal columns (half-block output defaults to $COLUMNS or 80).
+    #[structopt(short, long)]
+    width: Option<usize>,
+}
+
+/// Assumed width of a terminal cell in pixels, used to limit the width of sixel output.
+const SIXEL_CELL_WIDTH: usize = 10;
+
+/// Maximum size of a single chunk of base64 data in the kitty graphics protocol.
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+fn detect_protocol() -> Protocol {
+    let var = |name| env::var(name).unwrap_or_default();
+    let is_kitty = env::var_os("KITTY_WINDOW_ID").is_some()
+        || var("TERM").contains("kitty")
+        || ["WezTerm", "ghostty"].contains(&var("TERM_PROGRAM").as_str());
+    if is_kitty {
+        Protocol::Kitty
+    } else {
+        Protocol::Blocks
+    }
+}
+
+fn terminal_columns() -> usize {
+    env::var("COLUMNS").ok().and_then(|s| s.parse().ok()).filter(|&c| c > 0).unwrap_or(80)
+}
+
+/// Downscales RGBA pixels to the given dimensions by averaging boxes of source pixels.
+fn downscale(
+    data: &[u8], width: usize, height: usize, new_width: usize, new_height: usize,
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(new_width * new_height * 4);
+    // source range covered by target pixel `i`, at least one pixel wide
+    let range = |i: usize, n: usize, new_n: usize| {
+        (i * n / new_n, ((i + 1) * n / new_n).max(i * n / new_n + 1))
+    };
+    for y in 0..new_height {
+        let (y0, y1) = range(y, height, new_height);
+        for x in 0..new_width {
+            let (x0, x1) = range(x, width, new_width);
+            let mut sum = [0_usize; 4];
+            for row in data.chunks_exact(width * 4).take(y1).skip(y0) {
+                for px in row[x0 * 4..x1 * 4].chunks_exact(4) {
+                    sum.iter_mut().zip(px).for_each(|(s, &v)| *s += usize::from(v));
+                }
+            }
+            let n = (x1 - x0) * (y1 - y0);
+            out.extend(sum.iter().map(|&s| (s / n) as u8));
+        }
+    }
+    out
+}
+
+/// Fits the image into the given width (never upscales), returns the new dimensions.
+fn fit_width(
+    data: Vec<u8>, width: usize, height: usize, max_width: usize,
+) -> (Vec<u8>, usize, usize) {
+    if width <= max_width.max(1) {
+        return (data, width, height);
+    }
+    let new_width = max_width.max(1);
+    let new_height = (height * new_width / width).max(1);
+    (downscale(&data, width, height, new_width, new_height), new_width, new_height)
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let v = u32::from(b[0]) << 16 | u32::from(b[1]) << 8 | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(v >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Transmits and displays raw RGBA pixels via the kitty graphics protocol.
+fn write_kitty(
+    out: &mut impl Write, data: &[u8], width: usize, height: usize, columns: Option<usize>,
+) -> Result<()> {
+    let encoded = base64(data);
+    let chunks: Vec<_> = encoded.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        if i == 0 {
+            let columns = columns.map(|c| format!("c={},", c)).unwrap_or_default();
+            write!(out, "\x1b_Ga=T,f=32,s={},v={},{}m={};", width, height, columns, more)?;
+        } else {
+            write!(out, "\x1b_Gm={};", more)?;
+        }
+        out.write_all(chunk)?;
+        out.write_all(b"\x1b\\")?;
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Writes the image as sixels, quantized to a 6x6x6 color cube; transparent pixels are skipped.
+fn write_sixel(out: &mut impl Write, data: &[u8], width: usize, height: usize) -> Result<()> {
+    let level = |v: u8| (usize::from(v) * 5 + 127) / 255;
+    let colors: Vec<Option<usize>> = data
+        .chunks_exact(4)
+        .map(|p| Some(level(p[0]) * 36 + level(p[1]) * 6 + level(p[2])).filter(|_| p[3] >= 128))
+        .collect();
+    write!(out, "\x1bP0;1;0q\"1;1;{};{}", width, height)?;
+    for i in 0..216 {
+        write!(out, "#{};2;{};{};{}", i, i / 36 * 20, i / 6 % 6 * 20, i % 6 * 20)?;
+    }
+    let mut sixels = vec![0_u8; width];
+    for y0 in (0..height).step_by(6) {
+        let band = &colors[y0 * width..(y0 + 6).min(height) * width];
+        let mut used = [false; 216];
+        band.iter().flatten().for_each(|&c| used[c] = true);
+        for color in (0..216).filter(|&c| used[c]) {
+            sixels.fill(0);
+            for (dy, row) in band.chunks_exact(width).enumerate() {
+                for (s, &c) in sixels.iter_mut().zip(row) {
+                    if c == Some(color) {
+                        *s |= 1 << dy;
+                    }
+                }
+            }
+            write!(out, "#{}", color)?;
+            // run-length encode repeated sixels
+            let mut x = 0;
+            while x < width {
+                let n = sixels[x..].iter().take_while(|&&s| s == sixels[x]).count();
+                let ch = (63 + sixels[x]) as char;
+                if n > 3 {
+                    write!(out, "!{}{}", n, ch)?;
+                } else {
+                    (0..n).try_for_each(|_| write!(out, "{}", ch))?;
+                }
+                x += n;
+            }
+            out.write_all(b"$")?;
+        }
+        out.write_all(b"-")?;
+    }
+    out.write_all(b"\x1b\\\n")?;
+    Ok(())
+}
+
+/// Writes the image as 24-bit colored half-block characters, two pixel rows per line.
+fn write_blocks(out: &mut impl Write, data: &[u8], width: usize) -> Result<()> {
+    let rows: Vec<_> = data.chunks_exact(width * 4).collect();
+    for pair in rows.chunks(2) {
+        for x in 0..width {
+            let px = |row: &[u8]| {
+                Some([row[x * 4], row[x * 4 + 1], row[x * 4 + 2]]).filter(|_| row[x * 4 + 3] >= 128)
+            };
+            let (top, bottom) = (px(pair[0]), pair.get(1).and_then(|row| px(row)));
+            match (top, bottom) {
+                (Some([r, g, b]), Some([r2, g2, b2])) => {
+                    write!(out, "\x1b[38;2;{};{};{};48;2;{};{};{}m\u{2580}", r, g, b, r2, g2, b2)?;
+                }
+                (Some([r, g, b]), None) => write!(out, "\x1b[49;38;2;{};{};{}m\u{2580}", r, g, b)?,
+                (None, Some([r, g, b])) => write!(out, "\x1b[49;38;2;{};{};{}m\u{2584}", r, g, b)?,
+                (None, None) => write!(out, "\x1b[0m ")?,
+            }
+        }
+        writeln!(out, "\x1b[0m")?;
+    }
+    Ok(())
+}
+
+pub fn run(args: &Args) -> Result<()> {
+    let img = Image::read(&args.path)?.to_channels(Channels::Rgba);
+    let (width, height) = (img.header.width as usize, img.header.height as usize);
+    let protocol = match args.protocol {
+        Protocol::Auto => detect_protocol(),
+        protocol => protocol,
+    };
+    let mut out = BufWriter::new(io::stdout().lock());
+    match protocol {
+        Protocol::Kitty => write_kitty(&mut out, &img.data, width, height, args.width)?,
+        Protocol::Sixel => {
+            let max_width = args.width.map_or(usize::MAX, |w| w * SIXEL_CELL_WIDTH);
+            let (data, width, height) = fit_width(img.data, width, height, max_width);
+            write_sixel(&mut out, &data, width, height)?;
+        }
+        Protocol::Blocks | Protocol::Auto => {
+            let max_width = args.width.unwrap_or_else(terminal_columns);
+            let (data, width, _) = fit_width(img.data, width, height, max_width);
+            write_blocks(&mut out, &data, width)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
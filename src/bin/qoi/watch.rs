@@ -0,0 +1,101 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use anyhow::{ensure, Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use walkdir::WalkDir;
+
+use crate::image::{is_png_file, Image};
+
+/// How long to wait for a burst of file events to settle before converting, so that
+/// files that are still being written aren't picked up halfway.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Converts PNG images under `input` into QOI images under `output`, mirroring the tree.
+struct Watch<'a> {
+    input: PathBuf,
+    output: PathBuf,
+    convert: &'a dyn Fn(Image) -> Image,
+}
+
+impl Watch<'_> {
+    fn target(&self, source: &Path) -> Option<PathBuf> {
+        let relative = source.strip_prefix(&self.input).ok()?;
+        Some(self.output.join(relative).with_extension("qoi"))
+    }
+
+    /// Returns true if the target exists and is at least as recent as the source.
+    fn is_up_to_date(source: &Path, target: &Path) -> bool {
+        let mtime = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+        matches!((mtime(source), mtime(target)), (Some(s), Some(t)) if t >= s)
+    }
+
+    fn convert_file(&self, source: &Path, target: &Path) -> Result<()> {
+        let img = Image::read_png(source)
+            .with_context(|| format!("error reading PNG file: {}", source.to_string_lossy()))?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        (self.convert)(img).write(target)
+    }
+
+    /// Converts the source if it's a PNG file whose QOI target is missing or outdated.
+    fn update(&self, source: &Path) {
+        let target = match self.target(source) {
+            Some(target) if source.is_file() && is_png_file(source) => target,
+            _ => return,
+        };
+        if Self::is_up_to_date(source, &target) {
+            return;
+        }
+        let (s, t) = (source.to_string_lossy(), target.to_string_lossy());
+        match self.convert_file(source, &target) {
+            Ok(()) => println!("{} -> {}", s, t),
+            Err(err) => eprintln!("error converting {}: {:#}", s, err),
+        }
+    }
+}
+
+/// Converts all new or outdated PNG images under `input` into QOI images under `output`,
+/// then keeps watching `input` and converts PNG images as they are created or changed.
+///
+/// Runs until interrupted; failures to convert individual images are reported and skipped
+/// (a half-written image will be picked up again once it changes).
+pub fn run(input: &Path, output: &Path, convert: &dyn Fn(Image) -> Image) -> Result<()> {
+    ensure!(input.is_dir(), "--watch requires an input directory: {}", input.to_string_lossy());
+    fs::create_dir_all(output)
+        .with_context(|| format!("error creating directory: {}", output.to_string_lossy()))?;
+    // event paths are reported relative to the watched path as the OS sees it
+    let watch = Watch { input: input.canonicalize()?, output: output.to_path_buf(), convert };
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(&watch.input, RecursiveMode::Recursive)?;
+
+    for entry in WalkDir::new(&watch.input).sort_by_file_name().into_iter().filter_map(Result::ok) {
+        watch.update(entry.path());
+    }
+    eprintln!("watching {} for changes...", input.to_string_lossy());
+
+    let mut pending = BTreeSet::new();
+    for event in &rx {
+        let mut event = Some(event);
+        while let Some(result) = event {
+            match result {
+                Ok(ev) if matches!(ev.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                    pending.extend(ev.paths);
+                }
+                Ok(_) => {}
+                Err(err) => eprintln!("watch error: {}", err),
+            }
+            event = rx.recv_timeout(DEBOUNCE).ok();
+        }
+        for source in std::mem::take(&mut pending) {
+            watch.update(&source);
+        }
+    }
+    Ok(())
+}
@@ -1,17 +1,36 @@
+//! Constants defined by the QOI format specification.
+//!
+//! These are mostly useful for tools working with the raw encoded stream (see also
+//! [`ops`](crate::ops)); all names and values follow the current (1.0) specification.
+
+/// Op tag for [`Index`](crate::ops::Op::Index), lower 6 bits store the index position.
 pub const QOI_OP_INDEX: u8 = 0x00; // 00xxxxxx
+/// Op tag for [`Diff`](crate::ops::Op::Diff), lower 6 bits store biased RGB differences.
 pub const QOI_OP_DIFF: u8 = 0x40; // 01xxxxxx
+/// Op tag for [`Luma`](crate::ops::Op::Luma), lower 6 bits store the biased green difference.
 pub const QOI_OP_LUMA: u8 = 0x80; // 10xxxxxx
+/// Op tag for [`Run`](crate::ops::Op::Run), lower 6 bits store the biased run length.
 pub const QOI_OP_RUN: u8 = 0xc0; // 11xxxxxx
+/// Op tag for [`Rgb`](crate::ops::Op::Rgb), followed by three bytes of R, G, B.
 pub const QOI_OP_RGB: u8 = 0xfe; // 11111110
+/// Op tag for [`Rgba`](crate::ops::Op::Rgba), followed by four bytes of R, G, B, A.
 pub const QOI_OP_RGBA: u8 = 0xff; // 11111111
 
+/// Mask selecting the 2-bit tag of the `INDEX`, `DIFF`, `LUMA` and `RUN` ops.
+///
+/// Note that `RGB` and `RGBA` tags take precedence over `RUN` and must be checked first.
 pub const QOI_MASK_2: u8 = 0xc0; // (11)000000
 
+/// Size of the encoded image header in bytes.
 pub const QOI_HEADER_SIZE: usize = 14;
 
+/// End-of-stream marker following the last op.
 pub const QOI_PADDING: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 0x01]; // 7 zeros and one 0x01 marker
+/// Size of the end-of-stream marker in bytes.
 pub const QOI_PADDING_SIZE: usize = 8;
 
+/// Magic bytes at the start of the header (`b"qoif"`), as a big-endian integer.
 pub const QOI_MAGIC: u32 = u32::from_be_bytes(*b"qoif");
 
+/// Maximum number of pixels in an image accepted by this implementation.
 pub const QOI_PIXELS_MAX: usize = 400_000_000;
@@ -0,0 +1,56 @@
+//! Conversions between tightly packed pixel buffers of different layouts.
+//!
+//! All functions convert as many whole pixels as there are in the source buffer (a trailing
+//! partial pixel, if any, is ignored) and return the number of pixels converted. They fail
+//! with [`Error::OutputBufferTooSmall`] if the destination buffer can't fit all of them.
+//!
+//! For single pixels and packed `u32` values, see [`Pixel`](crate::Pixel).
+
+use crate::error::{Error, Result};
+use crate::types::Channels;
+use crate::utils::unlikely;
+
+#[inline]
+pub(crate) fn map_pixels<const S: usize, const D: usize>(
+    src: &[u8], dst: &mut [u8], f: impl Fn(&[u8]) -> [u8; D],
+) -> Result<usize> {
+    let n_pixels = src.len() / S;
+    let required = n_pixels * D;
+    if unlikely(dst.len() < required) {
+        return Err(Error::OutputBufferTooSmall { size: dst.len(), required });
+    }
+    for (s, d) in src.chunks_exact(S).zip(dst.chunks_exact_mut(D)) {
+        d.copy_from_slice(&f(s));
+    }
+    Ok(n_pixels)
+}
+
+/// Expands RGB pixels to RGBA, setting alpha to the given value (typically 255).
+pub fn rgb_to_rgba(src: &[u8], dst: &mut [u8], alpha: u8) -> Result<usize> {
+    map_pixels::<3, 4>(src, dst, |p| [p[0], p[1], p[2], alpha])
+}
+
+/// Converts RGBA pixels to RGB, dropping alpha.
+pub fn rgba_to_rgb(src: &[u8], dst: &mut [u8]) -> Result<usize> {
+    map_pixels::<4, 3>(src, dst, |p| [p[0], p[1], p[2]])
+}
+
+/// Converts RGB pixels to BGRA, setting alpha to the given value (typically 255).
+pub fn rgb_to_bgra(src: &[u8], dst: &mut [u8], alpha: u8) -> Result<usize> {
+    map_pixels::<3, 4>(src, dst, |p| [p[2], p[1], p[0], alpha])
+}
+
+/// Converts BGRA pixels to RGB, dropping alpha.
+pub fn bgra_to_rgb(src: &[u8], dst: &mut [u8]) -> Result<usize> {
+    map_pixels::<4, 3>(src, dst, |p| [p[2], p[1], p[0]])
+}
+
+/// Converts RGBA pixels to BGRA and vice versa.
+pub fn rgba_to_bgra(src: &[u8], dst: &mut [u8]) -> Result<usize> {
+    map_pixels::<4, 4>(src, dst, |p| [p[2], p[1], p[0], p[3]])
+}
+
+/// Swaps the red and blue channels in place (RGB to BGR, RGBA to BGRA and vice versa).
+pub fn swap_red_blue(buf: &mut [u8], channels: Channels) {
+    buf.chunks_exact_mut(channels.as_u8() as usize).for_each(|p| p.swap(0, 2));
+}
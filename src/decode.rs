@@ -1,7 +1,5 @@
-#[cfg(any(feature = "std", feature = "alloc"))]
-use alloc::{vec, vec::Vec};
-#[cfg(feature = "std")]
-use std::io::Read;
+use core::fmt::{self, Debug, Display};
+use core::ops::Deref;
 
 // TODO: can be removed once https://github.com/rust-lang/rust/issues/74985 is stable
 use bytemuck::{cast_slice_mut, Pod};
@@ -11,18 +9,35 @@ use crate::consts::{
     QOI_PADDING, QOI_PADDING_SIZE,
 };
 use crate::error::{Error, Result};
-use crate::header::Header;
+use crate::header::{Header, HeaderOptions};
+use crate::ops::Op;
 use crate::pixel::{Pixel, SupportedChannels};
+use crate::source::{ChannelSource, TargetChannels};
 use crate::types::Channels;
 use crate::utils::{cold, unlikely};
 
-const QOI_OP_INDEX_END: u8 = QOI_OP_INDEX | 0x3f;
-const QOI_OP_RUN_END: u8 = QOI_OP_RUN | 0x3d; // <- note, 0x3d (not 0x3f)
-const QOI_OP_DIFF_END: u8 = QOI_OP_DIFF | 0x3f;
-const QOI_OP_LUMA_END: u8 = QOI_OP_LUMA | 0x3f;
+pub const QOI_OP_INDEX_END: u8 = QOI_OP_INDEX | 0x3f;
+pub const QOI_OP_RUN_END: u8 = QOI_OP_RUN | 0x3d; // <- note, 0x3d (not 0x3f)
+pub const QOI_OP_DIFF_END: u8 = QOI_OP_DIFF | 0x3f;
+pub const QOI_OP_LUMA_END: u8 = QOI_OP_LUMA | 0x3f;
 
+/// Clamps the number of remaining pixels in a run, or fails in strict mode if it overflows.
 #[inline]
-fn decode_impl_slice<const N: usize, const RGBA: bool>(data: &[u8], out: &mut [u8]) -> Result<usize>
+pub const fn check_run_len(run: usize, pixels_left: usize, strict: bool) -> Result<usize> {
+    if unlikely(run > pixels_left) {
+        cold();
+        if strict {
+            return Err(Error::InvalidRunLength { run: run + 1, pixels_left: pixels_left + 1 });
+        }
+        return Ok(pixels_left);
+    }
+    Ok(run)
+}
+
+#[inline]
+fn decode_impl_slice<const N: usize, const RGBA: bool>(
+    data: &[u8], out: &mut [u8], strict: bool,
+) -> Result<usize>
 where
     Pixel<N>: SupportedChannels,
     [u8; N]: Pod,
@@ -34,6 +49,7 @@ where
     let mut index = [Pixel::<4>::new(); 256];
     let mut px = Pixel::<N>::new().with_a(0xff);
     let mut px_rgba: Pixel<4>;
+    let mut a = 0xff; // alpha has to be tracked separately when decoding RGBA into RGB
 
     while let [px_out, ptail @ ..] = pixels {
         pixels = ptail;
@@ -41,6 +57,7 @@ where
             [b1 @ QOI_OP_INDEX..=QOI_OP_INDEX_END, dtail @ ..] => {
                 px_rgba = index[*b1 as usize];
                 px.update(px_rgba);
+                a = px_rgba.a();
                 *px_out = px.into();
                 data = dtail;
                 continue;
@@ -49,13 +66,14 @@ where
                 px.update_rgb(*r, *g, *b);
                 data = dtail;
             }
-            [QOI_OP_RGBA, r, g, b, a, dtail @ ..] if RGBA => {
-                px.update_rgba(*r, *g, *b, *a);
+            [QOI_OP_RGBA, r, g, b, a_, dtail @ ..] if RGBA => {
+                px.update_rgba(*r, *g, *b, *a_);
+                a = *a_;
                 data = dtail;
             }
             [b1 @ QOI_OP_RUN..=QOI_OP_RUN_END, dtail @ ..] => {
                 *px_out = px.into();
-                let run = ((b1 & 0x3f) as usize).min(pixels.len());
+                let run = check_run_len((b1 & 0x3f) as usize, pixels.len(), strict)?;
                 let (phead, ptail) = pixels.split_at_mut(run); // can't panic
                 phead.fill(px.into());
                 pixels = ptail;
@@ -75,10 +93,11 @@ where
                 if unlikely(data.len() < QOI_PADDING_SIZE) {
                     return Err(Error::UnexpectedBufferEnd);
                 }
+                return Err(Error::InvalidOp { op: data[0] });
             }
         }
 
-        px_rgba = px.as_rgba(0xff);
+        px_rgba = px.as_rgba(a);
         index[px_rgba.hash_index() as usize] = px_rgba;
         *px_out = px.into();
     }
@@ -89,18 +108,18 @@ where
         return Err(Error::InvalidPadding);
     }
 
-    Ok(data_len.saturating_sub(data.len()).saturating_sub(QOI_PADDING_SIZE))
+    Ok(data_len - data.len() + QOI_PADDING_SIZE)
 }
 
 #[inline]
 fn decode_impl_slice_all(
-    data: &[u8], out: &mut [u8], channels: u8, src_channels: u8,
+    data: &[u8], out: &mut [u8], channels: u8, src_channels: u8, strict: bool,
 ) -> Result<usize> {
     match (channels, src_channels) {
-        (3, 3) => decode_impl_slice::<3, false>(data, out),
-        (3, 4) => decode_impl_slice::<3, true>(data, out),
-        (4, 3) => decode_impl_slice::<4, false>(data, out),
-        (4, 4) => decode_impl_slice::<4, true>(data, out),
+        (3, 3) => decode_impl_slice::<3, false>(data, out, strict),
+        (3, 4) => decode_impl_slice::<3, true>(data, out, strict),
+        (4, 3) => decode_impl_slice::<4, false>(data, out, strict),
+        (4, 4) => decode_impl_slice::<4, true>(data, out, strict),
         _ => {
             cold();
             Err(Error::InvalidChannels { channels })
@@ -119,16 +138,13 @@ pub fn decode_to_buf(buf: impl AsMut<[u8]>, data: impl AsRef<[u8]>) -> Result<He
     Ok(*decoder.header())
 }
 
-/// Decode the image into a newly allocated vector.
-///
-/// Note: the resulting number of channels will match the header. In order to change
-/// the number of channels, use [`Decoder::with_channels`].
-#[cfg(any(feature = "std", feature = "alloc"))]
+/// Decode a headerless image body into a pre-allocated buffer, returning the number of
+/// bytes written; the image header is supplied out-of-band instead, see [`Decoder::new_body`].
 #[inline]
-pub fn decode_to_vec(data: impl AsRef<[u8]>) -> Result<(Header, Vec<u8>)> {
-    let mut decoder = Decoder::new(&data)?;
-    let out = decoder.decode_to_vec()?;
-    Ok((*decoder.header(), out))
+pub fn decode_body_to_buf(
+    buf: impl AsMut<[u8]>, data: impl AsRef<[u8]>, header: Header,
+) -> Result<usize> {
+    Decoder::new_body(&data, header)?.decode_to_buf(buf)
 }
 
 /// Decode the image header from a slice of bytes.
@@ -137,86 +153,198 @@ pub fn decode_header(data: impl AsRef<[u8]>) -> Result<Header> {
     Header::decode(data)
 }
 
-#[cfg(feature = "std")]
+/// Snapshot of the decoder state at a pixel boundary.
+///
+/// Can be taken via [`Decoder::checkpoint`] and restored via [`Decoder::restore`] later on
+/// (e.g. to seek back and replay part of the image, or to build a seek index). A state is
+/// only meaningful for the image it was taken from.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct DecoderState {
+    index: [Pixel<4>; 64],
+    px: Pixel<4>,
+    run: usize,
+    pixel_pos: usize,
+    pub(crate) byte_offset: usize,
+}
+
+impl DecoderState {
+    #[inline]
+    const fn new() -> Self {
+        Self {
+            index: [Pixel::new(); 64],
+            px: Pixel::new().with_a(0xff),
+            run: 0,
+            pixel_pos: 0,
+            byte_offset: QOI_HEADER_SIZE,
+        }
+    }
+
+    /// Returns the index of previously seen pixels.
+    #[inline]
+    pub const fn index(&self) -> &[Pixel<4>; 64] {
+        &self.index
+    }
+
+    /// Returns the most recently decoded pixel.
+    #[inline]
+    pub const fn previous_pixel(&self) -> Pixel<4> {
+        self.px
+    }
+
+    /// Returns the number of pixels of the current run that are still to be emitted.
+    #[inline]
+    pub const fn pending_run(&self) -> usize {
+        self.run
+    }
+
+    /// Returns the number of pixels decoded so far.
+    #[inline]
+    pub const fn pixel_position(&self) -> usize {
+        self.pixel_pos
+    }
+
+    /// Returns the offset of the next op from the start of the image, header included.
+    #[inline]
+    pub const fn byte_offset(&self) -> usize {
+        self.byte_offset
+    }
+}
+
+impl Default for DecoderState {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Recoverable anomaly encountered while decoding, see [`Decoder::decode_to_buf_with_diagnostics`].
+///
+/// Images containing these are still decodable (unless strict decoding is enabled), but
+/// are unlikely to have been produced by a conforming encoder.
+///
+/// Note: new variants may be added in the future.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[non_exhaustive]
+pub enum Diagnostic {
+    /// Run extending past the last pixel of the image, clamped to the remaining pixels
+    RunOverflow { byte_offset: usize, pixel_pos: usize, run: usize, pixels_left: usize },
+    /// Index op referencing a slot that has never been written to (decodes as zeros)
+    UnsetIndex { byte_offset: usize, pixel_pos: usize, index: u8 },
+    /// Extra bytes after the end marker (input buffers only)
+    TrailingBytes { n_bytes: usize },
+    /// RGBA op in an image declared as RGB (only decodable with lenient channels)
+    UndeclaredAlpha { byte_offset: usize, pixel_pos: usize },
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::RunOverflow { byte_offset, pixel_pos, run, pixels_left } => write!(
+                f,
+                "run of {run} pixels clamped to {pixels_left} at pixel {pixel_pos} \
+                 (byte {byte_offset})"
+            ),
+            Self::UnsetIndex { byte_offset, pixel_pos, index } => write!(
+                f,
+                "index op referencing unset slot {index} at pixel {pixel_pos} (byte {byte_offset})"
+            ),
+            Self::TrailingBytes { n_bytes } => {
+                write!(f, "{n_bytes} trailing bytes after the end marker")
+            }
+            Self::UndeclaredAlpha { byte_offset, pixel_pos } => write!(
+                f,
+                "RGBA op in an image declared as RGB at pixel {pixel_pos} (byte {byte_offset})"
+            ),
+        }
+    }
+}
+
 #[inline]
-fn decode_impl_stream<R: Read, const N: usize, const RGBA: bool>(
-    data: &mut R, out: &mut [u8],
+fn read_op_bytes<R: Reader, const K: usize>(
+    reader: &mut R, state: &mut DecoderState,
+) -> Result<[u8; K]> {
+    let bytes = reader.read_array::<K>()?;
+    state.byte_offset += K;
+    Ok(bytes)
+}
+
+fn decode_impl_state<R: Reader, const N: usize, const RGBA: bool>(
+    reader: &mut R, state: &mut DecoderState, out: &mut [u8], n_pixels: usize, strict: bool,
+    undeclared_alpha: bool, diag: &mut impl FnMut(Diagnostic),
 ) -> Result<()>
 where
     Pixel<N>: SupportedChannels,
     [u8; N]: Pod,
 {
-    let mut pixels = cast_slice_mut::<_, [u8; N]>(out);
-
-    let mut index = [Pixel::<N>::new(); 256];
-    let mut px = Pixel::<N>::new().with_a(0xff);
-
-    while let [px_out, ptail @ ..] = pixels {
-        pixels = ptail;
-        let mut p = [0];
-        data.read_exact(&mut p)?;
-        let [b1] = p;
-        match b1 {
-            QOI_OP_INDEX..=QOI_OP_INDEX_END => {
-                px = index[b1 as usize];
-                *px_out = px.into();
-                continue;
-            }
-            QOI_OP_RGB => {
-                let mut p = [0; 3];
-                data.read_exact(&mut p)?;
-                px.update_rgb(p[0], p[1], p[2]);
-            }
-            QOI_OP_RGBA if RGBA => {
-                let mut p = [0; 4];
-                data.read_exact(&mut p)?;
-                px.update_rgba(p[0], p[1], p[2], p[3]);
-            }
-            QOI_OP_RUN..=QOI_OP_RUN_END => {
-                *px_out = px.into();
-                let run = ((b1 & 0x3f) as usize).min(pixels.len());
-                let (phead, ptail) = pixels.split_at_mut(run); // can't panic
-                phead.fill(px.into());
-                pixels = ptail;
-                continue;
-            }
-            QOI_OP_DIFF..=QOI_OP_DIFF_END => {
-                px.update_diff(b1);
+    let mut px_out = Pixel::<N>::new();
+    for out in cast_slice_mut::<_, [u8; N]>(out) {
+        if state.run != 0 {
+            state.run -= 1;
+        } else {
+            let [b1] = read_op_bytes(reader, state)?;
+            match b1 {
+                QOI_OP_INDEX..=QOI_OP_INDEX_END => {
+                    state.px = state.index[b1 as usize];
+                    // only zero pixels hash to slot 0, so other slots can't legitimately be zero
+                    if unlikely(b1 != 0 && state.px == Pixel::new()) {
+                        let (byte_offset, pixel_pos) = (state.byte_offset - 1, state.pixel_pos);
+                        diag(Diagnostic::UnsetIndex { byte_offset, pixel_pos, index: b1 });
+                    }
+                }
+                QOI_OP_RGB => {
+                    let [r, g, b] = read_op_bytes(reader, state)?;
+                    state.px.update_rgb(r, g, b);
+                }
+                QOI_OP_RGBA if RGBA => {
+                    if unlikely(undeclared_alpha) {
+                        let (byte_offset, pixel_pos) = (state.byte_offset - 1, state.pixel_pos);
+                        diag(Diagnostic::UndeclaredAlpha { byte_offset, pixel_pos });
+                    }
+                    let [r, g, b, a] = read_op_bytes(reader, state)?;
+                    state.px.update_rgba(r, g, b, a);
+                }
+                QOI_OP_RUN..=QOI_OP_RUN_END => {
+                    let (run, pixels_left) = ((b1 & 0x3f) as usize, n_pixels - state.pixel_pos - 1);
+                    state.run = check_run_len(run, pixels_left, strict)?;
+                    if unlikely(state.run != run) {
+                        let (byte_offset, pixel_pos) = (state.byte_offset - 1, state.pixel_pos);
+                        let (run, pixels_left) = (run + 1, pixels_left + 1);
+                        diag(Diagnostic::RunOverflow { byte_offset, pixel_pos, run, pixels_left });
+                    }
+                }
+                QOI_OP_DIFF..=QOI_OP_DIFF_END => state.px.update_diff(b1),
+                QOI_OP_LUMA..=QOI_OP_LUMA_END => {
+                    let [b2] = read_op_bytes(reader, state)?;
+                    state.px.update_luma(b1, b2);
+                }
+                _ => {
+                    cold();
+                    return Err(Error::InvalidOp { op: b1 });
+                }
             }
-            QOI_OP_LUMA..=QOI_OP_LUMA_END => {
-                let mut p = [0];
-                data.read_exact(&mut p)?;
-                let [b2] = p;
-                px.update_luma(b1, b2);
-            }
-            _ => {
-                cold();
+            if !matches!(b1, QOI_OP_INDEX..=QOI_OP_INDEX_END | QOI_OP_RUN..=QOI_OP_RUN_END) {
+                state.index[state.px.hash_index() as usize] = state.px;
             }
         }
-
-        index[px.hash_index() as usize] = px;
-        *px_out = px.into();
+        px_out.update(state.px);
+        *out = px_out.into();
+        state.pixel_pos += 1;
     }
-
-    let mut p = [0_u8; QOI_PADDING_SIZE];
-    data.read_exact(&mut p)?;
-    if unlikely(p != QOI_PADDING) {
-        return Err(Error::InvalidPadding);
-    }
-
     Ok(())
 }
 
-#[cfg(feature = "std")]
 #[inline]
-fn decode_impl_stream_all<R: Read>(
-    data: &mut R, out: &mut [u8], channels: u8, src_channels: u8,
+#[allow(clippy::too_many_arguments)]
+fn decode_impl_state_all<R: Reader>(
+    reader: &mut R, state: &mut DecoderState, out: &mut [u8], n_pixels: usize, channels: u8,
+    src_channels: u8, strict: bool, undeclared: bool, diag: &mut impl FnMut(Diagnostic),
 ) -> Result<()> {
+    let (r, s, o, n) = (reader, state, out, n_pixels);
     match (channels, src_channels) {
-        (3, 3) => decode_impl_stream::<_, 3, false>(data, out),
-        (3, 4) => decode_impl_stream::<_, 3, true>(data, out),
-        (4, 3) => decode_impl_stream::<_, 4, false>(data, out),
-        (4, 4) => decode_impl_stream::<_, 4, true>(data, out),
+        (3, 3) => decode_impl_state::<_, 3, false>(r, s, o, n, strict, undeclared, diag),
+        (3, 4) => decode_impl_state::<_, 3, true>(r, s, o, n, strict, undeclared, diag),
+        (4, 3) => decode_impl_state::<_, 4, false>(r, s, o, n, strict, undeclared, diag),
+        (4, 4) => decode_impl_state::<_, 4, true>(r, s, o, n, strict, undeclared, diag),
         _ => {
             cold();
             Err(Error::InvalidChannels { channels })
@@ -224,63 +352,300 @@ fn decode_impl_stream_all<R: Read>(
     }
 }
 
+/// Scans the ops covering the next `n_pixels` pixels and returns RGBA if any of them
+/// sets a non-opaque alpha value or if there are any RGBA ops in an image declared as RGB,
+/// and RGB otherwise.
+fn detect_channels(mut data: &[u8], mut n_pixels: usize, declared: Channels) -> Result<Channels> {
+    while n_pixels != 0 {
+        match Op::parse_from(data)? {
+            (Op::Rgba { a, .. }, _) if a != 0xff || declared.is_rgb() => {
+                return Ok(Channels::Rgba);
+            }
+            (op, len) => {
+                data = &data[len..];
+                n_pixels = n_pixels.saturating_sub(op.n_pixels());
+            }
+        }
+    }
+    Ok(Channels::Rgb)
+}
+
 #[doc(hidden)]
 pub trait Reader: Sized {
-    fn decode_header(&mut self) -> Result<Header>;
-    fn decode_image(&mut self, out: &mut [u8], channels: u8, src_channels: u8) -> Result<()>;
+    fn decode_header(&mut self) -> Result<Header> {
+        self.decode_header_impl(HeaderOptions::new()).map(|(header, _)| header)
+    }
+    fn decode_header_impl(&mut self, options: HeaderOptions) -> Result<(Header, u8)>;
+    fn decode_image(
+        &mut self, out: &mut [u8], channels: u8, src_channels: u8, strict: bool,
+    ) -> Result<usize>;
+    fn read_array<const K: usize>(&mut self) -> Result<[u8; K]>;
+    fn n_trailing_bytes(&self) -> Option<usize> {
+        None
+    }
 }
 
-pub struct Bytes<'a>(&'a [u8]);
+#[derive(Copy, Clone)]
+pub struct Bytes<'a> {
+    data: &'a [u8],
+    tail: &'a [u8],
+}
+
+impl Debug for Bytes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Bytes")
+            .field("len", &self.data.len())
+            .field("remaining", &self.tail.len())
+            .finish()
+    }
+}
 
 impl<'a> Bytes<'a> {
     #[inline]
     pub const fn new(buf: &'a [u8]) -> Self {
-        Self(buf)
+        Self { data: buf, tail: buf }
     }
 
     #[inline]
     pub const fn as_slice(&self) -> &[u8] {
-        self.0
+        self.tail
     }
 }
 
-impl<'a> Reader for Bytes<'a> {
+impl Reader for Bytes<'_> {
     #[inline]
-    fn decode_header(&mut self) -> Result<Header> {
-        let header = Header::decode(self.0)?;
-        self.0 = &self.0[QOI_HEADER_SIZE..]; // can't panic
+    fn decode_header_impl(&mut self, options: HeaderOptions) -> Result<(Header, u8)> {
+        let header = Header::decode_raw(self.tail, options)?;
+        self.tail = &self.tail[QOI_HEADER_SIZE..]; // can't panic
         Ok(header)
     }
 
     #[inline]
-    fn decode_image(&mut self, out: &mut [u8], channels: u8, src_channels: u8) -> Result<()> {
-        let n_read = decode_impl_slice_all(self.0, out, channels, src_channels)?;
-        self.0 = &self.0[n_read..];
-        Ok(())
+    fn decode_image(
+        &mut self, out: &mut [u8], channels: u8, src_channels: u8, strict: bool,
+    ) -> Result<usize> {
+        let n_read = decode_impl_slice_all(self.tail, out, channels, src_channels, strict)?;
+        self.tail = &self.tail[n_read..];
+        Ok(n_read)
+    }
+
+    #[inline]
+    fn read_array<const K: usize>(&mut self) -> Result<[u8; K]> {
+        if unlikely(self.tail.len() < K) {
+            return Err(Error::UnexpectedBufferEnd);
+        }
+        let (head, tail) = self.tail.split_at(K);
+        self.tail = tail;
+        let mut out = [0; K];
+        out.copy_from_slice(head);
+        Ok(out)
+    }
+
+    #[inline]
+    fn n_trailing_bytes(&self) -> Option<usize> {
+        Some(self.tail.len())
     }
 }
 
-#[cfg(feature = "std")]
-impl<R: Read> Reader for R {
+/// Reader over owned input bytes, see [`Decoder::from_owned`].
+///
+/// Note: the data is assumed to be immutable, i.e. `as_ref()` must return the same bytes
+/// every time it's called (which is always the case for `Vec<u8>`, `Box<[u8]>` and `Arc<[u8]>`).
+#[derive(Clone)]
+pub struct OwnedBytes<D> {
+    data: D,
+    pos: usize,
+}
+
+impl<D: AsRef<[u8]>> Debug for OwnedBytes<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OwnedBytes")
+            .field("len", &self.data.as_ref().len())
+            .field("pos", &self.pos)
+            .finish()
+    }
+}
+
+impl<D: AsRef<[u8]>> OwnedBytes<D> {
     #[inline]
-    fn decode_header(&mut self) -> Result<Header> {
-        let mut b = [0; QOI_HEADER_SIZE];
-        self.read_exact(&mut b)?;
-        Header::decode(b)
+    pub const fn new(data: D) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// Returns the remaining (not yet read) bytes.
+    #[inline]
+    pub fn as_slice(&self) -> &[u8] {
+        self.data.as_ref().get(self.pos..).unwrap_or_default()
+    }
+
+    /// Returns the owned data back.
+    #[inline]
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn into_data(self) -> D {
+        self.data
+    }
+
+    #[inline]
+    fn bytes(&self) -> Bytes<'_> {
+        Bytes { data: self.data.as_ref(), tail: self.as_slice() }
+    }
+}
+
+impl<D: AsRef<[u8]>> Reader for OwnedBytes<D> {
+    #[inline]
+    fn decode_header_impl(&mut self, options: HeaderOptions) -> Result<(Header, u8)> {
+        let header = self.bytes().decode_header_impl(options)?;
+        self.pos += QOI_HEADER_SIZE;
+        Ok(header)
+    }
+
+    #[inline]
+    fn decode_image(
+        &mut self, out: &mut [u8], channels: u8, src_channels: u8, strict: bool,
+    ) -> Result<usize> {
+        let n_read = self.bytes().decode_image(out, channels, src_channels, strict)?;
+        self.pos += n_read;
+        Ok(n_read)
+    }
+
+    #[inline]
+    fn read_array<const K: usize>(&mut self) -> Result<[u8; K]> {
+        let out = self.bytes().read_array::<K>()?;
+        self.pos += K;
+        Ok(out)
+    }
+
+    #[inline]
+    fn n_trailing_bytes(&self) -> Option<usize> {
+        Some(self.as_slice().len())
+    }
+}
+
+/// Reader over input split into multiple non-contiguous slices.
+pub struct Scattered<'a, S> {
+    slices: &'a [S],
+    pos: usize, // offset within the first slice
+}
+
+// can't be derived since that would require `S: Clone`
+impl<S> Clone for Scattered<'_, S> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S> Copy for Scattered<'_, S> {}
+
+impl<S> Debug for Scattered<'_, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Scattered")
+            .field("slices_left", &self.slices.len())
+            .field("pos", &self.pos)
+            .finish()
+    }
+}
+
+impl<'a, S: Deref<Target = [u8]>> Scattered<'a, S> {
+    #[inline]
+    pub const fn new(slices: &'a [S]) -> Self {
+        Self { slices, pos: 0 }
+    }
+}
+
+impl<S: Deref<Target = [u8]>> Reader for Scattered<'_, S> {
+    #[inline]
+    fn decode_header_impl(&mut self, options: HeaderOptions) -> Result<(Header, u8)> {
+        Header::decode_raw(&self.read_array::<QOI_HEADER_SIZE>()?, options)
     }
 
     #[inline]
-    fn decode_image(&mut self, out: &mut [u8], channels: u8, src_channels: u8) -> Result<()> {
-        decode_impl_stream_all(self, out, channels, src_channels)
+    fn decode_image(
+        &mut self, out: &mut [u8], channels: u8, src_channels: u8, strict: bool,
+    ) -> Result<usize> {
+        let mut state = DecoderState::new();
+        let n_pixels = out.len() / channels.max(1) as usize;
+        let diag = &mut |_| {};
+        decode_impl_state_all(
+            self,
+            &mut state,
+            out,
+            n_pixels,
+            channels,
+            src_channels,
+            strict,
+            false,
+            diag,
+        )?;
+        if unlikely(self.read_array::<QOI_PADDING_SIZE>()? != QOI_PADDING) {
+            return Err(Error::InvalidPadding);
+        }
+        Ok(state.byte_offset - QOI_HEADER_SIZE + QOI_PADDING_SIZE)
+    }
+
+    #[inline]
+    fn read_array<const K: usize>(&mut self) -> Result<[u8; K]> {
+        let mut out = [0; K];
+        let mut n_read = 0;
+        while n_read < K {
+            let (first, rest) = match self.slices.split_first() {
+                Some(split) => split,
+                None => return Err(Error::UnexpectedBufferEnd),
+            };
+            let chunk = &first[self.pos..];
+            let n = chunk.len().min(K - n_read);
+            out[n_read..n_read + n].copy_from_slice(&chunk[..n]);
+            n_read += n;
+            self.pos += n;
+            if self.pos == first.len() {
+                self.slices = rest;
+                self.pos = 0;
+            }
+        }
+        Ok(out)
+    }
+
+    #[inline]
+    fn n_trailing_bytes(&self) -> Option<usize> {
+        Some(self.slices.iter().map(|s| s.len()).sum::<usize>() - self.pos)
     }
 }
 
 /// Decode QOI images from slices or from streams.
+///
+/// Slice decoders are `Send + Sync`; stream decoders are `Send` and `Sync` whenever
+/// the underlying reader is, so they can be moved across threads or async tasks.
 #[derive(Clone)]
 pub struct Decoder<R> {
-    reader: R,
+    pub(crate) reader: R,
     header: Header,
+    colorspace_raw: u8,
     channels: Channels,
+    target: Option<TargetChannels>,
+    stride: Option<usize>,
+    pub(crate) state: DecoderState,
+    strict: bool,
+    lenient_channels: bool,
+    default_alpha: u8,
+    premultiply: bool,
+}
+
+impl<R> Debug for Decoder<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Decoder")
+            .field("header", &self.header)
+            .field("channels", &self.channels)
+            .field("strict", &self.strict)
+            .field("default_alpha", &self.default_alpha)
+            .field("pixel_position", &self.state.pixel_pos)
+            .field("byte_offset", &self.state.byte_offset)
+            .field("lenient_channels", &self.lenient_channels)
+            .field("target", &self.target)
+            .field("stride", &self.stride)
+            .field("premultiply", &self.premultiply)
+            .field("colorspace_raw", &self.colorspace_raw)
+            .finish_non_exhaustive()
+    }
 }
 
 impl<'a> Decoder<Bytes<'a>> {
@@ -293,7 +658,49 @@ impl<'a> Decoder<Bytes<'a>> {
     /// stream, use [`Decoder::from_stream`] instead.
     #[inline]
     pub fn new(data: &'a (impl AsRef<[u8]> + ?Sized)) -> Result<Self> {
-        Self::new_impl(Bytes::new(data.as_ref()))
+        Self::new_with_options(data, HeaderOptions::new())
+    }
+
+    /// Same as [`Decoder::new`], but with custom header validation options.
+    #[inline]
+    pub fn new_with_options(
+        data: &'a (impl AsRef<[u8]> + ?Sized), options: HeaderOptions,
+    ) -> Result<Self> {
+        Self::new_impl(Bytes::new(data.as_ref()), options)
+    }
+
+    /// Creates a new decoder from a headerless image body: the encoded ops followed by the
+    /// end marker, without the 14-byte header, which is supplied out-of-band instead.
+    ///
+    /// This allows embedding QOI payloads in custom containers or network protocols that
+    /// carry the image metadata on their own. The header dimensions are validated as
+    /// usual; byte offsets (see [`Decoder::bytes_consumed`]) are relative to the body.
+    #[inline]
+    pub fn new_body(data: &'a (impl AsRef<[u8]> + ?Sized), header: Header) -> Result<Self> {
+        Self::new_body_impl(Bytes::new(data.as_ref()), header)
+    }
+
+    /// Scans the remaining ops without decoding them and returns the number of channels
+    /// the image actually needs, regardless of what's declared in the header.
+    ///
+    /// This is RGBA if any of the ops sets a non-opaque alpha value, and RGB otherwise
+    /// (i.e. if the alpha channel of an RGBA image is fully opaque). Images declared as RGB
+    /// that contain any RGBA ops at all, even fully opaque ones, are detected as RGBA since
+    /// they can't be decoded as RGB.
+    pub fn detect_channels(&self) -> Result<Channels> {
+        let n_pixels = self.pixels_left().saturating_sub(self.state.run);
+        detect_channels(self.reader.as_slice(), n_pixels, self.header.channels)
+    }
+
+    /// Returns a new decoder that decodes into the number of channels detected via
+    /// [`Decoder::detect_channels`] instead of the ones declared in the header.
+    ///
+    /// If the image is declared as RGB but contains alpha, lenient channels are enabled
+    /// (see [`Decoder::with_lenient_channels`]), so the output is upgraded to RGBA instead
+    /// of failing to decode.
+    pub fn with_detected_channels(self) -> Result<Self> {
+        let channels = self.detect_channels()?;
+        self.with_detected_channels_impl(channels)
     }
 
     /// Returns the undecoded tail of the input slice of bytes.
@@ -301,40 +708,151 @@ impl<'a> Decoder<Bytes<'a>> {
     pub const fn data(&self) -> &[u8] {
         self.reader.as_slice()
     }
+
+    /// Restores a decoder state previously taken via [`Decoder::checkpoint`].
+    ///
+    /// The state must have been taken from a decoder over the same image.
+    #[inline]
+    pub fn restore(&mut self, state: DecoderState) -> Result<()> {
+        if unlikely(state.byte_offset > self.reader.data.len()) {
+            return Err(Error::UnexpectedBufferEnd);
+        }
+        self.reader.tail = &self.reader.data[state.byte_offset..];
+        self.state = state;
+        Ok(())
+    }
 }
 
-#[cfg(feature = "std")]
-impl<R: Read> Decoder<R> {
-    /// Creates a new decoder from a generic reader that implements [`Read`](std::io::Read).
+impl<D: AsRef<[u8]>> Decoder<OwnedBytes<D>> {
+    /// Creates a new decoder that takes ownership of the encoded bytes, e.g. a `Vec<u8>`,
+    /// `Box<[u8]>` or `Arc<[u8]>`.
     ///
-    /// The header will be decoded immediately upon construction.
+    /// Unlike [`Decoder::new`] which borrows the input, the decoder is `'static` as long as
+    /// the data is, so it can be stored or moved into spawned threads or async tasks, while
+    /// decoding as efficiently as from a slice. The data can be taken back via
+    /// [`Decoder::into_data`].
     ///
-    /// Note: while it's possible to pass a `&[u8]` slice here since it implements `Read`, it
-    /// would be more efficient to use a specialized constructor instead: [`Decoder::new`].
+    /// The header will be decoded immediately upon construction.
     #[inline]
-    pub fn from_stream(reader: R) -> Result<Self> {
-        Self::new_impl(reader)
+    pub fn from_owned(data: D) -> Result<Self> {
+        Self::from_owned_with_options(data, HeaderOptions::new())
     }
 
-    /// Returns an immutable reference to the underlying reader.
+    /// Same as [`Decoder::from_owned`], but with custom header validation options.
     #[inline]
-    pub const fn reader(&self) -> &R {
-        &self.reader
+    pub fn from_owned_with_options(data: D, options: HeaderOptions) -> Result<Self> {
+        Self::new_impl(OwnedBytes::new(data), options)
     }
 
-    /// Consumes the decoder and returns the underlying reader back.
+    /// Same as [`Decoder::new_body`], but takes ownership of the encoded bytes.
+    #[inline]
+    pub fn from_owned_body(data: D, header: Header) -> Result<Self> {
+        Self::new_body_impl(OwnedBytes::new(data), header)
+    }
+
+    /// Same as [`Decoder::detect_channels`] for slice decoders.
+    pub fn detect_channels(&self) -> Result<Channels> {
+        let n_pixels = self.pixels_left().saturating_sub(self.state.run);
+        detect_channels(self.reader.as_slice(), n_pixels, self.header.channels)
+    }
+
+    /// Same as [`Decoder::with_detected_channels`] for slice decoders.
+    pub fn with_detected_channels(self) -> Result<Self> {
+        let channels = self.detect_channels()?;
+        self.with_detected_channels_impl(channels)
+    }
+
+    /// Returns the undecoded tail of the owned bytes.
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        self.reader.as_slice()
+    }
+
+    /// Consumes the decoder and returns the owned bytes back.
     #[inline]
     #[allow(clippy::missing_const_for_fn)]
-    pub fn into_reader(self) -> R {
-        self.reader
+    pub fn into_data(self) -> D {
+        self.reader.into_data()
+    }
+
+    /// Restores a decoder state previously taken via [`Decoder::checkpoint`].
+    ///
+    /// The state must have been taken from a decoder over the same image.
+    #[inline]
+    pub fn restore(&mut self, state: DecoderState) -> Result<()> {
+        if unlikely(state.byte_offset > self.reader.data.as_ref().len()) {
+            return Err(Error::UnexpectedBufferEnd);
+        }
+        self.reader.pos = state.byte_offset;
+        self.state = state;
+        Ok(())
+    }
+}
+
+impl<'a, S: Deref<Target = [u8]>> Decoder<Scattered<'a, S>> {
+    /// Creates a new decoder from input split into multiple non-contiguous slices, e.g.
+    /// `&[&[u8]]`, `&[Vec<u8>]` or `&[IoSlice]`; ops spanning slice boundaries are stitched
+    /// together internally, so the input doesn't have to be copied into a single buffer.
+    ///
+    /// The header will be decoded immediately upon construction.
+    ///
+    /// Note: this is somewhat slower than decoding from a single contiguous slice of bytes
+    /// via [`Decoder::new`], but avoids the copy needed to coalesce the input.
+    #[inline]
+    pub fn from_slices(slices: &'a [S]) -> Result<Self> {
+        Self::new_impl(Scattered::new(slices), HeaderOptions::new())
+    }
+
+    /// Same as [`Decoder::from_slices`], but with custom header validation options.
+    #[inline]
+    pub fn from_slices_with_options(slices: &'a [S], options: HeaderOptions) -> Result<Self> {
+        Self::new_impl(Scattered::new(slices), options)
     }
 }
 
 impl<R: Reader> Decoder<R> {
     #[inline]
-    fn new_impl(mut reader: R) -> Result<Self> {
-        let header = reader.decode_header()?;
-        Ok(Self { reader, header, channels: header.channels })
+    pub(crate) fn new_impl(mut reader: R, options: HeaderOptions) -> Result<Self> {
+        let (header, colorspace_raw) = reader.decode_header_impl(options)?;
+        Ok(Self {
+            reader,
+            header,
+            colorspace_raw,
+            channels: header.channels,
+            target: None,
+            stride: None,
+            state: DecoderState::new(),
+            strict: false,
+            lenient_channels: false,
+            default_alpha: 0xff,
+            premultiply: false,
+        })
+    }
+
+    #[inline]
+    pub(crate) fn new_body_impl(reader: R, header: Header) -> Result<Self> {
+        let Header { width, height, channels, colorspace } = header;
+        let header = Header::try_new(width, height, channels, colorspace)?;
+        Ok(Self {
+            reader,
+            header,
+            colorspace_raw: colorspace.as_u8(),
+            channels,
+            target: None,
+            stride: None,
+            state: DecoderState { byte_offset: 0, ..DecoderState::new() },
+            strict: false,
+            lenient_channels: false,
+            default_alpha: 0xff,
+            premultiply: false,
+        })
+    }
+
+    #[inline]
+    fn with_detected_channels_impl(self, channels: Channels) -> Result<Self> {
+        let lenient =
+            self.lenient_channels || (channels.is_rgba() && self.header.channels.is_rgb());
+        self.with_lenient_channels(lenient).with_channels(channels)
     }
 
     /// Returns a new decoder with modified number of channels.
@@ -342,13 +860,165 @@ impl<R: Reader> Decoder<R> {
     /// By default, the number of channels in the decoded image will be equal
     /// to whatever is specified in the header. However, it is also possible
     /// to decode RGB into RGBA (in which case the alpha channel will be set
-    /// to 255), and vice versa (in which case the alpha channel will be ignored).
+    /// to 255, see [`Decoder::with_default_alpha`]), and vice versa (in which
+    /// case the alpha channel will be ignored).
+    ///
+    /// Accepts either [`Channels`] or a raw `u8` channel count, which is validated and
+    /// rejected with [`Error::InvalidChannels`] unless it's 3 or 4. Changing the number
+    /// of channels after some of the pixels have been decoded (see
+    /// [`Decoder::decode_pixels`]) would mix pixel layouts in the output and is rejected
+    /// with [`Error::DecodingStarted`].
+    ///
+    /// This resets the target pixel layout to plain RGB(A), see
+    /// [`Decoder::with_target_channels`].
+    #[inline]
+    pub fn with_channels<C>(self, channels: C) -> Result<Self>
+    where
+        C: TryInto<Channels>,
+        Error: From<C::Error>,
+    {
+        let channels = channels.try_into()?;
+        self.with_target_channels(channels.into())
+    }
+
+    /// Returns a new decoder that writes pixels in the given layout, e.g. BGRA or ARGB.
+    ///
+    /// Pixels are decoded as RGB or RGBA depending on whether the layout has alpha (see
+    /// [`Decoder::with_channels`]) and written straight into the output buffer in the
+    /// requested byte order, without a separate conversion pass over the decoded image.
+    /// All decoding methods then expect [`TargetChannels::bytes_per_pixel`] bytes per pixel
+    /// (see also [`Decoder::required_buf_len`]), except for [`Decoder::decode_to_planes`]
+    /// which always decodes channel planes in RGB(A) order.
+    ///
+    /// Changing the layout after some of the pixels have been decoded is rejected with
+    /// [`Error::DecodingStarted`].
+    #[inline]
+    pub fn with_target_channels(mut self, target: TargetChannels) -> Result<Self> {
+        if unlikely(self.state.pixel_pos != 0 && target != self.target_channels()) {
+            return Err(Error::DecodingStarted { pixel_pos: self.state.pixel_pos });
+        }
+        self.channels = target.channels();
+        self.target = if target.is_identity() { None } else { Some(target) };
+        Ok(self)
+    }
+
+    /// Returns a new decoder that writes decoded rows `stride` bytes apart.
+    ///
+    /// By default, rows are tightly packed. With a custom stride, e.g. to satisfy the row
+    /// alignment required by a graphics API, each row of pixels is written at the start of
+    /// its `stride`-byte slot and the padding bytes after it are left untouched; the last row
+    /// doesn't have to be padded, see [`Decoder::required_buf_len`]. This applies to
+    /// [`Decoder::decode_to_buf`], [`Decoder::decode_rows`] and [`Decoder::decode_region`]
+    /// (where it's the distance between the rows of the region), which then require the
+    /// decoder to be positioned at the start of a row; [`Decoder::decode_pixels`] ignores it.
+    ///
+    /// The stride must fit a row of decoded pixels, otherwise decoding fails with
+    /// [`Error::InvalidStride`].
+    #[inline]
+    pub const fn with_output_stride(mut self, stride: usize) -> Self {
+        self.stride = Some(stride);
+        self
+    }
+
+    /// Returns the distance in bytes between the starts of consecutive decoded rows, see
+    /// [`Decoder::with_output_stride`].
+    #[inline]
+    pub const fn output_stride(&self) -> usize {
+        self.output_stride_for(self.row_len())
+    }
+
+    /// Returns the pixel layout of the decoded image, see [`Decoder::with_target_channels`].
+    #[inline]
+    pub fn target_channels(&self) -> TargetChannels {
+        self.target.unwrap_or_else(|| self.channels.into())
+    }
+
+    /// Returns a new decoder with strict decoding enabled or disabled (disabled by default).
+    ///
+    /// By default, the decoder accepts some streams that are decodable but not spec-clean.
+    /// In strict mode, the following are treated as errors instead:
+    /// * Runs extending past the last pixel (normally clamped), see [`Error::InvalidRunLength`].
+    /// * Any bytes left after the end marker when decoding from a slice of bytes (normally
+    ///   ignored, see [`Decoder::data`]), see [`Error::TrailingBytes`].
+    #[inline]
+    pub const fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Returns a new decoder with the alpha value used when decoding RGB images into RGBA
+    /// (255 by default, e.g. 0 can be used for masks).
+    ///
+    /// Has no effect when decoding RGBA images or when decoding into RGB.
+    #[inline]
+    pub const fn with_default_alpha(mut self, alpha: u8) -> Self {
+        self.default_alpha = alpha;
+        self
+    }
+
+    /// Returns a new decoder with alpha premultiplication enabled or disabled (disabled by
+    /// default).
+    ///
+    /// When enabled, the color channels of decoded pixels are multiplied by alpha, as
+    /// expected e.g. by most compositors; this is done as part of decoding (in small chunks
+    /// when decoding the whole image), so no separate conversion pass is needed. The
+    /// default alpha (see [`Decoder::with_default_alpha`]) is applied first. Has no effect
+    /// when decoding into layouts without alpha.
     #[inline]
-    pub const fn with_channels(mut self, channels: Channels) -> Self {
-        self.channels = channels;
+    pub const fn with_premultiplied_alpha(mut self, premultiply: bool) -> Self {
+        self.premultiply = premultiply;
         self
     }
 
+    /// Returns true if alpha premultiplication is enabled, see
+    /// [`Decoder::with_premultiplied_alpha`].
+    #[inline]
+    pub const fn is_premultiplied(&self) -> bool {
+        self.premultiply
+    }
+
+    /// Returns a new decoder with lenient channels enabled or disabled (disabled by default).
+    ///
+    /// Some third-party encoders declare RGB images in the header but still emit RGBA ops,
+    /// which normally fail decoding with [`Error::InvalidOp`]. With lenient channels, such
+    /// ops are decoded as usual and reported as [`Diagnostic::UndeclaredAlpha`] (see
+    /// [`Decoder::decode_to_buf_with_diagnostics`]); to keep the alpha channel in the output,
+    /// decode into RGBA, e.g. via [`Decoder::with_detected_channels`]. The default alpha
+    /// is not applied in this mode.
+    #[inline]
+    pub const fn with_lenient_channels(mut self, lenient_channels: bool) -> Self {
+        self.lenient_channels = lenient_channels;
+        self
+    }
+
+    /// Returns true if lenient channels are enabled, see [`Decoder::with_lenient_channels`].
+    #[inline]
+    pub const fn lenient_channels(&self) -> bool {
+        self.lenient_channels
+    }
+
+    /// Returns the number of channels the ops are decoded as (accounting for lenient channels).
+    #[inline]
+    const fn src_channels(&self) -> u8 {
+        if self.lenient_channels {
+            4
+        } else {
+            self.header.channels.as_u8()
+        }
+    }
+
+    /// Returns the alpha value used when decoding RGB images into RGBA.
+    #[inline]
+    pub const fn default_alpha(&self) -> u8 {
+        self.default_alpha
+    }
+
+    /// Returns true if strict decoding is enabled.
+    #[inline]
+    pub const fn is_strict(&self) -> bool {
+        self.strict
+    }
+
     /// Returns the number of channels in the decoded image.
     ///
     /// Note: this may differ from the number of channels specified in the header.
@@ -357,40 +1027,487 @@ impl<R: Reader> Decoder<R> {
         self.channels
     }
 
+    /// Returns the number of bytes per decoded pixel in the target pixel layout.
+    #[inline]
+    const fn bytes_per_pixel(&self) -> usize {
+        match self.target {
+            Some(ref target) => target.bytes_per_pixel(),
+            None => self.channels.as_u8() as usize,
+        }
+    }
+
+    /// Returns the number of bytes per decoded row of pixels, without padding.
+    #[inline]
+    const fn row_len(&self) -> usize {
+        self.header.width as usize * self.bytes_per_pixel()
+    }
+
+    /// Returns true if the number of channels in the decoded image differs from the one
+    /// specified in the header (i.e. RGB is expanded to RGBA, or alpha is dropped).
+    #[inline]
+    pub const fn is_converting(&self) -> bool {
+        self.channels.as_u8() != self.header.channels.as_u8()
+    }
+
     /// Returns the decoded image header.
     #[inline]
     pub const fn header(&self) -> &Header {
         &self.header
     }
 
+    /// Returns the raw color space byte stored in the image header.
+    ///
+    /// This only differs from the header's color space if the decoder was created with
+    /// [`HeaderOptions::with_lenient_colorspace`] and the byte is neither 0 nor 1; in this
+    /// case, the header's color space falls back to [`ColorSpace::Srgb`](crate::ColorSpace).
+    #[inline]
+    pub const fn colorspace_raw(&self) -> u8 {
+        self.colorspace_raw
+    }
+
+    /// Returns the number of pixels that haven't been decoded yet.
+    #[inline]
+    pub const fn pixels_left(&self) -> usize {
+        self.header.n_pixels().saturating_sub(self.state.pixel_pos)
+    }
+
     /// The number of bytes the decoded image will take.
     ///
-    /// Can be used to pre-allocate the buffer to decode the image into.
+    /// Can be used to pre-allocate the buffer to decode the image into. If some of the
+    /// pixels have already been decoded via [`Decoder::decode_pixels`], only the remaining
+    /// pixels are accounted for. With a custom output stride (see
+    /// [`Decoder::with_output_stride`]), this is `stride * (rows - 1) + row_len`, i.e. the
+    /// last row isn't padded.
     #[inline]
     pub const fn required_buf_len(&self) -> usize {
-        self.header.n_pixels().saturating_mul(self.channels.as_u8() as usize)
+        let (width, pixels_left) = (self.header.width as usize, self.pixels_left());
+        match self.stride {
+            Some(stride) if width != 0 && pixels_left != 0 => {
+                let n_rows = (pixels_left + width - 1) / width;
+                stride.saturating_mul(n_rows - 1).saturating_add(self.row_len())
+            }
+            _ => pixels_left.saturating_mul(self.bytes_per_pixel()),
+        }
+    }
+
+    /// Returns the number of encoded bytes consumed so far, header included.
+    ///
+    /// Once the image has been fully decoded, this is the total length of the encoded
+    /// image including the end marker, so any data following it starts at this offset.
+    #[inline]
+    pub const fn bytes_consumed(&self) -> usize {
+        self.state.byte_offset
+    }
+
+    /// Returns a snapshot of the current decoder state.
+    ///
+    /// It can be passed to `Decoder::restore` later to resume decoding from this point.
+    #[inline]
+    pub const fn checkpoint(&self) -> DecoderState {
+        self.state
     }
 
     /// Decodes the image to a pre-allocated buffer and returns the number of bytes written.
     ///
-    /// The minimum size of the buffer can be found via [`Decoder::required_buf_len`].
+    /// The minimum size of the buffer can be found via [`Decoder::required_buf_len`]; the
+    /// buffer may be larger than that, in which case the rest of it is left untouched.
+    ///
+    /// If decoding fails due to truncated or corrupt input, the image header is attached to
+    /// the error (see [`Error::header`]).
     #[inline]
     pub fn decode_to_buf(&mut self, mut buf: impl AsMut<[u8]>) -> Result<usize> {
-        let buf = buf.as_mut();
+        self.decode_to_buf_impl(buf.as_mut(), None).map_err(|err| err.with_header(self.header))
+    }
+
+    /// Same as [`Decoder::decode_to_buf`], but also reports recoverable anomalies
+    /// (see [`Diagnostic`]) to the callback as they are encountered.
+    ///
+    /// In strict mode, anomalies that are treated as errors fail the decoding as usual
+    /// and are not reported. Decoding this way is somewhat slower than the regular one.
+    #[inline]
+    pub fn decode_to_buf_with_diagnostics(
+        &mut self, mut buf: impl AsMut<[u8]>, mut diag: impl FnMut(Diagnostic),
+    ) -> Result<usize> {
+        self.decode_to_buf_impl(buf.as_mut(), Some(&mut diag))
+            .map_err(|err| err.with_header(self.header))
+    }
+
+    #[inline]
+    fn decode_to_buf_impl(
+        &mut self, buf: &mut [u8], mut diag: Option<&mut dyn FnMut(Diagnostic)>,
+    ) -> Result<usize> {
+        if self.stride.is_some() {
+            let diag = &mut |d| diag.iter_mut().for_each(|diag| diag(d));
+            return self.decode_strided_impl(self.rows_left(), buf, diag);
+        }
         let size = self.required_buf_len();
         if unlikely(buf.len() < size) {
             return Err(Error::OutputBufferTooSmall { size: buf.len(), required: size });
         }
-        self.reader.decode_image(buf, self.channels.as_u8(), self.header.channels.as_u8())?;
+        match diag {
+            _ if self.target.is_some() => {
+                let diag = &mut |d| diag.iter_mut().for_each(|diag| diag(d));
+                self.decode_target_impl(&mut buf[..size], diag)?;
+                return Ok(size);
+            }
+            _ if self.premultiply && self.channels.is_rgba() && size != 0 => {
+                // premultiplied in chunks while the decoded pixels are still in cache
+                let diag = &mut |d| diag.iter_mut().for_each(|diag| diag(d));
+                for chunk in buf[..size].chunks_mut(STRIP_PIXELS * 16 * 4) {
+                    self.decode_pixels_impl(chunk, diag)?;
+                    self.apply_alpha(chunk);
+                }
+                return Ok(size);
+            }
+            Some(ref mut diag) if size != 0 => self.decode_pixels_impl(&mut buf[..size], diag)?,
+            _ if self.state.pixel_pos != 0 => {
+                self.decode_pixels_impl(&mut buf[..size], &mut |_| {})?;
+            }
+            _ => {
+                // the whole-image fast path doesn't detect anomalies other than trailing bytes
+                let (channels, src_channels) = (self.channels.as_u8(), self.src_channels());
+                // only the required prefix is decoded into, the buffer may be larger than
+                // that and not a multiple of the number of channels
+                let out = &mut buf[..size];
+                self.state.byte_offset +=
+                    self.reader.decode_image(out, channels, src_channels, self.strict)?;
+                self.state.pixel_pos = self.header.n_pixels();
+                self.check_trailing_bytes(&mut |d| diag.iter_mut().for_each(|diag| diag(d)))?;
+            }
+        }
+        self.apply_alpha(&mut buf[..size]);
         Ok(size)
     }
 
-    /// Decodes the image into a newly allocated vector of bytes and returns it.
-    #[cfg(any(feature = "std", feature = "alloc"))]
+    /// Decodes the next pixels of the image into the buffer and returns the number
+    /// of pixels decoded.
+    ///
+    /// The buffer length must be a multiple of the number of bytes per pixel (normally the
+    /// number of channels, see [`Decoder::with_target_channels`]); as many pixels as fit
+    /// into the buffer will be decoded (but no more than [`Decoder::pixels_left`]). Once the
+    /// last pixel is decoded, the end-of-stream padding is validated. Otherwise,
+    /// [`Error::InvalidImageLength`] is returned and nothing is decoded.
+    ///
+    /// If decoding fails due to truncated or corrupt input, the image header is attached to
+    /// the error (see [`Error::header`]).
     #[inline]
-    pub fn decode_to_vec(&mut self) -> Result<Vec<u8>> {
-        let mut out = vec![0; self.header.n_pixels() * self.channels.as_u8() as usize];
-        let _ = self.decode_to_buf(&mut out)?;
-        Ok(out)
+    pub fn decode_pixels(&mut self, mut buf: impl AsMut<[u8]>) -> Result<usize> {
+        let buf = buf.as_mut();
+        if self.target.is_none() {
+            return self.decode_plain(buf);
+        }
+        let bpp = self.bytes_per_pixel();
+        if unlikely(buf.len() % bpp != 0) {
+            let (size, width, height) = (buf.len(), self.header.width, self.header.height);
+            return Err(Error::InvalidImageLength { size, width, height });
+        }
+        let n_pixels = (buf.len() / bpp).min(self.pixels_left());
+        self.decode_target_impl(&mut buf[..n_pixels * bpp], &mut |_| {})
+            .map_err(|err| err.with_header(self.header))?;
+        Ok(n_pixels)
+    }
+
+    /// Same as [`Decoder::decode_pixels`], but always decodes into plain RGB(A) regardless
+    /// of the target pixel layout.
+    #[inline]
+    pub(crate) fn decode_plain(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let channels = self.channels.as_u8() as usize;
+        if unlikely(buf.len() % channels != 0) {
+            let (size, width, height) = (buf.len(), self.header.width, self.header.height);
+            return Err(Error::InvalidImageLength { size, width, height });
+        }
+        let n_pixels = (buf.len() / channels).min(self.pixels_left());
+        self.decode_pixels_impl(&mut buf[..n_pixels * channels], &mut |_| {})
+            .map_err(|err| err.with_header(self.header))?;
+        self.apply_alpha(&mut buf[..n_pixels * channels]);
+        Ok(n_pixels)
+    }
+
+    /// Returns the number of full rows that haven't been decoded yet.
+    #[inline]
+    pub const fn rows_left(&self) -> usize {
+        match self.header.width {
+            0 => 0,
+            width => self.pixels_left() / width as usize,
+        }
+    }
+
+    /// Decodes the next `n_rows` rows of the image into the buffer and returns the number
+    /// of rows decoded, which is less than `n_rows` only at the end of the image.
+    ///
+    /// The buffer must fit the rows to be decoded (`width * bytes_per_pixel` bytes per row,
+    /// without any padding unless a custom output stride is set, see
+    /// [`Decoder::with_output_stride`]); the rest of it is left untouched. This allows decoding large images in
+    /// strips with a small buffer instead of allocating the full image. The decoder must be
+    /// positioned at the start of a row, otherwise [`Error::DecodingStarted`] is returned.
+    ///
+    /// If decoding fails due to truncated or corrupt input, the image header is attached to
+    /// the error (see [`Error::header`]).
+    pub fn decode_rows(&mut self, n_rows: usize, mut buf: impl AsMut<[u8]>) -> Result<usize> {
+        let buf = buf.as_mut();
+        let width = self.header.width as usize;
+        let pixel_pos = self.state.pixel_pos;
+        if unlikely(width != 0 && pixel_pos % width != 0) {
+            return Err(Error::DecodingStarted { pixel_pos });
+        }
+        let n_rows = n_rows.min(self.rows_left());
+        if self.stride.is_some() {
+            self.decode_strided_impl(n_rows, buf, &mut |_| {})
+                .map_err(|err| err.with_header(self.header))?;
+            return Ok(n_rows);
+        }
+        let required = n_rows.saturating_mul(width * self.bytes_per_pixel());
+        if unlikely(buf.len() < required) {
+            return Err(Error::OutputBufferTooSmall { size: buf.len(), required });
+        }
+        let _ = self.decode_pixels(&mut buf[..required])?;
+        Ok(n_rows)
+    }
+
+    /// Decodes a rectangular region of the image into the buffer and returns the number of
+    /// bytes written.
+    ///
+    /// The region is `width` by `height` pixels with the top-left corner at (`x`, `y`) in
+    /// image coordinates; it's written to the buffer row by row without padding (unless a
+    /// custom output stride is set, see [`Decoder::with_output_stride`]), so the buffer
+    /// must fit at least `width * height * bytes_per_pixel` bytes. Pixels outside of the region still
+    /// have to be decoded but aren't written anywhere, so only the crop needs to be allocated.
+    ///
+    /// The decoder must be positioned at the start of a row (otherwise
+    /// [`Error::DecodingStarted`] is returned) no further than row `y`; afterwards, it's
+    /// positioned right after the last row of the region, so e.g. horizontal strips of tiles
+    /// can be decoded from top to bottom one after another. If the region doesn't fit into
+    /// the image or the remaining rows, [`Error::InvalidRegion`] is returned.
+    ///
+    /// If decoding fails due to truncated or corrupt input, the image header is attached to
+    /// the error (see [`Error::header`]).
+    pub fn decode_region(
+        &mut self, x: u32, y: u32, width: u32, height: u32, mut buf: impl AsMut<[u8]>,
+    ) -> Result<usize> {
+        let buf = buf.as_mut();
+        let image_width = self.header.width as usize;
+        let pixel_pos = self.state.pixel_pos;
+        if unlikely(image_width != 0 && pixel_pos % image_width != 0) {
+            return Err(Error::DecodingStarted { pixel_pos });
+        }
+        let row = pixel_pos.checked_div(image_width).unwrap_or(0);
+        let fits = |pos: u32, len: u32, size: u32| u64::from(pos) + u64::from(len) <= size.into();
+        if unlikely(
+            !fits(x, width, self.header.width)
+                || !fits(y, height, self.header.height)
+                || (y as usize) < row,
+        ) {
+            return Err(Error::InvalidRegion { x, y, width, height });
+        }
+        let row_len = width as usize * self.bytes_per_pixel();
+        let required = self.strided_len(height as usize, row_len)?;
+        if unlikely(buf.len() < required) {
+            return Err(Error::OutputBufferTooSmall { size: buf.len(), required });
+        }
+        if required == 0 || row_len == 0 {
+            return Ok(0);
+        }
+        self.skip_pixels((y as usize - row) * image_width)?;
+        let stride = self.output_stride_for(row_len);
+        for i in 0..height as usize {
+            self.skip_pixels(x as usize)?;
+            let _ = self.decode_pixels(&mut buf[i * stride..][..row_len])?;
+            self.skip_pixels(image_width - x as usize - width as usize)?;
+        }
+        Ok(required)
+    }
+
+    /// Returns the distance between rows of `row_len` bytes in the output buffer.
+    #[inline]
+    const fn output_stride_for(&self, row_len: usize) -> usize {
+        match self.stride {
+            Some(stride) => stride,
+            None => row_len,
+        }
+    }
+
+    /// Returns the buffer length needed to write `n_rows` rows of `row_len` bytes each at the
+    /// output stride, or fails if the stride doesn't fit a row.
+    #[inline]
+    const fn strided_len(&self, n_rows: usize, row_len: usize) -> Result<usize> {
+        let stride = self.output_stride_for(row_len);
+        if unlikely(stride < row_len) {
+            return Err(Error::InvalidStride { stride, min_stride: row_len });
+        }
+        Ok(match n_rows {
+            0 => 0,
+            n_rows => stride.saturating_mul(n_rows - 1).saturating_add(row_len),
+        })
+    }
+
+    /// Decodes the next `n_rows` full rows at the output stride and returns the number of
+    /// bytes spanned in the buffer.
+    fn decode_strided_impl(
+        &mut self, n_rows: usize, buf: &mut [u8], diag: &mut impl FnMut(Diagnostic),
+    ) -> Result<usize> {
+        let width = self.header.width as usize;
+        let pixel_pos = self.state.pixel_pos;
+        if unlikely(width != 0 && pixel_pos % width != 0) {
+            return Err(Error::DecodingStarted { pixel_pos });
+        }
+        let (row_len, stride) = (self.row_len(), self.output_stride());
+        let required = self.strided_len(n_rows, row_len)?;
+        if unlikely(buf.len() < required) {
+            return Err(Error::OutputBufferTooSmall { size: buf.len(), required });
+        }
+        for i in 0..n_rows {
+            let dst = &mut buf[i * stride..][..row_len];
+            if self.target.is_some() {
+                self.decode_target_impl(dst, diag)?;
+            } else {
+                self.decode_pixels_impl(dst, diag)?;
+                self.apply_alpha(dst);
+            }
+        }
+        Ok(required)
+    }
+
+    /// Decodes the next `n_pixels` pixels without writing them out.
+    fn skip_pixels(&mut self, mut n_pixels: usize) -> Result<()> {
+        let n = self.channels.as_u8() as usize;
+        let mut strip = [0_u8; STRIP_PIXELS * 4];
+        while n_pixels != 0 {
+            let n_strip = self.decode_plain(&mut strip[..n_pixels.min(STRIP_PIXELS) * n])?;
+            n_pixels -= n_strip;
+        }
+        Ok(())
+    }
+
+    /// Overwrites alpha of decoded pixels if expanding RGB to RGBA with a non-opaque alpha,
+    /// then premultiplies it if enabled.
+    #[inline]
+    fn apply_alpha(&self, buf: &mut [u8]) {
+        if !self.channels.is_rgba() {
+            return;
+        }
+        let opaque = self.src_channels() == 3;
+        if self.default_alpha != 0xff && opaque {
+            buf.chunks_exact_mut(4).for_each(|px| px[3] = self.default_alpha);
+        }
+        if self.premultiply && (self.default_alpha != 0xff || !opaque) {
+            for px in buf.chunks_exact_mut(4) {
+                let pixel = Pixel::from_array([px[0], px[1], px[2], px[3]]).premultiplied();
+                px.copy_from_slice(&pixel.to_array());
+            }
+        }
+    }
+
+    /// Decodes pixels in small strips and scatters them into the target pixel layout.
+    fn decode_target_impl(
+        &mut self, buf: &mut [u8], diag: &mut impl FnMut(Diagnostic),
+    ) -> Result<()> {
+        let map = self.target_channels().channel_map();
+        let (n, bpp) = (self.channels.as_u8() as usize, map.bytes_per_pixel());
+        let mut strip = [0_u8; STRIP_PIXELS * 4];
+        for dst in buf.chunks_mut(STRIP_PIXELS * bpp) {
+            let src = &mut strip[..dst.len() / bpp * n];
+            self.decode_pixels_impl(src, diag)?;
+            self.apply_alpha(src);
+            // padding bytes aren't referenced by any channel
+            dst.fill(0xff);
+            for (src, dst) in src.chunks_exact(n).zip(dst.chunks_exact_mut(bpp)) {
+                for (&value, source) in src.iter().zip(map.sources()) {
+                    if let ChannelSource::Byte(offset) = source {
+                        dst[offset as usize] = value;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn decode_pixels_impl(
+        &mut self, buf: &mut [u8], diag: &mut impl FnMut(Diagnostic),
+    ) -> Result<()> {
+        if buf.is_empty() {
+            return Ok(());
+        }
+        let (channels, src_channels) = (self.channels.as_u8(), self.src_channels());
+        let undeclared = self.lenient_channels && self.header.channels.is_rgb();
+        let n_pixels = self.header.n_pixels();
+        let (reader, state, strict) = (&mut self.reader, &mut self.state, self.strict);
+        decode_impl_state_all(
+            reader,
+            state,
+            buf,
+            n_pixels,
+            channels,
+            src_channels,
+            strict,
+            undeclared,
+            diag,
+        )?;
+        if self.pixels_left() == 0 {
+            self.decode_padding(diag)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn decode_padding(&mut self, diag: &mut impl FnMut(Diagnostic)) -> Result<()> {
+        let padding = read_op_bytes::<_, QOI_PADDING_SIZE>(&mut self.reader, &mut self.state)?;
+        if unlikely(padding != QOI_PADDING) {
+            return Err(Error::InvalidPadding);
+        }
+        self.state.run = 0;
+        self.check_trailing_bytes(diag)
+    }
+
+    #[inline]
+    fn check_trailing_bytes(&self, diag: &mut impl FnMut(Diagnostic)) -> Result<()> {
+        match self.reader.n_trailing_bytes() {
+            Some(n_bytes) if self.strict && n_bytes != 0 => Err(Error::TrailingBytes { n_bytes }),
+            Some(n_bytes) if n_bytes != 0 => {
+                diag(Diagnostic::TrailingBytes { n_bytes });
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Decodes the rest of the image into separate channel planes (e.g. R, G, B and A)
+    /// and returns the number of pixels decoded.
+    ///
+    /// There must be exactly as many planes as there are channels in the decoded image
+    /// (see [`Decoder::channels`]), otherwise [`Error::InvalidChannels`] is returned; each
+    /// plane must fit at least [`Decoder::pixels_left`] bytes. The image is decoded in small
+    /// strips in a single pass, without allocating a full-size interleaved buffer.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn decode_to_planes(&mut self, planes: &mut [impl AsMut<[u8]>]) -> Result<usize> {
+        let n = self.channels.as_u8() as usize;
+        if unlikely(planes.len() != n) {
+            return Err(Error::InvalidChannels { channels: planes.len().min(0xff) as u8 });
+        }
+        let n_pixels = self.pixels_left();
+        for plane in planes.iter_mut() {
+            let size = plane.as_mut().len();
+            if unlikely(size < n_pixels) {
+                return Err(Error::OutputBufferTooSmall { size, required: n_pixels });
+            }
+        }
+        if unlikely(self.header.n_pixels() == 0) {
+            // there are no pixels to decode, but the end marker still has to be valid
+            self.decode_padding(&mut |_| {}).map_err(|err| err.with_header(self.header))?;
+        }
+        let mut strip = [0_u8; STRIP_PIXELS * 4];
+        let mut pos = 0;
+        while pos < n_pixels {
+            let n_strip = self.decode_plain(&mut strip[..STRIP_PIXELS * n])?;
+            for (c, plane) in planes.iter_mut().enumerate() {
+                let plane = &mut plane.as_mut()[pos..pos + n_strip];
+                plane.iter_mut().zip(strip.chunks_exact(n)).for_each(|(v, px)| *v = px[c]);
+            }
+            pos += n_strip;
+        }
+        Ok(n_pixels)
     }
 }
+
+/// Number of pixels decoded at a time into a stack buffer, e.g. when decoding into separate
+/// planes or skipping pixels outside of a region.
+const STRIP_PIXELS: usize = 256;
@@ -1,85 +1,251 @@
-#[cfg(any(feature = "std", feature = "alloc"))]
-use alloc::{vec, vec::Vec};
 use core::convert::TryFrom;
-#[cfg(feature = "std")]
-use std::io::Write;
+use core::fmt::{self, Debug};
+use core::ops::Range;
 
 use bytemuck::Pod;
 
 use crate::consts::{QOI_HEADER_SIZE, QOI_OP_INDEX, QOI_OP_RUN, QOI_PADDING, QOI_PADDING_SIZE};
 use crate::error::{Error, Result};
-use crate::header::Header;
+use crate::header::{dims_to_u32, Header};
 use crate::pixel::{Pixel, SupportedChannels};
+use crate::source::SourceChannels;
 use crate::types::{Channels, ColorSpace};
-#[cfg(feature = "std")]
-use crate::utils::GenericWriter;
 use crate::utils::{unlikely, BytesMut, Writer};
 
-#[allow(clippy::cast_possible_truncation, unused_assignments, unused_variables)]
-fn encode_impl<W: Writer, const N: usize>(mut buf: W, data: &[u8]) -> Result<usize>
+/// Encoding modes, passed to the encoding loop as a const generic parameter.
+pub const MODE_DEFAULT: u8 = 0;
+/// Store mode: only `RGB`/`RGBA` ops and runs are emitted.
+pub const MODE_STORE: u8 = 1;
+
+/// Encoder state carried between pixels, so that encoding can be suspended and resumed.
+#[derive(Clone)]
+pub struct EncodeState<const N: usize> {
+    index: [Pixel<4>; 256],
+    px_prev: Pixel<N>,
+    hash_prev: u8,
+    run: u8,
+    index_allowed: bool,
+    pub pos: usize,
+}
+
+impl<const N: usize> EncodeState<N>
 where
     Pixel<N>: SupportedChannels,
-    [u8; N]: Pod,
 {
-    let cap = buf.capacity();
+    pub const fn new() -> Self {
+        let px_prev = Pixel::new().with_a(0xff);
+        Self {
+            index: [Pixel::new(); 256],
+            px_prev,
+            hash_prev: px_prev.hash_index(),
+            run: 0,
+            index_allowed: false,
+            pos: 0,
+        }
+    }
 
-    let mut index = [Pixel::new(); 256];
-    let mut px_prev = Pixel::new().with_a(0xff);
-    let mut hash_prev = px_prev.hash_index();
-    let mut run = 0_u8;
-    let mut px = Pixel::<N>::new().with_a(0xff);
-    let mut index_allowed = false;
-
-    let n_pixels = data.len() / N;
-
-    for (i, chunk) in data.chunks_exact(N).enumerate() {
-        px.read(chunk);
-        if px == px_prev {
-            run += 1;
-            if run == 62 || unlikely(i == n_pixels - 1) {
-                buf = buf.write_one(QOI_OP_RUN | (run - 1))?;
-                run = 0;
-            }
-        } else {
-            if run != 0 {
-                #[cfg(not(feature = "reference"))]
-                {
-                    // credits for the original idea: @zakarumych (had to be fixed though)
-                    buf = buf.write_one(if run == 1 && index_allowed {
-                        QOI_OP_INDEX | hash_prev
-                    } else {
-                        QOI_OP_RUN | (run - 1)
-                    })?;
-                }
-                #[cfg(feature = "reference")]
-                {
+    /// Encodes the next pixels out of `n_pixels` total; a pending run is only flushed
+    /// if the last pixel of the image has been encoded.
+    #[allow(clippy::cast_possible_truncation, unused_assignments, unused_variables)]
+    #[inline(always)]
+    pub fn encode_pixels<W, I, const MODE: u8>(
+        &mut self, mut buf: W, pixels: I, n_pixels: usize,
+    ) -> Result<W>
+    where
+        W: Writer,
+        I: Iterator<Item = Pixel<N>>,
+        [u8; N]: Pod,
+    {
+        // the state is kept in locals while encoding, so the hot loop doesn't change
+        let index = &mut self.index;
+        let mut px_prev = self.px_prev;
+        let mut hash_prev = self.hash_prev;
+        let mut run = self.run;
+        let mut index_allowed = self.index_allowed;
+        let mut pos = self.pos;
+
+        for px in pixels {
+            if px == px_prev {
+                run += 1;
+                if run == 62 || unlikely(pos == n_pixels - 1) {
                     buf = buf.write_one(QOI_OP_RUN | (run - 1))?;
+                    run = 0;
                 }
-                run = 0;
-            }
-            index_allowed = true;
-            let px_rgba = px.as_rgba(0xff);
-            hash_prev = px_rgba.hash_index();
-            let index_px = &mut index[hash_prev as usize];
-            if *index_px == px_rgba {
-                buf = buf.write_one(QOI_OP_INDEX | hash_prev)?;
             } else {
-                *index_px = px_rgba;
-                buf = px.encode_into(px_prev, buf)?;
+                if run != 0 {
+                    #[cfg(not(feature = "reference"))]
+                    {
+                        // credits for the original idea: @zakarumych (had to be fixed though)
+                        buf = buf.write_one(if run == 1 && index_allowed {
+                            QOI_OP_INDEX | hash_prev
+                        } else {
+                            QOI_OP_RUN | (run - 1)
+                        })?;
+                    }
+                    #[cfg(feature = "reference")]
+                    {
+                        buf = buf.write_one(QOI_OP_RUN | (run - 1))?;
+                    }
+                    run = 0;
+                }
+                if MODE == MODE_STORE {
+                    // the index is never used, so runs of one pixel are stored as runs too
+                    buf = px.encode_raw_into(px_prev, buf)?;
+                } else {
+                    index_allowed = true;
+                    let px_rgba = px.as_rgba(0xff);
+                    hash_prev = px_rgba.hash_index();
+                    let index_px = &mut index[hash_prev as usize];
+                    if *index_px == px_rgba {
+                        buf = buf.write_one(QOI_OP_INDEX | hash_prev)?;
+                    } else {
+                        *index_px = px_rgba;
+                        buf = px.encode_into(px_prev, buf)?;
+                    }
+                }
+                px_prev = px;
             }
-            px_prev = px;
+            pos += 1;
         }
+
+        self.px_prev = px_prev;
+        self.hash_prev = hash_prev;
+        self.run = run;
+        self.index_allowed = index_allowed;
+        self.pos = pos;
+        Ok(buf)
+    }
+}
+
+/// Same as [`encode_impl`], but un-premultiplies alpha first if the source pixels of the
+/// encoder have premultiplied alpha.
+#[inline]
+fn encode_impl_source_pixels<W, I, const N: usize, const MODE: u8>(
+    buf: W, pixels: I, enc: &Encoder,
+) -> Result<usize>
+where
+    W: Writer,
+    I: Iterator<Item = Pixel<N>>,
+    Pixel<N>: SupportedChannels,
+    [u8; N]: Pod,
+{
+    let n_pixels = enc.header.n_pixels();
+    if N == 4 && enc.premultiplied {
+        encode_impl::<_, _, N, MODE>(buf, pixels.map(Pixel::unpremultiplied), n_pixels)
+    } else {
+        encode_impl::<_, _, N, MODE>(buf, pixels, n_pixels)
     }
+}
 
-    buf = buf.write_many(&QOI_PADDING)?;
+pub fn encode_impl<W, I, const N: usize, const MODE: u8>(
+    buf: W, pixels: I, n_pixels: usize,
+) -> Result<usize>
+where
+    W: Writer,
+    I: Iterator<Item = Pixel<N>>,
+    Pixel<N>: SupportedChannels,
+    [u8; N]: Pod,
+{
+    let cap = buf.capacity();
+    let mut state = EncodeState::<N>::new();
+    let buf = state.encode_pixels::<_, _, MODE>(buf, pixels, n_pixels)?;
+    let buf = buf.write_many(&QOI_PADDING)?;
     Ok(cap.saturating_sub(buf.capacity()))
 }
 
+/// Separate channel planes of the source image, see [`EncoderBuilder::from_planes`].
+#[derive(Copy, Clone)]
+pub struct Planes<'a> {
+    planes: [&'a [u8]; 4],
+    len: usize,
+}
+
+impl Debug for Planes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{} planes of {} bytes]", self.len, self.planes[0].len())
+    }
+}
+
+impl<'a> Planes<'a> {
+    fn new(planes: &'a [impl AsRef<[u8]>]) -> Self {
+        let mut out: [&[u8]; 4] = [&[]; 4];
+        out.iter_mut().zip(planes).for_each(|(out, plane)| *out = plane.as_ref());
+        Self { planes: out, len: planes.len() }
+    }
+
+    /// Returns the number of planes.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the pixels from position `pos` onwards, gathered from the planes on the fly.
+    pub fn pixels<const N: usize>(
+        self, enc: &Encoder, pos: usize,
+    ) -> impl Iterator<Item = Pixel<N>> + 'a
+    where
+        Pixel<N>: SupportedChannels,
+    {
+        let (width, height) = (enc.header.width as usize, enc.header.height as usize);
+        let (stride, sources) = (enc.stride, enc.source.channel_map().sources());
+        let (y, x) =
+            (pos.checked_div(width).unwrap_or(height), pos.checked_rem(width).unwrap_or(0));
+        (y..height)
+            .flat_map(move |row| row * stride + if row == y { x } else { 0 }..row * stride + width)
+            .map(move |i| {
+                let mut px = Pixel::<N>::new();
+                px.read_planar(&self.planes, i, sources);
+                px
+            })
+    }
+}
+
+#[inline]
+fn encode_impl_source<W: Writer, const N: usize, const MODE: u8>(
+    out: W, enc: &Encoder,
+) -> Result<usize>
+where
+    Pixel<N>: SupportedChannels,
+    [u8; N]: Pod,
+{
+    let (width, height) = (enc.header.width as usize, enc.header.height as usize);
+    if let Some(planes) = enc.planes {
+        return encode_impl_source_pixels::<_, _, N, MODE>(out, planes.pixels::<N>(enc, 0), enc);
+    }
+    let bpp = enc.source.bytes_per_pixel();
+    let row_len = width * bpp;
+    let rows = enc.data.chunks(enc.stride.max(1)).take(height); // stride is 0 if width is 0
+    if enc.source.is_identity() {
+        let read = |chunk: &[u8]| {
+            let mut px = Pixel::<N>::new();
+            px.read(chunk);
+            px
+        };
+        if enc.stride == row_len {
+            let pixels = enc.data.chunks_exact(N).map(read);
+            encode_impl_source_pixels::<_, _, N, MODE>(out, pixels, enc)
+        } else {
+            let pixels = rows.flat_map(|row| row[..row_len].chunks_exact(N)).map(read);
+            encode_impl_source_pixels::<_, _, N, MODE>(out, pixels, enc)
+        }
+    } else {
+        let sources = enc.source.channel_map().sources();
+        let read = |chunk: &[u8]| {
+            let mut px = Pixel::<N>::new();
+            px.read_mapped(chunk, sources);
+            px
+        };
+        let pixels = rows.flat_map(|row| row[..row_len].chunks_exact(bpp)).map(read);
+        encode_impl_source_pixels::<_, _, N, MODE>(out, pixels, enc)
+    }
+}
+
 #[inline]
-fn encode_impl_all<W: Writer>(out: W, data: &[u8], channels: Channels) -> Result<usize> {
-    match channels {
-        Channels::Rgb => encode_impl::<_, 3>(out, data),
-        Channels::Rgba => encode_impl::<_, 4>(out, data),
+pub fn encode_impl_all<W: Writer>(out: W, enc: &Encoder) -> Result<usize> {
+    match (enc.header.channels, enc.mode()) {
+        (Channels::Rgb, MODE_STORE) => encode_impl_source::<_, 3, MODE_STORE>(out, enc),
+        (Channels::Rgba, MODE_STORE) => encode_impl_source::<_, 4, MODE_STORE>(out, enc),
+        (Channels::Rgb, _) => encode_impl_source::<_, 3, MODE_DEFAULT>(out, enc),
+        (Channels::Rgba, _) => encode_impl_source::<_, 4, MODE_DEFAULT>(out, enc),
     }
 }
 
@@ -88,12 +254,27 @@ fn encode_impl_all<W: Writer>(out: W, data: &[u8], channels: Channels) -> Result
 /// Can be used to pre-allocate the buffer to encode the image into.
 #[inline]
 pub fn encode_max_len(width: u32, height: u32, channels: impl Into<u8>) -> usize {
+    encode_max_len_impl(width, height, channels.into())
+}
+
+/// Same as [`encode_max_len`], but usable in const contexts, e.g. for sizing static buffers:
+///
+/// ```rust
+/// use qoi::{encoded_size_limit, Channels};
+///
+/// const BUF_LEN: usize = encoded_size_limit(64, 64, Channels::Rgba);
+/// static mut BUF: [u8; BUF_LEN] = [0; BUF_LEN];
+/// ```
+#[inline]
+pub const fn encoded_size_limit(width: u32, height: u32, channels: Channels) -> usize {
+    encode_max_len_impl(width, height, channels.as_u8())
+}
+
+#[inline]
+pub const fn encode_max_len_impl(width: u32, height: u32, channels: u8) -> usize {
     let (width, height) = (width as usize, height as usize);
     let n_pixels = width.saturating_mul(height);
-    QOI_HEADER_SIZE
-        + n_pixels.saturating_mul(channels.into() as usize)
-        + n_pixels
-        + QOI_PADDING_SIZE
+    QOI_HEADER_SIZE + n_pixels.saturating_mul(channels as usize) + n_pixels + QOI_PADDING_SIZE
 }
 
 /// Encode the image into a pre-allocated buffer.
@@ -106,17 +287,300 @@ pub fn encode_to_buf(
     Encoder::new(&data, width, height)?.encode_to_buf(buf)
 }
 
-/// Encode the image into a newly allocated vector.
-#[cfg(any(feature = "alloc", feature = "std"))]
+/// Encode a headerless image body into a pre-allocated buffer, see [`Encoder::encode_body_to_buf`].
+///
+/// Returns the total number of bytes written.
 #[inline]
-pub fn encode_to_vec(data: impl AsRef<[u8]>, width: u32, height: u32) -> Result<Vec<u8>> {
-    Encoder::new(&data, width, height)?.encode_to_vec()
+pub fn encode_body_to_buf(
+    buf: impl AsMut<[u8]>, data: impl AsRef<[u8]>, width: u32, height: u32,
+) -> Result<usize> {
+    Encoder::new(&data, width, height)?.encode_body_to_buf(buf)
+}
+
+/// Builder for [`Encoder`], supporting arbitrary source pixel layouts, row strides and
+/// planar input.
+///
+/// ### Example
+/// ```rust
+/// use qoi::{EncoderBuilder, SourceChannels};
+///
+/// let encoded = EncoderBuilder::new(&bgrx_pixels, width, height)
+///     .with_source_channels(SourceChannels::Bgrx)
+///     .with_stride(row_pitch)
+///     .build()?
+///     .encode_to_vec()?;
+/// ```
+#[derive(Copy, Clone)]
+pub struct EncoderBuilder<'a> {
+    data: &'a [u8],
+    planes: Option<Planes<'a>>,
+    width: u32,
+    height: u32,
+    source: Option<SourceChannels>,
+    channels: Option<Channels>,
+    stride: Option<usize>,
+    colorspace: ColorSpace,
+    allow_empty: bool,
+    allow_oversized: bool,
+    premultiplied: bool,
+}
+
+/// Pixel data is shown as its length only.
+struct DataLen(usize);
+
+impl Debug for DataLen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{} bytes]", self.0)
+    }
+}
+
+impl Debug for EncoderBuilder<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EncoderBuilder")
+            .field("data", &DataLen(self.data.len()))
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("source", &self.source)
+            .field("channels", &self.channels)
+            .field("stride", &self.stride)
+            .field("colorspace", &self.colorspace)
+            .field("allow_empty", &self.allow_empty)
+            .field("allow_oversized", &self.allow_oversized)
+            .field("planes", &self.planes)
+            .field("premultiplied", &self.premultiplied)
+            .finish()
+    }
+}
+
+impl<'a> EncoderBuilder<'a> {
+    /// Creates a new encoder builder from a given array of pixel data and image dimensions.
+    #[inline]
+    pub fn new(data: &'a (impl AsRef<[u8]> + ?Sized), width: u32, height: u32) -> Self {
+        Self {
+            data: data.as_ref(),
+            planes: None,
+            width,
+            height,
+            source: None,
+            channels: None,
+            stride: None,
+            colorspace: ColorSpace::default(),
+            allow_empty: false,
+            allow_oversized: false,
+            premultiplied: false,
+        }
+    }
+
+    /// Same as [`EncoderBuilder::new`], but takes `usize` dimensions.
+    ///
+    /// Fails with [`Error::DimensionOverflow`] if either dimension doesn't fit into `u32`.
+    #[inline]
+    pub fn new_usize(
+        data: &'a (impl AsRef<[u8]> + ?Sized), width: usize, height: usize,
+    ) -> Result<Self> {
+        let (width, height) = dims_to_u32(width, height)?;
+        Ok(Self::new(data, width, height))
+    }
+
+    /// Creates a new encoder builder from separate channel planes (e.g. R, G, B and A) and
+    /// image dimensions.
+    ///
+    /// Each plane stores one byte per pixel; there must be 3 or 4 planes, which are R, G, B
+    /// and optionally A by default. The planes are interleaved on the fly while encoding, so
+    /// no interleaved copy of the image is ever made. All other options apply as usual, e.g.
+    /// the stride (see [`EncoderBuilder::with_stride`]) is the distance between the rows of
+    /// each plane, and the source layout (see [`EncoderBuilder::with_source_channels`]) maps
+    /// plane indices rather than byte offsets to channels, so `SourceChannels::Bgr` means
+    /// that the planes are B, G and R. A mismatching number of planes is rejected with
+    /// [`Error::InvalidChannels`] when building the encoder.
+    #[inline]
+    pub fn from_planes(planes: &'a [impl AsRef<[u8]>], width: u32, height: u32) -> Self {
+        let mut builder = Self::new(&[], width, height);
+        builder.planes = Some(Planes::new(planes));
+        builder
+    }
+
+    /// Sets the pixel layout of the source data.
+    ///
+    /// If not set, the layout is inferred from the data length: either RGB or RGBA
+    /// (this requires the rows to be tightly packed, i.e. no custom stride).
+    #[inline]
+    pub const fn with_source_channels(mut self, source: SourceChannels) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Sets the number of channels in the encoded image, overriding the source layout.
+    ///
+    /// By default, layouts with alpha are encoded as RGBA and all others as RGB. Encoding
+    /// a source without alpha as RGBA sets alpha to 255 (e.g. for consumers that only
+    /// accept 4-channel images), encoding a source with alpha as RGB drops it.
+    #[inline]
+    pub const fn with_channels(mut self, channels: Channels) -> Self {
+        self.channels = Some(channels);
+        self
+    }
+
+    /// Sets the distance in bytes between the starts of consecutive rows.
+    ///
+    /// If not set, the rows are assumed to be tightly packed. Bytes between the end
+    /// of one row and the start of the next one are ignored; the last row may be either
+    /// padded to the full stride (i.e. the data is `stride * height` bytes long) or not.
+    #[inline]
+    pub const fn with_stride(mut self, stride: usize) -> Self {
+        self.stride = Some(stride);
+        self
+    }
+
+    /// Sets the color space stored in the image header (sRGB by default).
+    #[inline]
+    pub const fn with_colorspace(mut self, colorspace: ColorSpace) -> Self {
+        self.colorspace = colorspace;
+        self
+    }
+
+    /// Allows zero width or height, in which case the pixel data must be empty and the
+    /// encoded image will only consist of the header and the end marker.
+    ///
+    /// Such images can only be decoded with [`HeaderOptions::with_allow_empty`] enabled.
+    ///
+    /// [`HeaderOptions::with_allow_empty`]: crate::HeaderOptions::with_allow_empty
+    #[inline]
+    pub const fn with_allow_empty(mut self, allow_empty: bool) -> Self {
+        self.allow_empty = allow_empty;
+        self
+    }
+
+    /// Allows the pixel data to be longer than needed, in which case only the required
+    /// prefix is encoded and the rest is ignored.
+    ///
+    /// Note: the source layout can't be inferred from the data length in this case, so it
+    /// has to be set explicitly via [`EncoderBuilder::with_source_channels`].
+    #[inline]
+    pub const fn with_allow_oversized(mut self, allow_oversized: bool) -> Self {
+        self.allow_oversized = allow_oversized;
+        self
+    }
+
+    /// Sets whether the source pixels have premultiplied alpha (false by default).
+    ///
+    /// QOI images store straight (non-premultiplied) alpha, so if set, the color channels
+    /// are divided by alpha on the fly while encoding; see [`Pixel::unpremultiplied`] for
+    /// rounding. Has no effect when encoding as RGB.
+    ///
+    /// [`Pixel::unpremultiplied`]: crate::Pixel::unpremultiplied
+    #[inline]
+    pub const fn with_premultiplied_alpha(mut self, premultiplied: bool) -> Self {
+        self.premultiplied = premultiplied;
+        self
+    }
+
+    /// Validates the image dimensions, source layout and data length and creates the encoder.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn build(self) -> Result<Encoder<'a>> {
+        let (data, width, height) = (self.data, self.width, self.height);
+        let colorspace = self.colorspace;
+        let mut header =
+            Header::try_new_impl(width, height, Channels::default(), colorspace, self.allow_empty)?;
+        let size = data.len();
+        let source = if let Some(source) = self.source {
+            source
+        } else if let Some(ref planes) = self.planes {
+            if planes.len() == 3 {
+                SourceChannels::Rgb
+            } else {
+                SourceChannels::Rgba
+            }
+        } else if header.n_pixels() == 0 {
+            Channels::default().into()
+        } else {
+            let n_channels = size / header.n_pixels();
+            if header.n_pixels() * n_channels != size {
+                return Err(Error::InvalidImageLength { size, width, height });
+            }
+            Channels::try_from(n_channels.min(0xff) as u8)?.into()
+        };
+        // each plane stores one byte per pixel
+        let bpp = if self.planes.is_some() { 1 } else { source.bytes_per_pixel() };
+        let row_len = width as usize * bpp;
+        let stride = self.stride.unwrap_or(row_len);
+        if unlikely(stride < row_len) {
+            return Err(Error::InvalidStride { stride, min_stride: row_len });
+        }
+        let required = match height {
+            0 => 0,
+            _ => stride.saturating_mul(height as usize - 1).saturating_add(row_len),
+        };
+        let check_len = |size: usize| {
+            // buffers with padding after the last row (`stride * height` bytes) are accepted too
+            let padded = size == stride.saturating_mul(height as usize);
+            if unlikely(size < required || (size > required && !padded && !self.allow_oversized)) {
+                return Err(Error::InvalidImageLength { size, width, height });
+            }
+            Ok(())
+        };
+        let (data, planes) = if let Some(mut planes) = self.planes {
+            let n = planes.len();
+            if unlikely(!(3..=4).contains(&n) || n != source.bytes_per_pixel()) {
+                return Err(Error::InvalidChannels { channels: n.min(0xff) as u8 });
+            }
+            for plane in &mut planes.planes[..n] {
+                check_len(plane.len())?;
+                *plane = &plane[..required];
+            }
+            (data, Some(planes))
+        } else {
+            check_len(size)?;
+            (&data[..required], None)
+        };
+        let source = match self.channels {
+            Some(channels) if channels != source.channels() => {
+                SourceChannels::Custom(source.channel_map().with_channels(channels))
+            }
+            _ => source,
+        };
+        header.channels = source.channels();
+        Ok(Encoder {
+            data,
+            planes,
+            header,
+            source,
+            stride,
+            store: false,
+            flush: false,
+            premultiplied: self.premultiplied,
+        })
+    }
 }
 
 /// Encode QOI images into buffers or into streams.
+///
+/// Encoders are `Send + Sync` and can be freely moved or shared across threads.
+#[derive(Clone)]
 pub struct Encoder<'a> {
-    data: &'a [u8],
-    header: Header,
+    pub(crate) data: &'a [u8],
+    pub(crate) planes: Option<Planes<'a>>,
+    pub(crate) header: Header,
+    pub(crate) source: SourceChannels,
+    pub(crate) stride: usize,
+    pub(crate) store: bool,
+    pub(crate) flush: bool,
+    pub(crate) premultiplied: bool,
+}
+
+impl Debug for Encoder<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Encoder")
+            .field("data", &DataLen(self.data.len()))
+            .field("header", &self.header)
+            .field("source", &self.source)
+            .field("stride", &self.stride)
+            .field("store", &self.store)
+            .field("flush", &self.flush)
+            .field("planes", &self.planes)
+            .field("premultiplied", &self.premultiplied)
+            .finish()
+    }
 }
 
 impl<'a> Encoder<'a> {
@@ -124,19 +588,21 @@ impl<'a> Encoder<'a> {
     ///
     /// The number of channels will be inferred automatically (the valid values
     /// are 3 or 4). The color space will be set to sRGB by default.
+    ///
+    /// For other pixel layouts or padded rows, use [`EncoderBuilder`] instead.
     #[inline]
-    #[allow(clippy::cast_possible_truncation)]
     pub fn new(data: &'a (impl AsRef<[u8]> + ?Sized), width: u32, height: u32) -> Result<Self> {
-        let data = data.as_ref();
-        let mut header =
-            Header::try_new(width, height, Channels::default(), ColorSpace::default())?;
-        let size = data.len();
-        let n_channels = size / header.n_pixels();
-        if header.n_pixels() * n_channels != size {
-            return Err(Error::InvalidImageLength { size, width, height });
-        }
-        header.channels = Channels::try_from(n_channels.min(0xff) as u8)?;
-        Ok(Self { data, header })
+        EncoderBuilder::new(data, width, height).build()
+    }
+
+    /// Same as [`Encoder::new`], but takes `usize` dimensions.
+    ///
+    /// Fails with [`Error::DimensionOverflow`] if either dimension doesn't fit into `u32`.
+    #[inline]
+    pub fn new_usize(
+        data: &'a (impl AsRef<[u8]> + ?Sized), width: usize, height: usize,
+    ) -> Result<Self> {
+        EncoderBuilder::new_usize(data, width, height)?.build()
     }
 
     /// Returns a new encoder with modified color space.
@@ -149,12 +615,65 @@ impl<'a> Encoder<'a> {
         self
     }
 
-    /// Returns the inferred number of channels.
+    /// Returns a new encoder with the store mode enabled or disabled (disabled by default).
+    ///
+    /// In store mode, all compression heuristics are skipped: every pixel that doesn't repeat
+    /// the previous one is stored as is via an `RGB` or `RGBA` op. The output is a valid QOI
+    /// image that is typically much larger, but encoding takes nearly constant time per pixel,
+    /// which may be preferable for noisy inputs where compression saves little anyway.
+    #[inline]
+    pub const fn with_store(mut self, store: bool) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Returns true if the store mode is enabled, see [`Encoder::with_store`].
+    #[inline]
+    pub const fn is_store(&self) -> bool {
+        self.store
+    }
+
+    #[inline]
+    pub(crate) const fn mode(&self) -> u8 {
+        if self.store {
+            MODE_STORE
+        } else {
+            MODE_DEFAULT
+        }
+    }
+
+    /// Returns the number of channels in the encoded image.
     #[inline]
     pub const fn channels(&self) -> Channels {
         self.header.channels
     }
 
+    /// Returns the pixel layout of the source data.
+    #[inline]
+    pub const fn source_channels(&self) -> SourceChannels {
+        self.source
+    }
+
+    /// Returns the distance in bytes between the starts of consecutive source rows (of each
+    /// plane, for planar input).
+    #[inline]
+    pub const fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Returns true if the source data is planar, see [`EncoderBuilder::from_planes`].
+    #[inline]
+    pub const fn is_planar(&self) -> bool {
+        self.planes.is_some()
+    }
+
+    /// Returns true if the source pixels have premultiplied alpha, see
+    /// [`EncoderBuilder::with_premultiplied_alpha`].
+    #[inline]
+    pub const fn is_premultiplied(&self) -> bool {
+        self.premultiplied
+    }
+
     /// Returns the header that will be stored in the encoded image.
     #[inline]
     pub const fn header(&self) -> &Header {
@@ -165,7 +684,7 @@ impl<'a> Encoder<'a> {
     ///
     /// Can be used to pre-allocate the buffer to encode the image into.
     #[inline]
-    pub fn required_buf_len(&self) -> usize {
+    pub const fn required_buf_len(&self) -> usize {
         self.header.encode_max_len()
     }
 
@@ -181,30 +700,136 @@ impl<'a> Encoder<'a> {
         }
         let (head, tail) = buf.split_at_mut(QOI_HEADER_SIZE); // can't panic
         head.copy_from_slice(&self.header.encode());
-        let n_written = encode_impl_all(BytesMut::new(tail), self.data, self.header.channels)?;
+        let n_written = encode_impl_all(BytesMut::new(tail), self)?;
         Ok(QOI_HEADER_SIZE + n_written)
     }
 
-    /// Encodes the image into a newly allocated vector of bytes and returns it.
-    #[cfg(any(feature = "alloc", feature = "std"))]
+    /// Encodes the image body without the 14-byte header to a pre-allocated buffer and
+    /// returns the number of bytes written.
+    ///
+    /// This allows embedding QOI payloads in custom containers or network protocols that
+    /// carry the image metadata on their own; the body can be decoded back given the header,
+    /// see [`Decoder::new_body`](crate::Decoder::new_body). The buffer must fit at least
+    /// [`Encoder::required_buf_len`] minus [`QOI_HEADER_SIZE`] bytes.
+    #[inline]
+    pub fn encode_body_to_buf(&self, mut buf: impl AsMut<[u8]>) -> Result<usize> {
+        let buf = buf.as_mut();
+        let size_required = self.required_buf_len() - QOI_HEADER_SIZE;
+        if unlikely(buf.len() < size_required) {
+            return Err(Error::OutputBufferTooSmall { size: buf.len(), required: size_required });
+        }
+        encode_impl_all(BytesMut::new(buf), self)
+    }
+
+    /// Returns a new encoder that flushes the writer after encoding to a stream.
+    ///
+    /// This is disabled by default; see [`Encoder::encode_to_stream`].
+    #[inline]
+    pub const fn with_flush(mut self, flush: bool) -> Self {
+        self.flush = flush;
+        self
+    }
+}
+
+/// Encoder that owns its pixel data, e.g. a `Vec<u8>` or an `Arc<[u8]>`.
+///
+/// Unlike [`Encoder`] which borrows the pixels, it's `'static` as long as the data is, so it
+/// can be moved into spawned threads or async tasks. All encoding is done via a borrowed
+/// [`Encoder`] that can be obtained with [`OwnedEncoder::encoder`]; the most common
+/// methods are also available directly.
+///
+/// Note: the data is assumed to be immutable, i.e. `as_ref()` must return the same bytes
+/// every time it's called (which is always the case for `Vec<u8>`, `Box<[u8]>` and `Arc<[u8]>`).
+#[derive(Clone)]
+pub struct OwnedEncoder<D> {
+    data: D,
+    range: Range<usize>,
+    encoder: Encoder<'static>,
+}
+
+impl<D> Debug for OwnedEncoder<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OwnedEncoder").field("encoder", &self.encoder).finish_non_exhaustive()
+    }
+}
+
+impl<D: AsRef<[u8]>> OwnedEncoder<D> {
+    /// Creates a new encoder from owned pixel data, see [`Encoder::new`].
     #[inline]
-    pub fn encode_to_vec(&self) -> Result<Vec<u8>> {
-        let mut out = vec![0_u8; self.required_buf_len()];
-        let size = self.encode_to_buf(&mut out)?;
-        out.truncate(size);
-        Ok(out)
+    pub fn new(data: D, width: u32, height: u32) -> Result<Self> {
+        Self::with_encoder(data, |data| Encoder::new(data, width, height))
     }
 
-    /// Encodes the image directly to a generic writer that implements [`Write`](std::io::Write).
+    /// Creates a new encoder from owned pixel data, configured by the given function.
+    ///
+    /// This allows using any [`EncoderBuilder`] or [`Encoder`] options, e.g.:
     ///
-    /// Note: while it's possible to pass a `&mut [u8]` slice here since it implements `Write`,
-    /// it would more effficient to use a specialized method instead: [`Encoder::encode_to_buf`].
-    #[cfg(feature = "std")]
+    /// ```rust
+    /// let encoder = OwnedEncoder::with_encoder(bgrx_pixels, |data| {
+    ///     EncoderBuilder::new(data, width, height)
+    ///         .with_source_channels(SourceChannels::Bgrx)
+    ///         .build()
+    /// })?;
+    /// ```
+    ///
+    /// The encoder must encode from (a subslice of) the given data; encoders borrowing any
+    /// other data, including planar encoders (see [`EncoderBuilder::from_planes`]), are
+    /// rejected with [`Error::InvalidImageLength`].
+    pub fn with_encoder(data: D, f: impl FnOnce(&[u8]) -> Result<Encoder<'_>>) -> Result<Self> {
+        let encoder = f(data.as_ref())?;
+        // the built encoder may only borrow a part of the data, e.g. if it's oversized
+        let (base, size) = (data.as_ref().as_ptr() as usize, data.as_ref().len());
+        let start = (encoder.data.as_ptr() as usize).wrapping_sub(base);
+        let range = match encoder.data.len() {
+            0 => 0..0,
+            len if start <= size && len <= size - start => start..start + len,
+            _ => size..0,
+        };
+        if unlikely(encoder.planes.is_some() || range.start > range.end) {
+            let Header { width, height, .. } = encoder.header;
+            return Err(Error::InvalidImageLength { size, width, height });
+        }
+        let encoder = Encoder {
+            data: &[],
+            planes: None,
+            header: encoder.header,
+            source: encoder.source,
+            stride: encoder.stride,
+            store: encoder.store,
+            flush: encoder.flush,
+            premultiplied: encoder.premultiplied,
+        };
+        Ok(Self { data, range, encoder })
+    }
+
+    /// Returns an encoder borrowing the owned data.
+    #[inline]
+    pub fn encoder(&self) -> Encoder<'_> {
+        Encoder { data: &self.data.as_ref()[self.range.clone()], ..self.encoder }
+    }
+
+    /// Returns the header that will be stored in the encoded image.
+    #[inline]
+    pub const fn header(&self) -> &Header {
+        &self.encoder.header
+    }
+
+    /// Returns a reference to the owned pixel data.
+    #[inline]
+    pub const fn data(&self) -> &D {
+        &self.data
+    }
+
+    /// Consumes the encoder and returns the owned pixel data.
+    #[inline]
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn into_data(self) -> D {
+        self.data
+    }
+
+    /// Encodes the image to a pre-allocated buffer, see [`Encoder::encode_to_buf`].
     #[inline]
-    pub fn encode_to_stream<W: Write>(&self, writer: &mut W) -> Result<usize> {
-        writer.write_all(&self.header.encode())?;
-        let n_written =
-            encode_impl_all(GenericWriter::new(writer), self.data, self.header.channels)?;
-        Ok(n_written + QOI_HEADER_SIZE)
+    pub fn encode_to_buf(&self, buf: impl AsMut<[u8]>) -> Result<usize> {
+        self.encoder().encode_to_buf(buf)
     }
 }
@@ -1,10 +1,17 @@
+#[cfg(any(feature = "std", feature = "alloc"))]
+use alloc::boxed::Box;
 use core::convert::Infallible;
 use core::fmt::{self, Display};
 
 use crate::consts::QOI_MAGIC;
+use crate::header::Header;
 
 /// Errors that can occur during encoding or decoding.
+///
+/// Note: new variants may be added in the future; use [`Error::kind`] if you need
+/// to match on the error category in a forward-compatible way.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// Leading 4 magic bytes don't match when decoding
     InvalidMagic { magic: u32 },
@@ -14,17 +21,161 @@ pub enum Error {
     InvalidColorSpace { colorspace: u8 },
     /// Invalid image dimensions: can't be empty or larger than 400Mp
     InvalidImageDimensions { width: u32, height: u32 },
+    /// Image dimensions don't fit into `u32`
+    DimensionOverflow { width: usize, height: usize },
     /// Image dimensions are inconsistent with image buffer length
     InvalidImageLength { size: usize, width: u32, height: u32 },
+    /// Row stride is smaller than the length of a row of pixels
+    InvalidStride { stride: usize, min_stride: usize },
+    /// Channel map references a byte outside of the source pixel
+    InvalidChannelMap { bytes_per_pixel: u8, offset: u8 },
     /// Output buffer is too small to fit encoded/decoded image
     OutputBufferTooSmall { size: usize, required: usize },
-    /// Input buffer ended unexpectedly before decoding was finished
+    /// Input buffer ended unexpectedly before decoding was finished (truncated input)
     UnexpectedBufferEnd,
-    /// Invalid stream end marker encountered when decoding
+    /// Wrapped reader hit end-of-file before decoding was finished (truncated input)
+    UnexpectedStreamEnd,
+    /// Op that can't appear in this stream encountered when decoding (corrupt input)
+    InvalidOp { op: u8 },
+    /// Invalid stream end marker encountered when decoding (corrupt input)
     InvalidPadding,
+    /// Run extends past the last pixel of the image (strict decoding only)
+    InvalidRunLength { run: usize, pixels_left: usize },
+    /// Input buffer contains extra bytes after the end marker (strict decoding only)
+    TrailingBytes { n_bytes: usize },
+    /// Output channels can't be changed once some of the pixels have been decoded
+    DecodingStarted { pixel_pos: usize },
+    /// Region to decode doesn't fit into the image or into the not yet decoded rows
+    InvalidRegion { x: u32, y: u32, width: u32, height: u32 },
     #[cfg(feature = "std")]
     /// Generic I/O error from the wrapped reader/writer
     IoError(std::io::Error),
+    #[cfg(feature = "std")]
+    /// I/O error from the wrapped writer after some of the output has already been written
+    PartialWrite { n_written: usize, source: std::io::Error },
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    /// Truncated or corrupt stream data encountered after the image header was decoded
+    WithHeader { header: Header, source: Box<Self> },
+}
+
+/// A stable, field-less category of an [`Error`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// See [`Error::InvalidMagic`]
+    InvalidMagic,
+    /// See [`Error::InvalidChannels`]
+    InvalidChannels,
+    /// See [`Error::InvalidColorSpace`]
+    InvalidColorSpace,
+    /// See [`Error::InvalidImageDimensions`]
+    InvalidImageDimensions,
+    /// See [`Error::DimensionOverflow`]
+    DimensionOverflow,
+    /// See [`Error::InvalidImageLength`]
+    InvalidImageLength,
+    /// See [`Error::InvalidStride`]
+    InvalidStride,
+    /// See [`Error::InvalidChannelMap`]
+    InvalidChannelMap,
+    /// See [`Error::OutputBufferTooSmall`]
+    OutputBufferTooSmall,
+    /// See [`Error::UnexpectedBufferEnd`]
+    UnexpectedBufferEnd,
+    /// See [`Error::UnexpectedStreamEnd`]
+    UnexpectedStreamEnd,
+    /// See [`Error::InvalidOp`]
+    InvalidOp,
+    /// See [`Error::InvalidPadding`]
+    InvalidPadding,
+    /// See [`Error::InvalidRunLength`]
+    InvalidRunLength,
+    /// See [`Error::TrailingBytes`]
+    TrailingBytes,
+    /// See [`Error::DecodingStarted`]
+    DecodingStarted,
+    /// See [`Error::InvalidRegion`]
+    InvalidRegion,
+    /// Generic I/O error from the wrapped reader/writer
+    Io,
+}
+
+impl Error {
+    /// Returns the category of this error.
+    ///
+    /// For errors wrapped in [`Error::WithHeader`], this is the category of the wrapped error.
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            Self::InvalidMagic { .. } => ErrorKind::InvalidMagic,
+            Self::InvalidChannels { .. } => ErrorKind::InvalidChannels,
+            Self::InvalidColorSpace { .. } => ErrorKind::InvalidColorSpace,
+            Self::InvalidImageDimensions { .. } => ErrorKind::InvalidImageDimensions,
+            Self::DimensionOverflow { .. } => ErrorKind::DimensionOverflow,
+            Self::InvalidImageLength { .. } => ErrorKind::InvalidImageLength,
+            Self::InvalidStride { .. } => ErrorKind::InvalidStride,
+            Self::InvalidChannelMap { .. } => ErrorKind::InvalidChannelMap,
+            Self::OutputBufferTooSmall { .. } => ErrorKind::OutputBufferTooSmall,
+            Self::UnexpectedBufferEnd => ErrorKind::UnexpectedBufferEnd,
+            Self::UnexpectedStreamEnd => ErrorKind::UnexpectedStreamEnd,
+            Self::InvalidOp { .. } => ErrorKind::InvalidOp,
+            Self::InvalidPadding => ErrorKind::InvalidPadding,
+            Self::InvalidRunLength { .. } => ErrorKind::InvalidRunLength,
+            Self::TrailingBytes { .. } => ErrorKind::TrailingBytes,
+            Self::DecodingStarted { .. } => ErrorKind::DecodingStarted,
+            Self::InvalidRegion { .. } => ErrorKind::InvalidRegion,
+            #[cfg(feature = "std")]
+            Self::IoError(_) | Self::PartialWrite { .. } => ErrorKind::Io,
+            #[cfg(any(feature = "std", feature = "alloc"))]
+            Self::WithHeader { ref source, .. } => source.kind(),
+        }
+    }
+
+    /// Returns true if the input ended before decoding was finished.
+    ///
+    /// Truncated input may decode fine if retried with more data, as opposed to
+    /// corrupt input (invalid ops, invalid padding) which will never decode.
+    pub fn is_truncated(&self) -> bool {
+        matches!(self.kind(), ErrorKind::UnexpectedBufferEnd | ErrorKind::UnexpectedStreamEnd)
+    }
+
+    /// Returns the header of the image being decoded when the error occurred, if known.
+    ///
+    /// This is only available if `alloc` or `std` features are enabled.
+    pub const fn header(&self) -> Option<&Header> {
+        match *self {
+            #[cfg(any(feature = "std", feature = "alloc"))]
+            Self::WithHeader { ref header, .. } => Some(header),
+            _ => None,
+        }
+    }
+
+    /// Wraps a writer error, reporting the number of bytes written if there were any.
+    #[cfg(feature = "std")]
+    pub(crate) const fn write_failed(n_written: usize, source: std::io::Error) -> Self {
+        if n_written == 0 {
+            Self::IoError(source)
+        } else {
+            Self::PartialWrite { n_written, source }
+        }
+    }
+
+    /// Attaches the image header to errors caused by the encoded stream data, i.e. truncated
+    /// or corrupt input; other errors are returned as is, as are all errors without `alloc`
+    /// or `std` features.
+    #[allow(clippy::missing_const_for_fn, unused_variables)]
+    pub(crate) fn with_header(self, header: Header) -> Self {
+        match self {
+            #[cfg(any(feature = "std", feature = "alloc"))]
+            Self::UnexpectedBufferEnd
+            | Self::UnexpectedStreamEnd
+            | Self::InvalidOp { .. }
+            | Self::InvalidPadding
+            | Self::InvalidRunLength { .. }
+            | Self::TrailingBytes { .. } => Self::WithHeader { header, source: Box::new(self) },
+            _ => self,
+        }
+    }
 }
 
 /// Alias for [`Result`](std::result::Result) with the error type of [`Error`].
@@ -45,28 +196,75 @@ impl Display for Error {
             Self::InvalidImageDimensions { width, height } => {
                 write!(f, "invalid image dimensions: {width}x{height}")
             }
+            Self::DimensionOverflow { width, height } => {
+                write!(f, "image dimensions don't fit into u32: {width}x{height}")
+            }
             Self::InvalidImageLength { size, width, height } => {
                 write!(f, "invalid image length: {size} bytes for {width}x{height}")
             }
+            Self::InvalidStride { stride, min_stride } => {
+                write!(f, "invalid stride: {stride} (must be at least {min_stride})")
+            }
+            Self::InvalidChannelMap { bytes_per_pixel, offset } => {
+                write!(
+                    f,
+                    "invalid channel map: byte {offset} for {bytes_per_pixel} bytes per pixel"
+                )
+            }
             Self::OutputBufferTooSmall { size, required } => {
                 write!(f, "output buffer size too small: {size} (required: {required})")
             }
             Self::UnexpectedBufferEnd => {
                 write!(f, "unexpected input buffer end while decoding")
             }
+            Self::UnexpectedStreamEnd => {
+                write!(f, "unexpected end of stream while decoding")
+            }
+            Self::InvalidOp { op } => {
+                write!(f, "invalid op encountered while decoding: {op:#04x}")
+            }
             Self::InvalidPadding => {
                 write!(f, "invalid padding (stream end marker mismatch)")
             }
+            Self::InvalidRunLength { run, pixels_left } => {
+                write!(f, "invalid run length: {run} (only {pixels_left} pixels left)")
+            }
+            Self::TrailingBytes { n_bytes } => {
+                write!(f, "trailing bytes after stream end marker: {n_bytes}")
+            }
+            Self::DecodingStarted { pixel_pos } => {
+                write!(f, "can't change output channels after decoding {pixel_pos} pixels")
+            }
+            Self::InvalidRegion { x, y, width, height } => {
+                write!(f, "invalid region to decode: {width}x{height} at ({x}, {y})")
+            }
             #[cfg(feature = "std")]
             Self::IoError(ref err) => {
                 write!(f, "i/o error: {err}")
             }
+            #[cfg(feature = "std")]
+            Self::PartialWrite { n_written, ref source } => {
+                write!(f, "i/o error after writing {n_written} bytes: {source}")
+            }
+            #[cfg(any(feature = "std", feature = "alloc"))]
+            Self::WithHeader { ref header, ref source } => {
+                let (w, h, c) = (header.width, header.height, header.channels.as_u8());
+                write!(f, "{source} (while decoding {w}x{h}:{c} image)")
+            }
         }
     }
 }
 
 #[cfg(feature = "std")]
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            Self::IoError(ref err) | Self::PartialWrite { source: ref err, .. } => Some(err),
+            Self::WithHeader { ref source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 impl From<Infallible> for Error {
     fn from(_: Infallible) -> Self {
@@ -77,6 +275,10 @@ impl From<Infallible> for Error {
 #[cfg(feature = "std")]
 impl From<std::io::Error> for Error {
     fn from(err: std::io::Error) -> Self {
-        Self::IoError(err)
+        if err.kind() == std::io::ErrorKind::UnexpectedEof {
+            Self::UnexpectedStreamEnd
+        } else {
+            Self::IoError(err)
+        }
     }
 }
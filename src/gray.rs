@@ -0,0 +1,297 @@
+//! Two-channel (gray + alpha) extension of the QOI format.
+//!
+//! This is **not** part of the QOI specification: images are stored with the `channels`
+//! header byte set to 2 ([`QOI_GRAY_ALPHA_CHANNELS`]), so conforming decoders (including
+//! [`Decoder`](crate::Decoder)) reject them with [`Error::InvalidChannels`]. The header
+//! layout, the end marker, the index hash (that of the equivalent RGBA pixel with R = G = B)
+//! and the `INDEX` and `RUN` ops are the same as in regular QOI images; the rest of the ops
+//! are adapted to a single luma channel:
+//!
+//! | Op           | Encoding                     | Meaning                                 |
+//! |--------------|------------------------------|-----------------------------------------|
+//! | `DIFF`       | `01dddddd`                   | luma difference -32..=31 (bias 32)      |
+//! | `LUMA`       | `10dddddd aaaaaaaa`          | luma difference as above, alpha delta   |
+//! | `GRAY`       | `11111110 yyyyyyyy`          | new luma, alpha unchanged               |
+//! | `GRAY_ALPHA` | `11111111 yyyyyyyy aaaaaaaa` | new luma and alpha                      |
+//!
+//! The alpha delta in `LUMA` wraps around, so any alpha change can be encoded this way.
+//! Pixel data is stored as tightly packed `[gray, alpha]` pairs; see [`gray_alpha_to_rgba`]
+//! and [`rgba_to_gray_alpha`] for conversions to and from RGBA.
+
+#[cfg(any(feature = "std", feature = "alloc"))]
+use alloc::{vec, vec::Vec};
+
+use crate::consts::{
+    QOI_HEADER_SIZE, QOI_MAGIC, QOI_MASK_2, QOI_OP_DIFF, QOI_OP_INDEX, QOI_OP_LUMA, QOI_OP_RUN,
+    QOI_PADDING, QOI_PADDING_SIZE,
+};
+use crate::convert::map_pixels;
+use crate::encode::encode_max_len_impl;
+use crate::error::{Error, Result};
+use crate::header::Header;
+use crate::types::{Channels, ColorSpace};
+use crate::utils::{unlikely, BytesMut, Writer};
+
+/// Value of the `channels` header byte for gray + alpha images.
+pub const QOI_GRAY_ALPHA_CHANNELS: u8 = 2;
+/// Op tag for `GRAY`, followed by one byte of luma.
+pub const QOI_OP_GRAY: u8 = 0xfe; // 11111110
+/// Op tag for `GRAY_ALPHA`, followed by one byte of luma and one byte of alpha.
+pub const QOI_OP_GRAY_ALPHA: u8 = 0xff; // 11111111
+
+const N_CHANNELS: usize = QOI_GRAY_ALPHA_CHANNELS as usize;
+
+/// Header of a gray + alpha image: dimensions and color space.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GrayAlphaHeader {
+    /// Image width in pixels
+    pub width: u32,
+    /// Image height in pixels
+    pub height: u32,
+    /// Color space (informative field, doesn't affect encoding)
+    pub colorspace: ColorSpace,
+}
+
+impl GrayAlphaHeader {
+    /// Creates a new header and validates image dimensions (same limits as for [`Header`]).
+    #[inline]
+    pub fn try_new(width: u32, height: u32, colorspace: ColorSpace) -> Result<Self> {
+        Header::try_new(width, height, Channels::Rgba, colorspace)?;
+        Ok(Self { width, height, colorspace })
+    }
+
+    /// Creates a new header with modified color space.
+    #[inline]
+    pub const fn with_colorspace(mut self, colorspace: ColorSpace) -> Self {
+        self.colorspace = colorspace;
+        self
+    }
+
+    /// Serializes the header into a 14-byte array, exactly as stored in an image.
+    #[inline]
+    pub const fn encode(&self) -> [u8; QOI_HEADER_SIZE] {
+        let (width, height, colorspace) = (self.width, self.height, self.colorspace);
+        let mut out = Header { width, height, channels: Channels::Rgba, colorspace }.encode();
+        out[12] = QOI_GRAY_ALPHA_CHANNELS;
+        out
+    }
+
+    /// Deserializes the header from a byte array and validates it.
+    ///
+    /// Only the first 14 bytes are read, anything past that is ignored.
+    #[inline]
+    pub fn decode(data: impl AsRef<[u8]>) -> Result<Self> {
+        let data = data.as_ref();
+        if unlikely(data.len() < QOI_HEADER_SIZE) {
+            return Err(Error::UnexpectedBufferEnd);
+        }
+        let word = |i: usize| u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        let magic = word(0);
+        if unlikely(magic != QOI_MAGIC) {
+            return Err(Error::InvalidMagic { magic });
+        }
+        if unlikely(data[12] != QOI_GRAY_ALPHA_CHANNELS) {
+            return Err(Error::InvalidChannels { channels: data[12] });
+        }
+        Self::try_new(word(4), word(8), data[13].try_into()?)
+    }
+
+    /// Returns a number of pixels in the image.
+    #[inline]
+    pub const fn n_pixels(&self) -> usize {
+        (self.width as usize).saturating_mul(self.height as usize)
+    }
+
+    /// Returns the total number of bytes in the raw pixel array.
+    #[inline]
+    pub const fn n_bytes(&self) -> usize {
+        self.n_pixels() * N_CHANNELS
+    }
+
+    /// The maximum number of bytes the encoded image will take.
+    #[inline]
+    pub const fn encode_max_len(&self) -> usize {
+        encode_max_len_impl(self.width, self.height, QOI_GRAY_ALPHA_CHANNELS)
+    }
+}
+
+#[inline]
+const fn hash_index(px: [u8; 2]) -> u8 {
+    // same as for an RGBA pixel with r = g = b = y: (3 + 5 + 7) * y + 11 * a
+    px[0].wrapping_mul(15).wrapping_add(px[1].wrapping_mul(11)) & 63
+}
+
+fn encode_impl(mut buf: BytesMut, data: &[u8]) -> usize {
+    let capacity = buf.capacity();
+    let mut index = [[0_u8; 2]; 64];
+    let mut px_prev = [0_u8, 0xff];
+    let mut run = 0_u8;
+    let n_pixels = data.len() / N_CHANNELS;
+    for (i, px) in data.chunks_exact(N_CHANNELS).enumerate() {
+        let px = [px[0], px[1]];
+        if px == px_prev {
+            run += 1;
+            if run == 62 || i + 1 == n_pixels {
+                buf = buf.write_one(QOI_OP_RUN | (run - 1));
+                run = 0;
+            }
+            continue;
+        }
+        if run != 0 {
+            buf = buf.write_one(QOI_OP_RUN | (run - 1));
+            run = 0;
+        }
+        let hash = hash_index(px);
+        if index[hash as usize] == px {
+            buf = buf.write_one(QOI_OP_INDEX | hash);
+        } else {
+            index[hash as usize] = px;
+            let dy = px[0].wrapping_sub(px_prev[0]).wrapping_add(32);
+            buf = match (dy < 64, px[1] == px_prev[1]) {
+                (true, true) => buf.write_one(QOI_OP_DIFF | dy),
+                (true, false) => {
+                    buf.write_many(&[QOI_OP_LUMA | dy, px[1].wrapping_sub(px_prev[1])])
+                }
+                (false, true) => buf.write_many(&[QOI_OP_GRAY, px[0]]),
+                (false, false) => buf.write_many(&[QOI_OP_GRAY_ALPHA, px[0], px[1]]),
+            };
+        }
+        px_prev = px;
+    }
+    buf = buf.write_many(&QOI_PADDING);
+    capacity - buf.capacity()
+}
+
+/// Encodes a gray + alpha image into a pre-allocated buffer.
+///
+/// The minimum size of the buffer can be found via [`GrayAlphaHeader::encode_max_len`].
+/// Returns the total number of bytes written.
+pub fn encode_to_buf(
+    mut buf: impl AsMut<[u8]>, data: impl AsRef<[u8]>, header: &GrayAlphaHeader,
+) -> Result<usize> {
+    let (buf, data) = (buf.as_mut(), data.as_ref());
+    if unlikely(data.len() != header.n_bytes()) {
+        let (size, width, height) = (data.len(), header.width, header.height);
+        return Err(Error::InvalidImageLength { size, width, height });
+    }
+    let required = header.encode_max_len();
+    if unlikely(buf.len() < required) {
+        return Err(Error::OutputBufferTooSmall { size: buf.len(), required });
+    }
+    let (head, tail) = buf.split_at_mut(QOI_HEADER_SIZE);
+    head.copy_from_slice(&header.encode());
+    Ok(QOI_HEADER_SIZE + encode_impl(BytesMut::new(tail), data))
+}
+
+/// Encodes a gray + alpha image into a newly allocated vector.
+#[cfg(any(feature = "std", feature = "alloc"))]
+pub fn encode_to_vec(data: impl AsRef<[u8]>, header: &GrayAlphaHeader) -> Result<Vec<u8>> {
+    let mut out = vec![0; header.encode_max_len()];
+    let size = encode_to_buf(&mut out, data, header)?;
+    out.truncate(size);
+    Ok(out)
+}
+
+struct Bytes<'a>(&'a [u8]);
+
+impl Bytes<'_> {
+    #[inline]
+    fn read<const N: usize>(&mut self) -> Result<[u8; N]> {
+        if unlikely(self.0.len() < N) {
+            return Err(Error::UnexpectedBufferEnd);
+        }
+        let (head, tail) = self.0.split_at(N);
+        self.0 = tail;
+        let mut out = [0; N];
+        out.copy_from_slice(head);
+        Ok(out)
+    }
+}
+
+fn decode_impl(out: &mut [u8], data: &mut Bytes) -> Result<()> {
+    let mut index = [[0_u8; 2]; 64];
+    let mut px = [0_u8, 0xff];
+    let mut run = 0_u8;
+    for out in out.chunks_exact_mut(N_CHANNELS) {
+        if run != 0 {
+            run -= 1;
+        } else {
+            let [b1] = data.read()?;
+            match b1 {
+                QOI_OP_GRAY => px[0] = data.read::<1>()?[0],
+                QOI_OP_GRAY_ALPHA => px = data.read()?,
+                _ => match b1 & QOI_MASK_2 {
+                    QOI_OP_INDEX => px = index[b1 as usize],
+                    QOI_OP_DIFF => px[0] = px[0].wrapping_add(b1 & 0x3f).wrapping_sub(32),
+                    QOI_OP_LUMA => {
+                        let [da] = data.read()?;
+                        px = [
+                            px[0].wrapping_add(b1 & 0x3f).wrapping_sub(32),
+                            px[1].wrapping_add(da),
+                        ];
+                    }
+                    _ => run = b1 & 0x3f,
+                },
+            }
+            index[hash_index(px) as usize] = px;
+        }
+        out.copy_from_slice(&px);
+    }
+    if unlikely(data.read::<QOI_PADDING_SIZE>()? != QOI_PADDING) {
+        return Err(Error::InvalidPadding);
+    }
+    Ok(())
+}
+
+/// Decodes the header of a gray + alpha image.
+#[inline]
+pub fn decode_header(data: impl AsRef<[u8]>) -> Result<GrayAlphaHeader> {
+    GrayAlphaHeader::decode(data)
+}
+
+/// Decodes a gray + alpha image into a pre-allocated buffer.
+///
+/// The minimum size of the buffer can be found via [`GrayAlphaHeader::n_bytes`]; the
+/// buffer may be larger than that, in which case the rest of it is left untouched.
+/// Runs extending past the last pixel are clamped and any bytes following the end marker
+/// are ignored, same as in non-strict [`Decoder`](crate::Decoder) mode.
+pub fn decode_to_buf(mut buf: impl AsMut<[u8]>, data: impl AsRef<[u8]>) -> Result<GrayAlphaHeader> {
+    let (buf, data) = (buf.as_mut(), data.as_ref());
+    let header = GrayAlphaHeader::decode(data)?;
+    let required = header.n_bytes();
+    if unlikely(buf.len() < required) {
+        return Err(Error::OutputBufferTooSmall { size: buf.len(), required });
+    }
+    decode_impl(&mut buf[..required], &mut Bytes(&data[QOI_HEADER_SIZE..]))?;
+    Ok(header)
+}
+
+/// Decodes a gray + alpha image into a newly allocated vector.
+#[cfg(any(feature = "std", feature = "alloc"))]
+pub fn decode_to_vec(data: impl AsRef<[u8]>) -> Result<(GrayAlphaHeader, Vec<u8>)> {
+    let data = data.as_ref();
+    let header = GrayAlphaHeader::decode(data)?;
+    let mut out = vec![0; header.n_bytes()];
+    decode_impl(&mut out, &mut Bytes(&data[QOI_HEADER_SIZE..]))?;
+    Ok((header, out))
+}
+
+/// Expands gray + alpha pixels to RGBA and returns the number of pixels converted.
+///
+/// Follows the same conventions as the functions in [`convert`](crate::convert).
+pub fn gray_alpha_to_rgba(src: &[u8], dst: &mut [u8]) -> Result<usize> {
+    map_pixels::<2, 4>(src, dst, |p| [p[0], p[0], p[0], p[1]])
+}
+
+/// Converts RGBA pixels to gray + alpha and returns the number of pixels converted.
+///
+/// Luma is computed with BT.601 weights (`0.299 R + 0.587 G + 0.114 B`, rounded), so
+/// pixels with R = G = B are converted losslessly. Follows the same conventions as the
+/// functions in [`convert`](crate::convert).
+#[allow(clippy::cast_possible_truncation)]
+pub fn rgba_to_gray_alpha(src: &[u8], dst: &mut [u8]) -> Result<usize> {
+    map_pixels::<4, 2>(src, dst, |p| {
+        let [r, g, b] = [p[0], p[1], p[2]].map(u32::from);
+        [((77 * r + 150 * g + 29 * b + 128) >> 8) as u8, p[3]]
+    })
+}
@@ -1,9 +1,10 @@
 use core::convert::TryInto;
+use core::fmt::{self, Display};
 
 use bytemuck::cast_slice;
 
 use crate::consts::{QOI_HEADER_SIZE, QOI_MAGIC, QOI_PIXELS_MAX};
-use crate::encode_max_len;
+use crate::encoded_size_limit;
 use crate::error::{Error, Result};
 use crate::types::{Channels, ColorSpace};
 use crate::utils::unlikely;
@@ -38,19 +39,80 @@ impl Default for Header {
     }
 }
 
+/// Options controlling which headers are accepted when decoding.
+///
+/// By default, only headers that are fully valid according to the spec are accepted.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct HeaderOptions {
+    lenient_colorspace: bool,
+    allow_empty: bool,
+}
+
+impl HeaderOptions {
+    /// Creates the default (strict) header options.
+    #[inline]
+    pub const fn new() -> Self {
+        Self { lenient_colorspace: false, allow_empty: false }
+    }
+
+    /// Accept color space bytes other than 0 and 1, see [`Header::decode_lenient`].
+    #[inline]
+    pub const fn with_lenient_colorspace(mut self, lenient_colorspace: bool) -> Self {
+        self.lenient_colorspace = lenient_colorspace;
+        self
+    }
+
+    /// Accept images with zero width or height (which contain no pixels).
+    #[inline]
+    pub const fn with_allow_empty(mut self, allow_empty: bool) -> Self {
+        self.allow_empty = allow_empty;
+        self
+    }
+
+    /// Returns true if non-standard color space bytes are accepted.
+    #[inline]
+    pub const fn lenient_colorspace(&self) -> bool {
+        self.lenient_colorspace
+    }
+
+    /// Returns true if images with zero width or height are accepted.
+    #[inline]
+    pub const fn allow_empty(&self) -> bool {
+        self.allow_empty
+    }
+}
+
 impl Header {
     /// Creates a new header and validates image dimensions.
     #[inline]
     pub const fn try_new(
         width: u32, height: u32, channels: Channels, colorspace: ColorSpace,
+    ) -> Result<Self> {
+        Self::try_new_impl(width, height, channels, colorspace, false)
+    }
+
+    #[inline]
+    pub(crate) const fn try_new_impl(
+        width: u32, height: u32, channels: Channels, colorspace: ColorSpace, allow_empty: bool,
     ) -> Result<Self> {
         let n_pixels = (width as usize).saturating_mul(height as usize);
-        if unlikely(n_pixels == 0 || n_pixels > QOI_PIXELS_MAX) {
+        if unlikely((n_pixels == 0 && !allow_empty) || n_pixels > QOI_PIXELS_MAX) {
             return Err(Error::InvalidImageDimensions { width, height });
         }
         Ok(Self { width, height, channels, colorspace })
     }
 
+    /// Same as [`Header::try_new`], but takes `usize` dimensions.
+    ///
+    /// Fails with [`Error::DimensionOverflow`] if either dimension doesn't fit into `u32`.
+    #[inline]
+    pub fn try_new_usize(
+        width: usize, height: usize, channels: Channels, colorspace: ColorSpace,
+    ) -> Result<Self> {
+        let (width, height) = dims_to_u32(width, height)?;
+        Self::try_new(width, height, channels, colorspace)
+    }
+
     /// Creates a new header with modified channels.
     #[inline]
     pub const fn with_channels(mut self, channels: Channels) -> Self {
@@ -65,22 +127,51 @@ impl Header {
         self
     }
 
-    /// Serializes the header into a bytes array.
+    /// Serializes the header into a 14-byte array, exactly as stored in a QOI image.
     #[inline]
-    pub(crate) fn encode(&self) -> [u8; QOI_HEADER_SIZE] {
+    pub const fn encode(&self) -> [u8; QOI_HEADER_SIZE] {
         let mut out = [0; QOI_HEADER_SIZE];
-        out[..4].copy_from_slice(&QOI_MAGIC.to_be_bytes());
-        out[4..8].copy_from_slice(&self.width.to_be_bytes());
-        out[8..12].copy_from_slice(&self.height.to_be_bytes());
-        out[12] = self.channels.into();
-        out[13] = self.colorspace.into();
+        let (m, w, h) =
+            (QOI_MAGIC.to_be_bytes(), self.width.to_be_bytes(), self.height.to_be_bytes());
+        let mut i = 0;
+        while i < 4 {
+            out[i] = m[i];
+            out[4 + i] = w[i];
+            out[8 + i] = h[i];
+            i += 1;
+        }
+        out[12] = self.channels.as_u8();
+        out[13] = self.colorspace.as_u8();
         out
     }
 
-    /// Deserializes the header from a byte array.
+    /// Deserializes the header from a byte array and validates it.
+    ///
+    /// Only the first 14 bytes are read, anything past that is ignored.
+    #[inline]
+    pub fn decode(data: impl AsRef<[u8]>) -> Result<Self> {
+        Self::decode_with_options(data, HeaderOptions::new())
+    }
+
+    /// Same as [`Header::decode`], but accepts any color space byte.
+    ///
+    /// Values other than 0 and 1 are decoded as [`ColorSpace::Srgb`] instead of failing
+    /// with [`Error::InvalidColorSpace`]; all other fields are validated as usual. The raw
+    /// byte is available via [`Decoder::colorspace_raw`](crate::Decoder::colorspace_raw).
+    #[inline]
+    pub fn decode_lenient(data: impl AsRef<[u8]>) -> Result<Self> {
+        Self::decode_with_options(data, HeaderOptions::new().with_lenient_colorspace(true))
+    }
+
+    /// Same as [`Header::decode`], but with custom validation options.
+    #[inline]
+    pub fn decode_with_options(data: impl AsRef<[u8]>, options: HeaderOptions) -> Result<Self> {
+        Self::decode_raw(data.as_ref(), options).map(|(header, _)| header)
+    }
+
+    /// Decodes the header and also returns the raw color space byte.
     #[inline]
-    pub(crate) fn decode(data: impl AsRef<[u8]>) -> Result<Self> {
-        let data = data.as_ref();
+    pub(crate) fn decode_raw(data: &[u8], options: HeaderOptions) -> Result<(Self, u8)> {
         if unlikely(data.len() < QOI_HEADER_SIZE) {
             return Err(Error::UnexpectedBufferEnd);
         }
@@ -89,11 +180,15 @@ impl Header {
         let width = u32::from_be_bytes(v[1]);
         let height = u32::from_be_bytes(v[2]);
         let channels = data[12].try_into()?;
-        let colorspace = data[13].try_into()?;
+        let colorspace = match data[13].try_into() {
+            Err(Error::InvalidColorSpace { .. }) if options.lenient_colorspace => ColorSpace::Srgb,
+            colorspace => colorspace?,
+        };
         if unlikely(magic != QOI_MAGIC) {
             return Err(Error::InvalidMagic { magic });
         }
-        Self::try_new(width, height, channels, colorspace)
+        let header = Self::try_new_impl(width, height, channels, colorspace, options.allow_empty)?;
+        Ok((header, data[13]))
     }
 
     /// Returns a number of pixels in the image.
@@ -114,7 +209,66 @@ impl Header {
     ///
     /// Can be used to pre-allocate the buffer to encode the image into.
     #[inline]
-    pub fn encode_max_len(&self) -> usize {
-        encode_max_len(self.width, self.height, self.channels)
+    pub const fn encode_max_len(&self) -> usize {
+        encoded_size_limit(self.width, self.height, self.channels)
+    }
+
+    /// Returns the number of pixels in the image in millions.
+    #[inline]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn megapixels(&self) -> f64 {
+        self.n_pixels() as f64 / 1e6
+    }
+
+    /// Returns the size of the raw pixel array, formatted in human-readable units when displayed.
+    #[inline]
+    pub const fn n_bytes_human(&self) -> HumanBytes {
+        HumanBytes(self.n_bytes())
+    }
+}
+
+/// Converts `usize` image dimensions to `u32`, failing with [`Error::DimensionOverflow`].
+#[inline]
+#[allow(clippy::cast_possible_truncation)]
+pub const fn dims_to_u32(width: usize, height: usize) -> Result<(u32, u32)> {
+    if unlikely(width > u32::MAX as usize || height > u32::MAX as usize) {
+        return Err(Error::DimensionOverflow { width, height });
+    }
+    Ok((width as u32, height as u32))
+}
+
+/// Formats the header as e.g. `640x480 RGBA sRGB (0.3 MP)`.
+impl Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}x{} {} {} ({:.1} MP)",
+            self.width,
+            self.height,
+            self.channels,
+            self.colorspace,
+            self.megapixels()
+        )
+    }
+}
+
+/// Byte count that is displayed in human-readable binary units, e.g. `1.5 MiB`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HumanBytes(pub usize);
+
+impl Display for HumanBytes {
+    #[allow(clippy::cast_precision_loss)]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+        let mut value = self.0 as f64 / 1024.;
+        let mut unit = 0;
+        while value >= 1024. && unit < UNITS.len() - 1 {
+            value /= 1024.;
+            unit += 1;
+        }
+        write!(f, "{:.1} {}", value, UNITS[unit])
     }
 }
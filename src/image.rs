@@ -0,0 +1,145 @@
+//! Integration with the [`image`](https://docs.rs/image) crate.
+//!
+//! [`Decoder`] implements [`ImageDecoder`], so any decoder (from a slice or from a stream,
+//! with or without channel conversion) can be passed to `image`-based code, e.g. to load a
+//! `DynamicImage`:
+//!
+//! ```rust
+//! let decoder = qoi::Decoder::from_stream(file)?.with_channels(qoi::Channels::Rgba)?;
+//! let img = image::DynamicImage::from_decoder(decoder)?;
+//! ```
+//!
+//! For the other direction, [`WriteEncoder`] implements [`ImageEncoder`] on top of a
+//! writer, e.g. to save an `RgbaImage` via `img.write_with_encoder(WriteEncoder::new(file))`.
+//! QOI errors are reported as [`ImageError::Decoding`] and [`ImageError::Encoding`] with
+//! the original [`Error`] as the source (plain I/O errors as [`ImageError::IoError`]).
+
+use std::io::Write;
+
+use image::error::{
+    DecodingError, EncodingError, ImageFormatHint, UnsupportedError, UnsupportedErrorKind,
+};
+use image::{
+    ColorType, ExtendedColorType, ImageDecoder, ImageEncoder, ImageError, ImageFormat, ImageResult,
+};
+
+use crate::decode::{Decoder, Reader};
+use crate::encode::EncoderBuilder;
+use crate::error::Error;
+use crate::source::SourceChannels;
+use crate::types::{Channels, ColorSpace};
+
+const fn format_hint() -> ImageFormatHint {
+    ImageFormatHint::Exact(ImageFormat::Qoi)
+}
+
+fn decoding_error(err: Error) -> ImageError {
+    match err {
+        Error::IoError(err) => ImageError::IoError(err),
+        err => ImageError::Decoding(DecodingError::new(format_hint(), err)),
+    }
+}
+
+fn encoding_error(err: Error) -> ImageError {
+    match err {
+        Error::IoError(err) => ImageError::IoError(err),
+        err => ImageError::Encoding(EncodingError::new(format_hint(), err)),
+    }
+}
+
+const fn color_type(channels: Channels) -> ColorType {
+    match channels {
+        Channels::Rgb => ColorType::Rgb8,
+        Channels::Rgba => ColorType::Rgba8,
+    }
+}
+
+impl<R: Reader> ImageDecoder for Decoder<R> {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.header().width, self.header().height)
+    }
+
+    /// Returns the color type of the decoded image, which follows [`Decoder::channels`].
+    fn color_type(&self) -> ColorType {
+        color_type(self.channels())
+    }
+
+    fn original_color_type(&self) -> ExtendedColorType {
+        color_type(self.header().channels).into()
+    }
+
+    /// Decodes the whole image; the decoder must not have started decoding yet.
+    ///
+    /// The pixels are always written as tightly packed RGB(A) to match the color type,
+    /// regardless of [`Decoder::with_target_channels`] and [`Decoder::with_output_stride`].
+    fn read_image(self, buf: &mut [u8]) -> ImageResult<()> {
+        let pixel_pos = self.header().n_pixels() - self.pixels_left();
+        if pixel_pos != 0 {
+            return Err(decoding_error(Error::DecodingStarted { pixel_pos }));
+        }
+        let channels = self.channels();
+        let row_len = self.header().width as usize * channels.as_u8() as usize;
+        let decoder = self.with_channels(channels).map_err(decoding_error)?;
+        let mut decoder = decoder.with_output_stride(row_len);
+        decoder.decode_to_buf(buf).map(|_| ()).map_err(decoding_error)
+    }
+
+    fn read_image_boxed(self: Box<Self>, buf: &mut [u8]) -> ImageResult<()> {
+        (*self).read_image(buf)
+    }
+}
+
+/// Encoder that writes QOI images to a writer, for use with the `image` crate via
+/// [`ImageEncoder`].
+///
+/// Supported color types are [`ExtendedColorType::Rgb8`] and [`ExtendedColorType::Rgba8`];
+/// the number of channels in the encoded image matches the color type.
+#[derive(Debug)]
+pub struct WriteEncoder<W> {
+    writer: W,
+    colorspace: ColorSpace,
+}
+
+impl<W: Write> WriteEncoder<W> {
+    /// Creates a new encoder writing to the given writer.
+    #[inline]
+    pub const fn new(writer: W) -> Self {
+        Self { writer, colorspace: ColorSpace::Srgb }
+    }
+
+    /// Returns a new encoder with modified color space.
+    #[inline]
+    pub const fn with_colorspace(mut self, colorspace: ColorSpace) -> Self {
+        self.colorspace = colorspace;
+        self
+    }
+}
+
+impl<W: Write> ImageEncoder for WriteEncoder<W> {
+    /// Encodes the image and flushes the writer.
+    ///
+    /// Unlike encoders in the `image` crate itself, a buffer whose length doesn't match the
+    /// dimensions and the color type results in an error rather than a panic.
+    fn write_image(
+        mut self, buf: &[u8], width: u32, height: u32, color_type: ExtendedColorType,
+    ) -> ImageResult<()> {
+        let source = match color_type {
+            ExtendedColorType::Rgb8 => SourceChannels::Rgb,
+            ExtendedColorType::Rgba8 => SourceChannels::Rgba,
+            _ => {
+                let kind = UnsupportedErrorKind::Color(color_type);
+                return Err(ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+                    format_hint(),
+                    kind,
+                )));
+            }
+        };
+        let encoder = EncoderBuilder::new(buf, width, height)
+            .with_source_channels(source)
+            .with_colorspace(self.colorspace)
+            .build()
+            .map_err(encoding_error)?;
+        encoder.with_flush(true).encode_to_stream(&mut self.writer).map_err(encoding_error)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+/// Returns the version of this crate, e.g. `"0.4.1"`.
+///
+/// Useful for host applications that load the codec dynamically (e.g. via bindings)
+/// and need to report or check which version they got.
+#[inline]
+pub const fn crate_version() -> &'static str {
+    env!("CARGO_PKG_VERSION")
+}
+
+/// Optional crate features that may or may not be compiled into this build.
+///
+/// Note: new variants may be added in the future, as new optional features are added.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[non_exhaustive]
+pub enum Feature {
+    /// `std`: `std::io` streams, `std::error::Error` and heap allocations
+    Std,
+    /// `alloc`: heap allocations without `std`
+    Alloc,
+    /// `gray-alpha`: non-standard two-channel format extension, see `qoi::gray`
+    GrayAlpha,
+    /// `anim`: non-standard animation container, see `qoi::anim`
+    Anim,
+    /// `reference`: byte-exact output compatible with the reference encoder
+    Reference,
+    /// `batch`: batch conversion of directory trees, see `qoi::batch`
+    Batch,
+    /// `rayon`: running parallel APIs on a caller-provided rayon thread pool
+    Rayon,
+    /// `sdl2`: decoding straight into SDL2 streaming textures, see `qoi::sdl`
+    Sdl2,
+    /// `tokio`: async decoding/encoding over tokio streams, see `qoi::AsyncDecoder`
+    Tokio,
+    /// `futures-io`: async decoding/encoding over `futures-io` streams, see `qoi::AsyncDecoder`
+    FuturesIo,
+    /// `image`: integration with the `image` crate, see `qoi::image`
+    Image,
+}
+
+impl Feature {
+    /// All optional features known to this version of the crate, enabled or not.
+    pub const ALL: &'static [Self] = &[
+        Self::Std,
+        Self::Alloc,
+        Self::GrayAlpha,
+        Self::Anim,
+        Self::Reference,
+        Self::Batch,
+        Self::Rayon,
+        Self::Sdl2,
+        Self::Tokio,
+        Self::FuturesIo,
+        Self::Image,
+    ];
+
+    /// Returns the name of the cargo feature, e.g. `"gray-alpha"`.
+    #[inline]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Std => "std",
+            Self::Alloc => "alloc",
+            Self::GrayAlpha => "gray-alpha",
+            Self::Anim => "anim",
+            Self::Reference => "reference",
+            Self::Batch => "batch",
+            Self::Rayon => "rayon",
+            Self::Sdl2 => "sdl2",
+            Self::Tokio => "tokio",
+            Self::FuturesIo => "futures-io",
+            Self::Image => "image",
+        }
+    }
+
+    /// Returns true if the feature is compiled into this build.
+    ///
+    /// Note that `std` implies heap allocations, so [`Feature::Alloc`] is reported as
+    /// enabled whenever [`Feature::Std`] is.
+    #[inline]
+    pub const fn is_enabled(self) -> bool {
+        match self {
+            Self::Std => cfg!(feature = "std"),
+            Self::Alloc => cfg!(any(feature = "alloc", feature = "std")),
+            Self::GrayAlpha => cfg!(feature = "gray-alpha"),
+            Self::Anim => cfg!(feature = "anim"),
+            Self::Reference => cfg!(feature = "reference"),
+            Self::Batch => cfg!(feature = "batch"),
+            Self::Rayon => cfg!(feature = "rayon"),
+            Self::Sdl2 => cfg!(feature = "sdl2"),
+            Self::Tokio => cfg!(feature = "tokio"),
+            Self::FuturesIo => cfg!(feature = "futures-io"),
+            Self::Image => cfg!(feature = "image"),
+        }
+    }
+
+    /// Returns the feature with the given cargo feature name, if it's known.
+    #[inline]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|feature| feature.name() == name)
+    }
+}
+
+/// Returns the names of non-standard format extensions supported by this build, e.g.
+/// `"gray-alpha"` (see `qoi::gray`).
+///
+/// Images using an extension can only be decoded by builds that support it; plain QOI
+/// images are always supported, so the list is empty for a build without extensions.
+#[inline]
+pub const fn supported_extensions() -> &'static [&'static str] {
+    &[
+        #[cfg(feature = "gray-alpha")]
+        "gray-alpha",
+        #[cfg(feature = "anim")]
+        "anim",
+    ]
+}
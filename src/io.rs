@@ -0,0 +1,746 @@
+//! The `std::io` layer: decoding from readers, encoding to writers and the streaming
+//! adapters built on top of them.
+//!
+//! The codec itself only works with slices and the internal `Reader`/`Writer` traits;
+//! everything here plugs [`Read`] and [`Write`] into those, so it's all gated behind
+//! the `std` feature in one place.
+
+use core::fmt::{self, Debug};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::vec::Vec;
+
+use bytemuck::{cast_slice_mut, Pod};
+
+use crate::consts::{
+    QOI_HEADER_SIZE, QOI_OP_DIFF, QOI_OP_INDEX, QOI_OP_LUMA, QOI_OP_RGB, QOI_OP_RGBA, QOI_OP_RUN,
+    QOI_PADDING, QOI_PADDING_SIZE,
+};
+use crate::decode::{check_run_len, Decoder, DecoderState, Reader};
+use crate::decode::{QOI_OP_DIFF_END, QOI_OP_INDEX_END, QOI_OP_LUMA_END, QOI_OP_RUN_END};
+use crate::encode::{encode_impl_all, EncodeState, Encoder, OwnedEncoder};
+use crate::encode::{MODE_DEFAULT, MODE_STORE};
+use crate::error::{Error, Result};
+use crate::header::{Header, HeaderOptions};
+use crate::pixel::{Pixel, SupportedChannels};
+use crate::types::Channels;
+use crate::utils::{cold, unlikely, Writer};
+
+/// Decode image headers from a sequence of readers, one header per reader.
+///
+/// Only the first 14 bytes of each reader are consumed; the rest of the stream is
+/// left untouched. No heap allocations are made per input, so this is suitable for
+/// quickly cataloguing large numbers of images.
+#[inline]
+pub fn scan_headers<I>(readers: I) -> impl Iterator<Item = Result<Header>>
+where
+    I: IntoIterator,
+    I::Item: Read,
+{
+    readers.into_iter().map(|mut reader| reader.decode_header())
+}
+
+/// Decode image headers from a sequence of file paths, one header per file.
+///
+/// Each file is opened, its first 14 bytes are read and then it's closed again.
+/// See [`scan_headers`] for details.
+#[inline]
+pub fn scan_header_files<I>(paths: I) -> impl Iterator<Item = Result<Header>>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    paths.into_iter().map(|path| File::open(path)?.decode_header())
+}
+
+#[inline]
+fn decode_impl_stream<R: Read, const N: usize, const RGBA: bool>(
+    data: &mut R, out: &mut [u8], strict: bool,
+) -> Result<usize>
+where
+    Pixel<N>: SupportedChannels,
+    [u8; N]: Pod,
+{
+    let mut pixels = cast_slice_mut::<_, [u8; N]>(out);
+
+    let mut index = [Pixel::<4>::new(); 256];
+    let mut px = Pixel::<N>::new().with_a(0xff);
+    let mut px_rgba: Pixel<4>;
+    let mut a = 0xff; // alpha has to be tracked separately when decoding RGBA into RGB
+    let mut n_read = 0;
+
+    while let [px_out, ptail @ ..] = pixels {
+        pixels = ptail;
+        let mut p = [0];
+        data.read_exact(&mut p)?;
+        n_read += 1;
+        let [b1] = p;
+        match b1 {
+            QOI_OP_INDEX..=QOI_OP_INDEX_END => {
+                px_rgba = index[b1 as usize];
+                px.update(px_rgba);
+                a = px_rgba.a();
+                *px_out = px.into();
+                continue;
+            }
+            QOI_OP_RGB => {
+                let mut p = [0; 3];
+                data.read_exact(&mut p)?;
+                n_read += 3;
+                px.update_rgb(p[0], p[1], p[2]);
+            }
+            QOI_OP_RGBA if RGBA => {
+                let mut p = [0; 4];
+                data.read_exact(&mut p)?;
+                n_read += 4;
+                px.update_rgba(p[0], p[1], p[2], p[3]);
+                a = p[3];
+            }
+            QOI_OP_RUN..=QOI_OP_RUN_END => {
+                *px_out = px.into();
+                let run = check_run_len((b1 & 0x3f) as usize, pixels.len(), strict)?;
+                let (phead, ptail) = pixels.split_at_mut(run); // can't panic
+                phead.fill(px.into());
+                pixels = ptail;
+                continue;
+            }
+            QOI_OP_DIFF..=QOI_OP_DIFF_END => {
+                px.update_diff(b1);
+            }
+            QOI_OP_LUMA..=QOI_OP_LUMA_END => {
+                let mut p = [0];
+                data.read_exact(&mut p)?;
+                n_read += 1;
+                let [b2] = p;
+                px.update_luma(b1, b2);
+            }
+            _ => {
+                cold();
+                return Err(Error::InvalidOp { op: b1 });
+            }
+        }
+
+        px_rgba = px.as_rgba(a);
+        index[px_rgba.hash_index() as usize] = px_rgba;
+        *px_out = px.into();
+    }
+
+    let mut p = [0_u8; QOI_PADDING_SIZE];
+    data.read_exact(&mut p)?;
+    if unlikely(p != QOI_PADDING) {
+        return Err(Error::InvalidPadding);
+    }
+
+    Ok(n_read + QOI_PADDING_SIZE)
+}
+
+#[inline]
+fn decode_impl_stream_all<R: Read>(
+    data: &mut R, out: &mut [u8], channels: u8, src_channels: u8, strict: bool,
+) -> Result<usize> {
+    match (channels, src_channels) {
+        (3, 3) => decode_impl_stream::<_, 3, false>(data, out, strict),
+        (3, 4) => decode_impl_stream::<_, 3, true>(data, out, strict),
+        (4, 3) => decode_impl_stream::<_, 4, false>(data, out, strict),
+        (4, 4) => decode_impl_stream::<_, 4, true>(data, out, strict),
+        _ => {
+            cold();
+            Err(Error::InvalidChannels { channels })
+        }
+    }
+}
+
+impl<R: Read> Reader for R {
+    #[inline]
+    fn decode_header_impl(&mut self, options: HeaderOptions) -> Result<(Header, u8)> {
+        let mut b = [0; QOI_HEADER_SIZE];
+        self.read_exact(&mut b)?;
+        Header::decode_raw(&b, options)
+    }
+
+    #[inline]
+    fn decode_image(
+        &mut self, out: &mut [u8], channels: u8, src_channels: u8, strict: bool,
+    ) -> Result<usize> {
+        decode_impl_stream_all(self, out, channels, src_channels, strict)
+    }
+
+    #[inline]
+    fn read_array<const K: usize>(&mut self) -> Result<[u8; K]> {
+        let mut out = [0; K];
+        self.read_exact(&mut out)?;
+        Ok(out)
+    }
+}
+
+impl<R: Read> Decoder<R> {
+    /// Creates a new decoder from a generic reader that implements [`Read`](std::io::Read).
+    ///
+    /// The header will be decoded immediately upon construction.
+    ///
+    /// Short reads and reads failing with [`ErrorKind::Interrupted`](std::io::ErrorKind) are
+    /// retried internally, so the reader may return as few bytes at a time as it likes; any
+    /// other I/O error (including `WouldBlock`) fails the decoding.
+    ///
+    /// Note: while it's possible to pass a `&[u8]` slice here since it implements `Read`, it
+    /// would be more efficient to use a specialized constructor instead: [`Decoder::new`].
+    #[inline]
+    pub fn from_stream(reader: R) -> Result<Self> {
+        Self::new_impl(reader, HeaderOptions::new())
+    }
+
+    /// Same as [`Decoder::from_stream`], but with custom header validation options.
+    #[inline]
+    pub fn from_stream_with_options(reader: R, options: HeaderOptions) -> Result<Self> {
+        Self::new_impl(reader, options)
+    }
+
+    /// Same as [`Decoder::new_body`], but reads the headerless image body from a stream.
+    #[inline]
+    pub fn from_stream_body(reader: R, header: Header) -> Result<Self> {
+        Self::new_body_impl(reader, header)
+    }
+
+    /// Returns an immutable reference to the underlying reader.
+    #[inline]
+    pub const fn reader(&self) -> &R {
+        &self.reader
+    }
+
+    /// Restores a decoder state previously taken via [`Decoder::checkpoint`].
+    ///
+    /// The reader is seeked relative to its current position, so the state must have been
+    /// taken from a decoder over the same image (the stream doesn't have to start at it).
+    #[inline]
+    #[allow(clippy::cast_possible_wrap)]
+    pub fn restore(&mut self, state: DecoderState) -> Result<()>
+    where
+        R: Seek,
+    {
+        let offset = state.byte_offset as i64 - self.state.byte_offset as i64;
+        self.reader.seek(SeekFrom::Current(offset))?;
+        self.state = state;
+        Ok(())
+    }
+
+    /// Consumes the decoder and returns the underlying reader back.
+    ///
+    /// Once the image has been fully decoded, the reader is positioned right after its
+    /// end marker; see also [`Decoder::bytes_consumed`].
+    #[inline]
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Consumes the decoder and returns the underlying reader back.
+    #[inline]
+    #[deprecated(since = "0.4.2", note = "use `Decoder::into_inner` instead")]
+    pub fn into_reader(self) -> R {
+        self.into_inner()
+    }
+}
+
+pub struct GenericWriter<W> {
+    writer: W,
+    n_written: usize,
+}
+
+impl<W: Write> GenericWriter<W> {
+    pub const fn new(writer: W) -> Self {
+        Self { writer, n_written: 0 }
+    }
+
+    /// Same as `write_all()`, but keeps track of the number of bytes written so far
+    /// so that it can be reported if the write fails midway.
+    fn write_all(&mut self, mut v: &[u8]) -> Result<()> {
+        while !v.is_empty() {
+            match self.writer.write(v) {
+                Ok(0) => {
+                    let source = io::ErrorKind::WriteZero.into();
+                    return Err(Error::write_failed(self.n_written, source));
+                }
+                Ok(n) => {
+                    self.n_written += n;
+                    v = &v[n..];
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(source) => {
+                    return Err(Error::write_failed(self.n_written, source));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Writer for GenericWriter<W> {
+    fn write_one(mut self, v: u8) -> Result<Self> {
+        self.write_all(&[v]).map(|()| self)
+    }
+
+    fn write_many(mut self, v: &[u8]) -> Result<Self> {
+        self.write_all(v).map(|()| self)
+    }
+
+    fn capacity(&self) -> usize {
+        usize::MAX - self.n_written
+    }
+}
+
+/// Same as `EncodeState::encode_pixels`, but un-premultiplies alpha first if the source
+/// pixels of the encoder have premultiplied alpha.
+#[inline]
+fn encode_source_pixels<W, I, const N: usize, const MODE: u8>(
+    state: &mut EncodeState<N>, out: W, pixels: I, enc: &Encoder,
+) -> Result<W>
+where
+    W: Writer,
+    I: Iterator<Item = Pixel<N>>,
+    Pixel<N>: SupportedChannels,
+    [u8; N]: Pod,
+{
+    let n_pixels = enc.header.n_pixels();
+    if N == 4 && enc.premultiplied {
+        state.encode_pixels::<_, _, MODE>(out, pixels.map(Pixel::unpremultiplied), n_pixels)
+    } else {
+        state.encode_pixels::<_, _, MODE>(out, pixels, n_pixels)
+    }
+}
+
+/// Encodes the next `count` pixels of the image, resuming from the given state.
+fn encode_resume<W: Writer, const N: usize, const MODE: u8>(
+    out: W, enc: &Encoder, state: &mut EncodeState<N>, count: usize,
+) -> Result<W>
+where
+    Pixel<N>: SupportedChannels,
+    [u8; N]: Pod,
+{
+    if let Some(planes) = enc.planes {
+        let pixels = planes.pixels::<N>(enc, state.pos).take(count);
+        return encode_source_pixels::<_, _, N, MODE>(state, out, pixels, enc);
+    }
+    let width = enc.header.width as usize;
+    let bpp = enc.source.bytes_per_pixel();
+    let row_len = width * bpp;
+    let (y, x) = (state.pos / width, state.pos % width);
+    let rows = enc.data.chunks(enc.stride.max(1)).skip(y).enumerate();
+    let pixels = rows
+        .flat_map(|(i, row)| row[if i == 0 { x * bpp } else { 0 }..row_len].chunks_exact(bpp))
+        .take(count);
+    if enc.source.is_identity() {
+        let read = |chunk: &[u8]| {
+            let mut px = Pixel::<N>::new();
+            px.read(chunk);
+            px
+        };
+        encode_source_pixels::<_, _, N, MODE>(state, out, pixels.map(read), enc)
+    } else {
+        let sources = enc.source.channel_map().sources();
+        let read = |chunk: &[u8]| {
+            let mut px = Pixel::<N>::new();
+            px.read_mapped(chunk, sources);
+            px
+        };
+        encode_source_pixels::<_, _, N, MODE>(state, out, pixels.map(read), enc)
+    }
+}
+
+/// The minimum number of pixels encoded at once by [`ResumableEncoder::encode_step`].
+const MIN_STEP_PIXELS: usize = 64;
+
+#[derive(Clone)]
+enum ResumableState {
+    Rgb(EncodeState<3>),
+    Rgba(EncodeState<4>),
+}
+
+/// Encoder that can be suspended after writing a given number of bytes and resumed later.
+///
+/// Created via [`Encoder::resumable`]. This allows interleaving encoding with other work
+/// on a single thread (e.g. in a game loop or a single-threaded async runtime): each call
+/// to [`ResumableEncoder::encode_step`] does a bounded amount of work and returns.
+///
+/// ### Example
+/// ```rust
+/// let mut encoder = Encoder::new(&pixels, width, height)?.resumable();
+/// while !encoder.is_finished() {
+///     encoder.encode_step(&mut writer, 64 * 1024)?;
+///     do_other_work();
+/// }
+/// ```
+#[derive(Clone)]
+pub struct ResumableEncoder<'a> {
+    encoder: Encoder<'a>,
+    state: ResumableState,
+    n_written: usize,
+    finished: bool,
+}
+
+impl Debug for ResumableEncoder<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ResumableEncoder")
+            .field("encoder", &self.encoder)
+            .field("bytes_written", &self.n_written)
+            .field("pixels_left", &self.pixels_left())
+            .field("finished", &self.finished)
+            .finish()
+    }
+}
+
+impl<'a> ResumableEncoder<'a> {
+    #[inline]
+    const fn new(encoder: Encoder<'a>) -> Self {
+        let state = match encoder.header.channels {
+            Channels::Rgb => ResumableState::Rgb(EncodeState::new()),
+            Channels::Rgba => ResumableState::Rgba(EncodeState::new()),
+        };
+        Self { encoder, state, n_written: 0, finished: false }
+    }
+
+    /// Returns the header that will be stored in the encoded image.
+    #[inline]
+    pub const fn header(&self) -> &Header {
+        &self.encoder.header
+    }
+
+    /// Returns true once the whole image including the end marker has been written.
+    #[inline]
+    pub const fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Returns the total number of bytes written so far.
+    #[inline]
+    pub const fn bytes_written(&self) -> usize {
+        self.n_written
+    }
+
+    /// Returns the number of pixels that haven't been encoded yet.
+    #[inline]
+    pub const fn pixels_left(&self) -> usize {
+        let pos = match &self.state {
+            ResumableState::Rgb(state) => state.pos,
+            ResumableState::Rgba(state) => state.pos,
+        };
+        self.encoder.header.n_pixels() - pos
+    }
+
+    /// Continues encoding until at least `n_bytes` bytes have been written to the writer
+    /// (or the image is finished) and returns the number of bytes written in this step.
+    ///
+    /// Pixels are encoded in batches, so the step may overshoot `n_bytes` by a few hundred
+    /// bytes at most. The header is written in the first step and the end marker in the
+    /// last one; calling this method once the image is finished does nothing. If enabled
+    /// via [`Encoder::with_flush`], the writer is flushed at the end of the last step.
+    ///
+    /// If writing fails, [`Error::PartialWrite`] is returned with the number of bytes written
+    /// in this step (or [`Error::IoError`] if there were none); the encoding can't be resumed
+    /// after that.
+    pub fn encode_step<W: Write>(&mut self, writer: &mut W, n_bytes: usize) -> Result<usize> {
+        if self.finished {
+            return Ok(0);
+        }
+        let n_bytes = n_bytes.max(1);
+        let mut out = GenericWriter::new(&mut *writer);
+        let cap = out.capacity();
+        if self.n_written == 0 {
+            out = out.write_many(&self.encoder.header.encode())?;
+        }
+        let (enc, n_channels) = (&self.encoder, self.encoder.header.channels.as_u8() as usize);
+        while !self.finished && cap - out.capacity() < n_bytes {
+            let n_left = n_bytes - (cap - out.capacity());
+            let count = (n_left / (n_channels + 1)).max(MIN_STEP_PIXELS).min(self.pixels_left());
+            if count != 0 {
+                out = match (&mut self.state, enc.mode()) {
+                    (ResumableState::Rgb(state), MODE_STORE) => {
+                        encode_resume::<_, 3, MODE_STORE>(out, enc, state, count)
+                    }
+                    (ResumableState::Rgba(state), MODE_STORE) => {
+                        encode_resume::<_, 4, MODE_STORE>(out, enc, state, count)
+                    }
+                    (ResumableState::Rgb(state), _) => {
+                        encode_resume::<_, 3, MODE_DEFAULT>(out, enc, state, count)
+                    }
+                    (ResumableState::Rgba(state), _) => {
+                        encode_resume::<_, 4, MODE_DEFAULT>(out, enc, state, count)
+                    }
+                }?;
+            }
+            if self.pixels_left() == 0 {
+                out = out.write_many(&QOI_PADDING)?;
+                self.finished = true;
+            }
+        }
+        let n_written = cap - out.capacity();
+        self.n_written += n_written;
+        if self.finished && self.encoder.flush {
+            writer.flush()?;
+        }
+        Ok(n_written)
+    }
+}
+
+/// The minimum number of bytes encoded at once by [`EncodedReader`].
+const READER_CHUNK_SIZE: usize = 4096;
+
+/// Reader adapter that encodes the image lazily, as the encoded bytes are being read.
+///
+/// This is the inverse of [`Encoder::encode_to_stream`]: rather than pushing all of the
+/// encoded bytes into a writer at once, the image is encoded in small chunks on demand
+/// (see [`ResumableEncoder`]), e.g. for frameworks that pull the response body from a
+/// reader. Only one chunk of encoded data is buffered at a time.
+///
+/// ### Example
+/// ```rust
+/// let encoder = Encoder::new(&pixels, width, height)?;
+/// std::io::copy(&mut EncodedReader::new(encoder), &mut socket)?;
+/// ```
+#[derive(Clone)]
+pub struct EncodedReader<'a> {
+    encoder: ResumableEncoder<'a>,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl Debug for EncodedReader<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EncodedReader")
+            .field("encoder", &self.encoder)
+            .field("buffered", &(self.buf.len() - self.pos))
+            .finish()
+    }
+}
+
+impl<'a> EncodedReader<'a> {
+    /// Creates a new reader producing the image encoded by the given encoder.
+    #[inline]
+    pub const fn new(encoder: Encoder<'a>) -> Self {
+        Self { encoder: ResumableEncoder::new(encoder), buf: Vec::new(), pos: 0 }
+    }
+
+    /// Returns the header that will be stored in the encoded image.
+    #[inline]
+    pub const fn header(&self) -> &Header {
+        self.encoder.header()
+    }
+}
+
+impl Read for EncodedReader<'_> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.buf.len() {
+            if self.encoder.is_finished() || out.is_empty() {
+                return Ok(0);
+            }
+            self.buf.clear();
+            self.pos = 0;
+            let n_bytes = out.len().max(READER_CHUNK_SIZE);
+            self.encoder
+                .encode_step(&mut self.buf, n_bytes)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        }
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Encodes the next rows of pixels into `out`, followed by the end marker once the last
+/// pixel of the image has been encoded.
+fn encode_rows<W: Writer, const N: usize>(
+    out: W, state: &mut EncodeState<N>, pixels: &[u8], n_pixels: usize,
+) -> Result<W>
+where
+    Pixel<N>: SupportedChannels,
+    [u8; N]: Pod,
+{
+    let read = |chunk: &[u8]| {
+        let mut px = Pixel::<N>::new();
+        px.read(chunk);
+        px
+    };
+    let pixels = pixels.chunks_exact(N).map(read);
+    let out = state.encode_pixels::<_, _, MODE_DEFAULT>(out, pixels, n_pixels)?;
+    if state.pos == n_pixels {
+        out.write_many(&QOI_PADDING)
+    } else {
+        Ok(out)
+    }
+}
+
+/// Push-based encoder that accepts the pixels incrementally, row by row.
+///
+/// The header is written to the writer upon construction; then, each call to
+/// [`StreamEncoder::push_rows`] encodes the given rows and writes the encoded bytes out
+/// right away, so the whole image never has to be in memory at once (e.g. when capturing
+/// frames scanline by scanline). The end marker is written along with the last row.
+///
+/// The output is identical to that of [`Encoder`] with the default options.
+///
+/// ### Example
+/// ```rust
+/// let header = Header::try_new(width, height, Channels::Rgba, ColorSpace::Srgb)?;
+/// let mut encoder = StreamEncoder::new(file, header)?;
+/// while let Some(rows) = capture_next_rows() {
+///     encoder.push_rows(&rows)?;
+/// }
+/// let file = encoder.finish()?;
+/// ```
+pub struct StreamEncoder<W> {
+    writer: W,
+    header: Header,
+    state: ResumableState,
+    buf: Vec<u8>,
+    n_written: usize,
+}
+
+impl<W> Debug for StreamEncoder<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StreamEncoder")
+            .field("header", &self.header)
+            .field("bytes_written", &self.n_written)
+            .field("pixels_left", &self.pixels_left())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W> StreamEncoder<W> {
+    /// Returns the header of the image being encoded.
+    #[inline]
+    pub const fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Returns the total number of bytes written so far, including the header.
+    #[inline]
+    pub const fn bytes_written(&self) -> usize {
+        self.n_written
+    }
+
+    /// Returns the number of pixels that haven't been pushed yet.
+    #[inline]
+    pub const fn pixels_left(&self) -> usize {
+        let pos = match &self.state {
+            ResumableState::Rgb(state) => state.pos,
+            ResumableState::Rgba(state) => state.pos,
+        };
+        self.header.n_pixels() - pos
+    }
+
+    /// Returns the number of rows that haven't been pushed yet.
+    #[inline]
+    pub const fn rows_left(&self) -> usize {
+        self.pixels_left() / self.header.width as usize
+    }
+
+    /// Returns true once all of the rows including the end marker have been written.
+    #[inline]
+    pub const fn is_finished(&self) -> bool {
+        self.pixels_left() == 0
+    }
+
+    /// Returns an immutable reference to the underlying writer.
+    #[inline]
+    pub const fn writer(&self) -> &W {
+        &self.writer
+    }
+}
+
+impl<W: Write> StreamEncoder<W> {
+    /// Creates a new encoder for an image with the given header and writes the header.
+    ///
+    /// The pixels pushed later on must have as many channels as the header specifies.
+    pub fn new(mut writer: W, header: Header) -> Result<Self> {
+        let Header { width, height, channels, colorspace } = header;
+        let header = Header::try_new(width, height, channels, colorspace)?;
+        writer.write_all(&header.encode())?;
+        let state = match channels {
+            Channels::Rgb => ResumableState::Rgb(EncodeState::new()),
+            Channels::Rgba => ResumableState::Rgba(EncodeState::new()),
+        };
+        Ok(Self { writer, header, state, buf: Vec::new(), n_written: QOI_HEADER_SIZE })
+    }
+
+    /// Encodes the next rows of pixels and writes them out, returning the number of bytes
+    /// written in this call.
+    ///
+    /// The data must consist of whole rows (`width * channels` bytes each), with no more
+    /// rows than there are left in the image; otherwise, [`Error::InvalidImageLength`] is
+    /// returned and nothing is encoded. If writing fails, the encoding can't be resumed.
+    pub fn push_rows(&mut self, rows: impl AsRef<[u8]>) -> Result<usize> {
+        let rows = rows.as_ref();
+        let Header { width, height, .. } = self.header;
+        let row_len = width as usize * self.header.channels.as_u8() as usize;
+        if unlikely(rows.len() % row_len != 0 || rows.len() / row_len > self.rows_left()) {
+            return Err(Error::InvalidImageLength { size: rows.len(), width, height });
+        }
+        if rows.is_empty() {
+            return Ok(0);
+        }
+        let n_pixels = self.header.n_pixels();
+        let mut buf = core::mem::take(&mut self.buf);
+        buf.clear();
+        let out = GenericWriter::new(&mut buf);
+        let _ = match &mut self.state {
+            ResumableState::Rgb(state) => encode_rows(out, state, rows, n_pixels)?,
+            ResumableState::Rgba(state) => encode_rows(out, state, rows, n_pixels)?,
+        };
+        self.writer.write_all(&buf)?;
+        self.n_written += buf.len();
+        self.buf = buf;
+        Ok(self.buf.len())
+    }
+
+    /// Flushes the writer and returns it back, once all of the rows have been pushed.
+    ///
+    /// If some of the rows are missing, [`Error::InvalidImageLength`] is returned with the
+    /// number of bytes pushed so far.
+    pub fn finish(mut self) -> Result<W> {
+        let Header { width, height, channels, .. } = self.header;
+        if unlikely(!self.is_finished()) {
+            let n_pushed = self.header.n_pixels() - self.pixels_left();
+            let size = n_pushed * channels.as_u8() as usize;
+            return Err(Error::InvalidImageLength { size, width, height });
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<'a> Encoder<'a> {
+    /// Returns a resumable encoder that writes the image to a stream in steps, see
+    /// [`ResumableEncoder`].
+    #[inline]
+    pub const fn resumable(&self) -> ResumableEncoder<'a> {
+        ResumableEncoder::new(Encoder { ..*self })
+    }
+
+    /// Encodes the image directly to a generic writer that implements [`Write`](std::io::Write).
+    ///
+    /// Interrupted writes are retried; if writing fails otherwise after some bytes have already
+    /// been written, [`Error::PartialWrite`] is returned with the number of bytes written
+    /// (and [`Error::IoError`] if nothing was written yet).
+    /// If enabled via [`Encoder::with_flush`], the writer is flushed at the end.
+    ///
+    /// Note: while it's possible to pass a `&mut [u8]` slice here since it implements `Write`,
+    /// it would more effficient to use a specialized method instead: [`Encoder::encode_to_buf`].
+    #[inline]
+    pub fn encode_to_stream<W: Write>(&self, writer: &mut W) -> Result<usize> {
+        let out = GenericWriter::new(&mut *writer).write_many(&self.header.encode())?;
+        let n_written = encode_impl_all(out, self)?;
+        if self.flush {
+            writer.flush()?;
+        }
+        Ok(n_written + QOI_HEADER_SIZE)
+    }
+}
+
+impl<D: AsRef<[u8]>> OwnedEncoder<D> {
+    /// Encodes the image to a generic writer, see [`Encoder::encode_to_stream`].
+    #[inline]
+    pub fn encode_to_stream<W: Write>(&self, writer: &mut W) -> Result<usize> {
+        self.encoder().encode_to_stream(writer)
+    }
+}
@@ -64,6 +64,7 @@
     clippy::cargo_common_metadata,
     clippy::doc_markdown,
     clippy::return_self_not_must_use,
+    clippy::multiple_crate_versions
 )]
 #![cfg_attr(not(any(feature = "std", test)), no_std)]
 #[cfg(all(feature = "alloc", not(any(feature = "std", test))))]
@@ -71,25 +72,87 @@ extern crate alloc;
 #[cfg(any(feature = "std", test))]
 extern crate std as alloc;
 
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+mod async_io;
 mod decode;
 mod encode;
 mod error;
 mod header;
+mod info;
+#[cfg(feature = "std")]
+mod io;
 mod pixel;
+mod source;
+#[cfg(feature = "std")]
+mod transcode;
 mod types;
 mod utils;
+#[cfg(any(feature = "alloc", feature = "std"))]
+mod vec;
 
-#[doc(hidden)]
+#[cfg(feature = "anim")]
+pub mod anim;
+#[cfg(feature = "batch")]
+pub mod batch;
 pub mod consts;
+pub mod convert;
+#[cfg(feature = "gray-alpha")]
+pub mod gray;
+#[cfg(feature = "image")]
+pub mod image;
+pub mod metrics;
+pub mod ops;
+#[cfg(feature = "std")]
+pub mod parallel;
+#[cfg(feature = "sdl2")]
+pub mod sdl;
+#[cfg(feature = "test-util")]
+pub mod testutil;
 
-#[cfg(any(feature = "alloc", feature = "std"))]
-pub use crate::decode::decode_to_vec;
-pub use crate::decode::{decode_header, decode_to_buf, Decoder};
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+pub use crate::async_io::AsyncDecoder;
+pub use crate::decode::{
+    decode_body_to_buf, decode_header, decode_to_buf, Decoder, DecoderState, Diagnostic, OwnedBytes,
+};
 
-#[cfg(any(feature = "alloc", feature = "std"))]
-pub use crate::encode::encode_to_vec;
-pub use crate::encode::{encode_max_len, encode_to_buf, Encoder};
+pub use crate::encode::{
+    encode_body_to_buf, encode_max_len, encode_to_buf, encoded_size_limit, Encoder, EncoderBuilder,
+    OwnedEncoder,
+};
 
-pub use crate::error::{Error, Result};
-pub use crate::header::Header;
+pub use crate::error::{Error, ErrorKind, Result};
+pub use crate::header::{Header, HeaderOptions, HumanBytes};
+pub use crate::info::{crate_version, supported_extensions, Feature};
+#[cfg(feature = "std")]
+pub use crate::io::{
+    scan_header_files, scan_headers, EncodedReader, ResumableEncoder, StreamEncoder,
+};
+pub use crate::pixel::Pixel;
+pub use crate::source::{ChannelMap, ChannelSource, SourceChannels, TargetChannels};
+#[cfg(feature = "std")]
+pub use crate::transcode::{transcode_stream, TranscodeOptions};
 pub use crate::types::{Channels, ColorSpace};
+#[cfg(any(feature = "alloc", feature = "std"))]
+pub use crate::vec::{decode_body_to_vec, decode_to_vec, encode_body_to_vec, encode_to_vec};
+
+// All codec types are guaranteed to be `Send + Sync` (stream decoders as long as the
+// underlying reader is); this is part of the public API, so make sure it's not lost.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Encoder>();
+    assert_send_sync::<EncoderBuilder>();
+    assert_send_sync::<OwnedEncoder<&'static [u8]>>();
+    #[cfg(feature = "std")]
+    assert_send_sync::<OwnedEncoder<std::sync::Arc<[u8]>>>();
+    #[cfg(feature = "std")]
+    assert_send_sync::<StreamEncoder<std::vec::Vec<u8>>>();
+    assert_send_sync::<Decoder<decode::Bytes>>();
+    assert_send_sync::<Decoder<OwnedBytes<&'static [u8]>>>();
+    #[cfg(feature = "std")]
+    assert_send_sync::<Decoder<OwnedBytes<std::sync::Arc<[u8]>>>>();
+    #[cfg(feature = "std")]
+    assert_send_sync::<Decoder<std::io::Cursor<std::vec::Vec<u8>>>>();
+    assert_send_sync::<DecoderState>();
+    assert_send_sync::<Header>();
+    assert_send_sync::<Error>();
+};
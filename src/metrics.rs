@@ -0,0 +1,314 @@
+//! Image metrics: error metrics between two images and distinct color counts.
+//!
+//! Error metrics (per-channel maximum error, MSE and PSNR) are mostly useful for measuring
+//! how far a decoded image is from the original one when some kind of lossy processing is
+//! involved (e.g. channel conversion or a lossy preprocessing step before encoding).
+//!
+//! Color census (see [`ColorCensus`]) counts distinct colors in an image, e.g. to decide
+//! whether it would fit into a palette.
+
+#[cfg(feature = "std")]
+use std::collections::HashSet;
+#[cfg(feature = "std")]
+use std::io::Read;
+
+use crate::decode::{Decoder, Reader};
+use crate::error::{Error, Result};
+use crate::header::Header;
+use crate::types::{Channels, ColorSpace};
+use crate::utils::unlikely;
+
+/// Number of pixels decoded at a time when comparing against an encoded image.
+const STRIP_PIXELS: usize = 256;
+
+/// Error metrics between two images of the same dimensions and number of channels.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ErrorMetrics {
+    channels: Channels,
+    n_pixels: usize,
+    n_diff: usize,
+    max_error: [u8; 4],
+    sum_sq: [u64; 4],
+}
+
+impl ErrorMetrics {
+    const fn new(channels: Channels) -> Self {
+        Self { channels, n_pixels: 0, n_diff: 0, max_error: [0; 4], sum_sq: [0; 4] }
+    }
+
+    /// Accumulates the error between two equally sized buffers of whole pixels.
+    fn update(&mut self, a: &[u8], b: &[u8]) {
+        let n = self.channels.as_u8() as usize;
+        for (pa, pb) in a.chunks_exact(n).zip(b.chunks_exact(n)) {
+            self.n_pixels += 1;
+            if pa == pb {
+                continue;
+            }
+            self.n_diff += 1;
+            for c in 0..n {
+                let err = pa[c].abs_diff(pb[c]);
+                self.max_error[c] = self.max_error[c].max(err);
+                self.sum_sq[c] += u64::from(err) * u64::from(err);
+            }
+        }
+    }
+
+    /// Returns the number of channels that have been compared.
+    #[inline]
+    pub const fn channels(&self) -> Channels {
+        self.channels
+    }
+
+    /// Returns the total number of pixels that have been compared.
+    #[inline]
+    pub const fn n_pixels(&self) -> usize {
+        self.n_pixels
+    }
+
+    /// Returns the number of pixels that differ in at least one channel.
+    #[inline]
+    pub const fn n_diff(&self) -> usize {
+        self.n_diff
+    }
+
+    /// Returns true if the images are identical.
+    #[inline]
+    pub const fn is_identical(&self) -> bool {
+        self.n_diff == 0
+    }
+
+    /// Returns the maximum absolute error for each channel (alpha is always 0 for RGB images).
+    #[inline]
+    pub const fn max_channel_error(&self) -> [u8; 4] {
+        self.max_error
+    }
+
+    /// Returns the maximum absolute error across all channels.
+    #[inline]
+    pub fn max_error(&self) -> u8 {
+        self.max_error.iter().copied().max().unwrap_or(0)
+    }
+
+    /// Returns the mean squared error for each channel (alpha is always 0 for RGB images).
+    #[allow(clippy::cast_precision_loss)]
+    pub fn channel_mse(&self) -> [f64; 4] {
+        let n = self.n_pixels.max(1) as f64;
+        self.sum_sq.map(|s| s as f64 / n)
+    }
+
+    /// Returns the mean squared error across all channels.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn mse(&self) -> f64 {
+        let n = (self.n_pixels * self.channels.as_u8() as usize).max(1) as f64;
+        self.sum_sq.iter().sum::<u64>() as f64 / n
+    }
+
+    /// Returns the peak signal-to-noise ratio in decibels across all channels.
+    ///
+    /// For identical images this is infinite.
+    #[cfg(feature = "std")]
+    pub fn psnr(&self) -> f64 {
+        if self.is_identical() {
+            return f64::INFINITY;
+        }
+        10. * (255. * 255. / self.mse()).log10()
+    }
+}
+
+/// Compares two images with the same dimensions and pixel layout.
+///
+/// The number of channels is inferred from the length of the first buffer, just like in
+/// [`Encoder::new`](crate::Encoder::new) (the valid values are 3 or 4); the second buffer
+/// must be of the same length.
+#[allow(clippy::cast_possible_truncation)]
+pub fn compare(
+    a: impl AsRef<[u8]>, b: impl AsRef<[u8]>, width: u32, height: u32,
+) -> Result<ErrorMetrics> {
+    let (a, b) = (a.as_ref(), b.as_ref());
+    let header = Header::try_new(width, height, Channels::default(), ColorSpace::default())?;
+    let size = a.len();
+    let n_channels = size / header.n_pixels();
+    if unlikely(header.n_pixels() * n_channels != size) {
+        return Err(Error::InvalidImageLength { size, width, height });
+    }
+    let channels = Channels::try_from(n_channels.min(0xff) as u8)?;
+    if unlikely(b.len() != size) {
+        return Err(Error::InvalidImageLength { size: b.len(), width, height });
+    }
+    let mut metrics = ErrorMetrics::new(channels);
+    metrics.update(a, b);
+    Ok(metrics)
+}
+
+fn compare_decoder<R: Reader>(original: &[u8], mut decoder: Decoder<R>) -> Result<ErrorMetrics> {
+    let header = *decoder.header();
+    let n = header.channels.as_u8() as usize;
+    if unlikely(original.len() != header.n_bytes()) {
+        let (width, height) = (header.width, header.height);
+        return Err(Error::InvalidImageLength { size: original.len(), width, height });
+    }
+    let mut metrics = ErrorMetrics::new(header.channels);
+    let mut strip = [0_u8; STRIP_PIXELS * 4];
+    let mut original = original;
+    while decoder.pixels_left() != 0 {
+        let n_pixels = decoder.decode_pixels(&mut strip[..STRIP_PIXELS * n])?;
+        let (head, tail) = original.split_at(n_pixels * n);
+        metrics.update(head, &strip[..n_pixels * n]);
+        original = tail;
+    }
+    Ok(metrics)
+}
+
+/// Compares original pixels with an encoded image, decoding it in small strips.
+///
+/// The original pixels must have the same number of channels as stored in the header of
+/// the encoded image; no heap allocations are made.
+pub fn compare_encoded(
+    original: impl AsRef<[u8]>, encoded: impl AsRef<[u8]>,
+) -> Result<ErrorMetrics> {
+    compare_decoder(original.as_ref(), Decoder::new(encoded.as_ref())?)
+}
+
+/// Same as [`compare_encoded`], but reads the encoded image from a stream.
+#[cfg(feature = "std")]
+pub fn compare_stream<R: Read>(original: impl AsRef<[u8]>, reader: R) -> Result<ErrorMetrics> {
+    compare_decoder(original.as_ref(), Decoder::from_stream(reader)?)
+}
+
+/// Number of bits of the hash used to select a register in the approximate color counter.
+#[cfg(feature = "std")]
+const HLL_BITS: u32 = 12;
+
+/// Result of a [`ColorCensus`]: the number of distinct colors and whether it's exact.
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ColorCount {
+    n_colors: usize,
+    exact: bool,
+}
+
+#[cfg(feature = "std")]
+impl ColorCount {
+    /// Returns the number of distinct colors (an estimate if it's not exact).
+    #[inline]
+    pub const fn n_colors(&self) -> usize {
+        self.n_colors
+    }
+
+    /// Returns true if the number of colors is exact, i.e. it doesn't exceed the threshold.
+    #[inline]
+    pub const fn is_exact(&self) -> bool {
+        self.exact
+    }
+}
+
+/// Counts distinct colors, exactly up to a threshold and approximately beyond it.
+///
+/// Up to the threshold, colors are tracked in a set; once it's exceeded, the set is
+/// dropped and a HyperLogLog estimator with 4096 registers is used instead (so memory
+/// usage stays bounded, with a typical relative error of about 1.6%). The estimate is
+/// never below `threshold + 1`.
+///
+/// RGB pixels are counted as if they were fully opaque RGBA pixels.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+pub struct ColorCensus {
+    threshold: usize,
+    colors: HashSet<u32>,
+    registers: Vec<u8>,
+}
+
+#[cfg(feature = "std")]
+impl ColorCensus {
+    /// Creates a new census that counts colors exactly up to the given threshold.
+    pub fn new(threshold: usize) -> Self {
+        Self { threshold, colors: HashSet::new(), registers: Vec::new() }
+    }
+
+    /// Returns the threshold up to which colors are counted exactly.
+    #[inline]
+    pub const fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    #[inline]
+    #[allow(clippy::cast_possible_truncation)]
+    fn insert_approx(registers: &mut [u8], color: u32) {
+        // splitmix64 finalizer, so that close colors end up in unrelated registers
+        let mut h = u64::from(color).wrapping_add(0x9e37_79b9_7f4a_7c15);
+        h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        h ^= h >> 31;
+        let rank = ((h << HLL_BITS) | (1 << (HLL_BITS - 1))).leading_zeros() as u8 + 1;
+        let register = &mut registers[(h >> (64 - HLL_BITS)) as usize];
+        *register = (*register).max(rank);
+    }
+
+    /// Adds tightly packed pixels with the given number of channels to the census.
+    pub fn add_pixels(&mut self, pixels: &[u8], channels: Channels) {
+        let n = channels.as_u8() as usize;
+        for px in pixels.chunks_exact(n) {
+            let color =
+                u32::from_be_bytes([px[0], px[1], px[2], if n == 4 { px[3] } else { 0xff }]);
+            if self.registers.is_empty() {
+                if self.colors.insert(color) && self.colors.len() > self.threshold {
+                    self.registers = vec![0; 1 << HLL_BITS];
+                    for color in std::mem::take(&mut self.colors) {
+                        Self::insert_approx(&mut self.registers, color);
+                    }
+                }
+            } else {
+                Self::insert_approx(&mut self.registers, color);
+            }
+        }
+    }
+
+    /// Returns the current number of distinct colors.
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    #[allow(clippy::naive_bytecount)]
+    pub fn count(&self) -> ColorCount {
+        if self.registers.is_empty() {
+            return ColorCount { n_colors: self.colors.len(), exact: true };
+        }
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1. + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 1. / (1_u64 << r) as f64).sum();
+        let mut estimate = alpha * m * m / sum;
+        let n_zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if estimate <= 2.5 * m && n_zeros != 0 {
+            // linear counting is more accurate for small cardinalities
+            estimate = m * (m / n_zeros as f64).ln();
+        }
+        let n_colors = (estimate.round() as usize).max(self.threshold + 1);
+        ColorCount { n_colors, exact: false }
+    }
+}
+
+#[cfg(feature = "std")]
+fn count_colors_decoder<R: Reader>(
+    mut decoder: Decoder<R>, threshold: usize,
+) -> Result<ColorCount> {
+    let channels = decoder.channels();
+    let n = channels.as_u8() as usize;
+    let mut census = ColorCensus::new(threshold);
+    let mut strip = [0_u8; STRIP_PIXELS * 4];
+    while decoder.pixels_left() != 0 {
+        let n_pixels = decoder.decode_pixels(&mut strip[..STRIP_PIXELS * n])?;
+        census.add_pixels(&strip[..n_pixels * n], channels);
+    }
+    Ok(census.count())
+}
+
+/// Counts distinct colors in an encoded image while decoding it in small strips.
+///
+/// See [`ColorCensus`] for details on the threshold.
+#[cfg(feature = "std")]
+pub fn count_colors(encoded: impl AsRef<[u8]>, threshold: usize) -> Result<ColorCount> {
+    count_colors_decoder(Decoder::new(encoded.as_ref())?, threshold)
+}
+
+/// Same as [`count_colors`], but reads the encoded image from a stream.
+#[cfg(feature = "std")]
+pub fn count_colors_stream<R: Read>(reader: R, threshold: usize) -> Result<ColorCount> {
+    count_colors_decoder(Decoder::from_stream(reader)?, threshold)
+}
@@ -0,0 +1,177 @@
+//! Low-level access to individual QOI ops ("chunks").
+//!
+//! This is mostly useful for codec tooling, visualizers and format research; for regular
+//! encoding and decoding, see [`Encoder`](crate::Encoder) and [`Decoder`](crate::Decoder).
+
+use crate::consts::{
+    QOI_HEADER_SIZE, QOI_MASK_2, QOI_OP_DIFF, QOI_OP_INDEX, QOI_OP_LUMA, QOI_OP_RGB, QOI_OP_RGBA,
+    QOI_OP_RUN, QOI_PADDING, QOI_PADDING_SIZE,
+};
+use crate::error::{Error, Result};
+use crate::header::Header;
+use crate::utils::unlikely;
+
+/// A single QOI op, as stored in the encoded stream.
+///
+/// Field values are expected to be within the ranges documented on each variant; when
+/// encoding, values outside of these ranges are truncated to the available number of bits.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Op {
+    /// Pixel from the index of previously seen pixels, position in `0..=63`
+    Index(u8),
+    /// Small difference from the previous pixel, each component in `-2..=1`
+    Diff { dr: i8, dg: i8, db: i8 },
+    /// Green difference in `-32..=31`, red and blue differences relative to it in `-8..=7`
+    Luma { dg: i8, dr_dg: i8, db_dg: i8 },
+    /// Full RGB value, alpha is unchanged
+    Rgb { r: u8, g: u8, b: u8 },
+    /// Full RGBA value
+    Rgba { r: u8, g: u8, b: u8, a: u8 },
+    /// Run of the previous pixel, length in `1..=62`
+    Run(u8),
+}
+
+impl Op {
+    /// Returns the number of bytes this op takes in the encoded stream.
+    #[inline]
+    pub const fn n_bytes(&self) -> usize {
+        match self {
+            Self::Index(_) | Self::Diff { .. } | Self::Run(_) => 1,
+            Self::Luma { .. } => 2,
+            Self::Rgb { .. } => 4,
+            Self::Rgba { .. } => 5,
+        }
+    }
+
+    /// Returns the number of pixels this op produces when decoded.
+    #[inline]
+    pub const fn n_pixels(&self) -> usize {
+        match *self {
+            Self::Run(run) => run as usize,
+            _ => 1,
+        }
+    }
+
+    /// Serializes the op into the start of the buffer, returning the number of bytes written.
+    #[allow(clippy::cast_sign_loss)]
+    pub fn encode_to(&self, mut buf: impl AsMut<[u8]>) -> Result<usize> {
+        let buf = buf.as_mut();
+        let (size, required) = (buf.len(), self.n_bytes());
+        if unlikely(size < required) {
+            return Err(Error::OutputBufferTooSmall { size, required });
+        }
+        let bias = |v: i8, bias: i8, mask: u8| (v.wrapping_add(bias) as u8) & mask;
+        match *self {
+            Self::Index(i) => buf[0] = QOI_OP_INDEX | (i & 0x3f),
+            Self::Diff { dr, dg, db } => {
+                buf[0] = QOI_OP_DIFF
+                    | bias(dr, 2, 0x03) << 4
+                    | bias(dg, 2, 0x03) << 2
+                    | bias(db, 2, 0x03);
+            }
+            Self::Luma { dg, dr_dg, db_dg } => {
+                buf[0] = QOI_OP_LUMA | bias(dg, 32, 0x3f);
+                buf[1] = bias(dr_dg, 8, 0x0f) << 4 | bias(db_dg, 8, 0x0f);
+            }
+            Self::Rgb { r, g, b } => buf[..4].copy_from_slice(&[QOI_OP_RGB, r, g, b]),
+            Self::Rgba { r, g, b, a } => buf[..5].copy_from_slice(&[QOI_OP_RGBA, r, g, b, a]),
+            Self::Run(run) => buf[0] = QOI_OP_RUN | (run.wrapping_sub(1) % 62),
+        }
+        Ok(required)
+    }
+
+    /// Parses a single op from the start of the data, returning it along with its length.
+    ///
+    /// Note: the padding at the end of the stream is indistinguishable from regular ops,
+    /// use [`Ops`] to iterate over the ops of a complete image.
+    #[allow(clippy::cast_possible_wrap)]
+    pub fn parse_from(data: impl AsRef<[u8]>) -> Result<(Self, usize)> {
+        let data = data.as_ref();
+        let unbias = |v: u8, bias: u8| v.wrapping_sub(bias) as i8;
+        let op = match *data {
+            [QOI_OP_RGB, r, g, b, ..] => Self::Rgb { r, g, b },
+            [QOI_OP_RGBA, r, g, b, a, ..] => Self::Rgba { r, g, b, a },
+            [QOI_OP_RGB | QOI_OP_RGBA, ..] | [] => return Err(Error::UnexpectedBufferEnd),
+            [b1, ..] if b1 & QOI_MASK_2 == QOI_OP_INDEX => Self::Index(b1 & 0x3f),
+            [b1, ..] if b1 & QOI_MASK_2 == QOI_OP_DIFF => Self::Diff {
+                dr: unbias((b1 >> 4) & 0x03, 2),
+                dg: unbias((b1 >> 2) & 0x03, 2),
+                db: unbias(b1 & 0x03, 2),
+            },
+            [b1, b2, ..] if b1 & QOI_MASK_2 == QOI_OP_LUMA => Self::Luma {
+                dg: unbias(b1 & 0x3f, 32),
+                dr_dg: unbias(b2 >> 4, 8),
+                db_dg: unbias(b2 & 0x0f, 8),
+            },
+            [b1] if b1 & QOI_MASK_2 == QOI_OP_LUMA => return Err(Error::UnexpectedBufferEnd),
+            [b1, ..] => Self::Run((b1 & 0x3f) + 1),
+        };
+        Ok((op, op.n_bytes()))
+    }
+}
+
+/// Iterator over the ops of a complete encoded image.
+///
+/// The header is decoded upon construction; iteration stops once the ops cover all
+/// of the pixels in the image, after which the end-of-stream padding is validated.
+/// Any error is yielded once, after which the iterator is exhausted.
+#[derive(Clone, Debug)]
+pub struct Ops<'a> {
+    data: &'a [u8],
+    header: Header,
+    n_pixels_left: usize,
+    done: bool,
+}
+
+impl<'a> Ops<'a> {
+    /// Creates a new op iterator from a slice of bytes containing a complete image.
+    #[inline]
+    pub fn new(data: &'a (impl AsRef<[u8]> + ?Sized)) -> Result<Self> {
+        let data = data.as_ref();
+        let header = Header::decode(data)?;
+        let data = &data[QOI_HEADER_SIZE..]; // can't panic
+        Ok(Self { data, header, n_pixels_left: header.n_pixels(), done: false })
+    }
+
+    /// Returns the decoded image header.
+    #[inline]
+    pub const fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Returns the data that hasn't been consumed yet.
+    #[inline]
+    pub const fn data(&self) -> &[u8] {
+        self.data
+    }
+
+    fn next_impl(&mut self) -> Result<Option<Op>> {
+        if self.n_pixels_left == 0 {
+            if unlikely(self.data.len() < QOI_PADDING_SIZE) {
+                return Err(Error::UnexpectedBufferEnd);
+            } else if unlikely(self.data[..QOI_PADDING_SIZE] != QOI_PADDING) {
+                return Err(Error::InvalidPadding);
+            }
+            self.data = &self.data[QOI_PADDING_SIZE..];
+            return Ok(None);
+        }
+        let (op, len) = Op::parse_from(self.data)?;
+        self.data = &self.data[len..];
+        self.n_pixels_left = self.n_pixels_left.saturating_sub(op.n_pixels());
+        Ok(Some(op))
+    }
+}
+
+impl Iterator for Ops<'_> {
+    type Item = Result<Op>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.next_impl();
+        self.done = !matches!(result, Ok(Some(_)));
+        result.transpose()
+    }
+}
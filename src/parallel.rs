@@ -0,0 +1,245 @@
+//! Parallelism configuration shared by all APIs that process images on multiple threads.
+//!
+//! By default, work is distributed over a set of short-lived worker threads spawned for
+//! each call. With the `rayon` feature enabled, a caller-provided [`rayon::ThreadPool`]
+//! can be used instead, so that all codec work is confined to a dedicated pool.
+//!
+//! Parallel decoder APIs built on top of these options, such as
+//! [`Decoder::decode_to_buf_converted`], are defined here as well.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::vec::Vec;
+
+use crate::decode::{Decoder, Reader};
+use crate::error::{Error, Result};
+use crate::utils::unlikely;
+
+/// Default number of image rows per work item, see [`ParallelOptions::with_strip_rows`].
+pub const DEFAULT_STRIP_ROWS: usize = 64;
+
+/// Parallelism options: number of threads, thread pool and work item sizes.
+#[derive(Clone, Debug)]
+pub struct ParallelOptions {
+    threads: usize,
+    strip_rows: usize,
+    #[cfg(feature = "rayon")]
+    pool: Option<Arc<rayon::ThreadPool>>,
+}
+
+impl Default for ParallelOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParallelOptions {
+    /// Creates the default options: as many threads as there is available parallelism.
+    pub const fn new() -> Self {
+        Self {
+            threads: 0,
+            strip_rows: DEFAULT_STRIP_ROWS,
+            #[cfg(feature = "rayon")]
+            pool: None,
+        }
+    }
+
+    /// Number of worker threads (default: 0, meaning the available parallelism).
+    ///
+    /// Ignored if a thread pool is provided via [`ParallelOptions::with_pool`].
+    pub const fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Number of image rows per work item for APIs that split a single image into
+    /// horizontal strips (default: [`DEFAULT_STRIP_ROWS`], 0 is treated as 1).
+    pub const fn with_strip_rows(mut self, strip_rows: usize) -> Self {
+        self.strip_rows = strip_rows;
+        self
+    }
+
+    /// Runs all work on the given rayon thread pool instead of spawning worker threads.
+    #[cfg(feature = "rayon")]
+    pub fn with_pool(mut self, pool: Arc<rayon::ThreadPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Returns the number of worker threads (0 means the available parallelism).
+    pub const fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Returns the number of image rows per work item.
+    pub const fn strip_rows(&self) -> usize {
+        self.strip_rows
+    }
+
+    /// Returns the rayon thread pool, if any.
+    #[cfg(feature = "rayon")]
+    pub const fn pool(&self) -> Option<&Arc<rayon::ThreadPool>> {
+        self.pool.as_ref()
+    }
+
+    /// Returns the number of threads that will actually be used for the given number of items.
+    pub fn effective_threads(&self, n_items: usize) -> usize {
+        #[cfg(feature = "rayon")]
+        if let Some(pool) = &self.pool {
+            return pool.current_num_threads().min(n_items);
+        }
+        let threads = match self.threads {
+            0 => thread::available_parallelism().map_or(1, usize::from),
+            n => n,
+        };
+        threads.min(n_items)
+    }
+
+    /// Applies `f` to all items in parallel, passing the results to `on_result` on the
+    /// calling thread as soon as they are ready (in no particular order). If `f` panics for
+    /// any of the items, the panic is resumed on the calling thread once all items are done.
+    pub(crate) fn map_unordered<T, R, F>(&self, items: Vec<T>, f: F, mut on_result: impl FnMut(R))
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        let n_threads = self.effective_threads(items.len());
+        // panics are caught on the worker threads and resumed here once all items are done
+        let run = Arc::new(move |item| panic::catch_unwind(AssertUnwindSafe(|| f(item))));
+        let (sender, receiver) = mpsc::channel();
+        let mut payload = None;
+        let mut on_result = |result: thread::Result<R>| match result {
+            Ok(result) if payload.is_none() => on_result(result),
+            Ok(_) => {}
+            Err(err) => {
+                let _ = payload.get_or_insert(err);
+            }
+        };
+
+        #[cfg(feature = "rayon")]
+        if let Some(pool) = &self.pool {
+            for item in items {
+                let (run, sender) = (Arc::clone(&run), sender.clone());
+                pool.spawn(move || {
+                    let _ = sender.send(run(item));
+                });
+            }
+            drop(sender);
+            receiver.iter().for_each(on_result);
+            if let Some(payload) = payload {
+                panic::resume_unwind(payload);
+            }
+            return;
+        }
+
+        let queue = Arc::new(Mutex::new(items.into_iter()));
+        let workers: Vec<_> = (0..n_threads)
+            .map(|_| {
+                let (queue, sender, run) = (Arc::clone(&queue), sender.clone(), Arc::clone(&run));
+                thread::spawn(move || loop {
+                    let item = match queue.lock().map(|mut items| items.next()) {
+                        Ok(Some(item)) => item,
+                        _ => break,
+                    };
+                    if sender.send(run(item)).is_err() {
+                        break;
+                    }
+                })
+            })
+            .collect();
+        drop(sender);
+        receiver.iter().for_each(&mut on_result);
+        for worker in workers {
+            if let Err(err) = worker.join() {
+                on_result(Err(err));
+            }
+        }
+        if let Some(payload) = payload {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+impl<R: Reader> Decoder<R> {
+    /// Decodes the rest of the image and converts it to another pixel format, running
+    /// the conversion on multiple threads; returns the number of bytes written.
+    ///
+    /// Decoding is done in two stages: first, the ops are decoded sequentially into tightly
+    /// packed [`Decoder::channels`]-sized pixels, ignoring the target pixel layout and the
+    /// output stride (see [`Decoder::with_target_channels`] and
+    /// [`Decoder::with_output_stride`]); then, the decoded pixels are split into strips of
+    /// [`ParallelOptions::strip_rows`] rows which are passed to `convert` in parallel along
+    /// with the matching part of the output buffer (`dst_pixel_size` bytes per pixel, with no
+    /// padding between rows). The functions in [`convert`](crate::convert) can be used as is,
+    /// e.g. to swizzle channels.
+    ///
+    /// The buffer must fit at least `pixels_left() * dst_pixel_size` bytes. If `convert`
+    /// fails for any of the strips, the first error is returned.
+    pub fn decode_to_buf_converted<F>(
+        &mut self, mut buf: impl AsMut<[u8]>, dst_pixel_size: usize, options: &ParallelOptions,
+        convert: F,
+    ) -> Result<usize>
+    where
+        F: Fn(&[u8], &mut [u8]) -> Result<usize> + Send + Sync + 'static,
+    {
+        let buf = buf.as_mut();
+        let n_pixels = self.pixels_left();
+        let size = n_pixels.saturating_mul(dst_pixel_size);
+        if unlikely(buf.len() < size) {
+            return Err(Error::OutputBufferTooSmall { size: buf.len(), required: size });
+        }
+        let n = self.channels().as_u8() as usize;
+        let mut src = vec![0; n_pixels.saturating_mul(n)];
+        if unlikely(self.header().n_pixels() == 0) {
+            // there are no pixels to decode, but the end marker still has to be valid
+            let _ = self.decode_to_vec()?;
+        }
+        let _ = self.decode_plain(&mut src)?;
+        if size == 0 {
+            return Ok(0);
+        }
+        let buf = &mut buf[..size];
+        let strip_pixels = options.strip_rows().max(1).saturating_mul(self.header().width as usize);
+        let n_strips = (n_pixels + strip_pixels - 1) / strip_pixels;
+        if options.effective_threads(n_strips) <= 1 {
+            let dst_strips = buf.chunks_mut(strip_pixels * dst_pixel_size);
+            for (src, dst) in src.chunks(strip_pixels * n).zip(dst_strips) {
+                let _ = convert(src, dst)?;
+            }
+            return Ok(size);
+        }
+
+        let src = Arc::new(src);
+        let strips = (0..n_pixels).step_by(strip_pixels).collect();
+        let convert_strip = move |start: usize| {
+            let end = (start + strip_pixels).min(n_pixels);
+            let mut dst = vec![0; (end - start) * dst_pixel_size];
+            convert(&src[start * n..end * n], &mut dst).map(|_| (start, dst))
+        };
+        let mut result = Ok(size);
+        options.map_unordered(strips, convert_strip, |strip| match strip {
+            Ok((start, dst)) => {
+                let offset = start * dst_pixel_size;
+                buf[offset..offset + dst.len()].copy_from_slice(&dst);
+            }
+            Err(err) if result.is_ok() => result = Err(err),
+            Err(_) => {}
+        });
+        result
+    }
+
+    /// Same as [`Decoder::decode_to_buf_converted`], but returns a newly allocated vector.
+    #[inline]
+    pub fn decode_to_vec_converted<F>(
+        &mut self, dst_pixel_size: usize, options: &ParallelOptions, convert: F,
+    ) -> Result<Vec<u8>>
+    where
+        F: Fn(&[u8], &mut [u8]) -> Result<usize> + Send + Sync + 'static,
+    {
+        let mut out = vec![0; self.pixels_left().saturating_mul(dst_pixel_size)];
+        let _ = self.decode_to_buf_converted(&mut out, dst_pixel_size, options, convert)?;
+        Ok(out)
+    }
+}
@@ -1,20 +1,137 @@
 use crate::consts::{QOI_OP_DIFF, QOI_OP_LUMA, QOI_OP_RGB, QOI_OP_RGBA};
 use crate::error::Result;
+use crate::source::ChannelSource;
 use crate::utils::Writer;
-use bytemuck::{cast, Pod};
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+/// A single RGB (`N = 3`) or RGBA (`N = 4`) pixel, as seen by the QOI codec.
+///
+/// All the arithmetic helpers here match the wrapping semantics of the QOI spec, so they
+/// can be used to build custom op-level tooling without reimplementing the pixel math.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 #[repr(transparent)]
 pub struct Pixel<const N: usize>([u8; N]);
 
+impl<const N: usize> Default for Pixel<N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<const N: usize> Pixel<N> {
+    /// Creates a new pixel with all channels set to zero.
     #[inline]
     pub const fn new() -> Self {
         Self([0; N])
     }
 
+    /// Creates a new pixel from raw channel values.
+    #[inline]
+    pub const fn from_array(channels: [u8; N]) -> Self {
+        Self(channels)
+    }
+
+    /// Returns the raw channel values.
+    #[inline]
+    pub const fn to_array(self) -> [u8; N] {
+        self.0
+    }
+
+    /// Returns the R, G and B channel values.
+    #[inline]
+    pub const fn rgb(self) -> [u8; 3] {
+        [self.0[0], self.0[1], self.0[2]]
+    }
+
+    /// Returns the R, G, B and A channel values (alpha is 255 for RGB pixels).
+    #[inline]
+    pub const fn rgba(self) -> [u8; 4] {
+        [self.0[0], self.0[1], self.0[2], self.a_or(0xff)]
+    }
+
+    /// Returns the alpha channel value (255 for RGB pixels).
+    #[inline]
+    pub const fn a(self) -> u8 {
+        self.a_or(0xff)
+    }
+
+    /// Converts the pixel to RGB, dropping alpha.
+    #[inline]
+    pub const fn to_rgb(self) -> Pixel<3> {
+        Pixel(self.rgb())
+    }
+
+    /// Converts the pixel to RGBA (alpha is set to 255 for RGB pixels).
+    #[inline]
+    pub const fn to_rgba(self) -> Pixel<4> {
+        Pixel(self.rgba())
+    }
+
+    /// Packs the pixel into a `u32` as `0xRRGGBBAA` (alpha is 255 for RGB pixels).
+    #[inline]
+    pub const fn to_u32(self) -> u32 {
+        u32::from_be_bytes(self.rgba())
+    }
+
+    /// Returns the wrapping per-channel difference `self - prev` as signed values,
+    /// in RGBA order (the alpha difference is always zero for RGB pixels).
+    ///
+    /// These are the values that the `QOI_OP_DIFF` and `QOI_OP_LUMA` ops operate on.
+    #[inline]
+    #[allow(clippy::cast_possible_wrap)]
+    pub const fn diff(self, prev: Self) -> [i8; 4] {
+        [
+            self.r().wrapping_sub(prev.r()) as i8,
+            self.g().wrapping_sub(prev.g()) as i8,
+            self.b().wrapping_sub(prev.b()) as i8,
+            self.a_or(0).wrapping_sub(prev.a_or(0)) as i8,
+        ]
+    }
+
+    /// Returns the pixel with color channels multiplied by alpha (rounded to nearest).
+    ///
+    /// RGB pixels are returned as is.
+    #[inline]
+    #[allow(clippy::cast_lossless, clippy::cast_possible_truncation)]
+    pub const fn premultiplied(self) -> Self {
+        if N < 4 || self.0[N - 1] == 0xff {
+            return self;
+        }
+        let a = self.0[N - 1] as u16;
+        let mut out = self;
+        let mut i = 0;
+        while i < 3 {
+            // exact rounding of `c * a / 255`
+            let t = self.0[i] as u16 * a + 0x80;
+            out.0[i] = ((t + (t >> 8)) >> 8) as u8;
+            i += 1;
+        }
+        out
+    }
+
+    /// Returns the pixel with color channels divided by alpha (rounded to nearest), i.e.
+    /// the inverse of [`Pixel::premultiplied`] up to rounding.
+    ///
+    /// RGB pixels and pixels with zero alpha are returned as is.
     #[inline]
-    pub fn read(&mut self, s: &[u8]) {
+    #[allow(clippy::cast_lossless, clippy::cast_possible_truncation)]
+    pub const fn unpremultiplied(self) -> Self {
+        if N < 4 || self.0[N - 1] == 0xff || self.0[N - 1] == 0 {
+            return self;
+        }
+        let a = self.0[N - 1] as u16;
+        let mut out = self;
+        let mut i = 0;
+        while i < 3 {
+            let c = (self.0[i] as u16 * 0xff + a / 2) / a;
+            out.0[i] = if c > 0xff { 0xff } else { c as u8 };
+            i += 1;
+        }
+        out
+    }
+
+    #[inline]
+    pub(crate) fn read(&mut self, s: &[u8]) {
         if s.len() == N {
             let mut i = 0;
             while i < N {
@@ -27,7 +144,34 @@ impl<const N: usize> Pixel<N> {
     }
 
     #[inline]
-    pub fn update<const M: usize>(&mut self, px: Pixel<M>) {
+    pub(crate) fn read_mapped(&mut self, s: &[u8], sources: [ChannelSource; 4]) {
+        let mut i = 0;
+        while i < N {
+            self.0[i] = match sources[i] {
+                ChannelSource::Byte(offset) => s[offset as usize],
+                ChannelSource::Const(value) => value,
+            };
+            i += 1;
+        }
+    }
+
+    /// Gathers the channels of the pixel at byte index `i` within each of the planes.
+    #[inline]
+    pub(crate) fn read_planar(
+        &mut self, planes: &[&[u8]; 4], i: usize, sources: [ChannelSource; 4],
+    ) {
+        let mut c = 0;
+        while c < N {
+            self.0[c] = match sources[c] {
+                ChannelSource::Byte(plane) => planes[plane as usize][i],
+                ChannelSource::Const(value) => value,
+            };
+            c += 1;
+        }
+    }
+
+    #[inline]
+    pub(crate) fn update<const M: usize>(&mut self, px: Pixel<M>) {
         let mut i = 0;
         while i < M && i < N {
             self.0[i] = px.0[i];
@@ -36,14 +180,14 @@ impl<const N: usize> Pixel<N> {
     }
 
     #[inline]
-    pub fn update_rgb(&mut self, r: u8, g: u8, b: u8) {
+    pub(crate) fn update_rgb(&mut self, r: u8, g: u8, b: u8) {
         self.0[0] = r;
         self.0[1] = g;
         self.0[2] = b;
     }
 
     #[inline]
-    pub fn update_rgba(&mut self, r: u8, g: u8, b: u8, a: u8) {
+    pub(crate) fn update_rgba(&mut self, r: u8, g: u8, b: u8, a: u8) {
         self.0[0] = r;
         self.0[1] = g;
         self.0[2] = b;
@@ -53,14 +197,14 @@ impl<const N: usize> Pixel<N> {
     }
 
     #[inline]
-    pub fn update_diff(&mut self, b1: u8) {
+    pub(crate) fn update_diff(&mut self, b1: u8) {
         self.0[0] = self.0[0].wrapping_add((b1 >> 4) & 0x03).wrapping_sub(2);
         self.0[1] = self.0[1].wrapping_add((b1 >> 2) & 0x03).wrapping_sub(2);
         self.0[2] = self.0[2].wrapping_add(b1 & 0x03).wrapping_sub(2);
     }
 
     #[inline]
-    pub fn update_luma(&mut self, b1: u8, b2: u8) {
+    pub(crate) fn update_luma(&mut self, b1: u8, b2: u8) {
         let vg = (b1 & 0x3f).wrapping_sub(32);
         let vg_8 = vg.wrapping_sub(8);
         let vr = vg_8.wrapping_add((b2 >> 4) & 0x0f);
@@ -71,7 +215,7 @@ impl<const N: usize> Pixel<N> {
     }
 
     #[inline]
-    pub const fn as_rgba(self, with_a: u8) -> Pixel<4> {
+    pub(crate) const fn as_rgba(self, with_a: u8) -> Pixel<4> {
         let mut i = 0;
         let mut out = Pixel::new();
         while i < N {
@@ -84,23 +228,26 @@ impl<const N: usize> Pixel<N> {
         out
     }
 
+    /// Returns the red channel value.
     #[inline]
     pub const fn r(self) -> u8 {
         self.0[0]
     }
 
+    /// Returns the green channel value.
     #[inline]
     pub const fn g(self) -> u8 {
         self.0[1]
     }
 
+    /// Returns the blue channel value.
     #[inline]
     pub const fn b(self) -> u8 {
         self.0[2]
     }
 
     #[inline]
-    pub const fn with_a(mut self, value: u8) -> Self {
+    pub(crate) const fn with_a(mut self, value: u8) -> Self {
         if N >= 4 {
             self.0[3] = value;
         }
@@ -108,7 +255,7 @@ impl<const N: usize> Pixel<N> {
     }
 
     #[inline]
-    pub const fn a_or(self, value: u8) -> u8 {
+    pub(crate) const fn a_or(self, value: u8) -> u8 {
         if N < 4 {
             value
         } else {
@@ -116,31 +263,19 @@ impl<const N: usize> Pixel<N> {
         }
     }
 
+    /// Returns the position of the pixel in the 64-entry index of previously seen pixels
+    /// (RGB pixels are hashed with alpha set to 255).
     #[inline]
     #[allow(clippy::cast_lossless, clippy::cast_possible_truncation)]
-    pub fn hash_index(self) -> u8
-    where
-        [u8; N]: Pod,
-    {
+    pub const fn hash_index(self) -> u8 {
         // credits for the initial idea: @zakarumych
-        let v = if N == 4 {
-            u32::from_ne_bytes(cast(self.0))
-        } else {
-            u32::from_ne_bytes([self.0[0], self.0[1], self.0[2], 0xff])
-        } as u64;
+        let v = u32::from_le_bytes([self.0[0], self.0[1], self.0[2], self.a_or(0xff)]) as u64;
         let s = ((v & 0xff00_ff00) << 32) | (v & 0x00ff_00ff);
-        s.wrapping_mul(0x0300_0700_0005_000b_u64).to_le().swap_bytes() as u8 & 63
+        (s.wrapping_mul(0x0300_0700_0005_000b_u64) >> 56) as u8 & 63
     }
 
     #[inline]
-    pub fn rgb_add(&mut self, r: u8, g: u8, b: u8) {
-        self.0[0] = self.0[0].wrapping_add(r);
-        self.0[1] = self.0[1].wrapping_add(g);
-        self.0[2] = self.0[2].wrapping_add(b);
-    }
-
-    #[inline]
-    pub fn encode_into<W: Writer>(&self, px_prev: Self, buf: W) -> Result<W> {
+    pub(crate) fn encode_into<W: Writer>(&self, px_prev: Self, buf: W) -> Result<W> {
         if N == 3 || self.a_or(0) == px_prev.a_or(0) {
             let vg = self.g().wrapping_sub(px_prev.g());
             let vg_32 = vg.wrapping_add(32);
@@ -168,6 +303,31 @@ impl<const N: usize> Pixel<N> {
             buf.write_many(&[QOI_OP_RGBA, self.r(), self.g(), self.b(), self.a_or(0xff)])
         }
     }
+
+    /// Same as `encode_into`, but only ever emits `RGB` and `RGBA` ops.
+    #[inline]
+    pub(crate) fn encode_raw_into<W: Writer>(&self, px_prev: Self, buf: W) -> Result<W> {
+        if N == 3 || self.a_or(0) == px_prev.a_or(0) {
+            buf.write_many(&[QOI_OP_RGB, self.r(), self.g(), self.b()])
+        } else {
+            buf.write_many(&[QOI_OP_RGBA, self.r(), self.g(), self.b(), self.a_or(0xff)])
+        }
+    }
+}
+
+impl Pixel<4> {
+    /// Unpacks an RGBA pixel from a `u32` in `0xRRGGBBAA` format.
+    #[inline]
+    pub const fn from_u32(rgba: u32) -> Self {
+        Self(rgba.to_be_bytes())
+    }
+}
+
+impl<const N: usize> From<[u8; N]> for Pixel<N> {
+    #[inline(always)]
+    fn from(channels: [u8; N]) -> Self {
+        Self(channels)
+    }
 }
 
 impl<const N: usize> From<Pixel<N>> for [u8; N] {
@@ -177,6 +337,7 @@ impl<const N: usize> From<Pixel<N>> for [u8; N] {
     }
 }
 
+#[doc(hidden)]
 pub trait SupportedChannels {}
 
 impl SupportedChannels for Pixel<3> {}
@@ -0,0 +1,152 @@
+//! Decoding QOI images straight into SDL2 streaming textures.
+//!
+//! [`decode_to_texture`] locks a streaming texture and decodes the image into it row by
+//! row, honoring the texture's pitch and converting pixels to its pixel format, so that
+//! no intermediate image buffer or per-project swizzling code is needed:
+//!
+//! ```rust
+//! let mut decoder = qoi::Decoder::new(&data)?;
+//! let header = *decoder.header();
+//! let mut texture = texture_creator.create_texture_streaming(
+//!     PixelFormatEnum::ARGB8888, header.width, header.height,
+//! )?;
+//! qoi::sdl::decode_to_texture(&mut decoder, &mut texture)?;
+//! ```
+//!
+//! Supported are all formats with 8 bits per channel: `RGB24`, `BGR24` and the packed
+//! 32-bit formats like `ARGB8888` or `RGBX8888` (see [`pixel_layout`]). SDL errors and
+//! unsupported textures are reported as [`Error::IoError`].
+
+use std::io;
+
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
+use sdl2::render::{Texture, TextureAccess};
+
+use crate::decode::{Decoder, Reader};
+use crate::error::{Error, Result};
+use crate::source::{ChannelSource, SourceChannels};
+use crate::utils::unlikely;
+
+fn invalid_input(msg: String) -> Error {
+    Error::IoError(io::Error::new(io::ErrorKind::InvalidInput, msg))
+}
+
+/// Returns the in-memory byte layout of a pixel in the given SDL pixel format, or `None`
+/// if the format doesn't have 8 bits per channel.
+///
+/// Packed 32-bit formats like `ARGB8888` are defined by SDL in terms of native-endian
+/// `u32` values, so the byte order depends on the target endianness.
+pub const fn pixel_layout(format: PixelFormatEnum) -> Option<SourceChannels> {
+    #[cfg(target_endian = "little")]
+    let layout = match format {
+        PixelFormatEnum::RGB888 => SourceChannels::Bgrx,
+        PixelFormatEnum::RGBX8888 => SourceChannels::Xbgr,
+        PixelFormatEnum::BGR888 => SourceChannels::Rgbx,
+        PixelFormatEnum::BGRX8888 => SourceChannels::Xrgb,
+        PixelFormatEnum::ARGB8888 => SourceChannels::Bgra,
+        PixelFormatEnum::RGBA8888 => SourceChannels::Abgr,
+        PixelFormatEnum::ABGR8888 => SourceChannels::Rgba,
+        PixelFormatEnum::BGRA8888 => SourceChannels::Argb,
+        _ => return pixel_layout_bytes(format),
+    };
+    #[cfg(target_endian = "big")]
+    let layout = match format {
+        PixelFormatEnum::RGB888 => SourceChannels::Xrgb,
+        PixelFormatEnum::RGBX8888 => SourceChannels::Rgbx,
+        PixelFormatEnum::BGR888 => SourceChannels::Xbgr,
+        PixelFormatEnum::BGRX8888 => SourceChannels::Bgrx,
+        PixelFormatEnum::ARGB8888 => SourceChannels::Argb,
+        PixelFormatEnum::RGBA8888 => SourceChannels::Rgba,
+        PixelFormatEnum::ABGR8888 => SourceChannels::Abgr,
+        PixelFormatEnum::BGRA8888 => SourceChannels::Bgra,
+        _ => return pixel_layout_bytes(format),
+    };
+    Some(layout)
+}
+
+/// Layouts of array formats, which don't depend on the target endianness.
+const fn pixel_layout_bytes(format: PixelFormatEnum) -> Option<SourceChannels> {
+    match format {
+        PixelFormatEnum::RGB24 => Some(SourceChannels::Rgb),
+        PixelFormatEnum::BGR24 => Some(SourceChannels::Bgr),
+        _ => None,
+    }
+}
+
+/// Decodes the rest of the image into a locked pixel buffer of the given SDL pixel format,
+/// where consecutive rows are `pitch` bytes apart, and returns the number of pixels decoded.
+///
+/// This is what [`decode_to_texture`] does under the hood; it can also be used with
+/// buffers locked by other means, e.g. SDL surfaces. The decoder must be positioned at
+/// the start of a row. If the image has no alpha or the decoder drops it, alpha is set to
+/// 255; padding bytes (`X` in formats like `RGBX8888`) are left untouched.
+pub fn decode_to_pixels<R: Reader>(
+    decoder: &mut Decoder<R>, pixels: &mut [u8], pitch: usize, format: PixelFormatEnum,
+) -> Result<usize> {
+    let layout = pixel_layout(format)
+        .ok_or_else(|| invalid_input(format!("unsupported pixel format: {format:?}")))?;
+    let map = layout.channel_map();
+    let (width, n_src) = (decoder.header().width as usize, decoder.channels().as_u8() as usize);
+    let pixel_pos = decoder.header().n_pixels() - decoder.pixels_left();
+    if unlikely(pixel_pos % width != 0) {
+        return Err(Error::DecodingStarted { pixel_pos });
+    }
+    let (n_rows, row_len) = (decoder.pixels_left() / width, width * map.bytes_per_pixel());
+    if unlikely(pitch < row_len) {
+        return Err(Error::InvalidStride { stride: pitch, min_stride: row_len });
+    }
+    let required = if n_rows == 0 { 0 } else { pitch * (n_rows - 1) + row_len };
+    if unlikely(pixels.len() < required) {
+        return Err(Error::OutputBufferTooSmall { size: pixels.len(), required });
+    }
+
+    // destination byte offsets of the R, G, B and (optionally) A channels
+    let mut offsets = [None; 4];
+    for (offset, source) in offsets.iter_mut().zip(map.sources()) {
+        if let ChannelSource::Byte(i) = source {
+            *offset = Some(i as usize);
+        }
+    }
+    let mut row = vec![0; width * n_src];
+    for y in 0..n_rows {
+        let _ = decoder.decode_plain(&mut row)?;
+        let dst = &mut pixels[y * pitch..][..row_len];
+        for (src, dst) in row.chunks_exact(n_src).zip(dst.chunks_exact_mut(map.bytes_per_pixel())) {
+            for (c, offset) in offsets.iter().enumerate() {
+                if let Some(offset) = *offset {
+                    dst[offset] = src.get(c).copied().unwrap_or(0xff);
+                }
+            }
+        }
+    }
+    Ok(n_rows * width)
+}
+
+/// Decodes the image into a streaming texture, converting it to the texture's pixel format.
+///
+/// The texture must have [`TextureAccess::Streaming`] access, be at least as large as the
+/// image, and have one of the pixel formats supported by [`pixel_layout`]; the image is
+/// decoded into its top-left corner. The decoder must not have started decoding yet.
+pub fn decode_to_texture<R: Reader>(decoder: &mut Decoder<R>, texture: &mut Texture) -> Result<()> {
+    let query = texture.query();
+    let header = *decoder.header();
+    if !matches!(query.access, TextureAccess::Streaming) {
+        return Err(invalid_input(format!("expected a streaming texture, got {:?}", query.access)));
+    }
+    if query.width < header.width || query.height < header.height {
+        let (w, h) = (query.width, query.height);
+        let msg =
+            format!("texture is {w}x{h}, too small for {}x{} image", header.width, header.height);
+        return Err(invalid_input(msg));
+    }
+    if decoder.pixels_left() != header.n_pixels() {
+        let pixel_pos = header.n_pixels() - decoder.pixels_left();
+        return Err(Error::DecodingStarted { pixel_pos });
+    }
+    let rect = Rect::new(0, 0, header.width, header.height);
+    texture
+        .with_lock(rect, |pixels, pitch| decode_to_pixels(decoder, pixels, pitch, query.format))
+        .map_err(|err| Error::IoError(io::Error::new(io::ErrorKind::Other, err)))?
+        .map(|_| ())
+}
@@ -0,0 +1,266 @@
+use crate::error::{Error, Result};
+use crate::types::Channels;
+
+/// Where the value of a single output channel comes from.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ChannelSource {
+    /// Byte at the given offset within the source pixel
+    Byte(u8),
+    /// Constant value, regardless of the source pixel
+    Const(u8),
+}
+
+/// Arbitrary mapping from source pixel bytes to RGB(A) channels.
+///
+/// Describes the number of bytes per source pixel, where each of the R, G and B
+/// channels come from, and optionally where the alpha channel comes from (if there's
+/// no alpha, the image will be encoded as RGB). Source bytes that aren't referenced
+/// by any channel are skipped.
+///
+/// When decoding (see [`TargetChannels::Custom`]), the same mapping describes where each
+/// channel is written to instead: channels mapped to constants aren't written anywhere,
+/// and bytes that aren't referenced by any channel are set to 255.
+///
+/// ### Example
+/// A 4-byte layout where bytes 2, 1, 0 are B, G, R, byte 3 is padding and alpha is
+/// a constant 255:
+/// ```rust
+/// use qoi::{ChannelMap, ChannelSource::{Byte, Const}};
+///
+/// let map = ChannelMap::new(4, [Byte(2), Byte(1), Byte(0)], Some(Const(255)))?;
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ChannelMap {
+    bytes_per_pixel: u8,
+    channels: [ChannelSource; 4],
+    has_alpha: bool,
+}
+
+impl ChannelMap {
+    /// Creates a new channel mapping and validates that all byte offsets are in range.
+    pub const fn new(
+        bytes_per_pixel: u8, rgb: [ChannelSource; 3], alpha: Option<ChannelSource>,
+    ) -> Result<Self> {
+        let (a, has_alpha) = match alpha {
+            Some(a) => (a, true),
+            None => (ChannelSource::Const(0xff), false),
+        };
+        let channels = [rgb[0], rgb[1], rgb[2], a];
+        let mut i = 0;
+        while i < 4 {
+            if let ChannelSource::Byte(offset) = channels[i] {
+                if offset >= bytes_per_pixel {
+                    return Err(Error::InvalidChannelMap { bytes_per_pixel, offset });
+                }
+            }
+            i += 1;
+        }
+        if bytes_per_pixel == 0 {
+            return Err(Error::InvalidChannelMap { bytes_per_pixel, offset: 0 });
+        }
+        Ok(Self { bytes_per_pixel, channels, has_alpha })
+    }
+
+    const fn new_unchecked(bytes_per_pixel: u8, rgb: [u8; 3], alpha: Option<u8>) -> Self {
+        let (a, has_alpha) = match alpha {
+            Some(a) => (ChannelSource::Byte(a), true),
+            None => (ChannelSource::Const(0xff), false),
+        };
+        let channels = [
+            ChannelSource::Byte(rgb[0]),
+            ChannelSource::Byte(rgb[1]),
+            ChannelSource::Byte(rgb[2]),
+            a,
+        ];
+        Self { bytes_per_pixel, channels, has_alpha }
+    }
+
+    /// Returns the number of bytes per source pixel.
+    #[inline]
+    pub const fn bytes_per_pixel(&self) -> usize {
+        self.bytes_per_pixel as usize
+    }
+
+    /// Returns the number of channels in the encoded image (RGBA if alpha is mapped).
+    #[inline]
+    pub const fn channels(&self) -> Channels {
+        if self.has_alpha {
+            Channels::Rgba
+        } else {
+            Channels::Rgb
+        }
+    }
+
+    /// Returns the same mapping with alpha either added (as a constant 255) or dropped.
+    #[inline]
+    pub(crate) const fn with_channels(mut self, channels: Channels) -> Self {
+        if channels.is_rgb() {
+            self.channels[3] = ChannelSource::Const(0xff);
+        }
+        self.has_alpha = channels.is_rgba();
+        self
+    }
+
+    /// Returns the sources of the R, G, B and A channels (in this order).
+    #[inline]
+    pub const fn sources(&self) -> [ChannelSource; 4] {
+        self.channels
+    }
+}
+
+/// Pixel layout of the source image data being encoded.
+///
+/// Layouts with alpha are encoded as RGBA images, all others as RGB images;
+/// `X` denotes a padding byte that is ignored.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum SourceChannels {
+    /// 3 bytes per pixel: R, G, B
+    Rgb,
+    /// 3 bytes per pixel: B, G, R
+    Bgr,
+    /// 4 bytes per pixel: R, G, B, A
+    Rgba,
+    /// 4 bytes per pixel: A, R, G, B
+    Argb,
+    /// 4 bytes per pixel: R, G, B, (ignored)
+    Rgbx,
+    /// 4 bytes per pixel: (ignored), R, G, B
+    Xrgb,
+    /// 4 bytes per pixel: B, G, R, A
+    Bgra,
+    /// 4 bytes per pixel: A, B, G, R
+    Abgr,
+    /// 4 bytes per pixel: B, G, R, (ignored)
+    Bgrx,
+    /// 4 bytes per pixel: (ignored), B, G, R
+    Xbgr,
+    /// Arbitrary layout, see [`ChannelMap`]
+    Custom(ChannelMap),
+}
+
+impl SourceChannels {
+    /// Returns the mapping from source pixel bytes to RGB(A) channels.
+    pub const fn channel_map(&self) -> ChannelMap {
+        match *self {
+            Self::Rgb => ChannelMap::new_unchecked(3, [0, 1, 2], None),
+            Self::Bgr => ChannelMap::new_unchecked(3, [2, 1, 0], None),
+            Self::Rgba => ChannelMap::new_unchecked(4, [0, 1, 2], Some(3)),
+            Self::Argb => ChannelMap::new_unchecked(4, [1, 2, 3], Some(0)),
+            Self::Rgbx => ChannelMap::new_unchecked(4, [0, 1, 2], None),
+            Self::Xrgb => ChannelMap::new_unchecked(4, [1, 2, 3], None),
+            Self::Bgra => ChannelMap::new_unchecked(4, [2, 1, 0], Some(3)),
+            Self::Abgr => ChannelMap::new_unchecked(4, [3, 2, 1], Some(0)),
+            Self::Bgrx => ChannelMap::new_unchecked(4, [2, 1, 0], None),
+            Self::Xbgr => ChannelMap::new_unchecked(4, [3, 2, 1], None),
+            Self::Custom(map) => map,
+        }
+    }
+
+    /// Returns the number of bytes per source pixel.
+    #[inline]
+    pub const fn bytes_per_pixel(&self) -> usize {
+        self.channel_map().bytes_per_pixel()
+    }
+
+    /// Returns the number of channels in the encoded image.
+    #[inline]
+    pub const fn channels(&self) -> Channels {
+        self.channel_map().channels()
+    }
+
+    /// Returns true if the source is plain RGB or RGBA that can be encoded as is.
+    #[inline]
+    pub(crate) const fn is_identity(&self) -> bool {
+        matches!(self, Self::Rgb | Self::Rgba)
+    }
+}
+
+impl From<Channels> for SourceChannels {
+    #[inline]
+    fn from(channels: Channels) -> Self {
+        match channels {
+            Channels::Rgb => Self::Rgb,
+            Channels::Rgba => Self::Rgba,
+        }
+    }
+}
+
+/// Pixel layout of the decoded image data, the decoding counterpart of [`SourceChannels`].
+///
+/// Layouts with alpha are decoded as RGBA (see [`Decoder::with_default_alpha`] for RGB
+/// images), all others as RGB with alpha dropped; `X` denotes a padding byte that is set
+/// to 255.
+///
+/// [`Decoder::with_default_alpha`]: crate::Decoder::with_default_alpha
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum TargetChannels {
+    /// 3 bytes per pixel: R, G, B
+    Rgb,
+    /// 3 bytes per pixel: B, G, R
+    Bgr,
+    /// 4 bytes per pixel: R, G, B, A
+    Rgba,
+    /// 4 bytes per pixel: A, R, G, B
+    Argb,
+    /// 4 bytes per pixel: R, G, B, (padding)
+    Rgbx,
+    /// 4 bytes per pixel: (padding), R, G, B
+    Xrgb,
+    /// 4 bytes per pixel: B, G, R, A
+    Bgra,
+    /// 4 bytes per pixel: A, B, G, R
+    Abgr,
+    /// 4 bytes per pixel: B, G, R, (padding)
+    Bgrx,
+    /// 4 bytes per pixel: (padding), B, G, R
+    Xbgr,
+    /// Arbitrary layout, see [`ChannelMap`]
+    Custom(ChannelMap),
+}
+
+impl TargetChannels {
+    /// Returns the mapping from RGB(A) channels to decoded pixel bytes.
+    pub const fn channel_map(&self) -> ChannelMap {
+        match *self {
+            Self::Rgb => ChannelMap::new_unchecked(3, [0, 1, 2], None),
+            Self::Bgr => ChannelMap::new_unchecked(3, [2, 1, 0], None),
+            Self::Rgba => ChannelMap::new_unchecked(4, [0, 1, 2], Some(3)),
+            Self::Argb => ChannelMap::new_unchecked(4, [1, 2, 3], Some(0)),
+            Self::Rgbx => ChannelMap::new_unchecked(4, [0, 1, 2], None),
+            Self::Xrgb => ChannelMap::new_unchecked(4, [1, 2, 3], None),
+            Self::Bgra => ChannelMap::new_unchecked(4, [2, 1, 0], Some(3)),
+            Self::Abgr => ChannelMap::new_unchecked(4, [3, 2, 1], Some(0)),
+            Self::Bgrx => ChannelMap::new_unchecked(4, [2, 1, 0], None),
+            Self::Xbgr => ChannelMap::new_unchecked(4, [3, 2, 1], None),
+            Self::Custom(map) => map,
+        }
+    }
+
+    /// Returns the number of bytes per decoded pixel.
+    #[inline]
+    pub const fn bytes_per_pixel(&self) -> usize {
+        self.channel_map().bytes_per_pixel()
+    }
+
+    /// Returns the number of channels decoded from the image (RGBA if alpha is kept).
+    #[inline]
+    pub const fn channels(&self) -> Channels {
+        self.channel_map().channels()
+    }
+
+    /// Returns true if the target is plain RGB or RGBA that can be decoded into as is.
+    #[inline]
+    pub(crate) const fn is_identity(&self) -> bool {
+        matches!(self, Self::Rgb | Self::Rgba)
+    }
+}
+
+impl From<Channels> for TargetChannels {
+    #[inline]
+    fn from(channels: Channels) -> Self {
+        match channels {
+            Channels::Rgb => Self::Rgb,
+            Channels::Rgba => Self::Rgba,
+        }
+    }
+}
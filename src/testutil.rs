@@ -0,0 +1,210 @@
+//! Synthetic image generators for testing and benchmarking.
+//!
+//! Requires the `test-util` feature. All generators are deterministic given the state
+//! of the random number generator passed in, and return tightly packed RGB(A) pixels.
+
+use alloc::vec::Vec;
+
+use rand::distributions::{Distribution, Standard};
+use rand::Rng;
+
+use crate::pixel::Pixel;
+use crate::types::Channels;
+
+struct GenState<const N: usize> {
+    index: [[u8; N]; 64],
+    pixels: Vec<u8>,
+    prev: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> GenState<N> {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            index: [[0; N]; 64],
+            pixels: Vec::with_capacity(capacity * N),
+            prev: Self::zero(),
+            len: 0,
+        }
+    }
+
+    fn write(&mut self, px: [u8; N]) {
+        self.index[Pixel::from_array(px).hash_index() as usize] = px;
+        self.pixels.extend_from_slice(&px);
+        self.prev = px;
+        self.len += 1;
+    }
+
+    fn pick_from_index(&self, rng: &mut impl Rng) -> [u8; N] {
+        self.index[rng.gen_range(0_usize..64)]
+    }
+
+    const fn zero() -> [u8; N] {
+        let mut px = [0; N];
+        if N >= 4 {
+            px[3] = 0xff;
+        }
+        px
+    }
+}
+
+/// Random image generator that picks each next pixel so as to produce a given mix of
+/// QOI ops: new colors, index lookups, runs, small and medium ("luma") differences.
+///
+/// The generated pixels form a single long row, which makes it handy for hunting
+/// down minimal failing examples in roundtrip tests.
+#[derive(Copy, Clone, Debug)]
+#[allow(clippy::struct_field_names)]
+pub struct ImageGen {
+    p_new: f64,
+    p_index: f64,
+    p_repeat: f64,
+    p_diff: f64,
+    p_luma: f64,
+}
+
+impl ImageGen {
+    /// Creates a generator with the given (relative) op probabilities; the remainder
+    /// up to 1 is the probability of emitting an opaque black pixel.
+    pub const fn new(p_new: f64, p_index: f64, p_repeat: f64, p_diff: f64, p_luma: f64) -> Self {
+        Self { p_new, p_index, p_repeat, p_diff, p_luma }
+    }
+
+    /// Creates a generator with random op probabilities.
+    pub fn new_random(rng: &mut impl Rng) -> Self {
+        let p: [f64; 6] = rng.gen();
+        let t = p.iter().sum::<f64>();
+        Self::new(p[0] / t, p[1] / t, p[2] / t, p[3] / t, p[4] / t)
+    }
+
+    /// Generates at least `min_len` pixels (runs may overshoot it slightly).
+    pub fn generate(&self, rng: &mut impl Rng, channels: Channels, min_len: usize) -> Vec<u8> {
+        match channels {
+            Channels::Rgb => self.generate_const::<_, 3>(rng, min_len),
+            Channels::Rgba => self.generate_const::<_, 4>(rng, min_len),
+        }
+    }
+
+    fn generate_const<R: Rng, const N: usize>(&self, rng: &mut R, min_len: usize) -> Vec<u8>
+    where
+        Standard: Distribution<[u8; N]>,
+    {
+        let mut s = GenState::<N>::with_capacity(min_len);
+        let zero = GenState::<N>::zero();
+
+        while s.len < min_len {
+            let mut p = rng.gen_range(0.0..1.0);
+
+            if p < self.p_new {
+                s.write(rng.gen());
+                continue;
+            }
+            p -= self.p_new;
+
+            if p < self.p_index {
+                let px = s.pick_from_index(rng);
+                s.write(px);
+                continue;
+            }
+            p -= self.p_index;
+
+            if p < self.p_repeat {
+                let px = s.prev;
+                let n_repeat = rng.gen_range(1_usize..=70);
+                for _ in 0..n_repeat {
+                    s.write(px);
+                }
+                continue;
+            }
+            p -= self.p_repeat;
+
+            if p < self.p_diff {
+                let mut px = s.prev;
+                px[0] = px[0].wrapping_add(rng.gen_range(0_u8..4).wrapping_sub(2));
+                px[1] = px[1].wrapping_add(rng.gen_range(0_u8..4).wrapping_sub(2));
+                px[2] = px[2].wrapping_add(rng.gen_range(0_u8..4).wrapping_sub(2));
+                s.write(px);
+                continue;
+            }
+            p -= self.p_diff;
+
+            if p < self.p_luma {
+                let mut px = s.prev;
+                let vg = rng.gen_range(0_u8..64).wrapping_sub(32);
+                let vr = rng.gen_range(0_u8..16).wrapping_sub(8).wrapping_add(vg);
+                let vb = rng.gen_range(0_u8..16).wrapping_sub(8).wrapping_add(vg);
+                px[0] = px[0].wrapping_add(vr);
+                px[1] = px[1].wrapping_add(vg);
+                px[2] = px[2].wrapping_add(vb);
+                s.write(px);
+                continue;
+            }
+
+            s.write(zero);
+        }
+
+        s.pixels
+    }
+}
+
+fn image_from_fn(
+    width: u32, height: u32, channels: Channels, mut f: impl FnMut(u32, u32) -> [u8; 4],
+) -> Vec<u8> {
+    let n = channels.as_u8() as usize;
+    let mut out = Vec::with_capacity(width as usize * height as usize * n);
+    for y in 0..height {
+        for x in 0..width {
+            out.extend_from_slice(&f(x, y)[..n]);
+        }
+    }
+    out
+}
+
+/// Smooth gradient with alpha fading out horizontally, mostly encoded as DIFF and LUMA ops.
+#[allow(clippy::cast_possible_truncation)]
+pub fn gradient(width: u32, height: u32, channels: Channels) -> Vec<u8> {
+    image_from_fn(width, height, channels, |x, y| {
+        [(x / 2) as u8, (y / 2) as u8, ((x + y) / 4) as u8, 0xff - (x / 4) as u8]
+    })
+}
+
+/// Uniform random noise, mostly encoded as RGB(A) ops; this is the worst case for QOI.
+pub fn noise(rng: &mut impl Rng, width: u32, height: u32, channels: Channels) -> Vec<u8> {
+    let n_bytes = width as usize * height as usize * channels.as_u8() as usize;
+    (0..n_bytes).map(|_| rng.gen()).collect()
+}
+
+/// Screenshot-like image: flat background with a couple of colored panels and sparse
+/// "text" glyph noise, mostly encoded as runs and index lookups.
+pub fn screenshot(rng: &mut impl Rng, width: u32, height: u32, channels: Channels) -> Vec<u8> {
+    let palette =
+        [[240, 240, 240, 0xff], [30, 30, 30, 0xff], [40, 90, 200, 0xff], [250, 250, 210, 0xc0]];
+    image_from_fn(width, height, channels, |x, y| {
+        let panel = if y < 32 {
+            2
+        } else if x < 128 {
+            3
+        } else {
+            0
+        };
+        let is_text = y % 16 < 10 && x % 8 < 6 && rng.gen_ratio(1, 3);
+        if is_text {
+            palette[1]
+        } else {
+            palette[panel]
+        }
+    })
+}
+
+/// Random colors repeated for random stretches of up to 200 pixels, mostly encoded as runs.
+pub fn runs(rng: &mut impl Rng, width: u32, height: u32, channels: Channels) -> Vec<u8> {
+    let (n, channels) = (width as usize * height as usize, channels.as_u8() as usize);
+    let mut out = Vec::with_capacity(n * channels);
+    while out.len() < n * channels {
+        let px: [u8; 4] = rng.gen();
+        for _ in 0..rng.gen_range(1..200).min(n - out.len() / channels) {
+            out.extend_from_slice(&px[..channels]);
+        }
+    }
+    out
+}
@@ -0,0 +1,196 @@
+use std::io::{Read, Write};
+
+use bytemuck::Pod;
+
+use crate::consts::QOI_HEADER_SIZE;
+use crate::decode::Decoder;
+use crate::encode::{encode_impl, MODE_DEFAULT};
+use crate::error::{Error, Result};
+use crate::header::HeaderOptions;
+use crate::io::GenericWriter;
+use crate::pixel::{Pixel, SupportedChannels};
+use crate::types::{Channels, ColorSpace};
+use crate::utils::Writer;
+
+/// Default number of rows decoded at a time when transcoding.
+const DEFAULT_STRIP_ROWS: usize = 16;
+
+/// Options for [`transcode_stream`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TranscodeOptions {
+    channels: Option<Channels>,
+    colorspace: Option<ColorSpace>,
+    header_options: HeaderOptions,
+    strict: bool,
+    strip_rows: usize,
+}
+
+impl Default for TranscodeOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TranscodeOptions {
+    /// Creates the default options: channels and color space are kept as is.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            channels: None,
+            colorspace: None,
+            header_options: HeaderOptions::new(),
+            strict: false,
+            strip_rows: DEFAULT_STRIP_ROWS,
+        }
+    }
+
+    /// Sets the number of channels in the output image (same as the input by default).
+    ///
+    /// Converting RGB to RGBA sets alpha to 255, converting RGBA to RGB drops alpha.
+    #[inline]
+    pub const fn with_channels(mut self, channels: Channels) -> Self {
+        self.channels = Some(channels);
+        self
+    }
+
+    /// Sets the color space stored in the output header (same as the input by default).
+    #[inline]
+    pub const fn with_colorspace(mut self, colorspace: ColorSpace) -> Self {
+        self.colorspace = Some(colorspace);
+        self
+    }
+
+    /// Sets the header validation options for the input image.
+    #[inline]
+    pub const fn with_header_options(mut self, header_options: HeaderOptions) -> Self {
+        self.header_options = header_options;
+        self
+    }
+
+    /// Enables or disables strict decoding of the input image, see [`Decoder::with_strict`].
+    #[inline]
+    pub const fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets the number of rows decoded at a time (16 by default, at least 1).
+    ///
+    /// This determines the memory used for transcoding: a strip of `strip_rows * width`
+    /// pixels is allocated once, regardless of the image height.
+    #[inline]
+    pub const fn with_strip_rows(mut self, strip_rows: usize) -> Self {
+        self.strip_rows = strip_rows;
+        self
+    }
+
+    /// Returns the number of channels in the output image, if set.
+    #[inline]
+    pub const fn channels(&self) -> Option<Channels> {
+        self.channels
+    }
+
+    /// Returns the color space stored in the output header, if set.
+    #[inline]
+    pub const fn colorspace(&self) -> Option<ColorSpace> {
+        self.colorspace
+    }
+
+    /// Returns the number of rows decoded at a time.
+    #[inline]
+    pub const fn strip_rows(&self) -> usize {
+        self.strip_rows
+    }
+}
+
+/// Pixels decoded strip by strip; decoding stops at the first error which is stored.
+struct StripPixels<'a, R, const N: usize> {
+    decoder: &'a mut Decoder<R>,
+    strip: Vec<u8>,
+    pos: usize,
+    len: usize,
+    error: Option<Error>,
+}
+
+impl<R: Read, const N: usize> Iterator for StripPixels<'_, R, N>
+where
+    Pixel<N>: SupportedChannels,
+{
+    type Item = Pixel<N>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Pixel<N>> {
+        if self.pos == self.len {
+            if self.error.is_some() || self.decoder.pixels_left() == 0 {
+                return None;
+            }
+            match self.decoder.decode_pixels(&mut self.strip) {
+                Ok(n_pixels) => (self.pos, self.len) = (0, n_pixels * N),
+                Err(err) => {
+                    self.error = Some(err);
+                    return None;
+                }
+            }
+        }
+        let mut px = Pixel::<N>::new();
+        px.read(&self.strip[self.pos..self.pos + N]);
+        self.pos += N;
+        Some(px)
+    }
+}
+
+fn transcode_impl<R: Read, W: Write, const N: usize>(
+    decoder: &mut Decoder<R>, out: GenericWriter<&mut W>, strip_rows: usize,
+) -> Result<usize>
+where
+    Pixel<N>: SupportedChannels,
+    [u8; N]: Pod,
+{
+    let n_pixels = decoder.header().n_pixels();
+    let strip_len = (decoder.header().width as usize).saturating_mul(strip_rows.max(1)).max(1);
+    let mut pixels = StripPixels::<R, N> {
+        decoder,
+        strip: vec![0; strip_len.min(n_pixels.max(1)) * N],
+        pos: 0,
+        len: 0,
+        error: None,
+    };
+    let n_written = encode_impl::<_, _, N, MODE_DEFAULT>(out, &mut pixels, n_pixels)?;
+    if let Some(err) = pixels.error {
+        return Err(err);
+    }
+    if n_pixels == 0 {
+        // empty images have no pixels to decode, but the end marker still has to be checked
+        decoder.decode_to_buf([])?;
+    }
+    Ok(n_written)
+}
+
+/// Decodes a QOI image from a reader and re-encodes it into a writer in bounded memory,
+/// optionally changing the number of channels and the color space tag.
+///
+/// The image is processed in strips of rows (see [`TranscodeOptions::with_strip_rows`]),
+/// so the full frame is never held in memory. Returns the total number of bytes written.
+///
+/// Both the reader and the writer are accessed in small chunks, so wrapping them into
+/// [`BufReader`](std::io::BufReader) and [`BufWriter`](std::io::BufWriter) is recommended.
+/// If the input turns out to be invalid midway, the output will be incomplete.
+pub fn transcode_stream<R: Read, W: Write>(
+    reader: R, writer: &mut W, options: TranscodeOptions,
+) -> Result<usize> {
+    let decoder = Decoder::from_stream_with_options(reader, options.header_options)?;
+    let header = *decoder.header();
+    let channels = options.channels.unwrap_or(header.channels);
+    let mut decoder = decoder.with_channels(channels)?.with_strict(options.strict);
+    let header = header
+        .with_channels(channels)
+        .with_colorspace(options.colorspace.unwrap_or(header.colorspace));
+
+    let out = GenericWriter::new(&mut *writer).write_many(&header.encode())?;
+    let n_written = match channels {
+        Channels::Rgb => transcode_impl::<_, _, 3>(&mut decoder, out, options.strip_rows)?,
+        Channels::Rgba => transcode_impl::<_, _, 4>(&mut decoder, out, options.strip_rows)?,
+    };
+    Ok(QOI_HEADER_SIZE + n_written)
+}
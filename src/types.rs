@@ -1,4 +1,5 @@
 use core::convert::TryFrom;
+use core::fmt::{self, Display};
 
 use crate::error::{Error, Result};
 use crate::utils::unlikely;
@@ -7,10 +8,11 @@ use crate::utils::unlikely;
 ///
 /// Note: the color space is purely informative. Although it is saved to the
 /// file header, it does not affect encoding/decoding in any way.
-#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord, Default)]
 #[repr(u8)]
 pub enum ColorSpace {
     /// sRGB with linear alpha
+    #[default]
     Srgb = 0,
     /// All channels are linear
     Linear = 1,
@@ -33,9 +35,12 @@ impl ColorSpace {
     }
 }
 
-impl Default for ColorSpace {
-    fn default() -> Self {
-        Self::Srgb
+impl Display for ColorSpace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match *self {
+            Self::Srgb => "sRGB",
+            Self::Linear => "linear",
+        })
     }
 }
 
@@ -60,10 +65,11 @@ impl TryFrom<u8> for ColorSpace {
 }
 
 /// Number of 8-bit channels in a pixel.
-#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord, Default)]
 #[repr(u8)]
 pub enum Channels {
     /// Three 8-bit channels (RGB)
+    #[default]
     Rgb = 3,
     /// Four 8-bit channels (RGBA)
     Rgba = 4,
@@ -86,9 +92,12 @@ impl Channels {
     }
 }
 
-impl Default for Channels {
-    fn default() -> Self {
-        Self::Rgb
+impl Display for Channels {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match *self {
+            Self::Rgb => "RGB",
+            Self::Rgba => "RGBA",
+        })
     }
 }
 
@@ -1,6 +1,3 @@
-#[cfg(feature = "std")]
-use std::io::Write;
-
 use crate::error::Result;
 
 #[inline(always)]
@@ -59,7 +56,7 @@ impl<'a> BytesMut<'a> {
     }
 }
 
-impl<'a> Writer for BytesMut<'a> {
+impl Writer for BytesMut<'_> {
     #[inline]
     fn write_one(self, v: u8) -> Result<Self> {
         Ok(BytesMut::write_one(self, v))
@@ -75,33 +72,3 @@ impl<'a> Writer for BytesMut<'a> {
         self.0.len()
     }
 }
-
-#[cfg(feature = "std")]
-pub struct GenericWriter<W> {
-    writer: W,
-    n_written: usize,
-}
-
-#[cfg(feature = "std")]
-impl<W: Write> GenericWriter<W> {
-    pub const fn new(writer: W) -> Self {
-        Self { writer, n_written: 0 }
-    }
-}
-
-#[cfg(feature = "std")]
-impl<W: Write> Writer for GenericWriter<W> {
-    fn write_one(mut self, v: u8) -> Result<Self> {
-        self.n_written += 1;
-        self.writer.write_all(&[v]).map(|()| self).map_err(Into::into)
-    }
-
-    fn write_many(mut self, v: &[u8]) -> Result<Self> {
-        self.n_written += v.len();
-        self.writer.write_all(v).map(|()| self).map_err(Into::into)
-    }
-
-    fn capacity(&self) -> usize {
-        usize::MAX - self.n_written
-    }
-}
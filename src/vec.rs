@@ -0,0 +1,97 @@
+//! The allocating layer: decoding and encoding into newly allocated vectors, available
+//! with either the `alloc` or the `std` feature.
+
+use alloc::{vec, vec::Vec};
+
+use crate::consts::QOI_HEADER_SIZE;
+use crate::decode::{Decoder, Diagnostic, Reader};
+use crate::encode::{Encoder, OwnedEncoder};
+use crate::error::Result;
+use crate::header::Header;
+
+/// Decode the image into a newly allocated vector.
+///
+/// Note: the resulting number of channels will match the header. In order to change
+/// the number of channels, use [`Decoder::with_channels`].
+#[inline]
+pub fn decode_to_vec(data: impl AsRef<[u8]>) -> Result<(Header, Vec<u8>)> {
+    let mut decoder = Decoder::new(&data)?;
+    let out = decoder.decode_to_vec()?;
+    Ok((*decoder.header(), out))
+}
+
+/// Decode a headerless image body into a newly allocated vector, see [`Decoder::new_body`].
+#[inline]
+pub fn decode_body_to_vec(data: impl AsRef<[u8]>, header: Header) -> Result<Vec<u8>> {
+    Decoder::new_body(&data, header)?.decode_to_vec()
+}
+
+/// Encode the image into a newly allocated vector.
+#[inline]
+pub fn encode_to_vec(data: impl AsRef<[u8]>, width: u32, height: u32) -> Result<Vec<u8>> {
+    Encoder::new(&data, width, height)?.encode_to_vec()
+}
+
+/// Encode a headerless image body into a newly allocated vector, see [`Encoder::encode_body_to_buf`].
+#[inline]
+pub fn encode_body_to_vec(data: impl AsRef<[u8]>, width: u32, height: u32) -> Result<Vec<u8>> {
+    Encoder::new(&data, width, height)?.encode_body_to_vec()
+}
+
+impl<R: Reader> Decoder<R> {
+    /// Decodes the image into a newly allocated vector of bytes and returns it.
+    #[inline]
+    pub fn decode_to_vec(&mut self) -> Result<Vec<u8>> {
+        let mut out = vec![0; self.required_buf_len()];
+        let _ = self.decode_to_buf(&mut out)?;
+        Ok(out)
+    }
+
+    /// Same as [`Decoder::decode_to_vec`], but also reports recoverable anomalies
+    /// to the callback, see [`Decoder::decode_to_buf_with_diagnostics`].
+    #[inline]
+    pub fn decode_to_vec_with_diagnostics(
+        &mut self, diag: impl FnMut(Diagnostic),
+    ) -> Result<Vec<u8>> {
+        let mut out = vec![0; self.required_buf_len()];
+        let _ = self.decode_to_buf_with_diagnostics(&mut out, diag)?;
+        Ok(out)
+    }
+
+    /// Same as [`Decoder::decode_to_planes`], but returns newly allocated planes.
+    #[inline]
+    pub fn decode_to_planes_vec(&mut self) -> Result<Vec<Vec<u8>>> {
+        let mut planes = vec![vec![0; self.pixels_left()]; self.channels().as_u8() as usize];
+        let _ = self.decode_to_planes(&mut planes)?;
+        Ok(planes)
+    }
+}
+
+impl Encoder<'_> {
+    /// Encodes the image into a newly allocated vector of bytes and returns it.
+    #[inline]
+    pub fn encode_to_vec(&self) -> Result<Vec<u8>> {
+        let mut out = vec![0_u8; self.required_buf_len()];
+        let size = self.encode_to_buf(&mut out)?;
+        out.truncate(size);
+        Ok(out)
+    }
+
+    /// Encodes the image body without the header into a newly allocated vector of bytes,
+    /// see [`Encoder::encode_body_to_buf`].
+    #[inline]
+    pub fn encode_body_to_vec(&self) -> Result<Vec<u8>> {
+        let mut out = vec![0_u8; self.required_buf_len() - QOI_HEADER_SIZE];
+        let size = self.encode_body_to_buf(&mut out)?;
+        out.truncate(size);
+        Ok(out)
+    }
+}
+
+impl<D: AsRef<[u8]>> OwnedEncoder<D> {
+    /// Encodes the image into a newly allocated vector, see [`Encoder::encode_to_vec`].
+    #[inline]
+    pub fn encode_to_vec(&self) -> Result<Vec<u8>> {
+        self.encoder().encode_to_vec()
+    }
+}
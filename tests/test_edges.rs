@@ -0,0 +1,243 @@
+mod common;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use libqoi::{qoi_decode, qoi_encode};
+use qoi::consts::{
+    QOI_HEADER_SIZE, QOI_OP_INDEX, QOI_OP_RGB, QOI_OP_RUN, QOI_PADDING, QOI_PIXELS_MAX,
+};
+use qoi::{
+    ChannelSource, Channels, ColorSpace, Decoder, EncoderBuilder, ErrorKind, Header, SourceChannels,
+};
+
+use self::common::hash;
+
+/// Image dimensions at the edges: single pixel, single row/column, and lengths around
+/// the maximum run length (62) and the index size (64).
+const EDGE_DIMS: &[(u32, u32)] = &[
+    (1, 1),
+    (1, 2),
+    (2, 1),
+    (1, 62),
+    (62, 1),
+    (1, 63),
+    (63, 1),
+    (1, 64),
+    (64, 1),
+    (1, 65),
+    (65, 1),
+    (1, 125),
+    (125, 1),
+    (1, 300),
+    (300, 1),
+    (3, 67),
+    (67, 3),
+];
+
+const SOURCES_RGB: &[SourceChannels] = &[
+    SourceChannels::Rgb,
+    SourceChannels::Bgr,
+    SourceChannels::Rgbx,
+    SourceChannels::Xrgb,
+    SourceChannels::Bgrx,
+    SourceChannels::Xbgr,
+];
+
+const SOURCES_RGBA: &[SourceChannels] =
+    &[SourceChannels::Rgba, SourceChannels::Argb, SourceChannels::Bgra, SourceChannels::Abgr];
+
+/// RGBA test patterns of the given number of pixels, each targeting different ops at the
+/// end of the image: runs ending exactly at the last pixel, index lookups right after
+/// runs, a single odd pixel at the very end, and generic smooth/noisy content.
+fn patterns(rng: &mut impl Rng, n: usize) -> Vec<(&'static str, Vec<[u8; 4]>)> {
+    let palette = [[10, 20, 30, 0xff], [200, 100, 50, 0x80], [10, 20, 30, 0x40]];
+    let mut last_differs = vec![[7, 7, 7, 0xff]; n];
+    last_differs[n - 1] = [8, 7, 7, 0xff];
+    vec![
+        ("constant", vec![[1, 2, 3, 0xff]; n]),
+        ("black", vec![[0, 0, 0, 0xff]; n]),
+        ("last-differs", last_differs),
+        ("run-index", (0..n).map(|i| palette[(i / 3) % palette.len()]).collect()),
+        ("index-run", (0..n).map(|i| palette[i % 2 + usize::from(i % 7 == 6)]).collect()),
+        (
+            "gradient",
+            (0..n).map(|i| [i as u8, (i / 2) as u8, (i / 4) as u8, 0xff - (i / 8) as u8]).collect(),
+        ),
+        ("noise", (0..n).map(|_| rng.gen()).collect()),
+    ]
+}
+
+fn to_channels(pixels: &[[u8; 4]], channels: Channels) -> Vec<u8> {
+    let n = channels.as_u8() as usize;
+    pixels.iter().flat_map(|px| px[..n].iter().copied()).collect()
+}
+
+fn to_source(pixels: &[[u8; 4]], source: SourceChannels) -> Vec<u8> {
+    let map = source.channel_map();
+    let mut out = Vec::with_capacity(pixels.len() * map.bytes_per_pixel());
+    for px in pixels {
+        let mut bytes = vec![0x5a; map.bytes_per_pixel()];
+        for (value, src) in px.iter().zip(map.sources()) {
+            if let ChannelSource::Byte(offset) = src {
+                bytes[offset as usize] = *value;
+            }
+        }
+        out.extend(bytes);
+    }
+    out
+}
+
+fn check_decode(name: &str, encoded: &[u8], channels: Channels, expected: &[u8]) {
+    let n = channels.as_u8() as usize;
+    assert_eq!(qoi::decode_to_vec(encoded).unwrap().1, expected, "{}: slice", name);
+    let decoded = Decoder::from_stream(encoded).unwrap().decode_to_vec().unwrap();
+    assert_eq!(decoded, expected, "{}: stream", name);
+
+    // one pixel at a time, so that every run and index op is split across calls
+    let mut decoder = Decoder::new(encoded).unwrap().with_channels(channels).unwrap();
+    let decoded = decode_pixel_by_pixel(|px| decoder.decode_pixels(px), expected.len(), n);
+    assert_eq!(decoded, expected, "{}: slice pixels", name);
+    let mut decoder = Decoder::from_stream(encoded).unwrap();
+    let decoded = decode_pixel_by_pixel(|px| decoder.decode_pixels(px), expected.len(), n);
+    assert_eq!(decoded, expected, "{}: stream pixels", name);
+
+    let (_, decoded_c) = qoi_decode(encoded, channels.as_u8()).unwrap();
+    assert_eq!(decoded_c.as_ref(), expected, "{}: qoi.h", name);
+}
+
+fn decode_pixel_by_pixel(
+    mut decode: impl FnMut(&mut [u8]) -> qoi::Result<usize>, n_bytes: usize, channels: usize,
+) -> Vec<u8> {
+    let mut decoded = vec![0; n_bytes];
+    for px in decoded.chunks_exact_mut(channels) {
+        assert_eq!(decode(px).unwrap(), 1);
+    }
+    decoded
+}
+
+#[test]
+fn test_edge_dims() {
+    let mut rng = StdRng::seed_from_u64(0);
+    for &(w, h) in EDGE_DIMS {
+        let n = (w * h) as usize;
+        for (pattern, rgba) in patterns(&mut rng, n) {
+            for (channels, sources) in
+                [(Channels::Rgb, SOURCES_RGB), (Channels::Rgba, SOURCES_RGBA)]
+            {
+                let name = format!("{}x{}:{} {}", w, h, channels.as_u8(), pattern);
+                let pixels = to_channels(&rgba, channels);
+                let encoded = qoi::encode_to_vec(&pixels, w, h).unwrap();
+                let header = qoi::decode_header(&encoded).unwrap();
+                assert_eq!((header.width, header.height, header.channels), (w, h, channels));
+                check_decode(&name, &encoded, channels, &pixels);
+
+                // qoi.h must be able to decode our output and vice versa
+                let encoded_c = qoi_encode(&pixels, w, h, channels.as_u8()).unwrap();
+                check_decode(&format!("{} (qoi.h)", name), encoded_c.as_ref(), channels, &pixels);
+                #[cfg(feature = "reference")]
+                assert_eq!(encoded, encoded_c.as_ref(), "{}: reference", name);
+
+                for &source in sources {
+                    let name = format!("{} {:?}", name, source);
+                    let data = to_source(&rgba, source);
+                    let encoder = EncoderBuilder::new(&data, w, h)
+                        .with_source_channels(source)
+                        .build()
+                        .unwrap();
+                    assert_eq!(encoder.encode_to_vec().unwrap(), encoded, "{}: slice", name);
+                    let mut stream = Vec::new();
+                    let n_written = encoder.encode_to_stream(&mut stream).unwrap();
+                    assert_eq!(n_written, stream.len());
+                    assert_eq!(stream, encoded, "{}: stream", name);
+                }
+
+                // converting between RGB and RGBA on decode
+                let other = if channels.is_rgb() { Channels::Rgba } else { Channels::Rgb };
+                let opaque: Vec<_> = rgba.iter().map(|p| [p[0], p[1], p[2], 0xff]).collect();
+                let expected = to_channels(if channels.is_rgb() { &opaque } else { &rgba }, other);
+                let decoded =
+                    Decoder::new(&encoded).unwrap().with_channels(other).unwrap().decode_to_vec();
+                assert_eq!(decoded.unwrap(), expected, "{}: convert", name);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_max_dims() {
+    let max = QOI_PIXELS_MAX as u32;
+    let valid = [(max, 1), (1, max), (20_000, 20_000), (max / 3, 3)];
+    let invalid =
+        [(max + 1, 1), (1, max + 1), (20_000, 20_001), (u32::MAX, 1), (u32::MAX, u32::MAX)];
+    for (channels, colorspace) in
+        [(Channels::Rgb, ColorSpace::Srgb), (Channels::Rgba, ColorSpace::Linear)]
+    {
+        for (w, h) in valid {
+            let header = Header::try_new(w, h, channels, colorspace).unwrap();
+            assert_eq!(qoi::decode_header(header.encode()).unwrap(), header);
+            assert!(qoi::encode_max_len(w, h, channels) > header.n_bytes());
+        }
+        for (w, h) in invalid {
+            let err = Header::try_new(w, h, channels, colorspace).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidImageDimensions);
+            let err = qoi::Encoder::new(&[], w, h).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidImageDimensions);
+            let mut header = Header::try_new(1, 1, channels, colorspace).unwrap().encode();
+            header[4..8].copy_from_slice(&w.to_be_bytes());
+            header[8..12].copy_from_slice(&h.to_be_bytes());
+            let err = qoi::decode_header(header).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidImageDimensions);
+        }
+    }
+}
+
+fn check_max_size_decode(mut decode: impl FnMut(&mut [u8]) -> qoi::Result<usize>) {
+    let mut buf = vec![0; 3 << 20];
+    let mut n_decoded = 0;
+    while n_decoded < QOI_PIXELS_MAX {
+        let n = decode(&mut buf).unwrap();
+        let chunk = &buf[..n * 3];
+        let (head, tail) = if n_decoded + n == QOI_PIXELS_MAX {
+            chunk.split_at(chunk.len() - 6)
+        } else {
+            (chunk, &[][..])
+        };
+        assert!(head.chunks_exact(3).all(|px| px == [1, 2, 3]));
+        assert!(tail.is_empty() || tail == [9, 9, 9, 1, 2, 3]);
+        n_decoded += n;
+    }
+    assert_eq!(n_decoded, QOI_PIXELS_MAX);
+    assert_eq!(decode(&mut buf).unwrap(), 0);
+}
+
+/// Builds a QOI_PIXELS_MAX-sized image out of runs, ending in an index op right after a
+/// run, so that it can be decoded in chunks without allocating the whole image.
+fn max_size_image(width: u32, height: u32) -> Vec<u8> {
+    let header = Header::try_new(width, height, Channels::Rgb, ColorSpace::Srgb).unwrap();
+    let n_pixels = header.n_pixels();
+    assert_eq!(n_pixels, QOI_PIXELS_MAX);
+    let mut data = header.encode().to_vec();
+    data.extend([QOI_OP_RGB, 1, 2, 3]);
+    let mut n_run = n_pixels - 3;
+    while n_run > 0 {
+        let n = n_run.min(62);
+        data.push(QOI_OP_RUN | (n - 1) as u8);
+        n_run -= n;
+    }
+    data.extend([QOI_OP_RGB, 9, 9, 9]);
+    data.push(QOI_OP_INDEX | hash([1_u8, 2, 3]));
+    data.extend(QOI_PADDING);
+    data
+}
+
+#[test]
+fn test_max_dims_decode() {
+    for (w, h) in [(QOI_PIXELS_MAX as u32, 1), (1, QOI_PIXELS_MAX as u32)] {
+        let data = max_size_image(w, h);
+        assert_eq!(data[QOI_HEADER_SIZE], QOI_OP_RGB);
+        let mut decoder = Decoder::new(&data).unwrap();
+        check_max_size_decode(|buf| decoder.decode_pixels(buf));
+        let mut decoder = Decoder::from_stream(data.as_slice()).unwrap();
+        check_max_size_decode(|buf| decoder.decode_pixels(buf));
+    }
+}
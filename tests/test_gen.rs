@@ -1,153 +1,17 @@
-mod common;
-
 use bytemuck::cast_slice;
 use std::borrow::Cow;
 use std::fmt::Debug;
 
 use cfg_if::cfg_if;
-use rand::{
-    distributions::{Distribution, Standard},
-    rngs::StdRng,
-    Rng, SeedableRng,
-};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use libqoi::{qoi_decode, qoi_encode};
 use qoi::consts::{
     QOI_HEADER_SIZE, QOI_MASK_2, QOI_OP_DIFF, QOI_OP_INDEX, QOI_OP_LUMA, QOI_OP_RGB, QOI_OP_RGBA,
     QOI_OP_RUN, QOI_PADDING_SIZE,
 };
-use qoi::{decode_header, decode_to_vec, encode_to_vec};
-
-use self::common::hash;
-
-struct GenState<const N: usize> {
-    index: [[u8; N]; 64],
-    pixels: Vec<u8>,
-    prev: [u8; N],
-    len: usize,
-}
-
-impl<const N: usize> GenState<N> {
-    pub fn with_capacity(capacity: usize) -> Self {
-        Self {
-            index: [[0; N]; 64],
-            pixels: Vec::with_capacity(capacity * N),
-            prev: Self::zero(),
-            len: 0,
-        }
-    }
-    pub fn write(&mut self, px: [u8; N]) {
-        self.index[hash(px) as usize] = px;
-        for i in 0..N {
-            self.pixels.push(px[i]);
-        }
-        self.prev = px;
-        self.len += 1;
-    }
-
-    pub fn pick_from_index(&self, rng: &mut impl Rng) -> [u8; N] {
-        self.index[rng.gen_range(0_usize..64)]
-    }
-
-    pub fn zero() -> [u8; N] {
-        let mut px = [0; N];
-        if N >= 4 {
-            px[3] = 0xff;
-        }
-        px
-    }
-}
-
-struct ImageGen {
-    p_new: f64,
-    p_index: f64,
-    p_repeat: f64,
-    p_diff: f64,
-    p_luma: f64,
-}
-
-impl ImageGen {
-    pub fn new_random(rng: &mut impl Rng) -> Self {
-        let p: [f64; 6] = rng.gen();
-        let t = p.iter().sum::<f64>();
-        Self {
-            p_new: p[0] / t,
-            p_index: p[1] / t,
-            p_repeat: p[2] / t,
-            p_diff: p[3] / t,
-            p_luma: p[4] / t,
-        }
-    }
-
-    pub fn generate(&self, rng: &mut impl Rng, channels: usize, min_len: usize) -> Vec<u8> {
-        match channels {
-            3 => self.generate_const::<_, 3>(rng, min_len),
-            4 => self.generate_const::<_, 4>(rng, min_len),
-            _ => panic!(),
-        }
-    }
-
-    fn generate_const<R: Rng, const N: usize>(&self, rng: &mut R, min_len: usize) -> Vec<u8>
-    where
-        Standard: Distribution<[u8; N]>,
-    {
-        let mut s = GenState::<N>::with_capacity(min_len);
-        let zero = GenState::<N>::zero();
-
-        while s.len < min_len {
-            let mut p = rng.gen_range(0.0..1.0);
-
-            if p < self.p_new {
-                s.write(rng.gen());
-                continue;
-            }
-            p -= self.p_new;
-
-            if p < self.p_index {
-                let px = s.pick_from_index(rng);
-                s.write(px);
-                continue;
-            }
-            p -= self.p_index;
-
-            if p < self.p_repeat {
-                let px = s.prev;
-                let n_repeat = rng.gen_range(1_usize..=70);
-                for _ in 0..n_repeat {
-                    s.write(px);
-                }
-                continue;
-            }
-            p -= self.p_repeat;
-
-            if p < self.p_diff {
-                let mut px = s.prev;
-                px[0] = px[0].wrapping_add(rng.gen_range(0_u8..4).wrapping_sub(2));
-                px[1] = px[1].wrapping_add(rng.gen_range(0_u8..4).wrapping_sub(2));
-                px[2] = px[2].wrapping_add(rng.gen_range(0_u8..4).wrapping_sub(2));
-                s.write(px);
-                continue;
-            }
-            p -= self.p_diff;
-
-            if p < self.p_luma {
-                let mut px = s.prev;
-                let vg = rng.gen_range(0_u8..64).wrapping_sub(32);
-                let vr = rng.gen_range(0_u8..16).wrapping_sub(8).wrapping_add(vg);
-                let vb = rng.gen_range(0_u8..16).wrapping_sub(8).wrapping_add(vg);
-                px[0] = px[0].wrapping_add(vr);
-                px[1] = px[1].wrapping_add(vg);
-                px[2] = px[2].wrapping_add(vb);
-                s.write(px);
-                continue;
-            }
-
-            s.write(zero);
-        }
-
-        s.pixels
-    }
-}
+use qoi::testutil::ImageGen;
+use qoi::{decode_header, decode_to_vec, encode_to_vec, Channels};
 
 fn format_encoded(encoded: &[u8]) -> String {
     let header = decode_header(encoded).unwrap();
@@ -282,9 +146,9 @@ fn test_generated() {
     let mut n_pixels = 0;
     while n_pixels < 20_000_000 {
         let min_len = rng.gen_range(1..=5000);
-        let channels = rng.gen_range(3..=4);
+        let channels = rng.gen_range(3_usize..=4);
         let gen = ImageGen::new_random(&mut rng);
-        let img = gen.generate(&mut rng, channels, min_len);
+        let img = gen.generate(&mut rng, Channels::try_from(channels as u8).unwrap(), min_len);
 
         let encode = |data: &[u8], size| encode_to_vec(data, size, 1);
         let decode = |data: &[u8]| decode_to_vec(data).map(|r| r.1);
@@ -0,0 +1,35 @@
+use std::fs;
+use std::path::Path;
+
+use qoi::{decode_to_vec, encode_to_vec};
+
+/// Golden vectors: raw `<name>_<w>x<h>.rgb(a)` pixels along with their exact encodings,
+/// `.qoi` for the default encoder and `.ref.qoi` for the `reference` feature.
+#[test]
+fn test_golden_vectors() {
+    let suffix = if cfg!(feature = "reference") { "ref.qoi" } else { "qoi" };
+    let mut n_checked = 0;
+    for entry in fs::read_dir("assets/golden").unwrap() {
+        let path = entry.unwrap().path();
+        let ext = path.extension().unwrap().to_string_lossy().into_owned();
+        let n_channels = match ext.as_str() {
+            "rgb" => 3,
+            "rgba" => 4,
+            _ => continue,
+        };
+        let stem = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let (w, h) = stem.rsplit('_').next().unwrap().split_once('x').unwrap();
+        let (w, h) = (w.parse().unwrap(), h.parse().unwrap());
+        let pixels = fs::read(&path).unwrap();
+        let expected = fs::read(Path::new(&path).with_extension(suffix)).unwrap();
+
+        let encoded = encode_to_vec(&pixels, w, h).unwrap();
+        assert_eq!(encoded, expected, "encoded bytes differ: {}", stem);
+        let (header, decoded) = decode_to_vec(&expected).unwrap();
+        assert_eq!((header.width, header.height), (w, h));
+        assert_eq!(header.channels.as_u8(), n_channels);
+        assert_eq!(decoded, pixels, "decoded pixels differ: {}", stem);
+        n_checked += 1;
+    }
+    assert_eq!(n_checked, 5);
+}
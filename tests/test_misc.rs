@@ -3,4 +3,2656 @@ fn test_new_encoder() {
     // this used to fail due to `Bytes` not being `pub`
     let arr = [0u8];
     let _ = qoi::Decoder::new(&arr[..]);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_scan_headers() {
+    let paths = ["assets/dice.qoi", "assets/testcard_rgba.qoi", "assets/qoi_logo.qoi"];
+    let expected: Vec<_> =
+        paths.iter().map(|p| qoi::decode_header(std::fs::read(p).unwrap()).unwrap()).collect();
+
+    let headers: Vec<_> = qoi::scan_header_files(paths).map(Result::unwrap).collect();
+    assert_eq!(headers, expected);
+
+    let readers = paths.iter().map(|p| std::fs::File::open(p).unwrap());
+    let headers: Vec<_> = qoi::scan_headers(readers).map(Result::unwrap).collect();
+    assert_eq!(headers, expected);
+
+    let data: &[&[u8]] = &[b"qoif", b""];
+    let mut it = qoi::scan_headers(data.iter().copied());
+    assert_eq!(it.next().unwrap().unwrap_err().kind(), qoi::ErrorKind::UnexpectedStreamEnd);
+    assert_eq!(it.next().unwrap().unwrap_err().kind(), qoi::ErrorKind::UnexpectedStreamEnd);
+    assert!(it.next().is_none());
+}
+
+#[test]
+fn test_encode_store() {
+    use qoi::ops::{Op, Ops};
+
+    for path in ["assets/kodim10.qoi", "assets/testcard_rgba.qoi", "assets/wikipedia_008.qoi"] {
+        let (header, pixels) = qoi::decode_to_vec(std::fs::read(path).unwrap()).unwrap();
+        let encoder = qoi::Encoder::new(&pixels, header.width, header.height).unwrap();
+        assert!(!encoder.is_store());
+        let encoder = encoder.with_store(true);
+        assert!(encoder.is_store());
+        let encoded = encoder.encode_to_vec().unwrap();
+        assert!(encoded.len() > encoder.with_store(false).encode_to_vec().unwrap().len());
+        let (header1, decoded) = qoi::decode_to_vec(&encoded).unwrap();
+        assert_eq!(header1, header);
+        assert!(decoded == pixels);
+        for op in Ops::new(&encoded).unwrap() {
+            assert!(matches!(op.unwrap(), Op::Rgb { .. } | Op::Rgba { .. } | Op::Run(_)));
+        }
+    }
+}
+
+#[test]
+fn test_error_kind() {
+    use qoi::ErrorKind;
+
+    let kind = |data: &[u8]| qoi::decode_to_vec(data).unwrap_err().kind();
+    assert_eq!(kind(b"qoif"), ErrorKind::UnexpectedBufferEnd);
+    assert_eq!(kind(b"qoix\0\0\0\x01\0\0\0\x01\x03\0"), ErrorKind::InvalidMagic);
+    assert_eq!(kind(b"qoif\0\0\0\x01\0\0\0\x01\x05\0"), ErrorKind::InvalidChannels);
+    assert_eq!(kind(b"qoif\0\0\0\x01\0\0\0\x01\x03\x02"), ErrorKind::InvalidColorSpace);
+    assert_eq!(kind(b"qoif\0\0\0\0\0\0\0\x01\x03\0"), ErrorKind::InvalidImageDimensions);
+    let err = qoi::encode_to_vec([0; 7], 2, 1).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidImageLength);
+}
+
+#[test]
+fn test_truncated_vs_corrupt() {
+    let encoded = qoi::encode_to_vec([1, 2, 3, 4, 5, 6], 2, 1).unwrap();
+
+    for n in 0..encoded.len() {
+        let err = qoi::decode_to_vec(&encoded[..n]).unwrap_err();
+        assert!(err.is_truncated(), "{n}: {err:?}");
+        let err = qoi::Decoder::from_stream(&encoded[..n]).and_then(|mut d| d.decode_to_vec());
+        assert!(err.unwrap_err().is_truncated(), "{n}");
+    }
+
+    let header = qoi::Header::try_new(2, 1, qoi::Channels::Rgb, qoi::ColorSpace::Srgb).unwrap();
+    let mut corrupt = header.encode().to_vec();
+    corrupt.extend([0xff, 1, 2, 3, 4, 0, 0, 0, 0, 0, 0, 0, 1]);
+    let err = qoi::decode_to_vec(&corrupt).unwrap_err();
+    assert_eq!(err.kind(), qoi::ErrorKind::InvalidOp);
+    assert!(!err.is_truncated());
+    let err = qoi::Decoder::from_stream(corrupt.as_slice()).unwrap().decode_to_vec().unwrap_err();
+    assert_eq!(err.kind(), qoi::ErrorKind::InvalidOp);
+
+    let mut corrupt = encoded.clone();
+    *corrupt.last_mut().unwrap() = 2;
+    let err = qoi::decode_to_vec(&corrupt).unwrap_err();
+    assert_eq!(err.kind(), qoi::ErrorKind::InvalidPadding);
+    assert!(!err.is_truncated());
+}
+
+#[test]
+fn test_error_header() {
+    let encoded = qoi::encode_to_vec([1, 2, 3, 4, 5, 6, 7, 8], 1, 2).unwrap();
+    let expected = qoi::decode_header(&encoded).unwrap();
+
+    let err = qoi::decode_to_vec(&encoded[..encoded.len() - 1]).unwrap_err();
+    assert_eq!(err.header(), Some(&expected));
+    assert!(err.is_truncated());
+    match err {
+        qoi::Error::WithHeader { ref source, .. } => {
+            assert!(matches!(**source, qoi::Error::UnexpectedBufferEnd));
+        }
+        _ => panic!("expected header to be attached: {err:?}"),
+    }
+    assert!(err.to_string().ends_with("(while decoding 1x2:4 image)"));
+
+    let err = qoi::decode_to_vec(&encoded[..10]).unwrap_err();
+    assert_eq!(err.header(), None);
+
+    // caller errors are not wrapped
+    let mut decoder = qoi::Decoder::new(&encoded).unwrap();
+    let err = decoder.decode_to_buf([0; 7]).unwrap_err();
+    assert!(matches!(err, qoi::Error::OutputBufferTooSmall { size: 7, required: 8 }));
+    assert_eq!(err.header(), None);
+}
+
+#[test]
+fn test_header_encode_decode() {
+    use qoi::{Channels, ColorSpace, Header};
+
+    let header = Header::try_new(0x0001_2345, 3, Channels::Rgba, ColorSpace::Linear).unwrap();
+    let bytes: [u8; 14] = header.encode();
+    assert_eq!(&bytes[..4], b"qoif");
+    assert_eq!(&bytes[4..], &[0x00, 0x01, 0x23, 0x45, 0, 0, 0, 3, 4, 1]);
+    assert_eq!(Header::decode(bytes).unwrap(), header);
+
+    let encoded = qoi::encode_to_vec([0; 12], 2, 2).unwrap();
+    let header = Header::decode(&encoded).unwrap();
+    assert_eq!(header.encode(), encoded[..14]);
+}
+
+#[test]
+fn test_header_display() {
+    use qoi::{Channels, ColorSpace, Header, HumanBytes};
+
+    let header = Header::try_new(640, 480, Channels::Rgba, ColorSpace::Srgb).unwrap();
+    assert_eq!(header.to_string(), "640x480 RGBA sRGB (0.3 MP)");
+    assert_eq!(header.megapixels(), 0.3072);
+    assert_eq!(header.n_bytes_human().to_string(), "1.2 MiB");
+    let header = header.with_channels(Channels::Rgb).with_colorspace(ColorSpace::Linear);
+    assert_eq!(header.to_string(), "640x480 RGB linear (0.3 MP)");
+
+    assert_eq!(HumanBytes(0).to_string(), "0 B");
+    assert_eq!(HumanBytes(1023).to_string(), "1023 B");
+    assert_eq!(HumanBytes(1536).to_string(), "1.5 KiB");
+    assert_eq!(HumanBytes(3 << 30).to_string(), "3.0 GiB");
+}
+
+#[test]
+fn test_encode_source_channels() {
+    use qoi::{ChannelMap, ChannelSource::*, EncoderBuilder, ErrorKind, SourceChannels};
+
+    let (header, rgba) = qoi::decode_to_vec(std::fs::read("assets/dice.qoi").unwrap()).unwrap();
+    let (w, h) = (header.width, header.height);
+    let rgb: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+    let expected_rgba = qoi::encode_to_vec(&rgba, w, h).unwrap();
+    let expected_rgb = qoi::encode_to_vec(&rgb, w, h).unwrap();
+
+    let encode = |data: &[u8], source| {
+        EncoderBuilder::new(data, w, h).with_source_channels(source).build()?.encode_to_vec()
+    };
+    let bgra: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], p[3]]).collect();
+    assert_eq!(encode(&bgra, SourceChannels::Bgra).unwrap(), expected_rgba);
+    let xrgb: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [0, p[0], p[1], p[2]]).collect();
+    assert_eq!(encode(&xrgb, SourceChannels::Xrgb).unwrap(), expected_rgb);
+    let bgr: Vec<u8> = rgb.chunks_exact(3).flat_map(|p| [p[2], p[1], p[0]]).collect();
+    assert_eq!(encode(&bgr, SourceChannels::Bgr).unwrap(), expected_rgb);
+
+    let gray_alpha: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[0], p[3]]).collect();
+    let map = ChannelMap::new(2, [Byte(0), Byte(0), Byte(0)], Some(Byte(1))).unwrap();
+    let expected: Vec<u8> =
+        gray_alpha.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect();
+    let expected = qoi::encode_to_vec(expected, w, h).unwrap();
+    assert_eq!(encode(&gray_alpha, SourceChannels::Custom(map)).unwrap(), expected);
+
+    let err = ChannelMap::new(3, [Byte(0), Byte(1), Byte(3)], None).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidChannelMap);
+    let err = encode(&rgb, SourceChannels::Rgba).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidImageLength);
+}
+
+#[test]
+fn test_encode_stride() {
+    use qoi::{EncoderBuilder, ErrorKind, SourceChannels};
+
+    let (header, rgba) = qoi::decode_to_vec(std::fs::read("assets/dice.qoi").unwrap()).unwrap();
+    let (w, h) = (header.width, header.height);
+    let row_len = w as usize * 4;
+    let stride = row_len + 12;
+    let mut padded = vec![0xaa; stride * (h as usize - 1) + row_len];
+    for (src, dst) in rgba.chunks_exact(row_len).zip(padded.chunks_mut(stride)) {
+        dst[..row_len].copy_from_slice(src);
+    }
+    let expected = qoi::encode_to_vec(&rgba, w, h).unwrap();
+
+    let encoder = EncoderBuilder::new(&padded, w, h)
+        .with_source_channels(SourceChannels::Rgba)
+        .with_stride(stride)
+        .build()
+        .unwrap();
+    assert_eq!(encoder.stride(), stride);
+    assert_eq!(encoder.encode_to_vec().unwrap(), expected);
+
+    let bgrx: Vec<u8> = padded.chunks(4).flat_map(|p| [p[2], p[1], p[0], p[3]]).collect();
+    let encoded = EncoderBuilder::new(&bgrx, w, h)
+        .with_source_channels(SourceChannels::Bgra)
+        .with_stride(stride)
+        .build()
+        .unwrap()
+        .encode_to_vec()
+        .unwrap();
+    assert_eq!(encoded, expected);
+
+    let err = EncoderBuilder::new(&padded, w, h)
+        .with_source_channels(SourceChannels::Rgba)
+        .with_stride(row_len - 1)
+        .build()
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidStride);
+
+    // the last row may be padded to the full stride, but no further
+    padded.resize(stride * h as usize, 0xaa);
+    let builder = EncoderBuilder::new(&padded, w, h).with_source_channels(SourceChannels::Rgba);
+    let encoder = builder.with_stride(stride).build().unwrap();
+    assert_eq!(encoder.encode_to_vec().unwrap(), expected);
+    let err = builder.with_stride(stride - 4).build().err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidImageLength);
+    padded.push(0xaa);
+    let builder = EncoderBuilder::new(&padded, w, h).with_source_channels(SourceChannels::Rgba);
+    let err = builder.with_stride(stride).build().err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidImageLength);
+}
+
+#[test]
+fn test_lenient_colorspace() {
+    use qoi::{ColorSpace, Decoder, ErrorKind, Header, HeaderOptions};
+
+    let mut encoded = qoi::encode_to_vec([1, 2, 3, 4, 5, 6], 2, 1).unwrap();
+    encoded[13] = 0x42;
+    assert_eq!(Header::decode(&encoded).unwrap_err().kind(), ErrorKind::InvalidColorSpace);
+    assert_eq!(Decoder::new(&encoded).err().unwrap().kind(), ErrorKind::InvalidColorSpace);
+
+    let header = Header::decode_lenient(&encoded).unwrap();
+    assert_eq!(header.colorspace, ColorSpace::Srgb);
+    assert_eq!(header.encode()[13], 0);
+
+    let options = HeaderOptions::new().with_lenient_colorspace(true);
+    let mut decoder = Decoder::new_with_options(&encoded, options).unwrap();
+    assert_eq!(decoder.colorspace_raw(), 0x42);
+    let pixels = decoder.decode_to_vec().unwrap();
+    assert_eq!(pixels, [1, 2, 3, 4, 5, 6]);
+    let mut decoder = Decoder::from_stream_with_options(&encoded[..], options).unwrap();
+    assert_eq!(decoder.header(), &header);
+    assert_eq!(decoder.colorspace_raw(), 0x42);
+    assert_eq!(decoder.decode_to_vec().unwrap(), pixels);
+
+    encoded[13] = 1;
+    let decoder = Decoder::new_with_options(&encoded, options).unwrap();
+    assert_eq!(decoder.header().colorspace, ColorSpace::Linear);
+    assert_eq!(decoder.colorspace_raw(), 1);
+
+    assert_eq!(
+        Header::decode_lenient(&encoded[..13]).unwrap_err().kind(),
+        ErrorKind::UnexpectedBufferEnd
+    );
+}
+
+#[test]
+fn test_pixel() {
+    use qoi::Pixel;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    let px = Pixel::from([10, 20, 30]);
+    assert_eq!(px.rgb(), [10, 20, 30]);
+    assert_eq!(px.rgba(), [10, 20, 30, 255]);
+    assert_eq!(px.a(), 255);
+    assert_eq!(px.hash_index(), Pixel::from([10, 20, 30, 255]).hash_index());
+    assert_eq!(px.hash_index(), ((10 * 3 + 20 * 5 + 30 * 7 + 255 * 11) % 64) as u8);
+    assert_eq!(<[u8; 3]>::from(px), [10, 20, 30]);
+
+    let prev = Pixel::from_array([255, 18, 0, 7]);
+    let px = Pixel::from_array([1, 20, 254, 7]);
+    assert_eq!(px.diff(prev), [2, 2, -2, 0]);
+    assert_eq!(prev.diff(px), [-2, -2, 2, 0]);
+    assert_eq!(
+        Pixel::from_array([0, 0, 0, 1]).diff(Pixel::from_array([0, 0, 0, 255])),
+        [0, 0, 0, 2]
+    );
+    assert_eq!(Pixel::<4>::default().to_array(), [0; 4]);
+
+    let px = Pixel::from([10, 20, 30]);
+    assert_eq!((px.to_rgba(), px.to_rgba().to_rgb()), (Pixel::from([10, 20, 30, 255]), px));
+    assert_eq!(px.to_u32(), 0x0a14_1eff);
+    assert_eq!(Pixel::from_u32(0x0102_0304), Pixel::from([1, 2, 3, 4]));
+    let mut rng = StdRng::seed_from_u64(0);
+    for _ in 0..1000 {
+        let rgba: [u8; 4] = rng.gen();
+        let px = Pixel::from_u32(u32::from_be_bytes(rgba));
+        let [r, g, b, a] = rgba.map(usize::from);
+        assert_eq!(px.hash_index() as usize, (r * 3 + g * 5 + b * 7 + a * 11) % 64);
+        assert_eq!(px.to_u32(), u32::from_be_bytes(rgba));
+    }
+}
+
+#[test]
+fn test_convert() {
+    use qoi::convert::*;
+    use qoi::{Channels, ErrorKind};
+
+    let rgb = [1, 2, 3, 4, 5, 6, 7];
+    let mut rgba = [0; 8];
+    assert_eq!(rgb_to_rgba(&rgb, &mut rgba, 9).unwrap(), 2);
+    assert_eq!(rgba, [1, 2, 3, 9, 4, 5, 6, 9]);
+    let mut bgra = [0; 8];
+    assert_eq!(rgba_to_bgra(&rgba, &mut bgra).unwrap(), 2);
+    assert_eq!(bgra, [3, 2, 1, 9, 6, 5, 4, 9]);
+    let mut out = [0; 8];
+    assert_eq!(rgb_to_bgra(&rgb, &mut out, 9).unwrap(), 2);
+    assert_eq!(out, bgra);
+    let mut out = [0; 6];
+    assert_eq!(bgra_to_rgb(&bgra, &mut out).unwrap(), 2);
+    assert_eq!(out, rgb[..6]);
+    let mut out = [0; 6];
+    assert_eq!(rgba_to_rgb(&rgba, &mut out).unwrap(), 2);
+    assert_eq!(out, rgb[..6]);
+
+    swap_red_blue(&mut bgra, Channels::Rgba);
+    assert_eq!(bgra, rgba);
+    swap_red_blue(&mut out, Channels::Rgb);
+    assert_eq!(out, [3, 2, 1, 6, 5, 4]);
+
+    let err = rgb_to_rgba(&rgb, &mut [0; 7], 0).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::OutputBufferTooSmall);
+    assert_eq!(rgba_to_rgb(&[], &mut []).unwrap(), 0);
+}
+
+#[test]
+fn test_gray_alpha() {
+    use qoi::gray::{self, GrayAlphaHeader};
+    use qoi::{ColorSpace, ErrorKind};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    let rgba = qoi::decode_to_vec(std::fs::read("assets/dice.qoi").unwrap()).unwrap().1;
+    let mut ga = vec![0; rgba.len() / 2];
+    assert_eq!(gray::rgba_to_gray_alpha(&rgba, &mut ga).unwrap(), 800 * 600);
+    let mut expanded = vec![0; rgba.len()];
+    assert_eq!(gray::gray_alpha_to_rgba(&ga, &mut expanded).unwrap(), 800 * 600);
+    // gray pixels survive the roundtrip
+    let mut ga2 = vec![0; ga.len()];
+    gray::rgba_to_gray_alpha(&expanded, &mut ga2).unwrap();
+    assert!(ga2 == ga);
+    assert_eq!(gray::rgba_to_gray_alpha(&[255, 255, 255, 7, 0], &mut [0; 2]).unwrap(), 1);
+    assert_eq!(
+        gray::gray_alpha_to_rgba(&ga, &mut [0; 7]).unwrap_err().kind(),
+        ErrorKind::OutputBufferTooSmall
+    );
+
+    let header = GrayAlphaHeader::try_new(800, 600, ColorSpace::Linear).unwrap();
+    let encoded = gray::encode_to_vec(&ga, &header).unwrap();
+    assert!(encoded.len() < qoi::encode_to_vec(&expanded, 800, 600).unwrap().len() * 3 / 4);
+    assert_eq!(gray::decode_header(&encoded).unwrap(), header);
+    let (decoded_header, decoded) = gray::decode_to_vec(&encoded).unwrap();
+    assert_eq!(decoded_header, header);
+    assert!(decoded == ga);
+    // not readable by regular decoders, and vice versa
+    assert_eq!(qoi::decode_header(&encoded).unwrap_err().kind(), ErrorKind::InvalidChannels);
+    let regular = qoi::encode_to_vec(&rgba, 800, 600).unwrap();
+    assert_eq!(gray::decode_header(&regular).unwrap_err().kind(), ErrorKind::InvalidChannels);
+
+    // all ops, including long runs and wrapping deltas
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut data = vec![];
+    while data.len() < 2 * 10_000 {
+        let px: [u8; 2] = match rng.gen_range(0..4) {
+            0 => rng.gen(),
+            1 => [rng.gen_range(0..4), 0xff],
+            _ => data.get(data.len().wrapping_sub(2)..).map_or([0, 0], |p| [p[0], p[1]]),
+        };
+        let n = if rng.gen_bool(0.1) { rng.gen_range(1..200) } else { 1 };
+        (0..n).for_each(|_| data.extend(px));
+    }
+    data.truncate(2 * 10_000);
+    let header = GrayAlphaHeader::try_new(100, 100, ColorSpace::Srgb).unwrap();
+    let encoded = gray::encode_to_vec(&data, &header).unwrap();
+    assert!(encoded.len() <= header.encode_max_len());
+    let mut buf = vec![0xaa; data.len() + 3];
+    assert_eq!(gray::decode_to_buf(&mut buf, &encoded).unwrap(), header);
+    assert!(buf[..data.len()] == data[..] && buf[data.len()..] == [0xaa; 3]);
+
+    let err = gray::decode_to_vec(&encoded[..encoded.len() - 1]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedBufferEnd);
+    let mut corrupt = encoded.clone();
+    *corrupt.last_mut().unwrap() = 0;
+    assert_eq!(gray::decode_to_vec(&corrupt).unwrap_err().kind(), ErrorKind::InvalidPadding);
+    let err = gray::encode_to_vec(&data[1..], &header).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidImageLength);
+    let err = gray::encode_to_buf(&mut [0; 100], &data, &header).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::OutputBufferTooSmall);
+}
+
+#[test]
+fn test_animation() {
+    use qoi::anim::{AnimationDecoder, AnimationEncoder, AnimationHeader};
+    use qoi::{Channels, EncoderBuilder, ErrorKind, SourceChannels};
+    use std::io::Cursor;
+
+    let (w, h) = (64, 48);
+    let frames: Vec<(Vec<u8>, u32)> = (0..5)
+        .map(|i| {
+            let n = if i % 2 == 0 { 4 } else { 3 };
+            let pixels = (0..w * h * n).map(|j| ((j / n) % w + i * 7).min(255) as u8).collect();
+            (pixels, 40 + i as u32)
+        })
+        .collect();
+
+    let header = AnimationHeader::try_new(w as u32, h as u32).unwrap().with_loop_count(3);
+    let mut encoder = AnimationEncoder::new(vec![], header).unwrap();
+    for (pixels, duration) in &frames {
+        encoder.push_frame(pixels, *duration).unwrap();
+    }
+    assert_eq!(encoder.frames_written(), frames.len());
+    let n_written = encoder.bytes_written();
+    let data = encoder.finish().unwrap();
+    assert_eq!(data.len(), n_written + 8);
+    assert_eq!(&data[..4], b"qoia");
+    assert_eq!(AnimationHeader::decode(&data).unwrap(), header);
+    assert!(data.ends_with(&[0; 8]));
+
+    // frames are decoded as they are, or converted to the given number of channels
+    let mut decoder = AnimationDecoder::new(&data).unwrap();
+    assert_eq!(*decoder.header(), header);
+    for (pixels, duration) in &frames {
+        let frame = decoder.next_frame().unwrap().unwrap();
+        assert_eq!(frame.duration_ms, *duration);
+        assert_eq!(frame.header.channels.as_u8() as usize, pixels.len() / (w * h));
+        assert!(frame.pixels == *pixels);
+    }
+    assert!(decoder.next_frame().unwrap().is_none());
+    assert!(decoder.is_finished());
+    let decoder = AnimationDecoder::new(&data).unwrap().with_channels(Channels::Rgba).unwrap();
+    let decoded: Vec<_> = decoder.map(Result::unwrap).collect();
+    assert_eq!(decoded.len(), frames.len());
+    for (frame, (pixels, _)) in decoded.iter().zip(&frames) {
+        let expected: Vec<u8> = match pixels.len() / (w * h) {
+            3 => pixels.chunks(3).flat_map(|p| [p[0], p[1], p[2], 0xff]).collect(),
+            _ => pixels.clone(),
+        };
+        assert_eq!(frame.header.channels, Channels::Rgba);
+        assert!(frame.pixels == expected);
+    }
+
+    // frames can be skipped or revisited without decoding the rest, nothing is read past
+    // the end marker
+    let mut stream = data.clone();
+    stream.extend_from_slice(b"tail");
+    let mut decoder = AnimationDecoder::from_stream(Cursor::new(&stream)).unwrap();
+    assert!(decoder.seek_frame(3).unwrap());
+    assert_eq!(decoder.next_frame().unwrap().unwrap().pixels, frames[3].0);
+    assert!(decoder.seek_frame(1).unwrap());
+    let (duration, encoded) = decoder.next_encoded_frame().unwrap().unwrap();
+    assert_eq!(duration, frames[1].1);
+    assert_eq!(qoi::decode_to_vec(encoded).unwrap().1, frames[1].0);
+    assert_eq!(decoder.frame_count().unwrap(), frames.len());
+    assert_eq!(decoder.frames_read(), 2);
+    assert!(!decoder.seek_frame(frames.len()).unwrap());
+    assert!(decoder.next_frame().unwrap().is_none());
+    let mut reader = decoder.into_inner();
+    assert_eq!(reader.position() as usize, data.len());
+    let mut tail = vec![];
+    std::io::Read::read_to_end(&mut reader, &mut tail).unwrap();
+    assert_eq!(tail, b"tail");
+
+    // other pixel layouts are encoded via custom encoders
+    let bgra: Vec<u8> = frames[0].0.chunks(4).flat_map(|p| [p[2], p[1], p[0], p[3]]).collect();
+    let mut encoder = AnimationEncoder::new(vec![], header).unwrap();
+    let builder = EncoderBuilder::new(&bgra, w as u32, h as u32);
+    let frame = builder.with_source_channels(SourceChannels::Bgra).build().unwrap();
+    encoder.push_encoder(&frame, 10).unwrap();
+    let other = EncoderBuilder::new(&bgra, h as u32, w as u32).build().unwrap();
+    let err = encoder.push_encoder(&other, 10).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidImageDimensions);
+    let err = encoder.push_frame(&bgra[1..], 10).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidImageLength);
+    assert_eq!(encoder.frames_written(), 1);
+    let data = encoder.finish().unwrap();
+    let mut decoder = AnimationDecoder::new(&data).unwrap();
+    assert_eq!(decoder.next_frame().unwrap().unwrap().pixels, frames[0].0);
+
+    // invalid and truncated containers
+    let err = AnimationHeader::try_new(0, 1).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidImageDimensions);
+    let err = AnimationDecoder::new(&data[4..]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidMagic);
+    let mut decoder = AnimationDecoder::new(&data[..data.len() - 8]).unwrap();
+    decoder.next_frame().unwrap();
+    assert_eq!(decoder.next_frame().unwrap_err().kind(), ErrorKind::UnexpectedStreamEnd);
+    let mut decoder = AnimationDecoder::new(&data[..data.len() - 20]).unwrap();
+    assert_eq!(decoder.next_frame().unwrap_err().kind(), ErrorKind::UnexpectedStreamEnd);
+    // iteration stops after the first error
+    let mut decoder = AnimationDecoder::new(&data[..data.len() - 20]).unwrap();
+    let err = decoder.next().unwrap().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedStreamEnd);
+    assert!(decoder.is_finished());
+    assert!(decoder.next().is_none());
+}
+
+#[test]
+fn test_ops() {
+    use qoi::ops::{Op, Ops};
+    use qoi::ErrorKind;
+
+    let ops = [
+        Op::Index(63),
+        Op::Diff { dr: -2, dg: 0, db: 1 },
+        Op::Luma { dg: -32, dr_dg: 7, db_dg: -8 },
+        Op::Rgb { r: 1, g: 2, b: 3 },
+        Op::Rgba { r: 1, g: 2, b: 3, a: 4 },
+        Op::Run(1),
+        Op::Run(62),
+    ];
+    for op in ops {
+        let mut buf = [0; 8];
+        let n = op.encode_to(&mut buf).unwrap();
+        assert_eq!(n, op.n_bytes());
+        assert_eq!(Op::parse_from(&buf[..n]).unwrap(), (op, n));
+        if n > 1 {
+            assert_eq!(
+                Op::parse_from(&buf[..n - 1]).unwrap_err().kind(),
+                ErrorKind::UnexpectedBufferEnd
+            );
+        }
+        assert_eq!(
+            op.encode_to(&mut buf[..n - 1]).unwrap_err().kind(),
+            ErrorKind::OutputBufferTooSmall
+        );
+    }
+    assert_eq!(Op::parse_from([0xfd]).unwrap().0, Op::Run(62));
+    assert_eq!(Op::parse_from([0x40 | 0b10_11_01]).unwrap().0, Op::Diff { dr: 0, dg: 1, db: -1 });
+
+    let pixels = [0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255, 1, 2, 3, 255, 0, 0, 0, 255];
+    let encoded = qoi::encode_to_vec(pixels, 5, 1).unwrap();
+    let ops: Vec<_> = Ops::new(&encoded).unwrap().map(Result::unwrap).collect();
+    assert_eq!(ops.iter().map(Op::n_pixels).sum::<usize>(), 5);
+    assert_eq!(ops[..2], [Op::Run(3), Op::Luma { dg: 2, dr_dg: -1, db_dg: 1 }]);
+    assert_eq!(ops[2], Op::Luma { dg: -2, dr_dg: 1, db_dg: -1 });
+
+    let mut it = Ops::new(&encoded[..encoded.len() - 1]).unwrap();
+    let err = it.find_map(Result::err).unwrap();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedBufferEnd);
+    assert!(it.next().is_none());
+}
+
+#[test]
+fn test_const_sizes() {
+    use qoi::{Channels, ColorSpace, Header, Pixel};
+
+    const HEADER: Header =
+        Header { width: 64, height: 32, channels: Channels::Rgba, colorspace: ColorSpace::Srgb };
+    const MAX_LEN: usize = qoi::encoded_size_limit(64, 32, Channels::Rgba);
+    const N_BYTES: usize = HEADER.n_bytes();
+    const HEADER_BYTES: [u8; 14] = HEADER.encode();
+    const HASH: u8 = Pixel::from_array([1, 2, 3]).hash_index();
+
+    let buf = [0_u8; MAX_LEN];
+    assert_eq!(buf.len(), qoi::encode_max_len(64, 32, 4));
+    assert_eq!(HEADER.encode_max_len(), MAX_LEN);
+    assert_eq!(N_BYTES, 64 * 32 * 4);
+    assert_eq!(Header::decode(HEADER_BYTES).unwrap(), HEADER);
+    assert_eq!(HASH, Pixel::from_array([1, 2, 3, 255]).hash_index());
+}
+
+#[test]
+fn test_usize_dimensions() {
+    use qoi::{Channels, ColorSpace, Encoder, EncoderBuilder, Error, ErrorKind, Header};
+
+    let header = Header::try_new_usize(3, 2, Channels::Rgb, ColorSpace::Srgb).unwrap();
+    assert_eq!((header.width, header.height), (3, 2));
+    let encoder = Encoder::new_usize(&[0; 18], 3, 2).unwrap();
+    assert_eq!(encoder.header(), &header);
+
+    let too_big = u32::MAX as usize + 1;
+    let err = Header::try_new_usize(too_big, 1, Channels::Rgb, ColorSpace::Srgb).unwrap_err();
+    assert!(matches!(err, Error::DimensionOverflow { width, height: 1 } if width == too_big));
+    let err = Encoder::new_usize(&[0; 3], 1, too_big).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::DimensionOverflow);
+    let err = EncoderBuilder::new_usize(&[0; 3], too_big, too_big).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::DimensionOverflow);
+    let err = Header::try_new_usize(0, 1, Channels::Rgb, ColorSpace::Srgb).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidImageDimensions);
+}
+
+#[test]
+fn test_decoder_checkpoint() {
+    use qoi::{Channels, Decoder};
+    use std::io::Cursor;
+
+    for path in ["assets/dice.qoi", "assets/kodim10.qoi", "assets/wikipedia_008.qoi"] {
+        let encoded = std::fs::read(path).unwrap();
+        for channels in [Channels::Rgb, Channels::Rgba] {
+            let expected =
+                Decoder::new(&encoded).unwrap().with_channels(channels).unwrap().decode_to_vec();
+            let expected = expected.unwrap();
+            let half = expected.len() / channels.as_u8() as usize / 2 * channels.as_u8() as usize;
+
+            let mut decoder = Decoder::new(&encoded).unwrap().with_channels(channels).unwrap();
+            let mut out = vec![0; expected.len()];
+            for chunk in out[..half].chunks_mut(channels.as_u8() as usize * 1000) {
+                decoder.decode_pixels(chunk).unwrap();
+            }
+            let state = decoder.checkpoint();
+            assert_eq!(state.pixel_position(), half / channels.as_u8() as usize);
+            assert_eq!(decoder.required_buf_len(), expected.len() - half);
+            decoder.decode_to_buf(&mut out[half..]).unwrap();
+            let first = out.iter().zip(&expected).position(|(a, b)| a != b);
+            assert_eq!(first, None, "{path} {channels}");
+            assert_eq!(decoder.pixels_left(), 0);
+            assert!(decoder.data().is_empty());
+
+            decoder.restore(state).unwrap();
+            assert_eq!(decoder.decode_to_vec().unwrap(), expected[half..]);
+
+            let decoder = Decoder::from_stream(Cursor::new(&encoded)).unwrap();
+            let mut decoder = decoder.with_channels(channels).unwrap();
+            decoder.restore(state).unwrap();
+            assert_eq!(decoder.decode_to_vec().unwrap(), expected[half..]);
+            assert_eq!(decoder.reader().position() as usize, encoded.len());
+            decoder.restore(state).unwrap();
+            assert_eq!(decoder.decode_to_vec().unwrap(), expected[half..]);
+        }
+    }
+}
+
+#[test]
+fn test_decode_rgba_to_rgb() {
+    // the index hash must use the actual alpha even if it's not part of the output
+    let encoded = std::fs::read("assets/dice.qoi").unwrap();
+    let rgba = qoi::decode_to_vec(&encoded).unwrap().1;
+    let expected: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+    let decoder = qoi::Decoder::new(&encoded).unwrap();
+    assert_eq!(
+        decoder.with_channels(qoi::Channels::Rgb).unwrap().decode_to_vec().unwrap(),
+        expected
+    );
+    let decoder = qoi::Decoder::from_stream(&encoded[..]).unwrap();
+    assert_eq!(
+        decoder.with_channels(qoi::Channels::Rgb).unwrap().decode_to_vec().unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn test_decode_with_channels_validated() {
+    use qoi::{Channels, Decoder, Error, ErrorKind};
+
+    let encoded = std::fs::read("assets/dice.qoi").unwrap();
+    let decoder = Decoder::new(&encoded).unwrap();
+    assert!(!decoder.is_converting());
+    let decoder = decoder.with_channels(3_u8).unwrap();
+    assert_eq!(decoder.channels(), Channels::Rgb);
+    assert!(decoder.is_converting());
+    for channels in [0_u8, 1, 2, 5, 0xff] {
+        let err = Decoder::new(&encoded).unwrap().with_channels(channels).unwrap_err();
+        assert!(matches!(err, Error::InvalidChannels { channels: c } if c == channels));
+    }
+
+    // the output layout can't change once decoding has started
+    let mut decoder = Decoder::from_stream(&encoded[..]).unwrap().with_channels(4_u8).unwrap();
+    let err = decoder.decode_pixels(&mut [0; 41]).unwrap_err();
+    assert!(matches!(err, Error::InvalidImageLength { size: 41, width: 800, height: 600 }));
+    decoder.decode_pixels(&mut [0; 40]).unwrap();
+    let decoder = decoder.with_channels(Channels::Rgba).unwrap();
+    let err = decoder.with_channels(Channels::Rgb).unwrap_err();
+    assert!(matches!(err, Error::DecodingStarted { pixel_pos: 10 }));
+    assert_eq!(err.kind(), ErrorKind::DecodingStarted);
+}
+
+#[test]
+fn test_decode_to_larger_buf() {
+    use qoi::{Channels, Decoder, ErrorKind};
+
+    // buffers that are larger than needed (and not channel-aligned) are fine, only the
+    // required prefix is written to
+    let encoded = std::fs::read("assets/dice.qoi").unwrap();
+    for channels in [Channels::Rgb, Channels::Rgba] {
+        let expected =
+            Decoder::new(&encoded).unwrap().with_channels(channels).unwrap().decode_to_vec();
+        let expected = expected.unwrap();
+        for extra in 0..=5 {
+            let mut buf = vec![0xaa; expected.len() + extra];
+            let mut decoder = Decoder::new(&encoded).unwrap().with_channels(channels).unwrap();
+            assert_eq!(decoder.decode_to_buf(&mut buf).unwrap(), expected.len());
+            assert_eq!(
+                (&buf[..expected.len()], &buf[expected.len()..]),
+                (&expected[..], &vec![0xaa; extra][..])
+            );
+            let mut decoder =
+                Decoder::from_stream(&encoded[..]).unwrap().with_channels(channels).unwrap();
+            assert_eq!(decoder.decode_to_buf(&mut buf).unwrap(), expected.len());
+            let slices = [&encoded[..100], &encoded[100..]];
+            let mut decoder =
+                Decoder::from_slices(&slices).unwrap().with_channels(channels).unwrap();
+            assert_eq!(decoder.decode_to_buf(&mut buf).unwrap(), expected.len());
+            assert_eq!(buf[..expected.len()], expected);
+        }
+        let mut buf = vec![0; expected.len() - 1];
+        let err = qoi::decode_to_buf(&mut buf, &encoded).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::OutputBufferTooSmall);
+    }
+}
+
+#[test]
+fn test_decode_to_planes() {
+    use qoi::{Channels, Decoder, EncoderBuilder, ErrorKind, HeaderOptions};
+
+    let encoded = std::fs::read("assets/dice.qoi").unwrap();
+    let rgba = qoi::decode_to_vec(&encoded).unwrap().1;
+    let plane = |c: usize, n: usize| rgba.chunks_exact(4).skip(n).map(|p| p[c]).collect::<Vec<_>>();
+
+    let planes = Decoder::new(&encoded).unwrap().decode_to_planes_vec().unwrap();
+    assert_eq!(planes.len(), 4);
+    (0..4).for_each(|c| assert!(planes[c] == plane(c, 0)));
+
+    let decoder = Decoder::from_stream(&encoded[..]).unwrap().with_channels(Channels::Rgb);
+    let mut decoder = decoder.unwrap();
+    let mut planes = [vec![0; 800 * 600], vec![0; 800 * 600], vec![0; 800 * 600 + 5]];
+    assert_eq!(decoder.decode_to_planes(&mut planes).unwrap(), 800 * 600);
+    (0..3).for_each(|c| assert!(planes[c][..800 * 600] == plane(c, 0)));
+    assert_eq!(planes[2][800 * 600..], [0; 5]);
+
+    // only the remaining pixels are decoded
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    decoder.decode_pixels(&mut [0; 4 * 1000]).unwrap();
+    let planes = decoder.decode_to_planes_vec().unwrap();
+    (0..4).for_each(|c| assert!(planes[c] == plane(c, 1000)));
+    assert_eq!(decoder.pixels_left(), 0);
+
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    let err = decoder.decode_to_planes(&mut [[0; 800 * 600]; 3]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidChannels);
+    let mut planes = [vec![0; 800 * 600], vec![0; 800 * 600], vec![0; 10], vec![0; 800 * 600]];
+    let err = decoder.decode_to_planes(&mut planes).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::OutputBufferTooSmall);
+    assert_eq!(decoder.pixels_left(), 800 * 600);
+
+    // the end marker of empty images is validated too
+    let empty = EncoderBuilder::new(&[], 0, 3).with_allow_empty(true).build().unwrap();
+    let mut empty = empty.encode_to_vec().unwrap();
+    let options = HeaderOptions::new().with_allow_empty(true);
+    let mut decoder = Decoder::new_with_options(&empty, options).unwrap();
+    assert!(decoder.decode_to_planes_vec().unwrap().iter().all(Vec::is_empty));
+    *empty.last_mut().unwrap() = 0;
+    let mut decoder = Decoder::new_with_options(&empty, options).unwrap();
+    let err = decoder.decode_to_planes_vec().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidPadding);
+}
+
+#[test]
+fn test_decode_rows() {
+    use qoi::{Channels, Decoder, ErrorKind};
+
+    let encoded = std::fs::read("assets/dice.qoi").unwrap();
+    for channels in [Channels::Rgb, Channels::Rgba] {
+        let expected =
+            Decoder::new(&encoded).unwrap().with_channels(channels).unwrap().decode_to_vec();
+        let expected = expected.unwrap();
+        let row_len = 800 * channels.as_u8() as usize;
+        for n_rows in [1, 7, 600, 1000] {
+            let mut buf = vec![0; row_len * n_rows];
+            let mut decoder =
+                Decoder::from_stream(&encoded[..]).unwrap().with_channels(channels).unwrap();
+            let mut out = vec![];
+            while decoder.rows_left() != 0 {
+                let n = decoder.decode_rows(n_rows, &mut buf).unwrap();
+                assert_eq!(n, n_rows.min(600 - out.len() / row_len));
+                out.extend_from_slice(&buf[..n * row_len]);
+            }
+            assert_eq!(out, expected);
+            assert_eq!(decoder.pixels_left(), 0);
+            assert_eq!(decoder.decode_rows(n_rows, &mut buf).unwrap(), 0);
+        }
+    }
+
+    // the buffer must fit all rows that are requested and left
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    assert_eq!(decoder.rows_left(), 600);
+    let err = decoder.decode_rows(2, [0; 4 * 800 * 2 - 1]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::OutputBufferTooSmall);
+    assert_eq!(decoder.decode_rows(0, []).unwrap(), 0);
+
+    // decoding must be at a row boundary
+    decoder.decode_pixels([0; 4 * 10]).unwrap();
+    assert_eq!(decoder.rows_left(), 599);
+    let err = decoder.decode_rows(1, [0; 4 * 800]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::DecodingStarted);
+}
+
+#[test]
+fn test_decode_region() {
+    use qoi::{Channels, Decoder, ErrorKind};
+
+    let encoded = std::fs::read("assets/dice.qoi").unwrap();
+    for channels in [Channels::Rgb, Channels::Rgba] {
+        let n = channels.as_u8() as usize;
+        let image =
+            Decoder::new(&encoded).unwrap().with_channels(channels).unwrap().decode_to_vec();
+        let image = image.unwrap();
+        let crop = |x: usize, y: usize, w: usize, h: usize| -> Vec<u8> {
+            (y..y + h)
+                .flat_map(|r| &image[(r * 800 + x) * n..(r * 800 + x + w) * n])
+                .copied()
+                .collect()
+        };
+        for (x, y, w, h) in [(0, 0, 800, 600), (0, 0, 1, 1), (799, 599, 1, 1), (123, 45, 67, 89)] {
+            let mut buf = vec![0xaa; w * h * n + 3];
+            let mut decoder =
+                Decoder::from_stream(&encoded[..]).unwrap().with_channels(channels).unwrap();
+            let size = decoder.decode_region(x as _, y as _, w as _, h as _, &mut buf).unwrap();
+            assert_eq!(size, w * h * n);
+            assert_eq!(buf[..size], crop(x, y, w, h));
+            assert_eq!(buf[size..], [0xaa; 3]);
+            assert_eq!(decoder.rows_left(), 600 - y - h);
+        }
+
+        // strips of tiles can be decoded top to bottom
+        let mut decoder = Decoder::new(&encoded).unwrap().with_channels(channels).unwrap();
+        for y in [0, 100, 250, 599] {
+            let mut buf = vec![0; 200 * n];
+            decoder.decode_region(300, y, 200, 1, &mut buf).unwrap();
+            assert_eq!(buf, crop(300, y as usize, 200, 1));
+        }
+        assert_eq!(decoder.pixels_left(), 0);
+    }
+
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    assert_eq!(decoder.decode_region(800, 600, 0, 0, []).unwrap(), 0);
+    for (x, y, w, h) in [(1, 0, 800, 1), (0, 0, 1, 601), (801, 0, 0, 0), (u32::MAX, 0, 2, 1)] {
+        let err = decoder.decode_region(x, y, w, h, [0; 4]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidRegion);
+    }
+    let err = decoder.decode_region(0, 0, 2, 2, [0; 4 * 4 - 1]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::OutputBufferTooSmall);
+    assert_eq!(decoder.pixels_left(), 800 * 600);
+
+    // rows that have already been decoded can't be part of the region
+    decoder.decode_region(0, 10, 1, 1, [0; 4]).unwrap();
+    let err = decoder.decode_region(0, 10, 1, 1, [0; 4]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidRegion);
+    decoder.decode_pixels([0; 4]).unwrap();
+    let err = decoder.decode_region(0, 20, 1, 1, [0; 4]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::DecodingStarted);
+}
+
+#[test]
+fn test_target_channels() {
+    use qoi::{
+        ChannelMap, ChannelSource::*, Channels, Decoder, EncoderBuilder, ErrorKind, SourceChannels,
+        TargetChannels,
+    };
+
+    let rgba = std::fs::read("assets/testcard_rgba.qoi").unwrap();
+    let rgb = std::fs::read("assets/kodim10.qoi").unwrap();
+    let custom = ChannelMap::new(5, [Byte(4), Byte(0), Byte(2)], Some(Byte(1))).unwrap();
+    let targets = [
+        (TargetChannels::Bgr, [Some(2), Some(1), Some(0), None], 3),
+        (TargetChannels::Argb, [Some(1), Some(2), Some(3), Some(0)], 4),
+        (TargetChannels::Xrgb, [Some(1), Some(2), Some(3), None], 4),
+        (TargetChannels::Bgra, [Some(2), Some(1), Some(0), Some(3)], 4),
+        (TargetChannels::Abgr, [Some(3), Some(2), Some(1), Some(0)], 4),
+        (TargetChannels::Bgrx, [Some(2), Some(1), Some(0), None], 4),
+        (TargetChannels::Custom(custom), [Some(4), Some(0), Some(2), Some(1)], 5),
+    ];
+    for encoded in [&rgba, &rgb] {
+        let decoder = Decoder::new(encoded).unwrap().with_default_alpha(0x80);
+        let (header, plain) = (*decoder.header(), decoder.with_channels(4).unwrap());
+        let plain = plain.clone().decode_to_vec().unwrap();
+        for (target, offsets, bpp) in targets {
+            // expected output: channels at their offsets, padding bytes set to 255
+            let expected: Vec<u8> = plain
+                .chunks_exact(4)
+                .flat_map(|px| {
+                    let mut out = vec![0xff; bpp];
+                    offsets.iter().zip(px).for_each(|(&i, &v)| i.iter().for_each(|&i| out[i] = v));
+                    out
+                })
+                .collect();
+            let decoder = Decoder::new(encoded).unwrap().with_default_alpha(0x80);
+            let mut decoder = decoder.with_target_channels(target).unwrap();
+            assert_eq!(decoder.target_channels(), target);
+            assert_eq!(decoder.channels(), target.channels());
+            assert_eq!(decoder.required_buf_len(), header.n_pixels() * bpp);
+            assert!(decoder.clone().decode_to_vec().unwrap() == expected);
+
+            // pixels and rows are decoded in the same layout
+            let mut out = vec![0; expected.len()];
+            let (head, tail) = out.split_at_mut(bpp * (header.width as usize * 3 + 1000));
+            assert_eq!(decoder.decode_rows(3, &mut *head).unwrap(), 3);
+            let n_pixels = decoder.decode_pixels(&mut head[bpp * header.width as usize * 3..]);
+            assert_eq!(n_pixels.unwrap(), 1000);
+            let err = decoder.decode_pixels(&mut tail[..bpp + 1]).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidImageLength);
+            let err = decoder.clone().with_channels(4).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::DecodingStarted);
+            assert!(decoder.clone().with_target_channels(target).is_ok());
+            decoder.decode_to_buf(tail).unwrap();
+            assert!(out == expected);
+        }
+    }
+
+    // plain layouts are the same as setting the number of channels, and vice versa
+    let decoder = Decoder::new(&rgba).unwrap().with_target_channels(TargetChannels::Bgra).unwrap();
+    let decoder = decoder.with_channels(Channels::Rgb).unwrap();
+    assert_eq!(decoder.target_channels(), TargetChannels::Rgb);
+    let expected = Decoder::new(&rgba).unwrap().with_channels(3).unwrap().decode_to_vec();
+    assert!(decoder.clone().decode_to_vec().unwrap() == expected.unwrap());
+
+    // decoding into a layout and encoding from the same layout is lossless
+    let (header, pixels) = qoi::decode_to_vec(&rgba).unwrap();
+    let decoder = Decoder::new(&rgba).unwrap().with_target_channels(TargetChannels::Abgr);
+    let abgr = decoder.unwrap().decode_to_vec().unwrap();
+    let encoder = EncoderBuilder::new(&abgr, header.width, header.height)
+        .with_source_channels(SourceChannels::Abgr)
+        .build()
+        .unwrap();
+    assert!(qoi::decode_to_vec(encoder.encode_to_vec().unwrap()).unwrap().1 == pixels);
+}
+
+#[test]
+fn test_premultiplied_alpha() {
+    use qoi::{Decoder, EncoderBuilder, Pixel, SourceChannels, TargetChannels};
+
+    // premultiplication rounds to nearest, un-premultiplication inverts it exactly
+    for a in 0..=255_u32 {
+        for c in 0..=255_u32 {
+            let px = Pixel::from_array([c as u8, 0, 255, a as u8]);
+            let pm = px.premultiplied();
+            assert_eq!(pm.to_array(), [((2 * c * a + 255) / 510) as u8, 0, a as u8, a as u8]);
+            assert_eq!(pm.unpremultiplied().premultiplied(), pm);
+        }
+    }
+    assert_eq!(Pixel::from_array([10, 20, 30, 0]).unpremultiplied().rgb(), [10, 20, 30]);
+    assert_eq!(Pixel::from_array([10, 20, 30]).premultiplied().rgb(), [10, 20, 30]);
+
+    let encoded = std::fs::read("assets/testcard_rgba.qoi").unwrap();
+    let (header, straight) = qoi::decode_to_vec(&encoded).unwrap();
+    let (w, h) = (header.width, header.height);
+    let premultiply = |pixels: &[u8]| -> Vec<u8> {
+        let pixels = pixels.chunks_exact(4).map(|p| Pixel::from_array([p[0], p[1], p[2], p[3]]));
+        pixels.flat_map(|px| px.premultiplied().to_array()).collect()
+    };
+    let premultiplied = premultiply(&straight);
+    assert_ne!(premultiplied, straight);
+
+    let decoder = Decoder::new(&encoded).unwrap().with_premultiplied_alpha(true);
+    assert!(decoder.is_premultiplied());
+    assert!(decoder.clone().decode_to_vec().unwrap() == premultiplied);
+    let mut decoder = decoder.clone();
+    let mut out = vec![0; premultiplied.len()];
+    for chunk in out.chunks_mut(4 * 777) {
+        decoder.decode_pixels(chunk).unwrap();
+    }
+    assert!(out == premultiplied);
+    let decoder = Decoder::new(&encoded).unwrap().with_premultiplied_alpha(true);
+    let bgra = decoder.with_target_channels(TargetChannels::Bgra).unwrap().decode_to_vec();
+    let expected: Vec<u8> =
+        premultiplied.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], p[3]]).collect();
+    assert!(bgra.unwrap() == expected);
+    let decoder = Decoder::new(&encoded).unwrap().with_channels(3).unwrap();
+    let rgb = decoder.clone().with_premultiplied_alpha(true).decode_to_vec().unwrap();
+    assert!(rgb == decoder.clone().decode_to_vec().unwrap());
+
+    // the default alpha is applied before premultiplying
+    let encoded_rgb = std::fs::read("assets/kodim10.qoi").unwrap();
+    let decoder = Decoder::new(&encoded_rgb).unwrap().with_channels(4).unwrap();
+    let opaque = decoder.clone().with_premultiplied_alpha(true).decode_to_vec().unwrap();
+    assert!(opaque == decoder.clone().decode_to_vec().unwrap());
+    let mut decoder = decoder.with_default_alpha(0x80);
+    let half = decoder.clone().with_premultiplied_alpha(true).decode_to_vec().unwrap();
+    assert!(half == premultiply(&decoder.decode_to_vec().unwrap()));
+
+    // encoding premultiplied pixels stores them un-premultiplied
+    let restored: Vec<u8> = premultiplied
+        .chunks_exact(4)
+        .flat_map(|p| Pixel::from_array([p[0], p[1], p[2], p[3]]).unpremultiplied().to_array())
+        .collect();
+    let expected = qoi::encode_to_vec(&restored, w, h).unwrap();
+    let encoder =
+        EncoderBuilder::new(&premultiplied, w, h).with_premultiplied_alpha(true).build().unwrap();
+    assert!(encoder.is_premultiplied());
+    assert_eq!(encoder.encode_to_vec().unwrap(), expected);
+    let mut resumable = encoder.resumable();
+    let mut out = vec![];
+    while !resumable.is_finished() {
+        resumable.encode_step(&mut out, 1000).unwrap();
+    }
+    assert_eq!(out, expected);
+    let (_, decoded) = qoi::decode_to_vec(&expected).unwrap();
+    assert!(premultiply(&decoded) == premultiplied);
+    let argb: Vec<u8> =
+        premultiplied.chunks_exact(4).flat_map(|p| [p[3], p[0], p[1], p[2]]).collect();
+    let encoder = EncoderBuilder::new(&argb, w, h)
+        .with_source_channels(SourceChannels::Argb)
+        .with_premultiplied_alpha(true)
+        .build()
+        .unwrap();
+    assert_eq!(encoder.encode_to_vec().unwrap(), expected);
+}
+
+#[test]
+fn test_output_stride() {
+    use qoi::{Decoder, ErrorKind, TargetChannels};
+
+    let encoded = std::fs::read("assets/dice.qoi").unwrap();
+    let (header, rgba) = qoi::decode_to_vec(&encoded).unwrap();
+    let (width, height) = (header.width as usize, header.height as usize);
+    assert_eq!(Decoder::new(&encoded).unwrap().output_stride(), width * 4);
+
+    for (target, bpp) in [(TargetChannels::Rgba, 4), (TargetChannels::Bgr, 3)] {
+        let stride = width * bpp + 13;
+        let plain = Decoder::new(&encoded).unwrap().with_target_channels(target).unwrap();
+        let plain = plain.clone().decode_to_vec().unwrap();
+        let row = |y: usize| &plain[y * width * bpp..(y + 1) * width * bpp];
+        let check = |buf: &[u8], n_rows: usize| {
+            for (y, chunk) in buf.chunks(stride).take(n_rows).enumerate() {
+                assert_eq!(&chunk[..width * bpp], row(y));
+                assert!(chunk[width * bpp..].iter().all(|&b| b == 0xaa));
+            }
+        };
+
+        let decoder = Decoder::new(&encoded).unwrap().with_target_channels(target).unwrap();
+        let mut decoder = decoder.with_output_stride(stride);
+        assert_eq!(decoder.output_stride(), stride);
+        assert_eq!(decoder.required_buf_len(), stride * (height - 1) + width * bpp);
+        let mut buf = vec![0xaa; stride * height];
+        assert_eq!(decoder.clone().decode_to_buf(&mut buf).unwrap(), decoder.required_buf_len());
+        check(&buf, height);
+        assert_eq!(decoder.clone().decode_to_vec().unwrap().len(), decoder.required_buf_len());
+
+        // rows are decoded in strips at the same stride
+        let mut buf = vec![0xaa; stride * height];
+        for strip in buf.chunks_mut(stride * 7) {
+            let n_rows = strip.len() / stride;
+            assert_eq!(decoder.decode_rows(7, strip).unwrap(), n_rows);
+        }
+        check(&buf, height);
+
+        // regions are written at the stride as well
+        let decoder = Decoder::new(&encoded).unwrap().with_target_channels(target).unwrap();
+        let mut decoder = decoder.with_output_stride(stride);
+        let mut buf = vec![0xaa; stride * 3];
+        assert_eq!(decoder.decode_region(0, 0, 800, 3, &mut buf).unwrap(), stride * 2 + 800 * bpp);
+        check(&buf, 3);
+    }
+
+    let decoder = Decoder::new(&encoded).unwrap().with_output_stride(800 * 4 - 1);
+    let err = decoder.clone().decode_to_vec().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidStride);
+    let err = decoder.clone().decode_region(0, 0, 800, 2, vec![0; 6400]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidStride);
+    // a smaller stride is fine for narrower regions
+    let mut buf = vec![0; 800 * 4 * 2];
+    assert_eq!(decoder.clone().decode_region(1, 0, 799, 2, &mut buf).unwrap(), 6395);
+    assert_eq!(buf[..799 * 4], rgba[4..800 * 4]);
+    assert_eq!(buf[3199..3199 + 799 * 4], rgba[800 * 4 + 4..1600 * 4]);
+
+    let mut decoder = Decoder::new(&encoded).unwrap().with_output_stride(800 * 4);
+    decoder.decode_pixels([0; 4]).unwrap();
+    let err = decoder.decode_to_vec().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::DecodingStarted);
+}
+
+#[test]
+fn test_decode_converted() {
+    use qoi::parallel::ParallelOptions;
+    use qoi::{convert, Decoder, ErrorKind, TargetChannels};
+
+    let encoded = std::fs::read("assets/dice.qoi").unwrap();
+    let rgba = qoi::decode_to_vec(&encoded).unwrap().1;
+    let mut bgra = vec![0; rgba.len()];
+    convert::rgba_to_bgra(&rgba, &mut bgra).unwrap();
+
+    for threads in [1, 3, 0] {
+        for strip_rows in [0, 7, 64, 1000] {
+            let options = ParallelOptions::new().with_threads(threads).with_strip_rows(strip_rows);
+            let mut decoder = Decoder::new(&encoded).unwrap();
+            let out = decoder.decode_to_vec_converted(4, &options, convert::rgba_to_bgra);
+            assert!(out.unwrap() == bgra);
+            assert_eq!(decoder.pixels_left(), 0);
+
+            // neither the target pixel layout nor the output stride affect the strips
+            let mut decoder = Decoder::new(&encoded).unwrap();
+            decoder = decoder.with_target_channels(TargetChannels::Abgr).unwrap();
+            decoder = decoder.with_output_stride(800 * 4 + 12);
+            let out = decoder.decode_to_vec_converted(4, &options, convert::rgba_to_bgra);
+            assert!(out.unwrap() == bgra);
+            assert_eq!(decoder.pixels_left(), 0);
+        }
+    }
+
+    // output pixels may be of any size, e.g. RGB565
+    let options = ParallelOptions::new().with_threads(4).with_strip_rows(10);
+    let mut decoder = Decoder::new(&encoded).unwrap().with_channels(3).unwrap();
+    decoder.decode_pixels(&mut [0; 3 * 1000]).unwrap();
+    let mut out = vec![0xaa; 2 * 800 * 600];
+    let n = decoder
+        .decode_to_buf_converted(&mut out, 2, &options, |src, dst| {
+            for (s, d) in src.chunks_exact(3).zip(dst.chunks_exact_mut(2)) {
+                let v = (u16::from(s[0]) >> 3) << 11
+                    | (u16::from(s[1]) >> 2) << 5
+                    | u16::from(s[2]) >> 3;
+                d.copy_from_slice(&v.to_le_bytes());
+            }
+            Ok(src.len() / 3)
+        })
+        .unwrap();
+    assert_eq!(n, 2 * (800 * 600 - 1000));
+    for (p, d) in rgba.chunks_exact(4).skip(1000).zip(out.chunks_exact(2)) {
+        let v = u16::from_le_bytes([d[0], d[1]]);
+        assert_eq!(
+            [(v >> 11) as u8, (v >> 5 & 0x3f) as u8, (v & 0x1f) as u8],
+            [p[0] >> 3, p[1] >> 2, p[2] >> 3]
+        );
+    }
+    assert_eq!(out[n..], [0xaa; 2 * 1000]);
+
+    // the target pixel layout and output stride are ignored, `convert` always gets tightly
+    // packed RGB(A) pixels
+    let rgb = Decoder::new(&encoded).unwrap().with_channels(3).unwrap().decode_to_vec().unwrap();
+    let identity = |src: &[u8], dst: &mut [u8]| {
+        dst.copy_from_slice(src);
+        Ok(src.len())
+    };
+    let mut decoder = Decoder::new(&encoded).unwrap().with_channels(3).unwrap();
+    decoder = decoder.with_target_channels(TargetChannels::Rgbx).unwrap();
+    assert!(decoder.decode_to_vec_converted(3, &options, identity).unwrap() == rgb);
+    let mut decoder = Decoder::new(&encoded).unwrap().with_channels(3).unwrap();
+    decoder = decoder.with_output_stride(800 * 3 + 16);
+    assert!(decoder.decode_to_vec_converted(3, &options, identity).unwrap() == rgb);
+
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    let err = decoder.decode_to_buf_converted(&mut [0; 100], 4, &options, convert::rgba_to_bgra);
+    assert_eq!(err.unwrap_err().kind(), ErrorKind::OutputBufferTooSmall);
+    assert_eq!(decoder.pixels_left(), 800 * 600);
+    let err = decoder.decode_to_vec_converted(3, &options, convert::rgba_to_bgra).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::OutputBufferTooSmall);
+
+    // panics in the conversion are propagated instead of leaving strips unconverted
+    let pool = std::sync::Arc::new(rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap());
+    let pooled = ParallelOptions::new().with_pool(pool).with_strip_rows(7);
+    for options in [ParallelOptions::new().with_threads(3).with_strip_rows(7), pooled] {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            Decoder::new(&encoded).unwrap().decode_to_vec_converted(4, &options, |src, dst| {
+                assert_eq!(src.len(), 4 * 800 * 7, "last strip is shorter");
+                convert::rgba_to_bgra(src, dst)
+            })
+        }));
+        let payload = result.unwrap_err();
+        assert!(payload.downcast_ref::<String>().unwrap().contains("last strip is shorter"));
+    }
+}
+
+#[test]
+fn test_encode_decode_body() {
+    use qoi::{Channels, ColorSpace, Decoder, Encoder, ErrorKind, Header};
+
+    let encoded = std::fs::read("assets/wikipedia_008.qoi").unwrap();
+    let (header, pixels) = qoi::decode_to_vec(&encoded).unwrap();
+    let (w, h) = (header.width, header.height);
+    let body = qoi::encode_body_to_vec(&pixels, w, h).unwrap();
+    assert!(body[..] == qoi::encode_to_vec(&pixels, w, h).unwrap()[14..]);
+
+    let encoder = Encoder::new(&pixels, w, h).unwrap();
+    let mut buf = vec![0; encoder.required_buf_len() - 14];
+    let n = qoi::encode_body_to_buf(&mut buf, &pixels, w, h).unwrap();
+    assert!(buf[..n] == body[..]);
+    let err = encoder.encode_body_to_buf(&mut buf[..n]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::OutputBufferTooSmall);
+
+    assert!(qoi::decode_body_to_vec(&body, header).unwrap() == pixels);
+    let mut out = vec![0; pixels.len()];
+    assert_eq!(qoi::decode_body_to_buf(&mut out, &body, header).unwrap(), pixels.len());
+    assert!(out == pixels);
+
+    let mut decoder = Decoder::from_stream_body(&body[..], header).unwrap();
+    assert!(decoder.decode_to_vec().unwrap() == pixels);
+    assert_eq!(decoder.bytes_consumed(), body.len());
+    let mut decoder = Decoder::new_body(&body, header).unwrap().with_channels(3).unwrap();
+    decoder.decode_pixels(&mut [0; 3 * 10]).unwrap();
+    let state = decoder.checkpoint();
+    let rest = decoder.decode_to_vec().unwrap();
+    decoder.restore(state).unwrap();
+    assert!(decoder.decode_to_vec().unwrap() == rest);
+
+    let header =
+        Header { width: 0, height: h, channels: Channels::Rgb, colorspace: ColorSpace::Srgb };
+    let err = Decoder::new_body(&body, header).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidImageDimensions);
+    let header = Header::try_new(w, h, Channels::Rgba, ColorSpace::Srgb).unwrap();
+    let err = qoi::decode_body_to_vec(&body[..body.len() - 1], header).unwrap_err();
+    assert!(err.is_truncated());
+}
+
+#[test]
+fn test_decode_lenient_channels() {
+    use qoi::{Channels, Decoder, Diagnostic, ErrorKind};
+
+    let encoded = std::fs::read("assets/testcard_rgba.qoi").unwrap();
+    let (header, rgba) = qoi::decode_to_vec(&encoded).unwrap();
+    let rgb: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+    let mut lying = encoded.clone();
+    lying[12] = 3;
+
+    let err = qoi::decode_to_vec(&lying).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidOp);
+    assert_eq!(Decoder::new(&lying).unwrap().detect_channels().unwrap(), Channels::Rgba);
+    let mut decoder = Decoder::new(&lying).unwrap().with_detected_channels().unwrap();
+    assert!(decoder.lenient_channels());
+    assert_eq!(decoder.channels(), Channels::Rgba);
+    assert!(decoder.decode_to_vec().unwrap() == rgba);
+
+    // any RGBA op in an image declared as RGB is a mismatch, even if it's fully opaque
+    let rgb_header = qoi::Header::try_new(2, 1, Channels::Rgb, qoi::ColorSpace::Srgb).unwrap();
+    let mislabeled =
+        [&rgb_header.encode()[..], &[0xff, 1, 2, 3, 0xff, 0xc0], &[0, 0, 0, 0, 0, 0, 0, 1]];
+    let mislabeled = mislabeled.concat();
+    assert_eq!(qoi::decode_to_vec(&mislabeled).unwrap_err().kind(), ErrorKind::InvalidOp);
+    assert_eq!(Decoder::new(&mislabeled).unwrap().detect_channels().unwrap(), Channels::Rgba);
+    let mut decoder = Decoder::new(&mislabeled).unwrap().with_detected_channels().unwrap();
+    assert_eq!(decoder.decode_to_vec().unwrap(), [1, 2, 3, 0xff, 1, 2, 3, 0xff]);
+
+    let decoder = Decoder::new(&lying).unwrap().with_lenient_channels(true).with_default_alpha(0);
+    let out = decoder.clone().decode_to_vec().unwrap();
+    assert!(out == rgb);
+    let (mut n_diags, mut first) = (0, None);
+    let out = Decoder::from_stream(&lying[..])
+        .unwrap()
+        .with_lenient_channels(true)
+        .decode_to_vec_with_diagnostics(|diag| {
+            n_diags += 1;
+            first = first.or(Some(diag));
+        })
+        .unwrap();
+    assert!(out == rgb);
+    let n_rgba = qoi::ops::Ops::new(&encoded)
+        .unwrap()
+        .filter(|op| matches!(op, Ok(qoi::ops::Op::Rgba { .. })))
+        .count();
+    assert!(n_rgba > 0);
+    assert_eq!(n_diags, n_rgba);
+    assert!(
+        matches!(first, Some(Diagnostic::UndeclaredAlpha { byte_offset, .. }) if lying[byte_offset] == 0xff)
+    );
+    let mut decoder = decoder.with_channels(4).unwrap();
+    assert!(decoder.decode_to_vec().unwrap() == rgba);
+
+    // images that are declared as RGBA but are fully opaque can be decoded as RGB
+    let opaque: Vec<u8> = rgb.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 0xff]).collect();
+    let opaque = qoi::encode_to_vec(opaque, header.width, header.height).unwrap();
+    let mut decoder = Decoder::new(&opaque).unwrap().with_detected_channels().unwrap();
+    assert!(!decoder.lenient_channels());
+    assert_eq!(decoder.channels(), Channels::Rgb);
+    assert!(decoder.decode_to_vec().unwrap() == rgb);
+}
+
+#[test]
+fn test_decode_default_alpha() {
+    use qoi::{Channels, Decoder};
+
+    let rgba = qoi::decode_to_vec(std::fs::read("assets/dice.qoi").unwrap()).unwrap().1;
+    let rgb: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+    let encoded = qoi::encode_to_vec(&rgb, 800, 600).unwrap();
+    let expected: Vec<u8> = rgb.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 0]).collect();
+
+    let decoder = Decoder::new(&encoded).unwrap().with_channels(Channels::Rgba).unwrap();
+    assert_eq!(decoder.default_alpha(), 0xff);
+    let mut decoder = decoder.with_default_alpha(0);
+    assert_eq!(decoder.decode_to_vec().unwrap(), expected);
+    let decoder = Decoder::from_stream(&encoded[..]).unwrap().with_default_alpha(0);
+    let mut decoder = decoder.with_channels(Channels::Rgba).unwrap();
+    let mut out = vec![0xaa; expected.len()];
+    for chunk in out.chunks_mut(4 * 1000) {
+        decoder.decode_pixels(chunk).unwrap();
+    }
+    assert_eq!(out, expected);
+
+    // no effect on RGBA sources or RGB output
+    let mut decoder = Decoder::new(&encoded).unwrap().with_default_alpha(0);
+    assert_eq!(decoder.decode_to_vec().unwrap(), rgb);
+    let encoded = qoi::encode_to_vec(&rgba, 800, 600).unwrap();
+    let mut decoder = Decoder::new(&encoded).unwrap().with_default_alpha(0);
+    assert_eq!(decoder.decode_to_vec().unwrap(), rgba);
+}
+
+#[test]
+fn test_strict_decoding() {
+    use qoi::{Decoder, Error, ErrorKind};
+
+    let mut encoded = qoi::encode_to_vec([0, 0, 0, 0, 0, 0], 2, 1).unwrap();
+    assert_eq!(encoded[14..], [0xc1, 0, 0, 0, 0, 0, 0, 0, 1]);
+    encoded[14] = 0xc5; // run of 6 in a 2-pixel image
+
+    assert_eq!(qoi::decode_to_vec(&encoded).unwrap().1, [0; 6]);
+    let mut decoder = Decoder::new(&encoded).unwrap().with_strict(true);
+    assert!(decoder.is_strict());
+    match decoder.decode_to_vec().unwrap_err() {
+        Error::WithHeader { source, .. } => {
+            assert!(matches!(*source, Error::InvalidRunLength { run: 6, pixels_left: 2 }));
+        }
+        err => panic!("expected header to be attached: {err:?}"),
+    }
+    let mut decoder = Decoder::from_stream(&encoded[..]).unwrap().with_strict(true);
+    assert_eq!(decoder.decode_to_vec().unwrap_err().kind(), ErrorKind::InvalidRunLength);
+    let mut decoder = Decoder::new(&encoded).unwrap().with_strict(true);
+    let err = decoder.decode_pixels([0; 3]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidRunLength);
+    let slices = [&encoded[..15], &encoded[15..]];
+    let mut decoder = Decoder::from_slices(&slices).unwrap().with_strict(true);
+    assert_eq!(decoder.decode_to_vec().unwrap_err().kind(), ErrorKind::InvalidRunLength);
+    assert_eq!(Decoder::from_slices(&slices).unwrap().decode_to_vec().unwrap(), [0; 6]);
+
+    encoded[14] = 0xc1;
+    encoded.extend([1, 2, 3]);
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    assert_eq!(decoder.decode_to_vec().unwrap(), [0; 6]);
+    assert_eq!(decoder.data(), [1, 2, 3]);
+    let mut decoder = Decoder::new(&encoded).unwrap().with_strict(true);
+    let err = decoder.decode_to_vec().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TrailingBytes);
+    let mut decoder = Decoder::from_stream(&encoded[..]).unwrap().with_strict(true);
+    assert_eq!(decoder.decode_to_vec().unwrap(), [0; 6]);
+}
+
+#[test]
+fn test_empty_images() {
+    use qoi::{Channels, Decoder, EncoderBuilder, ErrorKind, HeaderOptions, SourceChannels};
+
+    let err = EncoderBuilder::new(&[], 0, 5).build().err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidImageDimensions);
+
+    let options = HeaderOptions::new().with_allow_empty(true);
+    for (width, height) in [(0, 5), (5, 0), (0, 0)] {
+        let encoder = EncoderBuilder::new(&[], width, height).with_allow_empty(true).build();
+        let encoded = encoder.unwrap().encode_to_vec().unwrap();
+        assert_eq!(encoded.len(), 14 + 8);
+        assert_eq!(encoded[14..], [0, 0, 0, 0, 0, 0, 0, 1]);
+
+        let err = qoi::decode_to_vec(&encoded).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidImageDimensions);
+        let header = qoi::Header::decode_with_options(&encoded, options).unwrap();
+        assert_eq!((header.width, header.height, header.channels), (width, height, Channels::Rgb));
+
+        let mut decoder = Decoder::new_with_options(&encoded, options).unwrap();
+        assert_eq!(decoder.decode_to_vec().unwrap(), []);
+        assert!(decoder.data().is_empty());
+        let mut decoder = Decoder::from_stream_with_options(&encoded[..], options).unwrap();
+        assert_eq!(decoder.decode_to_vec().unwrap(), []);
+    }
+
+    let encoded = EncoderBuilder::new(&[], 0, 3)
+        .with_source_channels(SourceChannels::Bgra)
+        .with_allow_empty(true)
+        .build()
+        .unwrap()
+        .encode_to_vec()
+        .unwrap();
+    assert_eq!(encoded[12], 4);
+    let err = EncoderBuilder::new(&[0; 3], 1, 0).with_allow_empty(true).build().err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidImageLength);
+}
+
+#[test]
+fn test_encode_oversized() {
+    use qoi::{EncoderBuilder, ErrorKind, SourceChannels};
+
+    let mut pixels = vec![0x7f; 4 * 3 * 2];
+    let expected = qoi::encode_to_vec(&pixels, 4, 2).unwrap();
+    pixels.extend([1, 2, 3, 4, 5]);
+
+    let err = qoi::encode_to_vec(&pixels, 4, 2).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidImageLength);
+    let builder = EncoderBuilder::new(&pixels, 4, 2).with_source_channels(SourceChannels::Rgb);
+    let err = builder.with_allow_oversized(false).build().err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidImageLength);
+
+    let encoder = builder.with_allow_oversized(true).build().unwrap();
+    assert_eq!(encoder.encode_to_vec().unwrap(), expected);
+    let builder =
+        EncoderBuilder::new(&pixels[..23], 4, 2).with_source_channels(SourceChannels::Rgb);
+    let err = builder.with_allow_oversized(true).build().err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidImageLength);
+}
+
+#[test]
+fn test_encode_to_flaky_stream() {
+    use std::io::{self, Write};
+
+    #[derive(Default)]
+    struct FlakyWriter {
+        out: Vec<u8>,
+        n_calls: usize,
+        fail_after: Option<usize>,
+        n_flushes: usize,
+    }
+
+    impl Write for FlakyWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.n_calls += 1;
+            if self.n_calls % 3 == 0 {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            if self.fail_after.map_or(false, |n| self.out.len() >= n) {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            let n = buf.len().min(2);
+            self.out.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.n_flushes += 1;
+            Ok(())
+        }
+    }
+
+    let (header, pixels) = qoi::decode_to_vec(std::fs::read("assets/dice.qoi").unwrap()).unwrap();
+    let encoder = qoi::Encoder::new(&pixels, header.width, header.height).unwrap();
+    let expected = encoder.encode_to_vec().unwrap();
+
+    let mut writer = FlakyWriter::default();
+    assert_eq!(encoder.encode_to_stream(&mut writer).unwrap(), expected.len());
+    assert_eq!(writer.out, expected);
+    assert_eq!(writer.n_flushes, 0);
+
+    let mut writer = FlakyWriter { fail_after: Some(1001), ..Default::default() };
+    match encoder.encode_to_stream(&mut writer).unwrap_err() {
+        qoi::Error::PartialWrite { n_written, source } => {
+            assert_eq!(n_written, writer.out.len());
+            assert!(n_written >= 1001);
+            assert_eq!(source.kind(), io::ErrorKind::BrokenPipe);
+        }
+        err => panic!("expected a partial write error: {err:?}"),
+    }
+
+    // nothing written at all is a plain i/o error
+    let mut writer = FlakyWriter { fail_after: Some(0), ..Default::default() };
+    match encoder.encode_to_stream(&mut writer).unwrap_err() {
+        qoi::Error::IoError(err) => assert_eq!(err.kind(), io::ErrorKind::BrokenPipe),
+        err => panic!("expected an i/o error: {err:?}"),
+    }
+    assert_eq!(writer.n_calls, 1);
+
+    let mut writer = FlakyWriter::default();
+    encoder.with_flush(true).encode_to_stream(&mut writer).unwrap();
+    assert_eq!(writer.n_flushes, 1);
+}
+
+#[test]
+fn test_decode_from_flaky_stream() {
+    use qoi::{Decoder, TranscodeOptions};
+    use std::io::{self, Read};
+
+    struct FlakyReader<'a> {
+        data: &'a [u8],
+        n_calls: usize,
+    }
+
+    impl Read for FlakyReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.n_calls += 1;
+            if self.n_calls % 3 == 0 {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            let n = buf.len().min(1 + self.n_calls % 4).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    let encoded = std::fs::read("assets/testcard_rgba.qoi").unwrap();
+    let (header, pixels) = qoi::decode_to_vec(&encoded).unwrap();
+    let flaky = || FlakyReader { data: &encoded, n_calls: 0 };
+
+    let mut decoder = Decoder::from_stream(flaky()).unwrap();
+    assert_eq!(*decoder.header(), header);
+    assert!(decoder.decode_to_vec().unwrap() == pixels);
+    assert!(decoder.reader().data.is_empty());
+
+    let mut decoder = Decoder::from_stream(flaky()).unwrap().with_channels(3).unwrap();
+    let mut out = vec![0; 3 * header.n_pixels()];
+    for chunk in out.chunks_mut(3 * 1000) {
+        decoder.decode_pixels(chunk).unwrap();
+    }
+    assert!(out.chunks_exact(3).zip(pixels.chunks_exact(4)).all(|(a, b)| a == &b[..3]));
+
+    let mut transcoded = vec![];
+    qoi::transcode_stream(flaky(), &mut transcoded, TranscodeOptions::new()).unwrap();
+    assert!(transcoded == qoi::encode_to_vec(&pixels, header.width, header.height).unwrap());
+
+    let truncated = FlakyReader { data: &encoded[..encoded.len() - 1], n_calls: 0 };
+    let err = Decoder::from_stream(truncated).unwrap().decode_to_vec().unwrap_err();
+    assert!(err.is_truncated());
+}
+
+#[tokio::test]
+async fn test_async_stream() {
+    use qoi::{Decoder, Encoder, ErrorKind};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let images: Vec<_> = ["assets/dice.qoi", "assets/testcard.qoi", "assets/kodim10.qoi"]
+        .iter()
+        .map(|path| qoi::decode_to_vec(std::fs::read(path).unwrap()).unwrap())
+        .collect();
+
+    // images are streamed back to back through a pipe that only fits a few bytes at a time,
+    // followed by some trailing bytes that must be left untouched
+    let (mut writer, mut reader) = tokio::io::duplex(7);
+    let sent = images.clone();
+    let send = tokio::spawn(async move {
+        for (header, pixels) in &sent {
+            let encoder = Encoder::new(pixels, header.width, header.height).unwrap();
+            let expected = encoder.encode_to_vec().unwrap();
+            let encoder = encoder.with_flush(true);
+            assert_eq!(encoder.encode_to_async_stream(&mut writer).await.unwrap(), expected.len());
+            let mut out = vec![];
+            encoder.encode_to_async_stream(&mut out).await.unwrap();
+            assert_eq!(out, expected);
+        }
+        writer.write_all(b"tail").await.unwrap();
+    });
+    for (header, pixels) in &images {
+        let mut decoder = Decoder::from_async_stream(reader).await.unwrap();
+        assert_eq!(decoder.header(), header);
+        if header.channels.is_rgba() {
+            let err = decoder.decode_pixels(&mut [0; 5]).await.unwrap_err();
+            assert_eq!(err.kind(), qoi::ErrorKind::InvalidImageLength);
+            let mut out = vec![0; pixels.len()];
+            for chunk in out.chunks_mut(4 * 1000) {
+                let n_pixels = chunk.len() / 4;
+                assert_eq!(decoder.decode_pixels(chunk).await.unwrap(), n_pixels);
+            }
+            assert!(out == *pixels);
+        } else {
+            assert!(decoder.decode_to_vec().await.unwrap() == *pixels);
+        }
+        assert_eq!(decoder.pixels_left(), 0);
+        reader = decoder.into_inner();
+    }
+    let mut tail = vec![];
+    reader.read_to_end(&mut tail).await.unwrap();
+    assert_eq!(tail, b"tail");
+    send.await.unwrap();
+
+    let (header, pixels) = &images[0];
+    let encoded = qoi::encode_to_vec(pixels, header.width, header.height).unwrap();
+    let decoder = Decoder::from_async_stream(&encoded[..]).await.unwrap();
+    let mut decoder = decoder.with_channels(3).unwrap();
+    let mut out = vec![0; 3 * header.n_pixels()];
+    assert_eq!(decoder.decode_to_buf(&mut out).await.unwrap(), out.len());
+    assert!(out.chunks_exact(3).zip(pixels.chunks_exact(4)).all(|(a, b)| a == &b[..3]));
+
+    let mut decoder = Decoder::from_async_stream(&encoded[..encoded.len() - 1]).await.unwrap();
+    assert!(decoder.decode_to_vec().await.unwrap_err().is_truncated());
+    let err = Decoder::from_async_stream(&encoded[..10]).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedStreamEnd);
+}
+
+#[test]
+fn test_futures_stream() {
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use futures::executor::block_on;
+    use futures::io::AsyncRead;
+    use qoi::Decoder;
+
+    // returns a few bytes at a time, interleaved with spurious wakeups and interruptions
+    struct Flaky<'a>(&'a [u8], usize);
+
+    impl AsyncRead for Flaky<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            self.1 += 1;
+            match self.1 % 3 {
+                0 => {
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+                1 => Poll::Ready(Err(io::ErrorKind::Interrupted.into())),
+                _ => {
+                    let n = buf.len().min(self.0.len()).min(5);
+                    buf[..n].copy_from_slice(&self.0[..n]);
+                    self.0 = &self.0[n..];
+                    Poll::Ready(Ok(n))
+                }
+            }
+        }
+    }
+
+    block_on(async {
+        let mut stream = vec![];
+        let mut images = vec![];
+        for path in ["assets/dice.qoi", "assets/kodim10.qoi"] {
+            let (header, pixels) = qoi::decode_to_vec(std::fs::read(path).unwrap()).unwrap();
+            let encoder = qoi::Encoder::new(&pixels, header.width, header.height).unwrap();
+            let n = encoder.encode_to_futures_stream(&mut stream).await.unwrap();
+            assert_eq!(n, encoder.encode_to_vec().unwrap().len());
+            images.push((header, pixels));
+        }
+        let n_bytes = stream.len();
+        stream.extend(b"tail");
+
+        let mut reader = Flaky(&stream, 0);
+        for (header, pixels) in &images {
+            let mut decoder = Decoder::from_futures_stream(reader).await.unwrap();
+            assert_eq!(decoder.header(), header);
+            assert!(decoder.decode_to_vec().await.unwrap() == *pixels);
+            reader = decoder.into_inner();
+        }
+        assert_eq!(reader.0, b"tail");
+
+        let mut decoder = Decoder::from_futures_stream(&stream[..]).await.unwrap();
+        let mut out = vec![0; images[0].1.len()];
+        assert_eq!(decoder.decode_to_buf(&mut out).await.unwrap(), out.len());
+        let n_consumed = decoder.bytes_consumed();
+        assert_eq!(decoder.into_inner(), &stream[n_consumed..]);
+
+        let truncated = Flaky(&stream[..n_bytes / 4], 0);
+        let mut decoder = Decoder::from_futures_stream(truncated).await.unwrap();
+        assert!(decoder.decode_to_vec().await.unwrap_err().is_truncated());
+    });
+}
+
+#[test]
+fn test_bytes_consumed() {
+    use qoi::Decoder;
+    use std::io::Read;
+
+    let first = std::fs::read("assets/dice.qoi").unwrap();
+    let second = std::fs::read("assets/qoi_logo.qoi").unwrap();
+    let data = [&first[..], &second[..], b"tail"].concat();
+
+    let mut decoder = Decoder::new(&data).unwrap();
+    assert_eq!(decoder.bytes_consumed(), 14);
+    decoder.decode_to_vec().unwrap();
+    assert_eq!(decoder.bytes_consumed(), first.len());
+    assert_eq!(decoder.data(), &data[first.len()..]);
+    let mut decoder = Decoder::new(decoder.data()).unwrap();
+    decoder.decode_pixels([0; 4 * 1000]).unwrap();
+    decoder.decode_to_vec().unwrap();
+    assert_eq!(decoder.bytes_consumed(), second.len());
+    assert_eq!(decoder.data(), b"tail");
+
+    let mut decoder = Decoder::from_stream(&data[..]).unwrap();
+    decoder.decode_to_vec().unwrap();
+    assert_eq!(decoder.bytes_consumed(), first.len());
+    let mut decoder = Decoder::from_stream(decoder.into_inner()).unwrap();
+    decoder.decode_to_vec().unwrap();
+    assert_eq!(decoder.bytes_consumed(), second.len());
+    let mut tail = vec![];
+    decoder.into_inner().read_to_end(&mut tail).unwrap();
+    assert_eq!(tail, b"tail");
+}
+
+#[test]
+fn test_send_sync() {
+    use qoi::{Decoder, Encoder};
+    use std::io::Cursor;
+
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+    let encoded = std::fs::read("assets/dice.qoi").unwrap();
+    let stream = Decoder::from_stream(Cursor::new(encoded.clone())).unwrap();
+    assert_send_sync(&stream);
+    let (header, expected) = qoi::decode_to_vec(&encoded).unwrap();
+    let encoder = Encoder::new(&expected, header.width, header.height).unwrap();
+    assert_send_sync(&encoder);
+
+    let handles = vec![
+        std::thread::spawn(move || stream.with_channels(qoi::Channels::Rgba)?.decode_to_vec()),
+        std::thread::spawn(move || Decoder::new(&encoded)?.decode_to_vec()),
+    ];
+    for handle in handles {
+        assert_eq!(handle.join().unwrap().unwrap(), expected);
+    }
+    assert_eq!(
+        Decoder::new(&encoder.encode_to_vec().unwrap()).unwrap().decode_to_vec().unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn test_capabilities() {
+    use qoi::Feature;
+
+    assert_eq!(qoi::crate_version(), env!("CARGO_PKG_VERSION"));
+    // dev-dependencies enable gray-alpha, anim, batch, rayon, tokio, futures-io and image on
+    // top of the defaults
+    for feature in [
+        Feature::Std,
+        Feature::Alloc,
+        Feature::GrayAlpha,
+        Feature::Anim,
+        Feature::Batch,
+        Feature::Rayon,
+        Feature::Tokio,
+        Feature::FuturesIo,
+        Feature::Image,
+    ] {
+        assert!(feature.is_enabled(), "{:?}", feature);
+    }
+    assert_eq!(Feature::Reference.is_enabled(), cfg!(feature = "reference"));
+    assert_eq!(Feature::Sdl2.is_enabled(), cfg!(feature = "sdl2"));
+    for &feature in Feature::ALL {
+        assert_eq!(Feature::from_name(feature.name()), Some(feature));
+    }
+    assert_eq!(Feature::from_name("gray-alpha"), Some(Feature::GrayAlpha));
+    assert_eq!(Feature::from_name("animation"), None);
+    assert_eq!(qoi::supported_extensions(), ["gray-alpha", "anim"]);
+}
+
+#[test]
+fn test_owned_encoder() {
+    use qoi::{ColorSpace, Encoder, EncoderBuilder, OwnedEncoder, SourceChannels};
+    use std::sync::Arc;
+
+    let (header, pixels) = qoi::decode_to_vec(std::fs::read("assets/dice.qoi").unwrap()).unwrap();
+    let (w, h) = (header.width, header.height);
+    let expected = qoi::encode_to_vec(&pixels, w, h).unwrap();
+
+    let encoder = OwnedEncoder::new(pixels.clone(), w, h).unwrap();
+    let handle = std::thread::spawn(move || encoder.encode_to_vec());
+    assert!(handle.join().unwrap().unwrap() == expected);
+
+    let data: Arc<[u8]> = pixels.clone().into();
+    let encoder = OwnedEncoder::with_encoder(data, |data| {
+        Ok(Encoder::new(data, w, h)?.with_colorspace(ColorSpace::Linear))
+    })
+    .unwrap();
+    assert_eq!(encoder.header().colorspace, ColorSpace::Linear);
+    let mut out = vec![0; encoder.encoder().required_buf_len()];
+    let n = std::thread::spawn(move || encoder.encode_to_buf(&mut out).map(|n| (n, out)));
+    let (n, out) = n.join().unwrap().unwrap();
+    assert!(out[14..n] == expected[14..] && out[13] == 1);
+
+    // any builder options can be used, invalid configurations are rejected upfront
+    let bgra: Vec<u8> = pixels.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], p[3]]).collect();
+    let encoder = OwnedEncoder::with_encoder(bgra, |data| {
+        EncoderBuilder::new(data, w, h).with_source_channels(SourceChannels::Bgra).build()
+    })
+    .unwrap();
+    let mut stream = vec![];
+    assert_eq!(encoder.encode_to_stream(&mut stream).unwrap(), expected.len());
+    assert!(stream == expected);
+    assert_eq!(encoder.into_data().len(), pixels.len());
+    assert!(OwnedEncoder::new(vec![0; 7], 2, 1).is_err());
+
+    // only the part of the data the built encoder borrows is encoded
+    let oversized = [&pixels[..], &[0xff; 100]].concat();
+    let encoder = OwnedEncoder::with_encoder(oversized, |data| {
+        let builder = EncoderBuilder::new(data, w, h).with_allow_oversized(true);
+        builder.with_source_channels(SourceChannels::Rgba).build()
+    })
+    .unwrap();
+    assert!(encoder.encode_to_vec().unwrap() == expected);
+    let tail = [[0; 3], [1, 2, 3], [4, 5, 6]].concat();
+    let encoder = OwnedEncoder::with_encoder(tail, |data| Encoder::new(&data[3..], 2, 1)).unwrap();
+    assert_eq!(qoi::decode_to_vec(encoder.encode_to_vec().unwrap()).unwrap().1, [1, 2, 3, 4, 5, 6]);
+    static OTHER: [u8; 6] = [0; 6];
+    let err = OwnedEncoder::with_encoder(vec![0; 6], |_| Encoder::new(&OTHER, 2, 1)).unwrap_err();
+    assert_eq!(err.kind(), qoi::ErrorKind::InvalidImageLength);
+}
+
+#[test]
+fn test_owned_decoder() {
+    use qoi::{Channels, Decoder, OwnedBytes};
+    use std::sync::Arc;
+
+    struct Stored {
+        decoder: Decoder<OwnedBytes<Vec<u8>>>,
+    }
+
+    let data = std::fs::read("assets/dice.qoi").unwrap();
+    let (header, expected) = qoi::decode_to_vec(&data).unwrap();
+
+    let stored = Stored { decoder: Decoder::from_owned(data.clone()).unwrap() };
+    let handle = std::thread::spawn(move || {
+        let mut decoder = stored.decoder;
+        decoder.decode_to_vec().map(|pixels| (pixels, decoder.into_data()))
+    });
+    let (pixels, returned) = handle.join().unwrap().unwrap();
+    assert!(pixels == expected && returned == data);
+
+    // pixel-by-pixel decoding with checkpoints, trailing bytes and channel detection
+    let mut with_tail = data.clone();
+    with_tail.extend([1, 2, 3]);
+    let shared: Arc<[u8]> = with_tail.into();
+    let mut decoder = Decoder::from_owned(Arc::clone(&shared)).unwrap();
+    assert_eq!(decoder.detect_channels().unwrap(), Channels::Rgba);
+    let mut out = vec![0; expected.len()];
+    let n = decoder.decode_pixels(&mut out[..4000]).unwrap();
+    let checkpoint = decoder.checkpoint();
+    decoder.decode_pixels(&mut out[4 * n..]).unwrap();
+    assert!(out == expected);
+    assert_eq!(decoder.data(), &[1, 2, 3]);
+    decoder.restore(checkpoint).unwrap();
+    assert_eq!(decoder.pixels_left(), header.n_pixels() - n);
+    out[4 * n..].fill(0);
+    decoder.decode_pixels(&mut out[4 * n..]).unwrap();
+    assert!(out == expected);
+
+    let body = data[14..].to_vec();
+    let mut decoder = Decoder::from_owned_body(body, header).unwrap();
+    assert!(decoder.decode_to_vec().unwrap() == expected);
+    assert!(Decoder::from_owned(data[..10].to_vec()).is_err());
+    assert!(Decoder::from_owned(&data[..data.len() - 1]).unwrap().decode_to_vec().is_err());
+}
+
+#[test]
+fn test_clone_debug() {
+    use qoi::{Decoder, EncodedReader, EncoderBuilder};
+
+    let (header, pixels) = qoi::decode_to_vec(std::fs::read("assets/dice.qoi").unwrap()).unwrap();
+    let builder = EncoderBuilder::new(&pixels, header.width, header.height);
+    let encoder = builder.build().unwrap();
+    let encoded = encoder.encode_to_vec().unwrap();
+    let (encoder2, builder2) = (encoder.clone(), builder);
+    assert!(encoder2.encode_to_vec().unwrap() == encoded);
+    assert!(builder2.build().unwrap().encode_to_vec().unwrap() == encoded);
+
+    // pixel data is not dumped in full
+    let debug = format!("{:?}", encoder);
+    assert!(debug.starts_with("Encoder { data: [1920000 bytes], header: Header {"), "{}", debug);
+    assert!(debug.contains("store: false"), "{}", debug);
+    assert!(format!("{:?}", builder).contains("[1920000 bytes], width: 800"));
+
+    // cloned codecs carry on independently from the same point
+    let mut resumable = encoder.resumable();
+    resumable.encode_step(&mut vec![], 1000).unwrap();
+    let (mut a, mut b) = (resumable.clone(), resumable);
+    assert_eq!(format!("{:?}", a), format!("{:?}", b));
+    let (mut out_a, mut out_b) = (vec![], vec![]);
+    while !a.is_finished() {
+        a.encode_step(&mut out_a, 1 << 20).unwrap();
+    }
+    while !b.is_finished() {
+        b.encode_step(&mut out_b, 100).unwrap();
+    }
+    assert!(out_a == out_b);
+    let reader = EncodedReader::new(encoder.clone());
+    assert!(format!("{:?}", reader).starts_with("EncodedReader { encoder: ResumableEncoder {"));
+
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    decoder.decode_pixels(vec![0; 4000]).unwrap();
+    let debug = format!("{:?}", decoder);
+    assert!(
+        debug.contains("channels: Rgba, strict: false, default_alpha: 255, pixel_position: 1000")
+    );
+    let mut decoder2 = decoder.clone();
+    let rest = decoder.decode_to_vec().unwrap();
+    assert!(rest == decoder2.decode_to_vec().unwrap() && rest == pixels[4000..]);
+    let stream = Decoder::from_stream(&encoded[..]).unwrap();
+    assert!(format!("{:?}", stream).starts_with("Decoder { header: Header {"));
+}
+
+#[test]
+fn test_encode_all_source_channels() {
+    use qoi::{EncoderBuilder, SourceChannels::*};
+
+    let (header, rgba) = qoi::decode_to_vec(std::fs::read("assets/dice.qoi").unwrap()).unwrap();
+    let (w, h) = (header.width, header.height);
+    let rgb: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+    let expected_rgba = qoi::encode_to_vec(&rgba, w, h).unwrap();
+    let expected_rgb = qoi::encode_to_vec(&rgb, w, h).unwrap();
+
+    for source in [Rgb, Bgr, Rgba, Argb, Rgbx, Xrgb, Bgra, Abgr, Bgrx, Xbgr] {
+        // the variant name spells out the source layout, e.g. "bgrx"
+        let layout = format!("{:?}", source).to_ascii_lowercase();
+        assert_eq!(layout.len(), source.bytes_per_pixel());
+        let data: Vec<u8> = rgba
+            .chunks_exact(4)
+            .enumerate()
+            .flat_map(|(i, p)| {
+                layout.bytes().map(move |c| match c {
+                    b'r' => p[0],
+                    b'g' => p[1],
+                    b'b' => p[2],
+                    b'a' => p[3],
+                    _ => i as u8,
+                })
+            })
+            .collect();
+        let encoded = EncoderBuilder::new(&data, w, h)
+            .with_source_channels(source)
+            .build()
+            .unwrap()
+            .encode_to_vec()
+            .unwrap();
+        let expected = if layout.contains('a') { &expected_rgba } else { &expected_rgb };
+        assert_eq!(&encoded, expected, "{:?}", source);
+    }
+}
+
+#[test]
+fn test_encode_with_channels() {
+    use qoi::{Channels, EncoderBuilder, SourceChannels};
+
+    let (header, rgba) = qoi::decode_to_vec(std::fs::read("assets/dice.qoi").unwrap()).unwrap();
+    let (w, h) = (header.width, header.height);
+    let rgb: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+    let opaque: Vec<u8> = rgb.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 0xff]).collect();
+    let expected_rgb = qoi::encode_to_vec(&rgb, w, h).unwrap();
+    let expected_opaque = qoi::encode_to_vec(&opaque, w, h).unwrap();
+
+    let encode = |data: &[u8], source: Option<SourceChannels>, channels| {
+        let mut builder = EncoderBuilder::new(data, w, h).with_channels(channels);
+        if let Some(source) = source {
+            builder = builder.with_source_channels(source);
+        }
+        let encoder = builder.build().unwrap();
+        assert_eq!(encoder.channels(), channels);
+        encoder.encode_to_vec().unwrap()
+    };
+    assert_eq!(encode(&rgb, None, Channels::Rgba), expected_opaque);
+    assert_eq!(encode(&rgb, None, Channels::Rgb), expected_rgb);
+    assert_eq!(encode(&rgba, None, Channels::Rgb), expected_rgb);
+    assert_eq!(encode(&opaque, Some(SourceChannels::Rgbx), Channels::Rgba), expected_opaque);
+    assert_eq!(
+        encode(&rgba, Some(SourceChannels::Rgba), Channels::Rgba),
+        qoi::encode_to_vec(&rgba, w, h).unwrap()
+    );
+    assert_eq!(qoi::decode_to_vec(encode(&rgb, None, Channels::Rgba)).unwrap().1, opaque);
+}
+
+#[test]
+fn test_encode_planes() {
+    use qoi::{Channels, EncoderBuilder, ErrorKind, OwnedEncoder, SourceChannels};
+
+    let (header, rgba) = qoi::decode_to_vec(std::fs::read("assets/dice.qoi").unwrap()).unwrap();
+    let (w, h) = (header.width, header.height);
+    let rgb: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+    let planes: Vec<Vec<u8>> =
+        (0..4).map(|c| rgba.chunks_exact(4).map(|p| p[c]).collect()).collect();
+    let (expected_rgb, expected_rgba) =
+        (qoi::encode_to_vec(&rgb, w, h).unwrap(), qoi::encode_to_vec(&rgba, w, h).unwrap());
+
+    let encoder = EncoderBuilder::from_planes(&planes[..3], w, h).build().unwrap();
+    assert!(encoder.is_planar());
+    assert_eq!(encoder.stride(), w as usize);
+    assert_eq!(encoder.encode_to_vec().unwrap(), expected_rgb);
+    let encoder = EncoderBuilder::from_planes(&planes, w, h).build().unwrap();
+    assert_eq!(encoder.encode_to_vec().unwrap(), expected_rgba);
+    let mut out = vec![];
+    encoder.encode_to_stream(&mut out).unwrap();
+    assert_eq!(out, expected_rgba);
+    let mut resumable = encoder.resumable();
+    let mut out = vec![];
+    while !resumable.is_finished() {
+        resumable.encode_step(&mut out, 1000).unwrap();
+    }
+    assert_eq!(out, expected_rgba);
+
+    // the source layout maps planes to channels, the stride applies to each plane
+    let stride = w as usize + 3;
+    let padded: Vec<Vec<u8>> = planes
+        .iter()
+        .rev()
+        .map(|p| p.chunks_exact(w as usize).flat_map(|r| [r, &[9; 3]].concat()).collect())
+        .collect();
+    let encoded = EncoderBuilder::from_planes(&padded[1..], w, h)
+        .with_source_channels(SourceChannels::Bgr)
+        .with_stride(stride)
+        .build()
+        .unwrap()
+        .encode_to_vec()
+        .unwrap();
+    assert_eq!(encoded, expected_rgb);
+    let encoded = EncoderBuilder::from_planes(&padded, w, h)
+        .with_source_channels(SourceChannels::Abgr)
+        .with_stride(stride)
+        .with_channels(Channels::Rgb)
+        .build()
+        .unwrap()
+        .encode_to_vec()
+        .unwrap();
+    assert_eq!(encoded, expected_rgb);
+
+    let err = EncoderBuilder::from_planes(&planes[..2], w, h).build().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidChannels);
+    let err = EncoderBuilder::from_planes(&planes, w, h)
+        .with_source_channels(SourceChannels::Rgb)
+        .build()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidChannels);
+    let short = [&planes[0][..], &planes[1][..], &planes[2][1..]];
+    let err = EncoderBuilder::from_planes(&short, w, h).build().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidImageLength);
+    static PLANES: [[u8; 4]; 3] = [[1; 4], [2; 4], [3; 4]];
+    let err =
+        OwnedEncoder::with_encoder(rgb, |_| EncoderBuilder::from_planes(&PLANES, 2, 2).build())
+            .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidImageLength);
+}
+
+#[test]
+fn test_transcode_stream() {
+    use qoi::{Channels, ColorSpace, Encoder, EncoderBuilder, HeaderOptions, TranscodeOptions};
+
+    for path in ["assets/dice.qoi", "assets/qoi_logo.qoi"] {
+        let encoded = std::fs::read(path).unwrap();
+        let (header, rgba) = qoi::decode_to_vec(&encoded).unwrap();
+        let (w, h) = (header.width, header.height);
+        let rgb: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+        let expected = Encoder::new(&rgba, w, h).unwrap().encode_to_vec().unwrap();
+        let expected_rgb = Encoder::new(&rgb, w, h)
+            .unwrap()
+            .with_colorspace(ColorSpace::Linear)
+            .encode_to_vec()
+            .unwrap();
+
+        for strip_rows in [0, 1, 3, 16, 100_000] {
+            let options = TranscodeOptions::new().with_strip_rows(strip_rows);
+            let mut out = vec![];
+            let n_written = qoi::transcode_stream(&encoded[..], &mut out, options).unwrap();
+            assert_eq!((n_written, &out), (expected.len(), &expected));
+
+            let options = options.with_channels(Channels::Rgb).with_colorspace(ColorSpace::Linear);
+            let mut out = vec![];
+            qoi::transcode_stream(&encoded[..], &mut out, options).unwrap();
+            assert_eq!(out, expected_rgb);
+        }
+
+        // the output is incomplete if the input is truncated, but the error is reported
+        let mut out = vec![];
+        let truncated = &encoded[..encoded.len() / 2];
+        assert!(qoi::transcode_stream(truncated, &mut out, TranscodeOptions::new()).is_err());
+        assert!(out.len() < expected.len());
+    }
+
+    let empty = EncoderBuilder::new(&[], 0, 7).with_allow_empty(true).build().unwrap();
+    let encoded = empty.encode_to_vec().unwrap();
+    let mut out = vec![];
+    assert!(qoi::transcode_stream(&encoded[..], &mut out, TranscodeOptions::new()).is_err());
+    let header_options = HeaderOptions::new().with_allow_empty(true);
+    let options = TranscodeOptions::new().with_header_options(header_options);
+    out.clear();
+    qoi::transcode_stream(&encoded[..], &mut out, options).unwrap();
+    assert_eq!(out, encoded);
+    out.clear();
+    let bad_padding = [&encoded[..encoded.len() - 1], &[2]].concat();
+    assert!(qoi::transcode_stream(&bad_padding[..], &mut out, options).is_err());
+}
+
+#[test]
+fn test_resumable_encoder() {
+    use qoi::{EncoderBuilder, SourceChannels};
+
+    let (header, rgba) = qoi::decode_to_vec(std::fs::read("assets/dice.qoi").unwrap()).unwrap();
+    let (w, h) = (header.width, header.height);
+    let rgb: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+    let mut padded: Vec<u8> =
+        rgb.chunks_exact(3 * w as usize).flat_map(|r| [r, &[7; 5]].concat()).collect();
+    padded.truncate(padded.len() - 5);
+    let builders = [
+        EncoderBuilder::new(&rgba, w, h),
+        EncoderBuilder::new(&rgb, w, h),
+        EncoderBuilder::new(&padded, w, h)
+            .with_source_channels(SourceChannels::Rgb)
+            .with_stride(3 * w as usize + 5),
+    ];
+    for builder in builders {
+        for store in [false, true] {
+            let encoder = builder.build().unwrap().with_store(store);
+            let expected = encoder.encode_to_vec().unwrap();
+            for n_bytes in [0, 1, 100, 1000, 12345, usize::MAX] {
+                let mut resumable = encoder.resumable();
+                let (mut out, mut n_steps) = (vec![], 0);
+                while !resumable.is_finished() {
+                    let n_written = resumable.encode_step(&mut out, n_bytes).unwrap();
+                    assert_eq!(n_written, out.len() - (resumable.bytes_written() - n_written));
+                    if !resumable.is_finished() {
+                        assert!(n_written >= n_bytes && n_written <= n_bytes.max(1) + 64 * 5);
+                    }
+                    n_steps += 1;
+                }
+                assert_eq!(out, expected);
+                assert_eq!(resumable.bytes_written(), expected.len());
+                assert_eq!(resumable.pixels_left(), 0);
+                assert!(n_steps >= expected.len() / n_bytes.saturating_add(64 * 5));
+                assert_eq!(resumable.encode_step(&mut out, n_bytes).unwrap(), 0);
+            }
+        }
+    }
+
+    let empty = EncoderBuilder::new(&[], 0, 3).with_allow_empty(true).build().unwrap();
+    let (mut resumable, mut out) = (empty.resumable(), vec![]);
+    assert_eq!(resumable.encode_step(&mut out, 1).unwrap(), 14);
+    assert!(!resumable.is_finished());
+    assert_eq!(resumable.encode_step(&mut out, 1).unwrap(), 8);
+    assert!(resumable.is_finished());
+    assert_eq!(out, empty.encode_to_vec().unwrap());
+}
+
+#[test]
+fn test_stream_encoder() {
+    use qoi::{Channels, ColorSpace, ErrorKind, Header, StreamEncoder};
+
+    let (header, rgba) = qoi::decode_to_vec(std::fs::read("assets/dice.qoi").unwrap()).unwrap();
+    let (w, h) = (header.width, header.height);
+    let rgb: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+    for (pixels, channels) in [(&rgba, Channels::Rgba), (&rgb, Channels::Rgb)] {
+        let expected = qoi::encode_to_vec(pixels, w, h).unwrap();
+        let header = Header::try_new(w, h, channels, ColorSpace::Srgb).unwrap();
+        let row_len = w as usize * channels.as_u8() as usize;
+        for n_rows in [1, 7, h as usize] {
+            let mut encoder = StreamEncoder::new(vec![], header).unwrap();
+            assert_eq!(encoder.writer().len(), 14);
+            for rows in pixels.chunks(n_rows * row_len) {
+                let rows_left = encoder.rows_left();
+                let n_written = encoder.push_rows(rows).unwrap();
+                assert_eq!(encoder.writer().len(), encoder.bytes_written());
+                assert!(n_written > 0);
+                assert_eq!(encoder.rows_left(), rows_left - rows.len() / row_len);
+            }
+            assert!(encoder.is_finished());
+            assert_eq!(encoder.push_rows([]).unwrap(), 0);
+            assert_eq!(encoder.finish().unwrap(), expected);
+        }
+
+        let mut encoder = StreamEncoder::new(vec![], header).unwrap();
+        let err = encoder.push_rows(&pixels[..row_len + 1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidImageLength);
+        encoder.push_rows(&pixels[..row_len * 2]).unwrap();
+        let err = encoder.push_rows(pixels).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidImageLength);
+        assert_eq!(encoder.pixels_left(), (h as usize - 2) * w as usize);
+        assert_eq!(encoder.finish().unwrap_err().kind(), ErrorKind::InvalidImageLength);
+    }
+    let header = Header { width: 0, ..header };
+    let err = StreamEncoder::new(vec![], header).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidImageDimensions);
+}
+
+#[test]
+fn test_decode_from_slices() {
+    use qoi::{Channels, Decoder, ErrorKind};
+    use std::io::IoSlice;
+
+    let encoded = std::fs::read("assets/dice.qoi").unwrap();
+    let (header, expected) = qoi::decode_to_vec(&encoded).unwrap();
+    let expected_rgb: Vec<u8> = expected.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+
+    for chunk_size in [1, 2, 3, 5, 14, 15, 1000, encoded.len()] {
+        let slices: Vec<&[u8]> = encoded.chunks(chunk_size).collect();
+        let mut decoder = Decoder::from_slices(&slices).unwrap();
+        assert_eq!(decoder.header(), &header);
+        assert_eq!(decoder.decode_to_vec().unwrap(), expected);
+        assert_eq!(decoder.bytes_consumed(), encoded.len());
+
+        // empty slices in between are skipped
+        let vecs: Vec<Vec<u8>> = slices.iter().flat_map(|s| [s.to_vec(), vec![]]).collect();
+        let mut decoder =
+            Decoder::from_slices(&vecs).unwrap().with_channels(Channels::Rgb).unwrap();
+        assert_eq!(decoder.decode_to_vec().unwrap(), expected_rgb);
+
+        let io_slices: Vec<_> = slices.iter().map(|s| IoSlice::new(s)).collect();
+        let mut decoder = Decoder::from_slices(&io_slices).unwrap();
+        let mut decoded = vec![0; expected.len()];
+        for row in decoded.chunks_mut(4 * header.width as usize) {
+            decoder.decode_pixels(row).unwrap();
+        }
+        assert_eq!(decoded, expected);
+    }
+
+    let slices = [&encoded[..100], &encoded[100..encoded.len() - 1]];
+    let err = Decoder::from_slices(&slices).unwrap().decode_to_vec().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedBufferEnd);
+    let slices = [&encoded[..10]];
+    let err = Decoder::from_slices(&slices).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedBufferEnd);
+    let slices = [&encoded[..], b"tail"];
+    assert!(Decoder::from_slices(&slices).unwrap().decode_to_vec().is_ok());
+    let err = Decoder::from_slices(&slices).unwrap().with_strict(true).decode_to_vec().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TrailingBytes);
+}
+
+#[test]
+fn test_encoded_reader() {
+    use qoi::{EncodedReader, Encoder, EncoderBuilder, SourceChannels};
+    use std::io::Read;
+
+    let (header, rgba) = qoi::decode_to_vec(std::fs::read("assets/dice.qoi").unwrap()).unwrap();
+    let (w, h) = (header.width, header.height);
+    let encoder = Encoder::new(&rgba, w, h).unwrap();
+    let expected = encoder.encode_to_vec().unwrap();
+
+    let mut out = vec![];
+    let reader = EncodedReader::new(Encoder::new(&rgba, w, h).unwrap());
+    assert_eq!(reader.header(), encoder.header());
+    std::io::copy(&mut { reader }, &mut out).unwrap();
+    assert_eq!(out, expected);
+
+    for buf_size in [1, 7, 4096, 100_000] {
+        let mut reader = EncodedReader::new(Encoder::new(&rgba, w, h).unwrap());
+        let (mut out, mut buf) = (vec![], vec![0; buf_size]);
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            assert!(n <= buf_size);
+            out.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(out, expected);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+    }
+
+    let bgra: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], p[3]]).collect();
+    let encoder = EncoderBuilder::new(&bgra, w, h)
+        .with_source_channels(SourceChannels::Bgra)
+        .build()
+        .unwrap();
+    let mut out = vec![];
+    EncodedReader::new(encoder).read_to_end(&mut out).unwrap();
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn test_error_metrics() {
+    use qoi::metrics::{compare, compare_encoded, compare_stream};
+    use qoi::{Encoder, ErrorKind};
+
+    let a = [10_u8, 20, 30, 40, 50, 60, 70, 80, 90];
+    let metrics = compare(a, a, 3, 1).unwrap();
+    assert!(metrics.is_identical());
+    assert_eq!((metrics.n_pixels(), metrics.max_error(), metrics.mse()), (3, 0, 0.));
+    assert_eq!(metrics.psnr(), f64::INFINITY);
+
+    let b = [12_u8, 20, 30, 40, 50, 60, 70, 80, 86];
+    let metrics = compare(a, b, 3, 1).unwrap();
+    assert_eq!((metrics.n_diff(), metrics.max_error()), (2, 4));
+    assert_eq!(metrics.max_channel_error(), [2, 0, 4, 0]);
+    assert_eq!(metrics.channel_mse(), [4. / 3., 0., 16. / 3., 0.]);
+    assert_eq!(metrics.mse(), 20. / 9.);
+    assert!((metrics.psnr() - 10. * (65025. * 9. / 20_f64).log10()).abs() < 1e-9);
+
+    // RGBA is inferred from the length, mismatched buffers are rejected
+    assert_eq!(compare(&a[..8], &b[..8], 2, 1).unwrap().channels().as_u8(), 4);
+    assert_eq!(compare(a, &b[..8], 3, 1).unwrap_err().kind(), ErrorKind::InvalidImageLength);
+    assert_eq!(compare(&a[..8], &b[..8], 3, 1).unwrap_err().kind(), ErrorKind::InvalidImageLength);
+
+    let encoded = std::fs::read("assets/dice.qoi").unwrap();
+    let (header, mut pixels) = qoi::decode_to_vec(&encoded).unwrap();
+    assert!(compare_encoded(&pixels, &encoded).unwrap().is_identical());
+    pixels[1000] ^= 0x10;
+    let metrics = compare_stream(&pixels, &encoded[..]).unwrap();
+    assert_eq!((metrics.n_pixels(), metrics.n_diff()), (header.n_pixels(), 1));
+    assert_eq!(metrics.max_error(), 0x10);
+    let err = compare_encoded(&pixels[4..], &encoded).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidImageLength);
+    let encoded = Encoder::new(&pixels, header.width, header.height).unwrap().encode_to_vec();
+    assert_eq!(compare_encoded(&pixels, encoded.unwrap()).unwrap().n_diff(), 0);
+}
+
+#[test]
+fn test_color_census() {
+    use qoi::metrics::{count_colors, count_colors_stream, ColorCensus};
+    use qoi::Channels;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::collections::HashSet;
+
+    let mut census = ColorCensus::new(3);
+    census.add_pixels(&[1, 2, 3, 1, 2, 3, 4, 5, 6, 7], Channels::Rgb);
+    assert_eq!((census.count().n_colors(), census.count().is_exact()), (2, true));
+    // RGB pixels are treated as opaque RGBA
+    census.add_pixels(&[1, 2, 3, 0xff, 1, 2, 3, 0], Channels::Rgba);
+    assert_eq!((census.count().n_colors(), census.count().is_exact()), (3, true));
+    census.add_pixels(&[9, 9, 9], Channels::Rgb);
+    assert_eq!((census.count().n_colors(), census.count().is_exact()), (4, false));
+
+    let encoded = std::fs::read("assets/dice.qoi").unwrap();
+    let pixels = qoi::decode_to_vec(&encoded).unwrap().1;
+    let expected = pixels.chunks_exact(4).collect::<HashSet<_>>().len();
+    let count = count_colors(&encoded, usize::MAX).unwrap();
+    assert_eq!((count.n_colors(), count.is_exact()), (expected, true));
+    let count = count_colors_stream(&encoded[..], expected).unwrap();
+    assert_eq!((count.n_colors(), count.is_exact()), (expected, true));
+    let count = count_colors(&encoded, 100).unwrap();
+    assert!(!count.is_exact());
+    let error = count.n_colors() as f64 / expected as f64 - 1.;
+    assert!(error.abs() < 0.05, "{} vs {expected}", count.n_colors());
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let pixels: Vec<u8> = (0..3 * 500_000).map(|_| rng.gen()).collect();
+    let expected = pixels.chunks_exact(3).collect::<HashSet<_>>().len();
+    let encoded = qoi::encode_to_vec(&pixels, 1000, 500).unwrap();
+    let count = count_colors(encoded, 1000).unwrap();
+    let error = count.n_colors() as f64 / expected as f64 - 1.;
+    assert!(error.abs() < 0.05, "{} vs {expected}", count.n_colors());
+}
+
+#[test]
+#[cfg(feature = "sdl2")]
+fn test_sdl_decode_to_texture() {
+    use qoi::sdl::{decode_to_pixels, decode_to_texture, pixel_layout};
+    use qoi::{ChannelSource, Decoder, ErrorKind};
+    use sdl2::pixels::PixelFormatEnum;
+    use sdl2::rect::Rect;
+    use sdl2::surface::Surface;
+
+    let data = std::fs::read("assets/dice.qoi").unwrap();
+    let (header, rgba) = qoi::decode_to_vec(&data).unwrap();
+    let (w, h) = (header.width as usize, header.height as usize);
+    let check = |pixels: &[u8], pitch: usize, format| {
+        let map = pixel_layout(format).unwrap().channel_map();
+        let n = if map.channels().is_rgba() { 4 } else { 3 };
+        for (y, row) in rgba.chunks_exact(w * 4).enumerate() {
+            let dst = &pixels[y * pitch..][..w * map.bytes_per_pixel()];
+            for (src, dst) in row.chunks_exact(4).zip(dst.chunks_exact(map.bytes_per_pixel())) {
+                for (c, source) in map.sources()[..n].iter().enumerate() {
+                    if let ChannelSource::Byte(i) = source {
+                        assert_eq!(dst[*i as usize], src[c], "{format:?}");
+                    }
+                }
+            }
+        }
+    };
+
+    // software renderer, doesn't need a window or a video driver
+    let canvas = Surface::new(1, 1, PixelFormatEnum::RGBA32).unwrap().into_canvas().unwrap();
+    let creator = canvas.texture_creator();
+    for format in [
+        PixelFormatEnum::RGB24,
+        PixelFormatEnum::BGR24,
+        PixelFormatEnum::RGB888,
+        PixelFormatEnum::ARGB8888,
+        PixelFormatEnum::RGBA32,
+        PixelFormatEnum::BGRA32,
+    ] {
+        let mut texture = creator.create_texture_streaming(format, w as u32 + 3, h as u32).unwrap();
+        let mut decoder = Decoder::new(&data).unwrap();
+        decode_to_texture(&mut decoder, &mut texture).unwrap();
+        assert_eq!(decoder.pixels_left(), 0);
+        let rect = Rect::new(0, 0, w as u32, h as u32);
+        texture.with_lock(rect, |pixels, pitch| check(pixels, pitch, format)).unwrap();
+
+        // pitched buffers without SDL; RGB images get opaque alpha
+        let pitch = w * 4 + 5;
+        let mut pixels = vec![0; pitch * h];
+        let mut decoder = Decoder::new(&data).unwrap();
+        assert_eq!(decode_to_pixels(&mut decoder, &mut pixels, pitch, format).unwrap(), w * h);
+        check(&pixels, pitch, format);
+    }
+
+    let mut decoder = Decoder::new(&data).unwrap();
+    let mut texture = creator.create_texture_static(PixelFormatEnum::RGB24, 800, 600).unwrap();
+    assert_eq!(decode_to_texture(&mut decoder, &mut texture).unwrap_err().kind(), ErrorKind::Io);
+    let mut texture = creator.create_texture_streaming(PixelFormatEnum::RGB24, 800, 599).unwrap();
+    assert_eq!(decode_to_texture(&mut decoder, &mut texture).unwrap_err().kind(), ErrorKind::Io);
+    let mut pixels = vec![0; w * h * 3];
+    let err = decode_to_pixels(&mut decoder, &mut pixels, w * 2, PixelFormatEnum::RGB24);
+    assert_eq!(err.unwrap_err().kind(), ErrorKind::InvalidStride);
+    let err = decode_to_pixels(&mut decoder, &mut pixels, w * 3, PixelFormatEnum::RGB565);
+    assert_eq!(err.unwrap_err().kind(), ErrorKind::Io);
+    decoder.decode_pixels([0; 4]).unwrap();
+    let err = decode_to_pixels(&mut decoder, &mut pixels, w * 3, PixelFormatEnum::RGB24);
+    assert_eq!(err.unwrap_err().kind(), ErrorKind::DecodingStarted);
+}
+
+#[test]
+fn test_image_decoder_encoder() {
+    use image::{
+        ColorType, DynamicImage, ExtendedColorType, ImageDecoder, ImageEncoder, ImageError,
+    };
+    use qoi::image::WriteEncoder;
+    use qoi::{Channels, ColorSpace, Decoder};
+
+    let data = std::fs::read("assets/dice.qoi").unwrap();
+    let (header, rgba) = qoi::decode_to_vec(&data).unwrap();
+
+    let decoder = Decoder::from_stream(&data[..]).unwrap();
+    assert_eq!(decoder.dimensions(), (800, 600));
+    assert_eq!(decoder.color_type(), ColorType::Rgba8);
+    let img = DynamicImage::from_decoder(decoder).unwrap();
+    assert_eq!(img.as_bytes(), rgba);
+
+    let decoder = Decoder::new(&data).unwrap().with_channels(Channels::Rgb).unwrap();
+    assert_eq!(decoder.color_type(), ColorType::Rgb8);
+    assert_eq!(decoder.original_color_type(), ExtendedColorType::Rgba8);
+    let rgb = DynamicImage::from_decoder(decoder).unwrap().into_rgb8();
+    let expected: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+    assert_eq!(rgb.as_raw(), &expected);
+
+    let mut decoder = Decoder::new(&data).unwrap();
+    decoder.decode_pixels([0; 4]).unwrap();
+    let err = decoder.read_image(&mut vec![0; rgba.len()]).unwrap_err();
+    assert!(matches!(err, ImageError::Decoding(_)));
+
+    let mut encoded = vec![];
+    img.write_with_encoder(WriteEncoder::new(&mut encoded)).unwrap();
+    assert_eq!(encoded, qoi::encode_to_vec(&rgba, 800, 600).unwrap());
+    let mut encoded = vec![];
+    let encoder = WriteEncoder::new(&mut encoded).with_colorspace(ColorSpace::Linear);
+    encoder.write_image(&expected, 800, 600, ExtendedColorType::Rgb8).unwrap();
+    let (decoded_header, decoded) = qoi::decode_to_vec(&encoded).unwrap();
+    assert_eq!(
+        (decoded_header.channels, decoded_header.colorspace),
+        (Channels::Rgb, ColorSpace::Linear)
+    );
+    assert_eq!((decoded_header.width, decoded_header.height), (header.width, header.height));
+    assert_eq!(decoded, expected);
+
+    let err = WriteEncoder::new(vec![]).write_image(&[0; 4], 2, 2, ExtendedColorType::L8);
+    assert!(matches!(err.unwrap_err(), ImageError::Unsupported(_)));
+    let err = WriteEncoder::new(vec![]).write_image(&[0; 11], 2, 2, ExtendedColorType::Rgb8);
+    assert!(matches!(err.unwrap_err(), ImageError::Encoding(_)));
+}
+
+#[test]
+fn test_batch_encode_decode_image() {
+    use qoi::batch::{decode_image, encode_image, read_image, ImageFormat};
+
+    let png = std::fs::read("assets/dice.png").unwrap();
+    let (header, pixels) = read_image("assets/dice.png").unwrap();
+    assert_eq!(ImageFormat::from_magic(&png), Some(ImageFormat::Png));
+    assert!(decode_image(&png, ImageFormat::Png).unwrap() == (header, pixels.clone()));
+
+    for format in
+        [ImageFormat::Qoi, ImageFormat::Png, ImageFormat::Ppm, ImageFormat::Tga, ImageFormat::Bmp]
+    {
+        let rgb: Vec<u8> = pixels.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+        let (header, pixels) = match format {
+            ImageFormat::Ppm => (header.with_channels(qoi::Channels::Rgb), rgb),
+            _ => (header, pixels.clone()),
+        };
+        // 3x2 images have padded rows in BMP
+        let small = qoi::Header::try_new(3, 2, header.channels, header.colorspace).unwrap();
+        for (header, pixels) in
+            [(header, pixels.clone()), (small, pixels[..small.n_bytes()].to_vec())]
+        {
+            let mut out = vec![];
+            encode_image(&mut out, format, &header, &pixels).unwrap();
+            let detected = Some(format).filter(|&f| f != ImageFormat::Tga);
+            assert_eq!(ImageFormat::from_magic(&out), detected);
+            assert!(decode_image(&out, format).unwrap() == (header, pixels));
+        }
+    }
+    assert_eq!(ImageFormat::from_magic(b"qoi"), None);
+    assert!(decode_image(b"qoif", ImageFormat::Qoi).is_err());
+}
+
+#[test]
+fn test_batch_tga_bmp_layouts() {
+    use qoi::batch::{decode_image, ImageFormat};
+    use qoi::{Channels, ErrorKind, Header};
+
+    let decode = |data: &[u8], format| decode_image(data, format).map(|(h, p)| (h.channels, p));
+    let rgb = |w, h| Header::try_new(w, h, Channels::Rgb, qoi::ColorSpace::Srgb).unwrap();
+
+    // TGA: bottom-up BGR with an image id, top-down grayscale, top-down BGRA
+    let mut tga = vec![2, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 2, 0, 24, 0x00, b'i', b'd'];
+    tga.extend([3, 2, 1, 6, 5, 4, 13, 12, 11, 16, 15, 14]);
+    let (header, pixels) = decode_image(&tga, ImageFormat::Tga).unwrap();
+    assert_eq!(header, rgb(2, 2));
+    assert_eq!(pixels, [11, 12, 13, 14, 15, 16, 1, 2, 3, 4, 5, 6]);
+    let tga = [0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1, 0, 8, 0x20, 10, 20];
+    assert_eq!(
+        decode(&tga, ImageFormat::Tga).unwrap(),
+        (Channels::Rgb, vec![10, 10, 10, 20, 20, 20])
+    );
+    let tga = [0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 32, 0x28, 3, 2, 1, 4];
+    assert_eq!(decode(&tga, ImageFormat::Tga).unwrap(), (Channels::Rgba, vec![1, 2, 3, 4]));
+    // no alpha bits in the descriptor: the 4th byte is padding
+    let tga = [0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 32, 0x20, 3, 2, 1, 4];
+    assert_eq!(decode(&tga, ImageFormat::Tga).unwrap(), (Channels::Rgb, vec![1, 2, 3]));
+    let rle = [0, 0, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 24, 0x20, 0, 3, 2, 1];
+    assert_eq!(decode(&rle, ImageFormat::Tga).unwrap_err().kind(), ErrorKind::Io);
+    let err = decode(&tga[..20], ImageFormat::Tga).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedBufferEnd);
+
+    // BMP: 40-byte info header, optionally followed by channel masks
+    let bmp = |width: i32, height: i32, bits: u16, compression: u32, masks: &[u32], px: &[u8]| {
+        let (info_size, offset) =
+            (if masks.len() == 4 { 56_u32 } else { 40 }, 54 + 4 * masks.len());
+        let mut out = b"BM".to_vec();
+        out.extend(((offset + px.len()) as u32).to_le_bytes());
+        out.extend([0; 4]);
+        out.extend((offset as u32).to_le_bytes());
+        out.extend(info_size.to_le_bytes());
+        out.extend(width.to_le_bytes());
+        out.extend(height.to_le_bytes());
+        out.extend(1_u16.to_le_bytes());
+        out.extend(bits.to_le_bytes());
+        out.extend(compression.to_le_bytes());
+        out.extend([0; 20]);
+        masks.iter().for_each(|mask| out.extend(mask.to_le_bytes()));
+        out.extend(px);
+        out
+    };
+    // bottom-up, rows of 3 bytes padded to 4
+    let data = bmp(1, 2, 24, 0, &[], &[3, 2, 1, 0, 6, 5, 4, 0]);
+    assert_eq!(decode(&data, ImageFormat::Bmp).unwrap(), (Channels::Rgb, vec![4, 5, 6, 1, 2, 3]));
+    // top-down
+    let data = bmp(1, -2, 24, 0, &[], &[3, 2, 1, 0, 6, 5, 4, 0]);
+    assert_eq!(decode(&data, ImageFormat::Bmp).unwrap(), (Channels::Rgb, vec![1, 2, 3, 4, 5, 6]));
+    // 32-bit without masks: the 4th byte is padding
+    let data = bmp(1, 1, 32, 0, &[], &[3, 2, 1, 9]);
+    assert_eq!(decode(&data, ImageFormat::Bmp).unwrap(), (Channels::Rgb, vec![1, 2, 3]));
+    // RGBA byte order via bit fields, with and without the alpha mask
+    let masks = [0xff, 0xff00, 0xff_0000, 0xff00_0000];
+    let data = bmp(1, 1, 32, 3, &masks, &[1, 2, 3, 4]);
+    assert_eq!(decode(&data, ImageFormat::Bmp).unwrap(), (Channels::Rgba, vec![1, 2, 3, 4]));
+    let data = bmp(1, 1, 32, 3, &masks[..3], &[1, 2, 3, 4]);
+    assert_eq!(decode(&data, ImageFormat::Bmp).unwrap(), (Channels::Rgb, vec![1, 2, 3]));
+    let data = bmp(1, 1, 32, 3, &[0xf00, 0xff00, 0xff_0000], &[1, 2, 3, 4]);
+    assert_eq!(decode(&data, ImageFormat::Bmp).unwrap_err().kind(), ErrorKind::Io);
+    let data = bmp(1, 1, 8, 0, &[], &[0, 0, 0, 0]);
+    assert_eq!(decode(&data, ImageFormat::Bmp).unwrap_err().kind(), ErrorKind::Io);
+    let data = bmp(1, 2, 24, 0, &[], &[3, 2, 1, 0, 6, 5]);
+    let err = decode(&data, ImageFormat::Bmp).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedBufferEnd);
+}
+
+#[test]
+fn test_batch_convert_tree() {
+    use qoi::batch::{convert_tree, convert_tree_with_progress, read_image, write_image};
+    use qoi::batch::{BatchOptions, ImageFormat};
+    use qoi::parallel::ParallelOptions;
+    use std::fs;
+    use std::sync::Arc;
+
+    let root = std::env::temp_dir().join(format!("qoi-batch-{}", std::process::id()));
+    let (src, dst, back) = (root.join("src"), root.join("dst"), root.join("back"));
+    fs::create_dir_all(src.join("a/b")).unwrap();
+    fs::copy("assets/dice.png", src.join("dice.png")).unwrap();
+    fs::copy("assets/testcard.png", src.join("a/b/testcard.PNG")).unwrap();
+    fs::write(src.join("a/notes.txt"), "not an image").unwrap();
+    fs::write(src.join("a/broken.png"), "not a png").unwrap();
+    let (header, pixels) = read_image("assets/kodim10.qoi").unwrap();
+    write_image(src.join("a/kodim10.ppm"), &header, &pixels).unwrap();
+    assert_eq!(ImageFormat::from_path(src.join("a/b/testcard.PNG")), Some(ImageFormat::Png));
+
+    let mut seen = vec![];
+    let parallel = ParallelOptions::new().with_threads(2);
+    let options = BatchOptions::new(ImageFormat::Qoi).with_parallel(parallel);
+    let report = convert_tree_with_progress(&src, &dst, &options, |p| {
+        seen.push((p.done, p.total, p.result.is_ok()));
+    })
+    .unwrap();
+    assert_eq!((report.n_converted, report.n_skipped), (3, 0));
+    assert_eq!(report.errors.len(), 1);
+    assert!(report.errors[0].0.ends_with("a/broken.png"));
+    seen.sort_unstable();
+    assert_eq!(seen, [(1, 4, seen[0].2), (2, 4, seen[1].2), (3, 4, seen[2].2), (4, 4, seen[3].2)]);
+    assert!(!dst.join("a/notes.qoi").exists());
+    for (path, expected) in
+        [("dice", "dice"), ("a/b/testcard", "testcard"), ("a/kodim10", "kodim10")]
+    {
+        let decoded = qoi::decode_to_vec(fs::read(dst.join(path).with_extension("qoi")).unwrap());
+        let expected = qoi::decode_to_vec(fs::read(format!("assets/{expected}.qoi")).unwrap());
+        assert!(decoded.unwrap() == expected.unwrap(), "{path}");
+    }
+
+    // existing files are skipped unless overwriting
+    let report = convert_tree(&src, &dst, &options).unwrap();
+    assert_eq!((report.n_converted, report.n_skipped, report.errors.len()), (0, 3, 1));
+    let report = convert_tree(&src, &dst, &options.clone().with_overwrite(true)).unwrap();
+    assert_eq!((report.n_converted, report.n_skipped), (3, 0));
+
+    // all work can be confined to a caller-provided pool
+    let pool = Arc::new(rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap());
+    let parallel = ParallelOptions::new().with_pool(pool);
+    assert_eq!(parallel.effective_threads(10), 1);
+    let options = options.with_parallel(parallel).with_overwrite(true);
+    let report = convert_tree(&src, &dst, &options).unwrap();
+    assert_eq!((report.n_converted, report.errors.len()), (3, 1));
+
+    // and back: alpha can't be stored in PPM
+    let report = convert_tree(&dst, &back, &BatchOptions::new(ImageFormat::Ppm)).unwrap();
+    assert_eq!((report.n_converted, report.errors.len()), (1, 2));
+    assert!(read_image(back.join("a/kodim10.ppm")).unwrap() == (header, pixels));
+    let report = convert_tree(&dst, &back, &BatchOptions::new(ImageFormat::Png)).unwrap();
+    assert_eq!((report.n_converted, report.errors.len()), (3, 0));
+    let png = read_image(back.join("dice.png")).unwrap();
+    assert!(png == read_image("assets/dice.png").unwrap());
+
+    // images with the same stem map to the same target, only the first one is converted
+    let dup = root.join("dup");
+    fs::create_dir_all(&dup).unwrap();
+    fs::copy("assets/dice.png", dup.join("a.png")).unwrap();
+    fs::copy(src.join("a/kodim10.ppm"), dup.join("a.ppm")).unwrap();
+    let report = convert_tree(&dup, root.join("dup-out"), &options).unwrap();
+    assert_eq!((report.n_converted, report.errors.len()), (1, 1));
+    assert!(report.errors[0].0.ends_with("a.ppm"));
+    assert_eq!(report.errors[0].1.kind(), qoi::ErrorKind::Io);
+    let decoded = qoi::decode_to_vec(fs::read(root.join("dup-out/a.qoi")).unwrap()).unwrap();
+    assert!(decoded == qoi::decode_to_vec(fs::read("assets/dice.qoi").unwrap()).unwrap());
+
+    assert!(convert_tree(src.join("dice.png"), &dst, &options).is_err());
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_decode_diagnostics() {
+    use qoi::{Channels, ColorSpace, Decoder, Diagnostic, ErrorKind, Header};
+
+    let mut data = vec![];
+    data.extend_from_slice(
+        &Header::try_new(2, 2, Channels::Rgba, ColorSpace::Srgb).unwrap().encode(),
+    );
+    data.extend_from_slice(&[0x05, 0xfe, 1, 2, 3, 0xfd]); // unset index, RGB, run of 62
+    data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 42, 42, 42]);
+    let expected_pixels = [0, 0, 0, 0, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3, 0];
+    let expected = [
+        Diagnostic::UnsetIndex { byte_offset: 14, pixel_pos: 0, index: 5 },
+        Diagnostic::RunOverflow { byte_offset: 19, pixel_pos: 2, run: 62, pixels_left: 2 },
+        Diagnostic::TrailingBytes { n_bytes: 3 },
+    ];
+    assert_eq!(expected[1].to_string(), "run of 62 pixels clamped to 2 at pixel 2 (byte 19)");
+
+    let mut diags = vec![];
+    let pixels =
+        Decoder::new(&data).unwrap().decode_to_vec_with_diagnostics(|d| diags.push(d)).unwrap();
+    assert_eq!((pixels.as_slice(), diags.as_slice()), (&expected_pixels[..], &expected[..]));
+    assert_eq!(Decoder::new(&data).unwrap().decode_to_vec().unwrap(), pixels);
+
+    // streams can't detect trailing bytes, scattered slices can
+    let mut diags = vec![];
+    let mut decoder = Decoder::from_stream(&data[..]).unwrap();
+    decoder.decode_to_vec_with_diagnostics(|d| diags.push(d)).unwrap();
+    assert_eq!(diags, &expected[..2]);
+    let mut diags = vec![];
+    let slices = [&data[..16], &data[16..]];
+    let mut decoder = Decoder::from_slices(&slices).unwrap();
+    decoder.decode_to_vec_with_diagnostics(|d| diags.push(d)).unwrap();
+    assert_eq!(diags, expected);
+
+    // diagnostics resume after partial decoding, strict mode still fails on anomalies
+    let mut diags = vec![];
+    let mut decoder = Decoder::new(&data).unwrap();
+    decoder.decode_pixels([0; 4]).unwrap();
+    decoder.decode_to_vec_with_diagnostics(|d| diags.push(d)).unwrap();
+    assert_eq!(diags, &expected[1..]);
+    let mut diags = vec![];
+    let mut decoder = Decoder::new(&data).unwrap().with_strict(true);
+    let err = decoder.decode_to_vec_with_diagnostics(|d| diags.push(d)).unwrap_err();
+    assert_eq!((err.kind(), diags.as_slice()), (ErrorKind::InvalidRunLength, &expected[..1]));
+
+    // well-formed images produce no diagnostics, index 0 is a valid reference to zeros
+    let mut data = data[..14].to_vec();
+    data.extend_from_slice(&[0x00, 0xfe, 1, 2, 3, 0xc1, 0, 0, 0, 0, 0, 0, 0, 1]);
+    let mut decoder = Decoder::new(&data).unwrap();
+    assert_eq!(decoder.decode_to_vec_with_diagnostics(|_| unreachable!()).unwrap(), pixels);
+    let encoded = std::fs::read("assets/dice.qoi").unwrap();
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    decoder.decode_to_vec_with_diagnostics(|_| unreachable!()).unwrap();
+}
@@ -0,0 +1,144 @@
+//! Performance smoke test: a cheap local guardrail against large throughput regressions.
+//!
+//! It's ignored by default since timings are only meaningful on a quiet machine; run it via
+//!
+//! ```sh
+//! cargo test --test test_perf -- --ignored --nocapture
+//! ```
+//!
+//! The first run records a baseline (encode/decode throughput over a deterministic synthetic
+//! corpus); subsequent runs fail if any throughput drops below a fraction of the baseline.
+//! Environment variables:
+//!
+//! - `QOI_PERF_BASELINE`: baseline file path (default: `qoi-perf-baseline.txt` in cargo's
+//!   temporary directory for integration tests under `target/`).
+//! - `QOI_PERF_MIN_RATIO`: minimum allowed fraction of the baseline throughput (default: 0.5).
+//! - `QOI_PERF_RECORD`: if set to `1`, (re)record the baseline instead of comparing to it.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use qoi::testutil::{gradient, noise, runs, screenshot};
+use qoi::{decode_to_vec, encode_to_vec, Channels};
+
+const IMAGE_SIZE: u32 = 512;
+const N_ITERATIONS: usize = 7;
+const DEFAULT_MIN_RATIO: f64 = 0.5;
+
+type Metrics = BTreeMap<String, f64>;
+
+fn corpus(channels: Channels) -> Vec<Vec<u8>> {
+    let mut rng = StdRng::seed_from_u64(0);
+    let (w, h) = (IMAGE_SIZE, IMAGE_SIZE);
+    vec![
+        gradient(w, h, channels),
+        noise(&mut rng, w, h, channels),
+        screenshot(&mut rng, w, h, channels),
+        runs(&mut rng, w, h, channels),
+    ]
+}
+
+/// Returns the throughput in megapixels per second, based on the fastest of the iterations
+/// (the least noisy estimate on a machine that's busy with something else now and then).
+fn measure(n_pixels: usize, mut f: impl FnMut()) -> f64 {
+    let best = (0..N_ITERATIONS)
+        .map(|_| {
+            let t = Instant::now();
+            f();
+            t.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+        .max(Duration::from_nanos(1));
+    n_pixels as f64 / best.as_secs_f64() / 1e6
+}
+
+fn measure_all() -> Metrics {
+    let mut metrics = Metrics::new();
+    for (channels, name) in [(Channels::Rgb, "rgb"), (Channels::Rgba, "rgba")] {
+        let images = corpus(channels);
+        let encoded: Vec<_> =
+            images.iter().map(|img| encode_to_vec(img, IMAGE_SIZE, IMAGE_SIZE).unwrap()).collect();
+        for (img, data) in images.iter().zip(&encoded) {
+            assert_eq!(&decode_to_vec(data).unwrap().1, img);
+        }
+        let n_pixels = images.len() * (IMAGE_SIZE * IMAGE_SIZE) as usize;
+        let encode = measure(n_pixels, || {
+            for img in &images {
+                encode_to_vec(img, IMAGE_SIZE, IMAGE_SIZE).unwrap();
+            }
+        });
+        let decode = measure(n_pixels, || {
+            for data in &encoded {
+                decode_to_vec(data).unwrap();
+            }
+        });
+        metrics.insert(format!("encode:{}", name), encode);
+        metrics.insert(format!("decode:{}", name), decode);
+    }
+    metrics
+}
+
+fn parse_metrics(s: &str) -> Metrics {
+    s.lines()
+        .filter_map(|line| {
+            let (name, value) = line.split_once(' ')?;
+            Some((name.to_owned(), value.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+fn format_metrics(metrics: &Metrics) -> String {
+    metrics.iter().map(|(name, value)| format!("{} {:.1}\n", name, value)).collect()
+}
+
+#[test]
+#[ignore = "timing-sensitive; run explicitly with --ignored"]
+fn test_perf_smoke() {
+    let path = env::var_os("QOI_PERF_BASELINE").map_or_else(
+        || PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("qoi-perf-baseline.txt"),
+        PathBuf::from,
+    );
+    let min_ratio = env::var("QOI_PERF_MIN_RATIO")
+        .map_or(DEFAULT_MIN_RATIO, |s| s.parse().expect("invalid QOI_PERF_MIN_RATIO"));
+    let record = env::var("QOI_PERF_RECORD").map_or(false, |s| s == "1");
+
+    let metrics = measure_all();
+    let baseline = if record { None } else { fs::read_to_string(&path).ok() };
+    let baseline = match baseline {
+        Some(baseline) => parse_metrics(&baseline),
+        None => {
+            fs::write(&path, format_metrics(&metrics)).unwrap();
+            println!(
+                "recorded baseline (Mp/s) to {}:\n{}",
+                path.display(),
+                format_metrics(&metrics)
+            );
+            return;
+        }
+    };
+
+    let mut failed = vec![];
+    println!("{:<12} {:>10} {:>10} {:>8}", "", "baseline", "current", "ratio");
+    for (name, &current) in &metrics {
+        if let Some(&expected) = baseline.get(name) {
+            let ratio = current / expected;
+            println!("{:<12} {:>10.1} {:>10.1} {:>8.2}", name, expected, current, ratio);
+            if ratio < min_ratio {
+                failed.push(format!("{}: {:.1} Mp/s vs {:.1} Mp/s", name, current, expected));
+            }
+        }
+    }
+    assert!(
+        failed.is_empty(),
+        "throughput dropped below {:.0}% of the baseline in {}: {}",
+        min_ratio * 100.,
+        path.display(),
+        failed.join(", ")
+    );
+}
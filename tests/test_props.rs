@@ -0,0 +1,99 @@
+use proptest::prelude::*;
+
+use qoi::{decode_to_vec, EncoderBuilder, ErrorKind, SourceChannels};
+
+fn source_channels() -> impl Strategy<Value = SourceChannels> {
+    use SourceChannels::*;
+    prop::sample::select(vec![Rgb, Bgr, Rgba, Argb, Rgbx, Xrgb, Bgra, Abgr, Bgrx, Xbgr])
+}
+
+/// Converts RGBA pixels into the given source layout, filling ignored bytes with junk.
+fn to_source_layout(rgba: &[u8], source: SourceChannels) -> Vec<u8> {
+    // the variant name spells out the source layout, e.g. "bgrx"
+    let layout = format!("{:?}", source).to_ascii_lowercase();
+    rgba.chunks_exact(4)
+        .flat_map(|p| {
+            layout.bytes().map(move |c| match c {
+                b'r' => p[0],
+                b'g' => p[1],
+                b'b' => p[2],
+                b'a' => p[3],
+                _ => p[0] ^ 0x5a,
+            })
+        })
+        .collect()
+}
+
+/// Generates RGBA pixels with lots of repetition, so that all ops get exercised.
+fn rgba_pixels(n_pixels: usize) -> impl Strategy<Value = Vec<u8>> {
+    let palette = prop::array::uniform4(any::<u8>());
+    prop::collection::vec(palette, 1..8).prop_flat_map(move |palette| {
+        let px = prop_oneof![prop::sample::select(palette), prop::array::uniform4(any::<u8>())];
+        prop::collection::vec(px, n_pixels).prop_map(|v| v.concat())
+    })
+}
+
+fn image() -> impl Strategy<Value = (u32, u32, Vec<u8>)> {
+    (1_u32..40, 1_u32..40).prop_flat_map(|(w, h)| {
+        rgba_pixels(w as usize * h as usize).prop_map(move |rgba| (w, h, rgba))
+    })
+}
+
+proptest! {
+    #[test]
+    fn prop_stride_roundtrip(
+        (w, h, rgba) in image(), source in source_channels(), padding in 0_usize..9,
+    ) {
+        let bpp = source.bytes_per_pixel();
+        let (row_len, n_channels) = (w as usize * bpp, source.channels().as_u8() as usize);
+        let stride = row_len + padding;
+        let pixels = to_source_layout(&rgba, source);
+        let mut data = vec![0xcc; stride * (h as usize - 1) + row_len];
+        for (src, dst) in pixels.chunks_exact(row_len).zip(data.chunks_mut(stride)) {
+            dst[..row_len].copy_from_slice(src);
+        }
+
+        let encoded = EncoderBuilder::new(&data, w, h)
+            .with_source_channels(source)
+            .with_stride(stride)
+            .build()
+            .unwrap()
+            .encode_to_vec()
+            .unwrap();
+        let (header, decoded) = decode_to_vec(&encoded).unwrap();
+        prop_assert_eq!((header.width, header.height), (w, h));
+        prop_assert_eq!(header.channels, source.channels());
+        let expected: Vec<u8> =
+            rgba.chunks_exact(4).flat_map(|p| p[..n_channels].to_vec()).collect();
+        prop_assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn prop_builder_validation(
+        w in 0_u32..20,
+        h in 0_u32..20,
+        source in source_channels(),
+        stride in prop::option::of(0_usize..100),
+        extra in -2_isize..3,
+    ) {
+        let row_len = w as usize * source.bytes_per_pixel();
+        let stride_or_default = stride.unwrap_or(row_len);
+        let required = if h == 0 { 0 } else { stride_or_default * (h as usize - 1) + row_len };
+        let data = vec![0; (required as isize + extra).max(0) as usize];
+
+        let mut builder = EncoderBuilder::new(&data, w, h).with_source_channels(source);
+        if let Some(stride) = stride {
+            builder = builder.with_stride(stride);
+        }
+        let expected = if w == 0 || h == 0 {
+            Some(ErrorKind::InvalidImageDimensions)
+        } else if stride_or_default < row_len {
+            Some(ErrorKind::InvalidStride)
+        } else if data.len() != required && data.len() != stride_or_default * h as usize {
+            Some(ErrorKind::InvalidImageLength)
+        } else {
+            None
+        };
+        prop_assert_eq!(builder.build().err().map(|e| e.kind()), expected);
+    }
+}